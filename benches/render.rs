@@ -0,0 +1,189 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use moostache::{HashMapLoader, RenderOptions, RenderSession, Template, TemplateLoader};
+use serde_json::json;
+use std::io;
+
+// run benchmarks with
+// cargo bench
+
+fn missing_variables(c: &mut Criterion) {
+    // 1000 escaped variables, none of which exist in
+    // the data, nested a few scopes deep so every lookup
+    // has to walk all the scopes before giving up
+    let mut source = String::from("{{#a}}{{#b}}{{#c}}");
+    for i in 0..1000 {
+        source.push_str(&format!("{{{{ missing{i}.field }}}}"));
+    }
+    source.push_str("{{/c}}{{/b}}{{/a}}");
+    let template = Template::parse(source).unwrap();
+    let data = json!({"a": {"b": {"c": {"d": 1}}}});
+    let mut writer = Vec::new();
+    c.bench_function("missing variables", |b| b.iter(|| {
+        writer.clear();
+        template.render_no_partials(black_box(&data), &mut writer).unwrap();
+    }));
+}
+
+fn unescaped_objects(c: &mut Criterion) {
+    // 10k objects each written out as json with a
+    // triple mustache, so most of the render time is
+    // spent serializing values
+    let template = Template::parse("{{#items}}{{{.}}}{{/items}}").unwrap();
+    let items: Vec<_> = (0..10_000)
+        .map(|i| json!({"id": i, "name": format!("item{i}"), "tags": ["a", "b"]}))
+        .collect();
+    let data = json!({"items": items});
+    let mut writer = Vec::new();
+    c.bench_function("unescaped objects", |b| b.iter(|| {
+        writer.clear();
+        template.render_no_partials(black_box(&data), &mut writer).unwrap();
+    }));
+}
+
+fn escaped_objects(c: &mut Criterion) {
+    // same as above but escaped, so every value
+    // goes through the html escaping writer
+    let template = Template::parse("{{#items}}{{.}}{{/items}}").unwrap();
+    let items: Vec<_> = (0..10_000)
+        .map(|i| json!({"id": i, "name": format!("item{i}"), "tags": ["a", "b"]}))
+        .collect();
+    let data = json!({"items": items});
+    let mut writer = Vec::new();
+    c.bench_function("escaped objects", |b| b.iter(|| {
+        writer.clear();
+        template.render_no_partials(black_box(&data), &mut writer).unwrap();
+    }));
+}
+
+#[derive(serde_derive::Serialize)]
+struct User {
+    id: u64,
+    name: String,
+    email: String,
+    admin: bool,
+    score: f64,
+}
+
+fn flat_struct(c: &mut Criterion) {
+    // a flat struct rendered by serializing it into a
+    // serde_json::Value first vs serializing it directly
+    let template = Template::parse("<p>{{id}}: {{name}} &lt;{{email}}&gt; admin={{admin}} score={{score}}</p>").unwrap();
+    let user = User {
+        id: 42,
+        name: "John Smith".into(),
+        email: "john@example.com".into(),
+        admin: false,
+        score: 97.5,
+    };
+    let mut expected = Vec::new();
+    template.render_serializable(&(), &user, &mut expected).unwrap();
+    let mut writer = Vec::new();
+    template.render_flat_serializable(&(), &user, &mut writer).unwrap();
+    assert_eq!(writer, expected);
+    let mut group = c.benchmark_group("flat struct");
+    group.bench_function("render_serializable", |b| b.iter(|| {
+        writer.clear();
+        template.render_serializable(&(), black_box(&user), &mut writer).unwrap();
+    }));
+    group.bench_function("render_flat_serializable", |b| b.iter(|| {
+        writer.clear();
+        template.render_flat_serializable(&(), black_box(&user), &mut writer).unwrap();
+    }));
+    group.finish();
+}
+
+fn complex_template_to_sink(c: &mut Criterion) {
+    // a page with nested sections, inverted sections,
+    // escaped and unescaped variables, and partials,
+    // rendered into io::sink so only the cost of
+    // rendering is measured and not writing the output
+    let loader = HashMapLoader::try_from(vec![
+        ("page", "<h1>{{title}}</h1>{{#users}}{{> user }}{{/users}}{{^users}}no users{{/users}}"),
+        ("user", "<div>{{name}} &lt;{{{email}}}&gt;{{#admin}} admin{{/admin}}<ul>{{#tags}}<li>{{.}}</li>{{/tags}}</ul></div>"),
+    ]).unwrap();
+    let users: Vec<_> = (0..1000)
+        .map(|i| json!({
+            "name": format!("<user{i}>"),
+            "email": format!("user{i}@example.com"),
+            "admin": i % 10 == 0,
+            "tags": ["a", "b", "c"],
+        }))
+        .collect();
+    let data = json!({"title": "users", "users": users});
+    let template = loader.get("page").unwrap();
+    c.bench_function("complex template to sink", |b| b.iter(|| {
+        template.render(&loader, black_box(&data), &mut io::sink()).unwrap();
+    }));
+}
+
+fn repeated_path(c: &mut Criterion) {
+    // the same deep path repeated 100 times inside a couple
+    // of sections, so every lookup walks past the section
+    // scopes to the root before following the path, vs
+    // memoizing resolved paths so it's only walked once
+    let mut source = String::from("{{#a}}{{#b}}");
+    for _ in 0..100 {
+        source.push_str("{{ user.profile.name }}");
+    }
+    source.push_str("{{/b}}{{/a}}");
+    let template = Template::parse(source).unwrap();
+    let data = json!({
+        "a": {"b": {"c": 1}},
+        "user": {"profile": {"name": "John"}},
+    });
+    let memoized = RenderOptions {
+        memoize_paths: true,
+        ..RenderOptions::default()
+    };
+    let mut group = c.benchmark_group("repeated path");
+    let mut writer = Vec::new();
+    group.bench_function("without memoization", |b| b.iter(|| {
+        writer.clear();
+        template.render_no_partials(black_box(&data), &mut writer).unwrap();
+    }));
+    let mut memoized_writer = Vec::new();
+    group.bench_function("with memoization", |b| b.iter(|| {
+        memoized_writer.clear();
+        template.render_with_options(&(), black_box(&data), &memoized, &mut memoized_writer).unwrap();
+    }));
+    group.finish();
+    assert_eq!(writer, memoized_writer);
+}
+
+fn render_session(c: &mut Criterion) {
+    // a small status page re-rendered over and over, through
+    // a session which reuses its output buffer vs returning
+    // a freshly allocated String every time
+    let template = Template::parse("<ul>{{#services}}<li>{{name}}: {{#up}}up{{/up}}{{^up}}down{{/up}}</li>{{/services}}</ul>").unwrap();
+    let services: Vec<_> = (0..20)
+        .map(|i| json!({"name": format!("service{i}"), "up": i % 3 != 0}))
+        .collect();
+    let data = json!({"services": services});
+    let mut group = c.benchmark_group("re-render");
+    group.bench_function("render_to_string", |b| b.iter(|| {
+        black_box(template.render_to_string(&(), black_box(&data)).unwrap());
+    }));
+    let mut session = RenderSession::new(&template, &());
+    group.bench_function("RenderSession::render", |b| b.iter(|| {
+        black_box(session.render(black_box(&data)).unwrap());
+    }));
+    group.finish();
+}
+
+fn small_renders(c: &mut Criterion) {
+    // a tiny template with a few nested sections rendered
+    // over and over, so the fixed cost of every render,
+    // like setting up the scope stack, isn't drowned out
+    // by the cost of rendering the template itself
+    let template = Template::parse("{{#a}}{{#b}}{{#c}}{{d}}{{/c}}{{/b}}{{/a}}").unwrap();
+    let data = json!({"a": {"b": {"c": {"d": "deep"}}}});
+    let mut writer = Vec::new();
+    c.bench_function("small renders", |b| b.iter(|| {
+        writer.clear();
+        template.render_no_partials(black_box(&data), &mut writer).unwrap();
+    }));
+    assert_eq!(writer, b"deep");
+}
+
+criterion_group!(benches, missing_variables, unescaped_objects, escaped_objects, flat_struct, complex_template_to_sink, repeated_path, render_session, small_renders);
+criterion_main!(benches);
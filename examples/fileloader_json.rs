@@ -1,4 +1,4 @@
-use moostache::{FileLoader, LoaderConfig, TemplateLoader};
+use moostache::{FileLoader, LoaderConfig, ParseOptions, TemplateLoader};
 use std::error::Error;
 use serde_json::json;
 use indoc::indoc;
@@ -20,9 +20,13 @@ const TEMPLATES_DIRECTORY: &str = "./examples/templates/";
 
 fn main() -> Result<(), Box<dyn Error>> {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_directory: TEMPLATES_DIRECTORY,
-        templates_extension: "html",
+        templates_directory: TEMPLATES_DIRECTORY.into(),
+        templates_extension: "html".into(),
+        fallback_extensions: Vec::new(),
+        case_insensitive_extension: false,
         cache_size: 200,
+        cache_bytes: None,
+        parse_options: ParseOptions::default(),
     })?;
     let blog = json!({
         "title": "John's blog",
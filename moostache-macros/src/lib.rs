@@ -0,0 +1,68 @@
+//! Compile-time checked templates for [moostache](https://docs.rs/moostache).
+//! 
+//! The [`template!`] macro parses a template at compile time, so
+//! a malformed template fails the build instead of failing at
+//! runtime.
+#![deny(missing_docs)]
+#![warn(clippy::pedantic)]
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, LitStr};
+
+/// Checks that a string literal is a valid moostache template at
+/// compile time, and evaluates to a [`moostache::Template`].
+/// 
+/// The template is still compiled at runtime, but since it's known
+/// to be valid this can never fail.
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache_macros::template;
+/// use serde_json::json;
+/// 
+/// let template = template!("hello {{name}}!");
+/// let rendered = template.render_no_partials_to_string(&json!({"name": "John"})).unwrap();
+/// assert_eq!(rendered, "hello John!");
+/// ```
+/// 
+/// Helper sections, e.g. `{{# eq status "active" }}`, are
+/// accepted if the `helpers` feature is enabled:
+/// 
+/// ```rust
+/// # #[cfg(feature = "helpers")] {
+/// use moostache_macros::template;
+/// use serde_json::json;
+/// 
+/// let template = template!("{{#eq status \"active\"}}on{{/eq}} {{#range 1 3}}{{.}}{{/range}}");
+/// let rendered = template.render_no_partials_to_string(&json!({"status": "active"})).unwrap();
+/// assert_eq!(rendered, "on 12");
+/// # }
+/// ```
+/// 
+/// Malformed templates fail to compile:
+/// 
+/// ```rust,compile_fail
+/// use moostache_macros::template;
+/// 
+/// let template = template!("{{ bad%name }}");
+/// ```
+#[proc_macro]
+pub fn template(input: TokenStream) -> TokenStream {
+    let source = parse_macro_input!(input as LitStr);
+    if let Err(err) = moostache::Template::parse(source.value()) {
+        return syn::Error::new(source.span(), err)
+            .to_compile_error()
+            .into();
+    }
+    let expanded = format!(
+        "::moostache::Template::parse({}).expect(\"template checked at compile time\")",
+        source.token(),
+    );
+    match expanded.parse() {
+        Ok(tokens) => tokens,
+        Err(err) => syn::Error::new(source.span(), err)
+            .to_compile_error()
+            .into(),
+    }
+}
@@ -7,38 +7,55 @@
     clippy::needless_pass_by_value,
     clippy::enum_glob_use,
     clippy::enum_variant_names,
+    // the recursive parts of parsing and rendering are
+    // named after their entrypoints, e.g. _render
+    clippy::used_underscore_items,
 )]
 
 use fnv::FnvBuildHasher;
+#[cfg(feature = "fs")]
 use lru::LruCache;
-use serde::Serialize;
-use serde_json::json;
+use serde::{ser::Impossible, Serialize, Serializer};
+use serde_json::{json, value::Serializer as JsonSerializer};
 use winnow::{
-    ascii::multispace0,
-    combinator::{alt, cut_err, delimited, repeat, separated},
-    error::{AddContext, ErrMode, ErrorKind, ParserError as WParserError},
+    ascii::{digit1, multispace0, multispace1},
+    combinator::{alt, cut_err, delimited, opt, preceded, repeat, separated, terminated},
+    error::{AddContext, ErrMode, ModalResult, ParserError as WParserError},
     stream::{FindSlice, Stream},
     token::{literal, take_while},
-    PResult,
     Parser,
     Stateful,
 };
 use yoke::{Yoke, Yokeable};
 use std::{
     borrow::{Borrow, Cow},
-    cell::RefCell,
-    collections::HashMap,
+    cell::{OnceCell, RefCell},
+    collections::{HashMap, HashSet},
     fmt::{Debug, Display},
-    fs,
-    hash::{BuildHasher, BuildHasherDefault, Hash},
+    hash::{BuildHasher, Hash},
     io::{self, Write},
+    iter::Peekable,
+    ops::{BitOr, Deref, Range},
+    path::PathBuf,
+    str,
+    sync::atomic::{AtomicBool, Ordering},
+};
+#[cfg(feature = "fs")]
+use std::{
+    cell::Cell,
+    fs,
+    hash::BuildHasherDefault,
     num::NonZeroUsize,
-    ops::Deref,
-    path::{Path, PathBuf, MAIN_SEPARATOR_STR},
+    path::{Path, MAIN_SEPARATOR, MAIN_SEPARATOR_STR},
     rc::Rc,
-    str,
+    time::SystemTime,
 };
+#[cfg(feature = "fs")]
 use walkdir::WalkDir;
+#[cfg(feature = "zip")]
+use std::io::{Read, Seek};
+#[cfg(feature = "zip")]
+use zip::ZipArchive;
 
 #[cfg(test)]
 mod tests;
@@ -57,13 +74,99 @@ enum Fragment<'src> {
     UnescapedVariable(&'src str),
     Section(&'src str),
     InvertedSection(&'src str),
+    // the path of a {{^? path }}, its content is rendered
+    // only if the path doesn't exist, unlike an inverted
+    // section which is also rendered if it's falsy
+    MissingSection(&'src str),
+    // the {{|}} in {{# section }} ... {{|}} ... {{/ section }},
+    // its content is rendered only if the section before it
+    // wasn't, it's parsed as if it was a section of its own
+    SectionElse(&'src str),
+    // the comparison of a {{# eq path literal }} or a
+    // {{# ne path literal }}, e.g. eq status "active", its
+    // content is rendered only if the path's value is equal,
+    // or not equal, to the literal, see compare_section
+    CompareSection(&'src str),
+    // the path and alias of a section, e.g. posts as post, whose
+    // items are each rendered in a scope of their own where
+    // they're the value of the alias, see split_alias
+    AliasSection(&'src str),
+    // the bounds of a {{# range start end }}, e.g. range 1 5,
+    // its content is rendered once per number from the start
+    // up to but not including the end, see range_bounds
+    RangeSection(&'src str),
+    // the path of a {{# plural path }}, e.g. plural count, its
+    // content is rendered unless the path's value is a number
+    // whose magnitude is 1, see is_singular
+    PluralSection(&'src str),
+    // the path of a {{# with path }}, e.g. with user, its
+    // content is rendered once with the path's value as the
+    // scope, without iterating arrays, unless it's null
+    WithSection(&'src str),
     Partial(&'src str),
+    // the variable path of a {{>* path }}, which resolves
+    // to the name of the partial during render
+    DynamicPartial(&'src str),
+}
+
+impl<'src> Fragment<'src> {
+    // the str held by any fragment variant
+    fn as_str(&self) -> &'src str {
+        match self {
+            Fragment::Literal(s) |
+            Fragment::EscapedVariable(s) |
+            Fragment::UnescapedVariable(s) |
+            Fragment::Section(s) |
+            Fragment::InvertedSection(s) |
+            Fragment::MissingSection(s) |
+            Fragment::SectionElse(s) |
+            Fragment::CompareSection(s) |
+            Fragment::AliasSection(s) |
+            Fragment::RangeSection(s) |
+            Fragment::PluralSection(s) |
+            Fragment::WithSection(s) |
+            Fragment::Partial(s) |
+            Fragment::DynamicPartial(s) => s,
+        }
+    }
+    // same fragment variant but holding a different str
+    fn with_str<'a>(&self, s: &'a str) -> Fragment<'a> {
+        match self {
+            Fragment::Literal(_) => Fragment::Literal(s),
+            Fragment::EscapedVariable(_) => Fragment::EscapedVariable(s),
+            Fragment::UnescapedVariable(_) => Fragment::UnescapedVariable(s),
+            Fragment::Section(_) => Fragment::Section(s),
+            Fragment::InvertedSection(_) => Fragment::InvertedSection(s),
+            Fragment::MissingSection(_) => Fragment::MissingSection(s),
+            Fragment::SectionElse(_) => Fragment::SectionElse(s),
+            Fragment::CompareSection(_) => Fragment::CompareSection(s),
+            Fragment::AliasSection(_) => Fragment::AliasSection(s),
+            Fragment::RangeSection(_) => Fragment::RangeSection(s),
+            Fragment::PluralSection(_) => Fragment::PluralSection(s),
+            Fragment::WithSection(_) => Fragment::WithSection(s),
+            Fragment::Partial(_) => Fragment::Partial(s),
+            Fragment::DynamicPartial(_) => Fragment::DynamicPartial(s),
+        }
+    }
+    // if the fragment has a section skip, i.e.
+    // it starts a section or an else
+    fn is_section(&self) -> bool {
+        !matches!(
+            self,
+            Fragment::Literal(_) |
+            Fragment::EscapedVariable(_) |
+            Fragment::UnescapedVariable(_) |
+            Fragment::Partial(_) |
+            Fragment::DynamicPartial(_)
+        )
+    }
 }
 
 // We have a stateful parser, and that state
 // is maintained in this struct.
 #[derive(Debug)]
 struct State<'src, 'skips> {
+    options: ParseOptions,
     fragment_index: usize,
     section_index: usize,
     section_starts: Vec<SectionMeta<'src>>,
@@ -74,35 +177,71 @@ struct State<'src, 'skips> {
 // Mostly this is for checking that all sections which
 // are opened are correctly closed, and also for calculating
 // section skips.
-impl<'src, 'skips> State<'src, 'skips> {
+impl<'src> State<'src, '_> {
     fn visited_fragment(&mut self) {
         self.fragment_index += 1;
     }
-    fn visited_section_start(&mut self, name: &'src str) {
+    fn visited_section_start(&mut self, name: &'src str, inverted: bool) {
         self.section_starts.push(SectionMeta {
             name,
+            inverted,
+            has_else: false,
             section_index: self.section_index,
             fragment_index: self.fragment_index,
         });
         self.section_skips.push(SectionSkip {
             nested_sections: 0,
             nested_fragments: 0,
+            has_aggregates: false,
         });
         self.fragment_index += 1;
         self.section_index += 1;
     }
-    fn visited_section_end(&mut self, name: &'src str) -> Result<(), ()> {
+    // aggregates are computed for every section they're
+    // nested in, so mark all the sections which are open
+    fn visited_variable(&mut self, name: &str) {
+        if is_aggregate(split_width(name).0) {
+            for start in &self.section_starts {
+                self.section_skips[start.section_index].has_aggregates = true;
+            }
+        }
+        self.visited_fragment();
+    }
+    fn visited_section_end(&mut self, name: &'src str) -> Result<(), InternalError> {
         let start = self.section_starts
             .pop()
-            .ok_or(())?;
+            .ok_or(InternalError::ParseErrorMismatchedSectionEndTag)?;
         if start.name != name {
-            return Err(());
+            return Err(InternalError::ParseErrorMismatchedSectionEndTag);
+        }
+        self.close_section(&start)
+    }
+    // an else ends the section it's in, and then starts
+    // a section of its own which the section's end tag
+    // will end instead
+    fn visited_section_else(&mut self) -> Result<(), InternalError> {
+        let start = match self.section_starts.pop() {
+            Some(start) if !start.inverted => start,
+            _ => return Err(InternalError::ParseErrorElseOutsideSection),
+        };
+        if start.has_else {
+            return Err(InternalError::ParseErrorDuplicateElse);
         }
+        self.close_section(&start)?;
+        self.visited_section_start(start.name, false);
+        if let Some(start) = self.section_starts.last_mut() {
+            start.has_else = true;
+        }
+        Ok(())
+    }
+    // section skips are u16s, so sections can't
+    // have more than 65k nested sections or fragments
+    fn close_section(&mut self, start: &SectionMeta<'src>) -> Result<(), InternalError> {
         let skip = &mut self.section_skips[start.section_index];
         skip.nested_sections = u16::try_from((self.section_index - 1) - start.section_index)
-            .expect("can't have more than 65k sections within a section");
+            .map_err(|_| InternalError::ParseErrorTooManySections)?;
         skip.nested_fragments = u16::try_from((self.fragment_index - 1) - start.fragment_index)
-            .expect("can't have more than 65k fragments within a section");
+            .map_err(|_| InternalError::ParseErrorTooManyFragments)?;
         Ok(())
     }
     fn still_expecting_section_ends(&self) -> bool {
@@ -115,6 +254,8 @@ impl<'src, 'skips> State<'src, 'skips> {
 #[derive(Debug)]
 struct SectionMeta<'src> {
     name: &'src str,
+    inverted: bool,
+    has_else: bool,
     section_index: usize,
     fragment_index: usize,
 }
@@ -136,6 +277,9 @@ struct SectionMeta<'src> {
 struct SectionSkip {
     nested_sections: u16,
     nested_fragments: u16,
+    // if any variable nested within the section is an
+    // aggregate, e.g. {{ @count }}, see section_aggregates
+    has_aggregates: bool,
 }
 
 type Input<'src, 'skips> = Stateful<&'src str, State<'src, 'skips>>;
@@ -143,10 +287,15 @@ type Input<'src, 'skips> = Stateful<&'src str, State<'src, 'skips>>;
 // We can't do "impl Input { fn new()" because Input is a type
 // alias and not a newtype.
 #[inline]
-fn new_input<'src, 'skips>(template: &'src str, skips: &'skips mut Vec<SectionSkip>) -> Input<'src, 'skips> {
+fn new_input<'src, 'skips>(
+    template: &'src str,
+    skips: &'skips mut Vec<SectionSkip>,
+    options: ParseOptions,
+) -> Input<'src, 'skips> {
     Input {
         input: template,
         state: State {
+            options,
             fragment_index: 0,
             section_index: 0,
             section_starts: Vec::new(),
@@ -155,30 +304,47 @@ fn new_input<'src, 'skips>(template: &'src str, skips: &'skips mut Vec<SectionSk
     }
 }
 
-// parses a source string into a compiled Template
-fn parse<S: Into<Cow<'static, str>>>(source: S) -> Result<Template, InternalError> {
-    let source = match source.into() {
-        Cow::Owned(s) => Yoke::attach_to_cart(s, |s| &s[..]).wrap_cart_in_option(),
-        Cow::Borrowed(s) => Yoke::new_owned(s),
-    };
+// parses a source string into a compiled Template, borrowed
+// source strings are borrowed by the template for as long as
+// it lives and owned source strings are moved into it
+fn parse<'src, S: Into<Cow<'src, str>>>(source: S, options: ParseOptions) -> Result<BorrowedTemplate<'src>, InternalError> {
     let mut skips = Vec::new();
 
-    let fragments = source.try_map_project(|source, _| {
-        let input = new_input(source, &mut skips);
-        match _parse.parse(input) {
-            Ok(frags) => Ok(Fragments(frags)),
-            Err(err) => Err(err.into_inner()),
-        }
-    })?;
+    let fragments = match source.into() {
+        Cow::Owned(source) => TemplateFragments::Owned(Yoke::try_attach_to_cart(source, |source| {
+            parse_fragments(source, &mut skips, options).map(Fragments)
+        })?),
+        Cow::Borrowed(source) => TemplateFragments::Borrowed(
+            source,
+            parse_fragments(source, &mut skips, options)?,
+        ),
+    };
+
+    Ok(BorrowedTemplate { fragments, skips, delimiters: options.delimiters })
+}
 
-    Ok(Template { fragments, skips })
+// parses a source string into fragments which point into it
+fn parse_fragments<'src>(
+    source: &'src str,
+    skips: &mut Vec<SectionSkip>,
+    options: ParseOptions,
+) -> Result<Vec<Fragment<'src>>, InternalError> {
+    let Delimiters { open, close } = options.delimiters;
+    if [open, close].iter().any(|delimiter| delimiter.is_empty() || delimiter.contains(is_multispace)) {
+        return Err(InternalError::ParseErrorInvalidDelimiters);
+    }
+    let input = new_input(source, skips, options);
+    match _parse.parse(input) {
+        Ok(frags) => Ok(frags),
+        Err(err) => Err(err.into_inner()),
+    }
 }
 
 // parses a source string into a compiled Template
 #[inline]
 fn _parse<'src>(
     input: &mut Input<'src, '_>,
-) -> PResult<Vec<Fragment<'src>>, InternalError> {
+) -> ModalResult<Vec<Fragment<'src>>, InternalError> {
     if input.input.is_empty() {
         return Err(ErrMode::Cut(InternalError::ParseErrorNoContent));
     }
@@ -186,10 +352,19 @@ fn _parse<'src>(
     let frags = repeat(1.., alt((
         parse_literal.map(Some),
         parse_section_end.map(|()| None),
+        parse_compare_section_start.map(Some),
+        parse_range_section_start.map(Some),
+        parse_plural_section_start.map(Some),
+        parse_with_section_start.map(Some),
+        parse_alias_section_start.map(Some),
         parse_section_start.map(Some),
+        parse_missing_section_start.map(Some),
         parse_inverted_section_start.map(Some),
+        parse_section_else.map(Some),
         parse_unescaped_variable.map(Some),
         parse_comment.map(|()| None),
+        parse_raw_block,
+        parse_dynamic_partial.map(Some),
         parse_partial.map(Some),
         parse_escaped_variable.map(Some),
     )))
@@ -209,16 +384,35 @@ fn _parse<'src>(
     Ok(frags)
 }
 
+// parses the open delimiter of a tag, {{ by default, followed
+// by the tag's sigil, if any, e.g. # for {{#, see Delimiters
+fn tag_open<'src, 's>(sigil: &'static str) -> impl Parser<Input<'src, 's>, &'src str, InternalError> {
+    move |input: &mut Input<'src, 's>| {
+        let open = input.state.options.delimiters.open;
+        (literal(open), literal(sigil)).take().parse_next(input)
+    }
+}
+
+// parses the sigil of a tag, if any, e.g. } for }}}, followed
+// by the close delimiter, }} by default, see Delimiters
+fn tag_close<'src, 's>(sigil: &'static str) -> impl Parser<Input<'src, 's>, &'src str, InternalError> {
+    move |input: &mut Input<'src, 's>| {
+        let close = input.state.options.delimiters.close;
+        (literal(sigil), literal(close)).take().parse_next(input)
+    }
+}
+
 // parses a fragment literal, i.e. anything that doesn't begin with
-// {{, until it reaches a {{ or EOF
+// {{, until it reaches a {{ or EOF, or the open delimiter if it
+// isn't {{
 fn parse_literal<'src>(
     input: &mut Input<'src, '_>,
-) -> PResult<Fragment<'src>, InternalError> {
+) -> ModalResult<Fragment<'src>, InternalError> {
     if input.is_empty() {
         return Err(ErrMode::Backtrack(InternalError::ParseErrorGeneric));
     }
 
-    if let Some(range) = input.input.find_slice("{{") {
+    if let Some(range) = input.input.find_slice(input.state.options.delimiters.open) {
         if range.start == 0 {
             return Err(ErrMode::Backtrack(InternalError::ParseErrorGeneric));
         }
@@ -242,48 +436,133 @@ fn is_variable_name(c: char) -> bool {
 }
 
 // valid variable names must be at least 1 char long, and
-// must only contain valid variable chars
+// must only contain valid variable chars, in strict mode
+// they also can't start or end with a dash
 fn parse_variable_name<'src>(
     input: &mut Input<'src, '_>,
-) -> PResult<&'src str, InternalError> {
-    take_while(1.., is_variable_name)
-        .parse_next(input)
+) -> ModalResult<&'src str, InternalError> {
+    let name = take_while(1.., is_variable_name)
+        .parse_next(input)?;
+    if input.state.options.strict_variable_names && (name.starts_with('-') || name.ends_with('-')) {
+        return Err(ErrMode::Backtrack(InternalError::ParseErrorGeneric));
+    }
+    Ok(name)
 }
 
 // a variable "path" can potentially be several variable names
-// delimited by dots, e.g. some.variable.path
+// delimited by dots, e.g. some.variable.path, or in brackets,
+// e.g. some[0].path, which is the same as some.0.path, optionally starting
+// with ../ to look it up in the enclosing scope, or it can be
+// @index, see RenderOptions::repeat_number_sections, or an
+// aggregate, e.g. @count or @sum:price, see aggregate
 fn parse_variable_path<'src>(
     input: &mut Input<'src, '_>,
-) -> PResult<&'src str, InternalError> {
+) -> ModalResult<&'src str, InternalError> {
+    delimited(
+        multispace0,
+        parse_path,
+        multispace0,
+    )
+        .parse_next(input)
+}
+
+// same as parse_variable_path but without any
+// surrounding whitespace
+fn parse_path<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<&'src str, InternalError> {
+    alt((
+        (
+            opt(literal("../")),
+            alt((
+                (
+                    alt((parse_variable_name, parse_bracket_key)),
+                    repeat(0.., alt((
+                        preceded('.', parse_variable_name),
+                        parse_bracket_key,
+                    ))).map(|()| ()),
+                ).void(),
+                literal(".").void(),
+            )),
+        ).take(),
+        literal("@index"),
+        literal("@count"),
+        (
+            alt((literal("@sum:"), literal("@min:"), literal("@max:"))),
+            parse_variable_name,
+        ).take(),
+    ))
+        .parse_next(input)
+}
+
+// a variable name in brackets, e.g. [0] or [name], which is
+// resolved the same as a dotted name, see try_resolve_value
+fn parse_bracket_key<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<&'src str, InternalError> {
+    delimited('[', parse_variable_name, ']')
+        .parse_next(input)
+}
+
+// the variable path of a variable tag, which can be followed
+// by a width, e.g. some.variable:10 or some.variable:>10,
+// see Width
+fn parse_variable_tag_path<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<&'src str, InternalError> {
     delimited(
         multispace0,
-        alt((
-            separated(
-                1..,
-                parse_variable_name, 
-                '.'
-            ).map(|()| ()).take(),
-            literal("."),
-        )),
+        (
+            parse_path,
+            opt((
+                literal(":"),
+                opt(alt((literal("<"), literal(">")))),
+                digit1.verify(|digits: &str| digits.parse::<u16>().is_ok()),
+            )),
+        ).take(),
         multispace0,
     )
         .parse_next(input)
 }
 
+// a section path is a variable path which can also end with
+// a .* wildcard, e.g. some.object.*, so the section is
+// rendered once for every value of the object
+fn parse_section_path<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<&'src str, InternalError> {
+    alt((
+        delimited(
+            multispace0,
+            (
+                separated(
+                    1..,
+                    parse_variable_name,
+                    '.'
+                ).map(|()| ()),
+                literal(".*"),
+            ).take(),
+            multispace0,
+        ),
+        parse_variable_path,
+    ))
+        .parse_next(input)
+}
+
 // parses an escaped variable, e.g. {{ some.variable }}
 fn parse_escaped_variable<'src>(
     input: &mut Input<'src, '_>,
-) -> PResult<Fragment<'src>, InternalError> {
+) -> ModalResult<Fragment<'src>, InternalError> {
     let result = delimited(
-        literal("{{"),
-        cut_err(parse_variable_path),
-        cut_err(literal("}}"))
+        tag_open(""),
+        cut_err(parse_variable_tag_path),
+        cut_err(tag_close(""))
     )
         .context(InternalError::ParseErrorInvalidEscapedVariableTag)
         .parse_next(input)
         .map(Fragment::EscapedVariable);
-    if result.is_ok() {
-        input.state.visited_fragment();
+    if let Ok(frag) = &result {
+        input.state.visited_variable(frag.as_str());
     }
     result
 }
@@ -291,17 +570,17 @@ fn parse_escaped_variable<'src>(
 // parses an unescaped variable, e.g. {{{ some.variable }}}
 fn parse_unescaped_variable<'src>(
     input: &mut Input<'src, '_>,
-) -> PResult<Fragment<'src>, InternalError> {
+) -> ModalResult<Fragment<'src>, InternalError> {
     let result = delimited(
-        literal("{{{"),
-        cut_err(parse_variable_path),
-        cut_err(literal("}}}"))
+        tag_open("{"),
+        cut_err(parse_variable_tag_path),
+        cut_err(tag_close("}"))
     )
         .context(InternalError::ParseErrorInvalidUnescapedVariableTag)
         .parse_next(input)
         .map(Fragment::UnescapedVariable);
-    if result.is_ok() {
-        input.state.visited_fragment();
+    if let Ok(frag) = &result {
+        input.state.visited_variable(frag.as_str());
     }
     result
 }
@@ -309,10 +588,12 @@ fn parse_unescaped_variable<'src>(
 // parses a comment, e.g. {{! comment }}
 fn parse_comment(
     input: &mut Input<'_, '_>
-) -> PResult<(), InternalError> {
-    if input.input.starts_with("{{!") {
-        if let Some(range) = input.input.find_slice("}}") {
-            input.input = &input.input[range.end..];
+) -> ModalResult<(), InternalError> {
+    let Delimiters { open, close } = input.state.options.delimiters;
+    if input.input.strip_prefix(open).is_some_and(|rest| rest.starts_with('!')) {
+        let offset = open.len() + 1;
+        if let Some(index) = input.input[offset..].find(close) {
+            input.input = &input.input[offset + index + close.len()..];
             return Ok(());
         }
         return Err(ErrMode::Cut(InternalError::ParseErrorInvalidCommentTag));
@@ -320,19 +601,65 @@ fn parse_comment(
     Err(ErrMode::Backtrack(InternalError::ParseErrorGeneric))
 }
 
+// is this char whitespace according to multispace0
+#[inline]
+fn is_multispace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n')
+}
+
+// parses a raw block, e.g. {{% raw %}} {{ not a tag }} {{% endraw %}},
+// everything between the start and end tags is kept as a literal,
+// raw blocks don't nest so the first end tag always ends the block
+// and there's no way to put an end tag inside a raw block
+fn parse_raw_block<'src>(
+    input: &mut Input<'src, '_>
+) -> ModalResult<Option<Fragment<'src>>, InternalError> {
+    (
+        tag_open("%"),
+        cut_err((multispace0, literal("raw"), multispace0, tag_close("%"))),
+    )
+        .context(InternalError::ParseErrorInvalidRawBlock)
+        .parse_next(input)?;
+
+    let Delimiters { open, close } = input.state.options.delimiters;
+    let mut offset = 0;
+    while let Some(index) = input.input[offset..].find(open) {
+        let start = offset + index;
+        offset = start + open.len();
+        let end_tag = input.input[offset..]
+            .strip_prefix('%')
+            .map(|rest| rest.trim_start_matches(is_multispace))
+            .and_then(|rest| rest.strip_prefix("endraw"))
+            .map(|rest| rest.trim_start_matches(is_multispace))
+            .and_then(|rest| rest.strip_prefix('%'))
+            .and_then(|rest| rest.strip_prefix(close));
+        if let Some(rest) = end_tag {
+            let raw = &input.input[..start];
+            input.input = rest;
+            if raw.is_empty() {
+                return Ok(None);
+            }
+            input.state.visited_fragment();
+            return Ok(Some(Fragment::Literal(raw)));
+        }
+    }
+
+    Err(ErrMode::Cut(InternalError::ParseErrorInvalidRawBlock))
+}
+
 // parses a section start, e.g. {{# section.start }}
 fn parse_section_start<'src>(
     input: &mut Input<'src, '_>
-) -> PResult<Fragment<'src>, InternalError> {
+) -> ModalResult<Fragment<'src>, InternalError> {
     let variable = delimited(
-        literal("{{#"),
-        cut_err(parse_variable_path),
-        cut_err(literal("}}")),
+        tag_open("#"),
+        cut_err(parse_section_path),
+        cut_err(tag_close("")),
     )
         .context(InternalError::ParseErrorInvalidSectionStartTag)
         .parse_next(input)?;
 
-    input.state.visited_section_start(variable);
+    input.state.visited_section_start(variable, false);
 
     Ok(Fragment::Section(variable))
 }
@@ -340,35 +667,227 @@ fn parse_section_start<'src>(
 // parses an inverted section start, e.g. {{^ inverted.section.start }}
 fn parse_inverted_section_start<'src>(
     input: &mut Input<'src, '_>,
-) -> PResult<Fragment<'src>, InternalError> {
+) -> ModalResult<Fragment<'src>, InternalError> {
     let variable = delimited(
-        literal("{{^"),
+        tag_open("^"),
         cut_err(parse_variable_path),
-        cut_err(literal("}}")),
+        cut_err(tag_close("")),
     )
         .context(InternalError::ParseErrorInvalidInvertedSectionStartTag)
         .parse_next(input)?;
 
-    input.state.visited_section_start(variable);
+    input.state.visited_section_start(variable, true);
 
     Ok(Fragment::InvertedSection(variable))
 }
 
+// parses a missing section start, e.g. {{^? missing.section.start }}
+fn parse_missing_section_start<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<Fragment<'src>, InternalError> {
+    let variable = delimited(
+        (tag_open("^"), multispace0, literal("?")),
+        cut_err(parse_variable_path),
+        cut_err(tag_close("")),
+    )
+        .context(InternalError::ParseErrorInvalidInvertedSectionStartTag)
+        .parse_next(input)?;
+
+    input.state.visited_section_start(variable, true);
+
+    Ok(Fragment::MissingSection(variable))
+}
+
+// parses an equality section start, e.g. {{# eq status "active" }}
+// or {{# ne count 0 }}, requires the helpers feature, sections
+// named eq or ne, e.g. {{# eq }}, are still parsed as sections
+fn parse_compare_section_start<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<Fragment<'src>, InternalError> {
+    if !cfg!(feature = "helpers") {
+        return Err(ErrMode::Backtrack(InternalError::ParseErrorGeneric));
+    }
+    let ((op, ..), comparison) = delimited(
+        (tag_open("#"), multispace0),
+        (
+            alt((literal("eq"), literal("ne"))),
+            multispace1,
+            parse_path,
+            multispace1,
+            cut_err(parse_compare_literal),
+        ).with_taken(),
+        cut_err((multispace0, tag_close(""))),
+    )
+        .context(InternalError::ParseErrorInvalidSectionStartTag)
+        .parse_next(input)?;
+
+    input.state.visited_section_start(op, false);
+
+    Ok(Fragment::CompareSection(comparison))
+}
+
+// parses a range section start, e.g. {{# range 1 5 }} or
+// {{# range 0 count }}, requires the helpers feature, sections
+// named range, e.g. {{# range }}, are still parsed as sections
+fn parse_range_section_start<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<Fragment<'src>, InternalError> {
+    if !cfg!(feature = "helpers") {
+        return Err(ErrMode::Backtrack(InternalError::ParseErrorGeneric));
+    }
+    let ((op, ..), range) = delimited(
+        (tag_open("#"), multispace0),
+        (
+            literal("range"),
+            multispace1,
+            parse_range_bound,
+            multispace1,
+            cut_err(parse_range_bound),
+        ).with_taken(),
+        cut_err((multispace0, tag_close(""))),
+    )
+        .context(InternalError::ParseErrorInvalidSectionStartTag)
+        .parse_next(input)?;
+
+    input.state.visited_section_start(op, false);
+
+    Ok(Fragment::RangeSection(range))
+}
+
+// parses a plural section start, e.g. {{# plural count }},
+// requires the helpers feature, sections named plural, e.g.
+// {{# plural }}, and alias sections, e.g. {{# plural as p }},
+// are still parsed as sections
+fn parse_plural_section_start<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<Fragment<'src>, InternalError> {
+    if !cfg!(feature = "helpers") {
+        return Err(ErrMode::Backtrack(InternalError::ParseErrorGeneric));
+    }
+    let ((op, ..), plural) = terminated(
+        preceded(
+            (tag_open("#"), multispace0),
+            (literal("plural"), multispace1, parse_path).with_taken(),
+        ),
+        (multispace0, tag_close("")),
+    )
+        .context(InternalError::ParseErrorInvalidSectionStartTag)
+        .parse_next(input)?;
+
+    input.state.visited_section_start(op, false);
+
+    Ok(Fragment::PluralSection(plural))
+}
+
+// parses a with section start, e.g. {{# with user }},
+// requires the helpers feature, sections named with, e.g.
+// {{# with }}, and alias sections, e.g. {{# with as w }},
+// are still parsed as sections
+fn parse_with_section_start<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<Fragment<'src>, InternalError> {
+    if !cfg!(feature = "helpers") {
+        return Err(ErrMode::Backtrack(InternalError::ParseErrorGeneric));
+    }
+    let ((op, ..), with) = terminated(
+        preceded(
+            (tag_open("#"), multispace0),
+            (literal("with"), multispace1, parse_path).with_taken(),
+        ),
+        (multispace0, tag_close("")),
+    )
+        .context(InternalError::ParseErrorInvalidSectionStartTag)
+        .parse_next(input)?;
+
+    input.state.visited_section_start(op, false);
+
+    Ok(Fragment::WithSection(with))
+}
+
+// a bound of a range section, which can be an integer,
+// e.g. -1 or 5, or the path of a variable holding one
+fn parse_range_bound<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<&'src str, InternalError> {
+    alt((
+        (opt('-'), digit1).take(),
+        parse_path,
+    ))
+        .parse_next(input)
+}
+
+// parses an alias section start, e.g. {{# posts as post }},
+// which is ended by its path alone, e.g. {{/ posts }}
+fn parse_alias_section_start<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<Fragment<'src>, InternalError> {
+    let ((path, ..), aliased) = delimited(
+        (tag_open("#"), multispace0),
+        (
+            parse_path,
+            multispace1,
+            literal("as"),
+            multispace1,
+            cut_err(parse_variable_name),
+        ).with_taken(),
+        cut_err((multispace0, tag_close(""))),
+    )
+        .context(InternalError::ParseErrorInvalidSectionStartTag)
+        .parse_next(input)?;
+
+    input.state.visited_section_start(path, false);
+
+    Ok(Fragment::AliasSection(aliased))
+}
+
+// the literal of an equality section, which can be a string
+// without quotes or backslashes in it, a number, true, false,
+// or null, e.g. "active", -1.5, or true
+fn parse_compare_literal<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<&'src str, InternalError> {
+    alt((
+        ('"', take_while(0.., |c| c != '"' && c != '\\'), '"').take(),
+        (opt('-'), digit1, opt(('.', digit1))).take(),
+        literal("true"),
+        literal("false"),
+        literal("null"),
+    ))
+        .parse_next(input)
+}
+
+// parses a section else, e.g. {{|}}, which splits a section
+// into what's rendered if it's truthy and what's rendered if
+// it's falsy, e.g. {{# section }} truthy {{|}} falsy {{/ section }}
+fn parse_section_else<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<Fragment<'src>, InternalError> {
+    let bar = delimited(
+        (tag_open(""), multispace0),
+        literal("|"),
+        cut_err((multispace0, tag_close(""))),
+    )
+        .context(InternalError::ParseErrorInvalidElseTag)
+        .parse_next(input)?;
+
+    input.state.visited_section_else().map_err(ErrMode::Cut)?;
+
+    Ok(Fragment::SectionElse(bar))
+}
+
 // parses a section end, e.g. {{/ section.end }}
 fn parse_section_end(
     input: &mut Input<'_, '_>,
-) -> PResult<(), InternalError> {
+) -> ModalResult<(), InternalError> {
     let variable = delimited(
-        literal("{{/"),
-        cut_err(parse_variable_path),
-        cut_err(literal("}}")),
+        tag_open("/"),
+        cut_err(parse_section_path),
+        cut_err(tag_close("")),
     )
         .context(InternalError::ParseErrorInvalidSectionEndTag)
         .parse_next(input)?;
 
-    if input.state.visited_section_end(variable).is_err() {
-        return Err(ErrMode::Cut(InternalError::ParseErrorMismatchedSectionEndTag));
-    }
+    input.state.visited_section_end(variable).map_err(ErrMode::Cut)?;
 
     Ok(())
 }
@@ -424,7 +943,7 @@ fn is_file_name(c: char) -> bool {
 // mustache tags), " " (whitespace, used as a delimiter)
 fn parse_file_name<'src>(
     input: &mut Input<'src, '_>
-) -> PResult<&'src str, InternalError> {
+) -> ModalResult<&'src str, InternalError> {
     take_while(1.., is_file_name)
         .parse_next(input)
 }
@@ -433,7 +952,7 @@ fn parse_file_name<'src>(
 // by slashes, e.g. some/file/path
 fn parse_file_path<'src>(
     input: &mut Input<'src, '_>,
-) -> PResult<&'src str, InternalError> {
+) -> ModalResult<&'src str, InternalError> {
     delimited(
         multispace0,
         separated(
@@ -449,11 +968,11 @@ fn parse_file_path<'src>(
 // parses a partial, e.g. {{> some/file/path }}
 fn parse_partial<'src>(
     input: &mut Input<'src, '_>,
-) -> PResult<Fragment<'src>, InternalError> {
+) -> ModalResult<Fragment<'src>, InternalError> {
     let result = delimited(
-        literal("{{>"),
+        tag_open(">"),
         cut_err(parse_file_path),
-        cut_err(literal("}}")),
+        cut_err(tag_close("")),
     )
         .context(InternalError::ParseErrorInvalidPartialTag)
         .parse_next(input)
@@ -464,6 +983,93 @@ fn parse_partial<'src>(
     result
 }
 
+// parses a dynamic partial, e.g. {{>* some.variable }}, where
+// the partial's name is the variable's value
+fn parse_dynamic_partial<'src>(
+    input: &mut Input<'src, '_>,
+) -> ModalResult<Fragment<'src>, InternalError> {
+    let result = delimited(
+        (tag_open(">"), multispace0, literal("*")),
+        cut_err(parse_variable_path),
+        cut_err(tag_close("")),
+    )
+        .context(InternalError::ParseErrorInvalidPartialTag)
+        .parse_next(input)
+        .map(Fragment::DynamicPartial);
+    if result.is_ok() {
+        input.state.visited_fragment();
+    }
+    result
+}
+
+/// Options which change how templates are parsed.
+/// See [`Template::parse_with_options`].
+/// 
+/// ### Examples
+/// 
+/// [`ParseOptions`] default values:
+/// 
+/// ```rust
+/// use moostache::{Delimiters, ParseOptions};
+/// 
+/// assert_eq!(
+///     ParseOptions::default(),
+///     ParseOptions {
+///         strict_variable_names: false,
+///         delimiters: Delimiters { open: "{{", close: "}}" },
+///     },
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// If variable names, e.g. the names in {{ some.variable }},
+    /// can't start or end with a dash, e.g. {{ some.-variable }},
+    /// to catch mistakes in templates. Otherwise dashes are
+    /// allowed anywhere in variable names.
+    pub strict_variable_names: bool,
+    /// Delimiters of every tag in the template. See [`Delimiters`].
+    pub delimiters: Delimiters,
+}
+
+/// The delimiters tags start and end with, {{ and }} by default.
+/// With other delimiters, e.g. `[[` and `]]`, every tag uses them
+/// instead, e.g. `[[ variable ]]`, `[[{ variable }]]`, `[[# section ]]`,
+/// `[[> partial ]]`, or `[[% raw %]]`, and {{ is just text, for
+/// embedding templates in files which already use {{, e.g. Vue
+/// templates. Delimiters can't be empty or have whitespace in
+/// them. See [`ParseOptions::delimiters`].
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::{Delimiters, ParseOptions, Template};
+/// use serde_json::json;
+/// 
+/// let options = ParseOptions {
+///     delimiters: Delimiters { open: "[[", close: "]]" },
+///     ..ParseOptions::default()
+/// };
+/// let template = Template::parse_with_options("<p>{{ vue }} [[ name ]]</p>", &options).unwrap();
+/// let rendered = template.render_no_partials_to_string(&json!({"name": "John"})).unwrap();
+/// assert_eq!(rendered, "<p>{{ vue }} John</p>");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Delimiters {
+    /// Delimiter tags start with, e.g. {{.
+    pub open: &'static str,
+    /// Delimiter tags end with, e.g. }}.
+    pub close: &'static str,
+}
+
+impl Default for Delimiters {
+    fn default() -> Self {
+        Self {
+            open: "{{",
+            close: "}}",
+        }
+    }
+}
+
 /// A compiled moostache template.
 /// 
 /// ### Examples
@@ -477,942 +1083,7102 @@ fn parse_partial<'src>(
 /// let rendered = template.render_no_partials_to_string(&data).unwrap();
 /// assert_eq!(rendered, "hello John!");
 /// ```
-pub struct Template {
+pub type Template = BorrowedTemplate<'static>;
+
+/// A compiled moostache template which may borrow its
+/// source string for `'src`, useful for parsing short-lived
+/// templates from strings the caller owns without copying
+/// them. Templates which don't borrow anything are
+/// [`Template`]s.
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::BorrowedTemplate;
+/// use serde_json::json;
+/// 
+/// let source = String::from("hello {{name}}!");
+/// let template = BorrowedTemplate::parse(source.as_str()).unwrap();
+/// let data = json!({"name": "John"});
+/// let rendered = template.render_no_partials_to_string(&data).unwrap();
+/// assert_eq!(rendered, "hello John!");
+/// ```
+pub struct BorrowedTemplate<'src> {
     // parsed template fragments
-    fragments: Yoke<Fragments<'static>, Option<String>>,
+    fragments: TemplateFragments<'src>,
     // parsed section skips, i.e. tell us where sections end
     skips: Vec<SectionSkip>,
+    // delimiters the template was parsed with, to find
+    // where its tags start and end in its source
+    delimiters: Delimiters,
+}
+// parsed template fragments and the source string they point
+// into, which is either borrowed or owned by the template
+enum TemplateFragments<'src> {
+    Borrowed(&'src str, Vec<Fragment<'src>>),
+    Owned(Yoke<Fragments<'static>, String>),
 }
 #[derive(Yokeable)]
 struct Fragments<'src>(Vec<Fragment<'src>>);
 
-impl Debug for Template {
+impl Debug for BorrowedTemplate<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Template")
-            .field("fragments", &self.fragments.get().0)
+            .field("fragments", &self.frags())
             .field("skips", &self.skips)
-            .finish()
+            .finish_non_exhaustive()
     }
 }
-impl PartialEq for Template {
+impl PartialEq for BorrowedTemplate<'_> {
     fn eq(&self, other: &Self) -> bool {
-        self.fragments.get().0 == other.fragments.get().0 && self.skips == other.skips
+        self.frags() == other.frags() && self.skips == other.skips
     }
 }
 
-impl Template {
-    /// Parse a [`&'static str`](std::str) or [`String`] into a compiled
-    /// moostache template.
+impl<'src> BorrowedTemplate<'src> {
+    // parsed template fragments
+    fn frags(&self) -> &[Fragment<'_>] {
+        match &self.fragments {
+            TemplateFragments::Borrowed(_, frags) => frags,
+            TemplateFragments::Owned(frags) => &frags.get().0,
+        }
+    }
+
+    /// The source string this template was parsed from. For
+    /// templates returned by [`Template::flatten`] this is the
+    /// text of all their fragments, since they weren't parsed.
     /// 
-    /// ### Errors
+    /// ### Examples
     /// 
-    /// Returns a [`MoostacheError`] parse error enum variant
-    /// if parsing fails for whatever reason.
-    #[inline]
-    pub fn parse<S: Into<Cow<'static, str>>>(source: S) -> Result<Template, MoostacheError> {
-        match parse(source) {
-            Err(err) => {
-                Err(MoostacheError::from_internal(err, String::new()))
-            },
-            Ok(template) => Ok(template),
+    /// ```rust
+    /// use moostache::Template;
+    /// 
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// assert_eq!(template.source(), "hello {{name}}!");
+    /// ```
+    #[must_use]
+    pub fn source(&self) -> &str {
+        match &self.fragments {
+            TemplateFragments::Borrowed(source, _) => source,
+            TemplateFragments::Owned(frags) => frags.backing_cart(),
         }
     }
 
-    /// Render this template.
+    /// How many partial tags, e.g. {{> partial }}, this template
+    /// directly contains. A partial referenced twice is counted
+    /// twice, and dynamic partials, e.g. {{>* name }}, aren't
+    /// counted since what they reference is only known during
+    /// renders. Templates with a count of zero can be rendered
+    /// without a loader, see [`Template::render_no_partials`].
     /// 
-    /// ### Errors
+    /// ### Examples
     /// 
-    /// If using [`HashMapLoader`] or [`FileLoader`] this function
-    /// can return any enum variant of [`MoostacheError`].
-    #[inline]
-    pub fn render<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
-        &self,
-        loader: &T,
-        value: &serde_json::Value,
-        writer: &mut W,
-    ) -> Result<(), T::Error> {
-        let mut scopes = Vec::new();
-        scopes.push(value);
-        _render(
-            &self.fragments.get().0,
-            &self.skips,
-            loader,
-            &mut scopes,
-            writer
-        )
+    /// ```rust
+    /// use moostache::Template;
+    /// 
+    /// let template = Template::parse("{{> header }}{{ body }}{{> footer }}").unwrap();
+    /// assert_eq!(template.partial_count(), 2);
+    /// ```
+    #[must_use]
+    pub fn partial_count(&self) -> usize {
+        self.partials().count()
     }
 
-    /// Render this template given a type that impls
-    /// [`serde::Serialize`].
+    // approximately how many bytes of memory this template
+    // uses, i.e. its source and its fragments and skips
+    #[cfg(feature = "fs")]
+    fn approx_bytes(&self) -> usize {
+        size_of::<Self>()
+            + self.source().len()
+            + size_of_val(self.frags())
+            + size_of_val(self.skips.as_slice())
+    }
+
+    // byte offset of a str pointing into the source string
+    fn source_offset(&self, s: &str) -> usize {
+        let offset = (s.as_ptr() as usize).wrapping_sub(self.source().as_ptr() as usize);
+        debug_assert!(offset + s.len() <= self.source().len());
+        offset
+    }
+
+    /// Inspect the fragments this template was parsed into, in
+    /// order of appearance. Comments and section end tags aren't
+    /// fragments, and raw blocks are parsed into literals.
     /// 
-    /// ### Errors
+    /// ### Examples
     /// 
-    /// If using [`HashMapLoader`] or [`FileLoader`] this function
-    /// can return any enum variant of [`MoostacheError`].
-    #[inline]
-    pub fn render_serializable<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write, S: Serialize>(
-        &self,
-        loader: &T,
-        serializeable: &S,
-        writer: &mut W,
-    ) -> Result<(), T::Error> {
-        let value = serde_json::to_value(serializeable)
-            .map_err(|_| MoostacheError::SerializationError)?;
-        self.render(
-            loader,
-            &value,
-            writer
-        )
+    /// ```rust
+    /// use moostache::{FragmentInfo, FragmentKind, Template};
+    /// 
+    /// let template = Template::parse("hi {{ name }}!").unwrap();
+    /// assert_eq!(
+    ///     template.inspect(),
+    ///     vec![
+    ///         FragmentInfo {
+    ///             kind: FragmentKind::Literal("hi ".to_owned()),
+    ///             span: 0..3,
+    ///         },
+    ///         FragmentInfo {
+    ///             kind: FragmentKind::Variable {
+    ///                 name: "name".to_owned(),
+    ///                 escaped: true,
+    ///             },
+    ///             span: 3..13,
+    ///         },
+    ///         FragmentInfo {
+    ///             kind: FragmentKind::Literal("!".to_owned()),
+    ///             span: 13..14,
+    ///         },
+    ///     ],
+    /// );
+    /// ```
+    #[must_use]
+    pub fn inspect(&self) -> Vec<FragmentInfo> {
+        let frags = self.frags();
+        let mut infos = Vec::with_capacity(frags.len());
+        let mut section_idx = 0;
+        for frag in frags {
+            let span = self.fragment_span(frag);
+            let kind = match frag {
+                Fragment::Literal(literal) => FragmentKind::Literal((*literal).to_owned()),
+                Fragment::EscapedVariable(name) | Fragment::UnescapedVariable(name) => FragmentKind::Variable {
+                    name: split_width(name).0.to_owned(),
+                    escaped: matches!(frag, Fragment::EscapedVariable(_)),
+                },
+                Fragment::Section(name) | Fragment::InvertedSection(name) => {
+                    let nested = self.skips[section_idx].nested_fragments as usize;
+                    section_idx += 1;
+                    FragmentKind::SectionStart {
+                        name: (*name).to_owned(),
+                        inverted: matches!(frag, Fragment::InvertedSection(_)),
+                        nested,
+                    }
+                },
+                Fragment::MissingSection(name) => {
+                    let nested = self.skips[section_idx].nested_fragments as usize;
+                    section_idx += 1;
+                    FragmentKind::MissingSectionStart {
+                        name: (*name).to_owned(),
+                        nested,
+                    }
+                },
+                Fragment::SectionElse(_) => {
+                    let nested = self.skips[section_idx].nested_fragments as usize;
+                    section_idx += 1;
+                    FragmentKind::SectionElse { nested }
+                },
+                Fragment::CompareSection(comparison) => {
+                    let nested = self.skips[section_idx].nested_fragments as usize;
+                    section_idx += 1;
+                    let (negated, name, literal) = split_comparison(comparison);
+                    FragmentKind::CompareSectionStart {
+                        name: name.to_owned(),
+                        literal: literal.to_owned(),
+                        negated,
+                        nested,
+                    }
+                },
+                Fragment::AliasSection(aliased) => {
+                    let nested = self.skips[section_idx].nested_fragments as usize;
+                    section_idx += 1;
+                    let (name, alias) = split_alias(aliased);
+                    FragmentKind::AliasSectionStart {
+                        name: name.to_owned(),
+                        alias: alias.unwrap_or_default().to_owned(),
+                        nested,
+                    }
+                },
+                Fragment::RangeSection(range) => {
+                    let nested = self.skips[section_idx].nested_fragments as usize;
+                    section_idx += 1;
+                    let (start, end) = split_range(range);
+                    FragmentKind::RangeSectionStart {
+                        start: start.to_owned(),
+                        end: end.to_owned(),
+                        nested,
+                    }
+                },
+                Fragment::PluralSection(plural) => {
+                    let nested = self.skips[section_idx].nested_fragments as usize;
+                    section_idx += 1;
+                    FragmentKind::PluralSectionStart {
+                        name: plural_path(plural).to_owned(),
+                        nested,
+                    }
+                },
+                Fragment::WithSection(with) => {
+                    let nested = self.skips[section_idx].nested_fragments as usize;
+                    section_idx += 1;
+                    FragmentKind::WithSectionStart {
+                        name: with_path(with).to_owned(),
+                        nested,
+                    }
+                },
+                Fragment::Partial(path) => FragmentKind::Partial((*path).to_owned()),
+                Fragment::DynamicPartial(name) => FragmentKind::DynamicPartial((*name).to_owned()),
+            };
+            infos.push(FragmentInfo { kind, span });
+        }
+        infos
     }
 
-    /// Render this template, assuming it has no partial tags.
+    // span of a fragment in the source, for tags it's widened
+    // from the name to the whole tag, names can only be
+    // surrounded by whitespace and sigils so the nearest
+    // braces are the tag's braces, or delimiters
+    fn fragment_span(&self, frag: &Fragment<'_>) -> Range<usize> {
+        let source = self.source();
+        let s = frag.as_str();
+        let start = self.source_offset(s);
+        let end = start + s.len();
+        let Delimiters { open, close } = self.delimiters;
+        let (open, close) = match frag {
+            Fragment::Literal(_) => return start..end,
+            Fragment::UnescapedVariable(_) => (Cow::Owned(format!("{open}{{")), Cow::Owned(format!("}}{close}"))),
+            _ => (Cow::Borrowed(open), Cow::Borrowed(close)),
+        };
+        let tag_start = source[..start].rfind(&*open).unwrap_or(start);
+        let tag_end = source[end..].find(&*close).map_or(end, |idx| end + idx + close.len());
+        tag_start..tag_end
+    }
+
+    /// Returns an indented outline of this template's
+    /// fragments, one per line, where the content of every
+    /// section is indented one level deeper than the section
+    /// itself. Literals are quoted. Useful for debugging
+    /// why a section isn't rendering as expected.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// 
+    /// let template = Template::parse("{{#a}}hi {{b}}{{|}}{{> c}}{{/a}}!").unwrap();
+    /// assert_eq!(
+    ///     template.debug_tree(),
+    ///     "{{# a }}\n  \"hi \"\n  {{ b }}\n{{|}}\n  {{> c }}\n{{/ a }}\n\"!\"\n",
+    /// );
+    /// ```
+    #[must_use]
+    pub fn debug_tree(&self) -> String {
+        let frags = self.frags();
+        let mut tree = String::new();
+        let mut push_line = |depth: usize, line: String| {
+            for _ in 0..depth {
+                tree.push_str("  ");
+            }
+            tree.push_str(&line);
+            tree.push('\n');
+        };
+        // the fragment index each open section ends at, and
+        // its name, innermost section last
+        let mut open: Vec<(usize, &str)> = Vec::new();
+        let mut section_idx = 0;
+        // one past the last fragment to end any sections
+        // which are still open
+        for frag_idx in 0..=frags.len() {
+            let frag = frags.get(frag_idx);
+            // an else replaces the end of the section it's in,
+            // which is the outermost of the sections ending here
+            let mut else_of = None;
+            while let Some(&(end, name)) = open.last() {
+                if end != frag_idx {
+                    break;
+                }
+                open.pop();
+                if matches!(frag, Some(Fragment::SectionElse(_))) {
+                    if let Some(inner) = else_of.replace(name) {
+                        push_line(open.len() + 1, format!("{{{{/ {inner} }}}}"));
+                    }
+                } else {
+                    push_line(open.len(), format!("{{{{/ {name} }}}}"));
+                }
+            }
+            let Some(frag) = frag else {
+                break;
+            };
+            let line = match frag {
+                Fragment::Literal(literal) => format!("{literal:?}"),
+                Fragment::EscapedVariable(name) => format!("{{{{ {name} }}}}"),
+                Fragment::UnescapedVariable(name) => format!("{{{{{{ {name} }}}}}}"),
+                Fragment::Section(name) => format!("{{{{# {name} }}}}"),
+                Fragment::InvertedSection(name) => format!("{{{{^ {name} }}}}"),
+                Fragment::MissingSection(name) => format!("{{{{^? {name} }}}}"),
+                Fragment::SectionElse(_) => "{{|}}".to_owned(),
+                Fragment::CompareSection(comparison) => format!("{{{{# {comparison} }}}}"),
+                Fragment::AliasSection(aliased) => format!("{{{{# {aliased} }}}}"),
+                Fragment::RangeSection(range) => format!("{{{{# {range} }}}}"),
+                Fragment::PluralSection(plural) => format!("{{{{# plural {} }}}}", plural_path(plural)),
+                Fragment::WithSection(with) => format!("{{{{# with {} }}}}", with_path(with)),
+                Fragment::Partial(path) => format!("{{{{> {path} }}}}"),
+                Fragment::DynamicPartial(name) => format!("{{{{>* {name} }}}}"),
+            };
+            push_line(open.len(), line);
+            let opened = match frag {
+                Fragment::Section(name) |
+                Fragment::InvertedSection(name) |
+                Fragment::MissingSection(name) => Some(*name),
+                Fragment::CompareSection(comparison) => Some(&comparison[..2]),
+                Fragment::AliasSection(aliased) => Some(split_alias(aliased).0),
+                Fragment::RangeSection(_) => Some("range"),
+                Fragment::PluralSection(_) => Some("plural"),
+                Fragment::WithSection(_) => Some("with"),
+                Fragment::SectionElse(_) => else_of,
+                _ => None,
+            };
+            if let Some(name) = opened {
+                open.push((frag_idx + 1 + self.skips[section_idx].nested_fragments as usize, name));
+                section_idx += 1;
+            }
+        }
+        tree
+    }
+
+    // names of all partials directly referenced
+    // by this template, in order of appearance
+    fn partials(&self) -> impl Iterator<Item = &str> {
+        self.frags().iter().filter_map(|frag| match frag {
+            Fragment::Partial(path) => Some(*path),
+            _ => None,
+        })
+    }
+
+    /// Parse a [`&str`](std::str) or [`String`] into a compiled
+    /// moostache template, a [`&str`](std::str) is borrowed by the
+    /// template and a [`String`] is moved into it.
     /// 
     /// ### Errors
     /// 
-    /// Returns a [`MoostacheError`] if the template contains a
-    /// partial, or serializing data during render fails for
-    /// whatever reason.
+    /// Returns a [`MoostacheError`] parse error enum variant
+    /// if parsing fails for whatever reason.
     #[inline]
-    pub fn render_no_partials<W: Write>(
-        &self,
-        value: &serde_json::Value,
-        writer: &mut W,
-    ) -> Result<(), MoostacheError> {
-        self.render(
-            &(),
-            value,
-            writer
-        )
+    pub fn parse<S: Into<Cow<'src, str>>>(source: S) -> Result<Self, MoostacheError> {
+        Self::parse_with_options(source, &ParseOptions::default())
     }
 
-    /// Render this template using a type which impls
-    /// [`serde::Serialize`] and assuming it has no partials.
+    /// Parse a [`&str`](std::str) or [`String`] into a compiled
+    /// moostache template using the given options.
     /// 
     /// ### Errors
     /// 
-    /// Returns a [`MoostacheError`] if the template contains a
-    /// partial, or serializing data during render fails for
-    /// whatever reason.
-    #[inline]
-    pub fn render_serializable_no_partials<S: Serialize, W: Write>(
-        &self,
-        serializeable: &S,
-        writer: &mut W,
-    ) -> Result<(), MoostacheError> {
-        self.render_serializable(
-            &(),
-            serializeable,
-            writer
-        )
+    /// Returns a [`MoostacheError`] parse error enum variant
+    /// if parsing fails for whatever reason.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{MoostacheError, ParseOptions, Template};
+    /// 
+    /// let options = ParseOptions {
+    ///     strict_variable_names: true,
+    ///     ..ParseOptions::default()
+    /// };
+    /// let err = Template::parse_with_options("{{ name- }}", &options).unwrap_err();
+    /// assert_eq!(err, MoostacheError::ParseErrorInvalidEscapedVariableTag("".into()));
+    /// ```
+    pub fn parse_with_options<S: Into<Cow<'src, str>>>(source: S, options: &ParseOptions) -> Result<Self, MoostacheError> {
+        match parse(source, *options) {
+            Err(err) => {
+                Err(MoostacheError::from_internal(err, String::new()))
+            },
+            Ok(template) => Ok(template),
+        }
     }
 
-    /// Render this template to a [`String`].
+    /// Parse a [`&str`](std::str) or [`String`] into a compiled
+    /// moostache template, like [`Template::parse`], and also
+    /// return [`Warning`]s about suspicious but valid parts of
+    /// the template, such as empty sections, in the order they
+    /// appear in the source.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns a [`MoostacheError`] parse error enum variant
+    /// if parsing fails for whatever reason.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{Template, Warning};
+    /// 
+    /// let (template, warnings) = Template::parse_with_warnings("{{#a}}{{/a}}{{b}}").unwrap();
+    /// assert_eq!(template.source(), "{{#a}}{{/a}}{{b}}");
+    /// assert_eq!(
+    ///     warnings,
+    ///     vec![Warning::EmptySection { name: "a".to_owned(), span: 0..6 }],
+    /// );
+    /// ```
+    pub fn parse_with_warnings<S: Into<Cow<'src, str>>>(source: S) -> Result<(Self, Vec<Warning>), MoostacheError> {
+        let template = Self::parse(source)?;
+        let warnings = template.warnings();
+        Ok((template, warnings))
+    }
+
+    /// Check that a [`&str`](std::str) parses into a moostache
+    /// template without keeping the compiled template, e.g. to
+    /// validate user submitted templates. Nothing is copied out
+    /// of the source and everything allocated while parsing is
+    /// freed before returning.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns the same [`MoostacheError`] parse error enum
+    /// variant [`Template::parse`] would.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{MoostacheError, Template};
+    /// 
+    /// assert_eq!(Template::validate("hello {{ name }}"), Ok(()));
+    /// assert_eq!(
+    ///     Template::validate("{{# name }}"),
+    ///     Err(MoostacheError::ParseErrorUnclosedSectionTags("".into())),
+    /// );
+    /// ```
+    pub fn validate(source: &str) -> Result<(), MoostacheError> {
+        let mut skips = Vec::new();
+        match parse_fragments(source, &mut skips, ParseOptions::default()) {
+            Err(err) => Err(MoostacheError::from_internal(err, String::new())),
+            Ok(_) => Ok(()),
+        }
+    }
+
+    // lints the template's fragments
+    fn warnings(&self) -> Vec<Warning> {
+        let infos = self.inspect();
+        let mut warnings = Vec::new();
+        let mut partials = HashSet::new();
+        for (idx, info) in infos.iter().enumerate() {
+            match &info.kind {
+                FragmentKind::SectionStart { name, nested: 0, .. }
+                | FragmentKind::MissingSectionStart { name, nested: 0 }
+                | FragmentKind::CompareSectionStart { name, nested: 0, .. }
+                | FragmentKind::AliasSectionStart { name, nested: 0, .. }
+                | FragmentKind::PluralSectionStart { name, nested: 0 }
+                | FragmentKind::WithSectionStart { name, nested: 0 } => {
+                    // a section with only an else isn't empty,
+                    // it's rendering the else on purpose
+                    let has_else = infos.get(idx + 1)
+                        .is_some_and(|next| matches!(next.kind, FragmentKind::SectionElse { .. }));
+                    if !has_else {
+                        warnings.push(Warning::EmptySection {
+                            name: name.clone(),
+                            span: info.span.clone(),
+                        });
+                    }
+                },
+                FragmentKind::Partial(name) if !partials.insert(name.as_str()) => {
+                    warnings.push(Warning::DuplicatePartial {
+                        name: name.clone(),
+                        span: info.span.clone(),
+                    });
+                },
+                _ => {},
+            }
+        }
+        warnings
+    }
+
+    /// Render this template using the loader's
+    /// [`render_options`](TemplateLoader::render_options).
+    /// 
+    /// Rendering doesn't buffer or validate its output, so
+    /// rendering into [`std::io::sink`] does all the work of a
+    /// render besides writing the output, which is useful
+    /// for measuring the cost of renders, e.g. in benchmarks.
     /// 
     /// ### Errors
     /// 
     /// If using [`HashMapLoader`] or [`FileLoader`] this function
     /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    /// use std::io;
+    /// 
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// template.render(&(), &json!({"name": "John"}), &mut io::sink()).unwrap();
+    /// ```
     #[inline]
-    pub fn render_to_string<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+    pub fn render<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
         &self,
         loader: &T,
         value: &serde_json::Value,
-    ) -> Result<String, T::Error> {
-        let mut writer = Vec::<u8>::new();
-        self.render(
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        self.render_with_options(
             loader,
             value,
-            &mut writer
-        )?;
-        let rendered = unsafe {
-            // SAFETY: templates are utf8 and value
-            // is utf8 so we know templates + value
-            // will also be utf8
-            debug_assert!(str::from_utf8(&writer).is_ok());
-            String::from_utf8_unchecked(writer)
-        };
-        Ok(rendered)
+            &loader.render_options(),
+            writer,
+        )
     }
 
-    /// Render this template assuming it has no partial tags
-    /// and return the result as a [`String`].
+    /// Render this template, like [`Template::render`], using
+    /// any shared handle to a [`serde_json::Value`], e.g. an
+    /// [`Rc`] or [`Arc`](std::sync::Arc), which is
+    /// held for the duration of the render. The value is never
+    /// cloned, so it's cheap to render many templates against
+    /// one large dataset.
     /// 
     /// ### Errors
     /// 
-    /// Returns a [`MoostacheError`] if the template contains a
-    /// partial, or serializing data during render fails for
-    /// whatever reason.
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    /// use std::sync::Arc;
+    /// 
+    /// let data = Arc::new(json!({"name": "John"}));
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// let mut writer = Vec::new();
+    /// template.render_shared(&(), Arc::clone(&data), &mut writer).unwrap();
+    /// assert_eq!(writer, b"hello John!");
+    /// ```
     #[inline]
-    pub fn render_no_partials_to_string(
+    pub fn render_shared<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, V: Deref<Target = serde_json::Value>, W: Write>(
         &self,
-        value: &serde_json::Value,
-    ) -> Result<String, MoostacheError> {
-        self.render_to_string(
-            &(),
-            value,
-        )
+        loader: &T,
+        value: V,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        self.render(loader, &value, writer)
     }
 
-    /// Render this template given a type which impls
-    /// [`serde::Serialize`] and return result as a [`String`].
+    /// Render this template using the given [`RenderOptions`].
     /// 
     /// ### Errors
     /// 
     /// If using [`HashMapLoader`] or [`FileLoader`] this function
     /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{
+    ///     ApostropheEntity,
+    ///     Escaper,
+    ///     HtmlEscapeConfig,
+    ///     RenderOptions,
+    ///     Template,
+    /// };
+    /// use serde_json::json;
+    /// 
+    /// let template = Template::parse("{{quote}}").unwrap();
+    /// let options = RenderOptions {
+    ///     escaper: Escaper::Html(HtmlEscapeConfig {
+    ///         apostrophe: ApostropheEntity::Named,
+    ///         ..HtmlEscapeConfig::default()
+    ///     }),
+    ///     ..RenderOptions::default()
+    /// };
+    /// let mut writer = Vec::new();
+    /// template.render_with_options(
+    ///     &(),
+    ///     &json!({"quote": "it's"}),
+    ///     &options,
+    ///     &mut writer,
+    /// ).unwrap();
+    /// assert_eq!(writer, b"it&apos;s");
+    /// ```
     #[inline]
-    pub fn render_serializable_to_string<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, S: Serialize>(
+    pub fn render_with_options<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
         &self,
         loader: &T,
-        serializable: &S,
-    ) -> Result<String, T::Error> {
-        let value = serde_json::to_value(serializable)
-            .map_err(|_| MoostacheError::SerializationError)?;
-        self.render_to_string(
-            loader,
-            &value,
-        )
+        value: &serde_json::Value,
+        options: &RenderOptions,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        let ctx = RenderContext::new(loader, options);
+        self.render_with_context(&ctx, &[value], writer)
     }
 
-    /// Render this template given a type which impls
-    /// [`serde::Serialize`], assume it has no partials,
-    /// and return result as a [`String`].
+    /// Render only the fragments of this template whose indexes
+    /// are within `frags`, i.e. the indexes of the fragments
+    /// returned by [`Template::inspect`]. The fragments must be
+    /// whole, so if the range includes the start of a section it
+    /// must also include all of its content and its else, if it
+    /// has one, and it can't include an else without its section.
+    /// The range can be within the content of a section, but its
+    /// fragments are still rendered with `value` as the only
+    /// scope.
     /// 
     /// ### Errors
     /// 
-    /// Returns a [`MoostacheError`] if the template contains a
-    /// partial, or serializing data during render fails for
-    /// whatever reason.
-    #[inline]
-    pub fn render_serializable_no_partials_to_string<S: Serialize>(
+    /// Returns [`MoostacheError::RenderErrorInvalidFragmentRange`]
+    /// if the range is out of bounds or splits a section. If using
+    /// [`HashMapLoader`] or [`FileLoader`] this function can also
+    /// return any other enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{MoostacheError, Template};
+    /// use serde_json::json;
+    /// 
+    /// let template = Template::parse("<h1>{{title}}</h1>{{#items}}<li>{{.}}</li>{{/items}}").unwrap();
+    /// let data = json!({"title": "list", "items": [1, 2]});
+    /// let mut writer = Vec::new();
+    /// template.render_range(1..3, &(), &data, &mut writer).unwrap();
+    /// assert_eq!(writer, b"list</h1>");
+    /// writer.clear();
+    /// template.render_range(3..7, &(), &data, &mut writer).unwrap();
+    /// assert_eq!(writer, b"<li>1</li><li>2</li>");
+    /// 
+    /// let err = template.render_range(3..5, &(), &data, &mut writer).unwrap_err();
+    /// assert_eq!(err, MoostacheError::RenderErrorInvalidFragmentRange(3..5));
+    /// ```
+    pub fn render_range<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
         &self,
-        serializable: &S,
-    ) -> Result<String, MoostacheError> {
-        let value = serde_json::to_value(serializable)
-            .map_err(|_| MoostacheError::SerializationError)?;
-        self.render_to_string(
-            &(),
-            &value,
-        )
-    }
-}
-
-// note: can't do impl<S: Into<ImmutableStr> TryFrom<S> below
-// because compiler complains that generic impl overlaps
-// with another generic impl in the std lib, so we do separate
-// impls for &'static str and String
-
-impl TryFrom<&'static str> for Template {
-    type Error = MoostacheError;
-    fn try_from(source: &'static str) -> Result<Self, Self::Error> {
-        Self::parse(source)
+        frags: Range<usize>,
+        loader: &T,
+        value: &serde_json::Value,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        let Some(sections) = self.section_range(&frags) else {
+            return Err(MoostacheError::RenderErrorInvalidFragmentRange(frags).into());
+        };
+        let options = loader.render_options();
+        let ctx = RenderContext::new(loader, &options);
+        _render_root(&self.frags()[frags], &self.skips[sections], &ctx, &[value], writer)
     }
-}
 
-impl TryFrom<String> for Template {
-    type Error = MoostacheError;
-    fn try_from(source: String) -> Result<Self, Self::Error> {
-        Self::parse(source)
+    // the range of section skips of the fragments within frags,
+    // or None if frags is out of bounds or splits a section
+    fn section_range(&self, frags: &Range<usize>) -> Option<Range<usize>> {
+        let all_frags = self.frags();
+        if frags.start > frags.end || frags.end > all_frags.len() {
+            return None;
+        }
+        if frags.start < frags.end && matches!(all_frags[frags.start], Fragment::SectionElse(_)) {
+            return None;
+        }
+        // an else right after the range belongs to the
+        // outermost section which ends where the range ends
+        let mut else_after = matches!(all_frags.get(frags.end), Some(Fragment::SectionElse(_)));
+        let mut start_section = 0;
+        let mut section_idx = 0;
+        for (frag_idx, frag) in all_frags[..frags.end].iter().enumerate() {
+            if frag_idx == frags.start {
+                start_section = section_idx;
+            }
+            if !frag.is_section() {
+                continue;
+            }
+            let end_frag = frag_idx + 1 + self.skips[section_idx].nested_fragments as usize;
+            section_idx += 1;
+            // sections which contain the start of the range must
+            // also contain its end, and sections which start
+            // within the range must end within it, with their else
+            let splits = if frags.contains(&frag_idx) {
+                end_frag > frags.end || (end_frag == frags.end && else_after)
+            } else {
+                frag_idx < frags.start && frags.start < end_frag && end_frag < frags.end
+            };
+            if splits {
+                return None;
+            }
+            if end_frag == frags.end {
+                else_after = false;
+            }
+        }
+        if frags.start == frags.end {
+            start_section = section_idx;
+        }
+        Some(start_section..section_idx)
     }
-}
-
-//////////////////////
-// TEMPLATE LOADERS //
-//////////////////////
 
-/// Loads templates and renders them.
-pub trait TemplateLoader<K: Borrow<str> + Eq + Hash = String> {
-    /// Output type of the [`get`](TemplateLoader::get) method.
-    type Output<'a>: Deref<Target = Template> + 'a where Self: 'a;
-    /// Error type of the [`get`](TemplateLoader::get) and render methods.
-    type Error: From<MoostacheError>;
-    
-    /// Get a template by name.
+    /// Render this template, checking `cancel` before every
+    /// fragment is rendered and stopping early if it's been set
+    /// to `true`, e.g. by another thread enforcing a deadline.
     /// 
     /// ### Errors
     /// 
-    /// Returns an [`Error`](TemplateLoader::Error) if getting
-    /// the template fails for whatever reason. In [`HashMapLoader`]
-    /// this would only ever return
-    /// [`MoostacheError::LoaderErrorTemplateNotFound`] since it
-    /// either has the template or it doesn't. In [`FileLoader`] it
-    /// can return almost any enum variant of [`MoostacheError`]
-    /// since it lazily loads and compiles templates on-demand.
-    fn get<'a>(&'a self, name: &str) -> Result<Self::Output<'a>, Self::Error>;
-
-    /// Insert a template by name.
-    fn insert(&mut self, name: K, value: Template) -> Option<Template>;
-
-    /// Remove a template by name.
-    fn remove(&mut self, name: &str) -> Option<Template>;
-    
-    /// Render a template by name, using a [`serde_json::Value`]
-    /// as data and writing output to a [`&mut impl Write`](std::io::Write).
+    /// Returns [`MoostacheError::RenderErrorCancelled`] if the
+    /// render was cancelled. Some of the output may have already
+    /// been written by then. If using [`HashMapLoader`] or
+    /// [`FileLoader`] this function can also return any other enum
+    /// variant of [`MoostacheError`].
     /// 
-    /// ### Errors
+    /// ### Examples
     /// 
-    /// If using [`HashMapLoader`] or [`FileLoader`] this function
-    /// can return any enum variant of [`MoostacheError`].
+    /// ```rust
+    /// use moostache::{MoostacheError, Template};
+    /// use serde_json::json;
+    /// use std::sync::atomic::AtomicBool;
+    /// 
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// let cancel = AtomicBool::new(true);
+    /// let mut writer = Vec::new();
+    /// let err = template.render_with_cancel(
+    ///     &(),
+    ///     &json!({"name": "John"}),
+    ///     &cancel,
+    ///     &mut writer,
+    /// ).unwrap_err();
+    /// assert_eq!(err, MoostacheError::RenderErrorCancelled);
+    /// ```
     #[inline]
-    fn render<W: Write>(
+    pub fn render_with_cancel<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
         &self,
-        name: &str,
+        loader: &T,
         value: &serde_json::Value,
+        cancel: &AtomicBool,
         writer: &mut W,
-    ) -> Result<(), Self::Error> {
-        let template = self.get(name)?;
-        template.render(self, value, writer)
+    ) -> Result<(), T::Error> {
+        let options = loader.render_options();
+        let ctx = RenderContext {
+            cancel: Some(cancel),
+            ..RenderContext::new(loader, &options)
+        };
+        self.render_with_context(&ctx, &[value], writer)
     }
 
-    /// Render a template by name, using a type which impls
-    /// [`serde::Serialize`] as data and writing output to a
-    /// [`&mut impl Write`](std::io::Write).
+    /// Render this template, looking up partials in `partials`
+    /// before falling back to the loader. If a partial exists in
+    /// both then the one in `partials` is used.
     /// 
     /// ### Errors
     /// 
     /// If using [`HashMapLoader`] or [`FileLoader`] this function
     /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    /// use std::collections::HashMap;
+    /// 
+    /// let template = Template::parse("{{> greet }}!").unwrap();
+    /// let greet = Template::parse("hello {{name}}").unwrap();
+    /// let partials = HashMap::from([("greet", &greet)]);
+    /// let mut writer = Vec::new();
+    /// template.render_with_partials(
+    ///     &(),
+    ///     &json!({"name": "John"}),
+    ///     &partials,
+    ///     &mut writer,
+    /// ).unwrap();
+    /// assert_eq!(writer, b"hello John!");
+    /// ```
     #[inline]
-    fn render_serializable<W: Write, S: Serialize>(
+    pub fn render_with_partials<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
         &self,
+        loader: &T,
+        value: &serde_json::Value,
+        partials: &HashMap<&str, &Template>,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        let options = loader.render_options();
+        let ctx = RenderContext {
+            inline_partials: Some(partials),
+            ..RenderContext::new(loader, &options)
+        };
+        self.render_with_context(&ctx, &[value], writer)
+    }
+
+    /// Render this template, pulling the items of some sections
+    /// lazily from iterators instead of from `value`. See
+    /// [`IterSections`].
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns [`MoostacheError::SerializationError`] if an item
+    /// from an iterator fails to serialize. If using [`HashMapLoader`]
+    /// or [`FileLoader`] this function can also return any other enum
+    /// variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{IterSections, Template};
+    /// use serde_json::json;
+    /// 
+    /// let template = Template::parse("{{#nums}}{{.}} {{/nums}}{{end}}").unwrap();
+    /// let mut sections = IterSections::new();
+    /// sections.insert("nums", (1..=3).map(|num| num * 10));
+    /// let mut writer = Vec::new();
+    /// template.render_with_iter_sections(
+    ///     &(),
+    ///     &json!({"end": "!"}),
+    ///     &sections,
+    ///     &mut writer,
+    /// ).unwrap();
+    /// assert_eq!(writer, b"10 20 30 !");
+    /// ```
+    #[inline]
+    pub fn render_with_iter_sections<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+        sections: &IterSections<'_>,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        let options = loader.render_options();
+        let ctx = RenderContext {
+            iter_sections: Some(sections),
+            ..RenderContext::new(loader, &options)
+        };
+        self.render_with_context(&ctx, &[value], writer)
+    }
+
+    // render this template as the template with the given
+    // name, see RenderOptions::relative_partials
+    fn render_named<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+        &self,
+        loader: &T,
         name: &str,
+        value: &serde_json::Value,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        let options = loader.render_options();
+        let ctx = RenderContext {
+            template_name: name,
+            ..RenderContext::new(loader, &options)
+        };
+        self.render_with_context(&ctx, &[value], writer)
+    }
+
+    // render this template with the given context, the
+    // first value is the innermost root scope
+    fn render_with_context<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+        &self,
+        ctx: &RenderContext<'_, '_, '_, T>,
+        values: &[&serde_json::Value],
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        _render_root(self.frags(), &self.skips, ctx, values, writer)
+    }
+
+    /// Render this template using a [`serde_json::Map`] as the
+    /// root scope, the same as if it was wrapped in a
+    /// [`serde_json::Value::Object`].
+    /// 
+    /// Keys are looked up in the map directly, so it's never
+    /// moved or cloned, unless the template uses the root scope
+    /// itself rather than one of its keys, e.g. `{{.}}` at the
+    /// top level, in which case it's cloned once for the render.
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::{json, Map};
+    /// 
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// let mut map = Map::new();
+    /// map.insert("name".to_owned(), json!("John"));
+    /// let mut writer = Vec::new();
+    /// template.render_map(&(), &map, &mut writer).unwrap();
+    /// assert_eq!(writer, b"hello John!");
+    /// ```
+    #[inline]
+    pub fn render_map<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+        &self,
+        loader: &T,
+        map: &serde_json::Map<String, serde_json::Value>,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        let options = loader.render_options();
+        let root = MapRoot::new(map);
+        let ctx = RenderContext {
+            root: Some(&root),
+            ..RenderContext::new(loader, &options)
+        };
+        self.render_with_context(&ctx, &[], writer)
+    }
+
+    /// Render this template using the value at `path`, e.g.
+    /// `user` or `user.address`, as its root. If nothing is
+    /// at `path` the template is rendered with a null root.
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    /// 
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// let data = json!({"user": {"name": "John"}});
+    /// let mut writer = Vec::new();
+    /// template.render_at("user", &(), &data, &mut writer).unwrap();
+    /// assert_eq!(writer, b"hello John!");
+    /// ```
+    #[inline]
+    pub fn render_at<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+        &self,
+        path: &str,
+        loader: &T,
+        value: &serde_json::Value,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        self.render(
+            loader,
+            resolve_value(path, &[value], None),
+            writer,
+        )
+    }
+
+    /// Render this template using several values as layered
+    /// root scopes, without merging them into one value.
+    /// Variables are looked up in each value in order and
+    /// resolve to the first one they're found in, e.g. user
+    /// data can be layered over site config. {{ . }} is the
+    /// first value. If there are no values the template is
+    /// rendered with a null root.
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    /// 
+    /// let template = Template::parse("{{greeting}} {{name}}!").unwrap();
+    /// let user = json!({"name": "John"});
+    /// let site = json!({"greeting": "hello", "name": "stranger"});
+    /// let mut writer = Vec::new();
+    /// template.render_layered(&(), &[&user, &site], &mut writer).unwrap();
+    /// assert_eq!(writer, b"hello John!");
+    /// ```
+    #[inline]
+    pub fn render_layered<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+        &self,
+        loader: &T,
+        values: &[&serde_json::Value],
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        let options = loader.render_options();
+        let ctx = RenderContext::new(loader, &options);
+        let values = if values.is_empty() { &[&NULL] } else { values };
+        self.render_with_context(&ctx, values, writer)
+    }
+
+    /// Render this template given a type that impls
+    /// [`serde::Serialize`].
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    #[inline]
+    pub fn render_serializable<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write, S: Serialize>(
+        &self,
+        loader: &T,
         serializeable: &S,
         writer: &mut W,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), T::Error> {
         let value = serde_json::to_value(serializeable)
-            .map_err(|_| MoostacheError::SerializationError)?;
+            .map_err(|err| MoostacheError::SerializationError(err.to_string()))?;
         self.render(
-            name,
+            loader,
             &value,
             writer
         )
     }
 
-    /// Renders a template by name, using a [`serde_json::Value`]
-    /// as data and returning the output as a [`String`].
+    /// Render this template given a type that impls
+    /// [`serde::Serialize`], without serializing it into a
+    /// [`serde_json::Value`] first if possible, which is faster.
+    /// 
+    /// This is only possible if the type serializes into a map,
+    /// e.g. a struct, whose values are all strings, numbers,
+    /// bools, or nulls, and the template only has literals and
+    /// variables which aren't paths, e.g. {{ name }} but not
+    /// {{ user.name }}. Otherwise this renders exactly like
+    /// [`Template::render_serializable`] does.
     /// 
     /// ### Errors
     /// 
     /// If using [`HashMapLoader`] or [`FileLoader`] this function
     /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_derive::Serialize;
+    /// 
+    /// #[derive(Serialize)]
+    /// struct User {
+    ///     name: &'static str,
+    ///     age: u32,
+    /// }
+    /// 
+    /// let template = Template::parse("{{name}} is {{age}}").unwrap();
+    /// let mut writer = Vec::new();
+    /// template.render_flat_serializable(
+    ///     &(),
+    ///     &User { name: "John", age: 30 },
+    ///     &mut writer,
+    /// ).unwrap();
+    /// assert_eq!(writer, b"John is 30");
+    /// ```
+    pub fn render_flat_serializable<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write, S: Serialize>(
+        &self,
+        loader: &T,
+        serializeable: &S,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        let frags = self.frags();
+        let is_flat = frags.iter().all(|frag| match frag {
+            Fragment::Literal(_) => true,
+            Fragment::EscapedVariable(name) |
+            Fragment::UnescapedVariable(name) => !name.contains(['.', '[', ':']),
+            _ => false,
+        });
+        let mut fields = FlatFields::default();
+        if !is_flat || serializeable.serialize(&mut fields).is_err() {
+            return self.render_serializable(loader, serializeable, writer);
+        }
+        let options = loader.render_options();
+        match options.max_output_bytes {
+            Some(remaining) => fields.render(frags, &options, &mut OutputLimitWriter { writer, remaining }),
+            None => fields.render(frags, &options, writer),
+        }?;
+        Ok(())
+    }
+
+    /// Render this template, assuming it has no partial tags.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns a [`MoostacheError`] if the template contains a
+    /// partial, or serializing data during render fails for
+    /// whatever reason.
     #[inline]
-    fn render_to_string(
+    pub fn render_no_partials<W: Write>(
         &self,
-        name: &str,
         value: &serde_json::Value,
-    ) -> Result<String, Self::Error> {
-        let mut writer = Vec::<u8>::new();
+        writer: &mut W,
+    ) -> Result<(), MoostacheError> {
         self.render(
-            name,
+            &(),
             value,
-            &mut writer
-        )?;
-        let rendered = unsafe {
-            // SAFETY: templates are utf8 and value
-            // is utf8 so we know templates + value
-            // will also be utf8
-            debug_assert!(str::from_utf8(&writer).is_ok());
-            String::from_utf8_unchecked(writer)
-        };
-        Ok(rendered)
+            writer
+        )
     }
 
-    /// Renders a template by name, using a type which impls
-    /// [`serde::Serialize`] as data and returning the output
-    /// as a [`String`].
+    /// Render this template using a type which impls
+    /// [`serde::Serialize`] and assuming it has no partials.
     /// 
     /// ### Errors
     /// 
-    /// If using [`HashMapLoader`] or [`FileLoader`] this function
-    /// can return any enum variant of [`MoostacheError`].
+    /// Returns a [`MoostacheError`] if the template contains a
+    /// partial, or serializing data during render fails for
+    /// whatever reason.
     #[inline]
-    fn render_serializable_to_string<S: Serialize>(
+    pub fn render_serializable_no_partials<S: Serialize, W: Write>(
         &self,
-        name: &str,
-        serializable: &S,
-    ) -> Result<String, Self::Error> {
-        let value = serde_json::to_value(serializable)
-            .map_err(|_| MoostacheError::SerializationError)?;
-        self.render_to_string(
-            name,
-            &value,
+        serializeable: &S,
+        writer: &mut W,
+    ) -> Result<(), MoostacheError> {
+        self.render_serializable(
+            &(),
+            serializeable,
+            writer
         )
     }
-}
-
-/// Useful struct for creating [`HashMapLoader`]s or
-/// [`FileLoader`]s.
-/// 
-/// ### Examples
-/// 
-/// Creating a [`HashMapLoader`]:
-/// 
-/// ```rust
-/// use moostache::{LoaderConfig, HashMapLoader};
-/// 
-/// let loader = HashMapLoader::try_from(LoaderConfig::default()).unwrap();
-/// ```
-/// 
-/// Creating a [`FileLoader`]:
-/// 
-/// ```rust
-/// use moostache::{LoaderConfig, FileLoader};
-/// 
-/// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
-/// ```
-/// 
-/// [`LoaderConfig`] default values:
-/// 
-/// ```rust
-/// use moostache::LoaderConfig;
-/// 
-/// assert_eq!(
-///     LoaderConfig::default(),
-///     LoaderConfig {
-///         templates_directory: "./templates/",
-///         templates_extension: ".html",
-///         cache_size: 200,
-///     },
-/// );
-/// ```
-#[derive(Clone, Debug, PartialEq)]
-pub struct LoaderConfig<'a> {
-    /// Directory to load templates from.
-    pub templates_directory: &'a str,
-    /// File extension of template files.
-    pub templates_extension: &'a str,
-    /// Max number of compiled templates to cache in memory.
-    pub cache_size: usize,
-}
 
-#[cfg(windows)]
-const DEFAULT_TEMPLATES_DIRECTORY: &str = ".\\templates\\";
+    /// Render this template to a [`String`], and also return
+    /// the names of all partials which were rendered, including
+    /// partials rendered by other partials, e.g. to know which
+    /// templates a cached render depends on. Partials within
+    /// sections which weren't rendered aren't included.
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{HashMapLoader, Template};
+    /// use maplit::{hashmap, hashset};
+    /// use serde_json::json;
+    /// 
+    /// let loader = HashMapLoader::try_from(hashmap! {
+    ///     "greet" => "hello {{name}}",
+    ///     "login" => "please log in",
+    /// }).unwrap();
+    /// let template = Template::parse("{{#name}}{{> greet }}{{/name}}{{^name}}{{> login }}{{/name}}").unwrap();
+    /// let (rendered, partials) = template
+    ///     .render_tracking_partials(&loader, &json!({"name": "John"}))
+    ///     .unwrap();
+    /// assert_eq!(rendered, "hello John");
+    /// assert_eq!(partials, hashset! {"greet".to_owned()});
+    /// ```
+    pub fn render_tracking_partials<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+    ) -> Result<(String, HashSet<String>), T::Error> {
+        let options = loader.render_options();
+        let loaded_partials = RefCell::new(HashSet::new());
+        let ctx = RenderContext {
+            loaded_partials: Some(&loaded_partials),
+            ..RenderContext::new(loader, &options)
+        };
+        let rendered = self.render_context_to_string(&ctx, value, false)?;
+        Ok((rendered, loaded_partials.into_inner()))
+    }
 
-#[cfg(not(windows))]
-const DEFAULT_TEMPLATES_DIRECTORY: &str = "./templates/";
+    /// Render this template to a [`String`], and also return how
+    /// many times the content of each section, e.g. {{# items }},
+    /// was rendered, keyed by the section's path, e.g. to debug
+    /// why some output is repeated more or fewer times than
+    /// expected. Counts include sections within other sections
+    /// and within partials, and sections which were visited but
+    /// were falsy have a count of zero.
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use maplit::hashmap;
+    /// use serde_json::json;
+    /// 
+    /// let template = Template::parse("{{#items}}{{#tags}}#{{.}} {{/tags}}{{/items}}{{#empty}}!{{/empty}}").unwrap();
+    /// let data = json!({"items": [{"tags": ["a", "b"]}, {"tags": ["c"]}], "empty": []});
+    /// let (rendered, counts) = template.render_counting_sections(&(), &data).unwrap();
+    /// assert_eq!(rendered, "#a #b #c ");
+    /// assert_eq!(counts, hashmap! {
+    ///     "items".to_owned() => 2,
+    ///     "tags".to_owned() => 3,
+    ///     "empty".to_owned() => 0,
+    /// });
+    /// ```
+    pub fn render_counting_sections<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+    ) -> Result<(String, HashMap<String, usize>), T::Error> {
+        let options = loader.render_options();
+        let section_counts = RefCell::new(HashMap::new());
+        let ctx = RenderContext {
+            section_counts: Some(&section_counts),
+            ..RenderContext::new(loader, &options)
+        };
+        let rendered = self.render_context_to_string(&ctx, value, false)?;
+        Ok((rendered, section_counts.into_inner()))
+    }
 
-impl Default for LoaderConfig<'_> {
-    fn default() -> Self {
-        Self {
-            templates_directory: DEFAULT_TEMPLATES_DIRECTORY,
-            templates_extension: ".html",
-            cache_size: 200,
-        }
+    /// Render this template to a [`String`].
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    #[inline]
+    pub fn render_to_string<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+    ) -> Result<String, T::Error> {
+        let options = loader.render_options();
+        self.render_context_to_string(&RenderContext::new(loader, &options), value, false)
     }
-}
 
-impl TryFrom<LoaderConfig<'_>> for HashMapLoader {
-    type Error = MoostacheError;
+    // render this template with the given context to a String,
+    // every render which returns a String goes through here so
+    // trailing whitespace and post_render are always applied,
+    // if checked the output is always validated as utf-8
+    fn render_context_to_string<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        ctx: &RenderContext<'_, '_, '_, T>,
+        value: &serde_json::Value,
+        checked: bool,
+    ) -> Result<String, T::Error> {
+        let mut writer = Vec::<u8>::new();
+        self.render_with_context(ctx, &[value], &mut writer)?;
+        let rendered = if checked {
+            checked_utf8(writer)?
+        } else {
+            rendered_utf8(ctx.loader, writer)?
+        };
+        let rendered = ctx.options.trailing_whitespace.apply(rendered);
+        Ok(ctx.loader.post_render(rendered))
+    }
+
+    /// Render this template to a [`String`], like
+    /// [`Template::render_to_string`], except the output is
+    /// validated as utf-8 instead of assumed to be, so a bug
+    /// which writes invalid utf-8 during render returns an
+    /// error instead of causing undefined behavior.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns [`MoostacheError::RenderErrorInvalidUtf8`] if
+    /// the output isn't valid utf-8. If using [`HashMapLoader`]
+    /// or [`FileLoader`] this function can also return any other
+    /// enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    /// 
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// let rendered = template.render_to_string_checked(&(), &json!({"name": "John"})).unwrap();
+    /// assert_eq!(rendered, "hello John!");
+    /// ```
+    pub fn render_to_string_checked<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+    ) -> Result<String, T::Error> {
+        let options = loader.render_options();
+        self.render_context_to_string(&RenderContext::new(loader, &options), value, true)
+    }
+
+    /// Render this template onto the end of an existing [`String`],
+    /// e.g. to assemble several renders into one document without
+    /// allocating a [`String`] for each of them. Like
+    /// [`Template::render`], the output isn't changed by
+    /// [`RenderOptions::trailing_whitespace`] or
+    /// [`post_render`](TemplateLoader::post_render).
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`], in which
+    /// case the [`String`] is left unchanged.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    /// 
+    /// let template = Template::parse("<p>{{.}}</p>").unwrap();
+    /// let mut page = String::from("<body>");
+    /// for paragraph in ["hello", "world"] {
+    ///     template.render_append(&(), &json!(paragraph), &mut page).unwrap();
+    /// }
+    /// page.push_str("</body>");
+    /// assert_eq!(page, "<body><p>hello</p><p>world</p></body>");
+    /// ```
+    pub fn render_append<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+        rendered: &mut String,
+    ) -> Result<(), T::Error> {
+        if loader.value_renderer().is_some() {
+            // value renderers can write any bytes so the
+            // output is validated before it's appended
+            let mut writer = Vec::new();
+            self.render(loader, value, &mut writer)?;
+            rendered.push_str(&checked_utf8(writer)?);
+            return Ok(());
+        }
+        let len = rendered.len();
+        // SAFETY: templates are utf8 and value is utf8 so we
+        // know templates + value will also be utf8, if the render
+        // fails partway through everything it wrote is removed
+        let writer = unsafe { rendered.as_mut_vec() };
+        let result = self.render(
+            loader,
+            value,
+            writer
+        );
+        if result.is_err() {
+            writer.truncate(len);
+        }
+        debug_assert!(str::from_utf8(writer).is_ok());
+        result
+    }
+
+    /// Render this template to an async writer, e.g. the body of
+    /// a response in an async HTTP framework. Renders are
+    /// synchronous, so the whole output is rendered into a buffer
+    /// first and then written to the writer, which is flushed
+    /// afterwards. Like [`Template::render`], the output isn't
+    /// changed by [`RenderOptions::trailing_whitespace`] or
+    /// [`post_render`](TemplateLoader::post_render). Requires the
+    /// `tokio` feature.
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`], in which
+    /// case nothing is written to the writer.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// # #[cfg(feature = "tokio")] {
+    /// use moostache::Template;
+    /// use serde_json::json;
+    /// 
+    /// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// let mut writer = Vec::new();
+    /// runtime.block_on(
+    ///     template.render_to_async_writer(&(), &json!({"name": "John"}), &mut writer)
+    /// ).unwrap();
+    /// assert_eq!(writer, b"hello John!");
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub async fn render_to_async_writer<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: tokio::io::AsyncWrite + Unpin>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+        writer: &mut W,
+    ) -> Result<(), T::Error> {
+        use tokio::io::AsyncWriteExt;
+        let mut buffer = Vec::new();
+        self.render(loader, value, &mut buffer)?;
+        writer.write_all(&buffer).await.map_err(MoostacheError::from_write_io)?;
+        writer.flush().await.map_err(MoostacheError::from_write_io)?;
+        Ok(())
+    }
+
+    /// Render this template assuming it has no partial tags
+    /// and return the result as a [`String`].
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns a [`MoostacheError`] if the template contains a
+    /// partial, or serializing data during render fails for
+    /// whatever reason.
+    #[inline]
+    pub fn render_no_partials_to_string(
+        &self,
+        value: &serde_json::Value,
+    ) -> Result<String, MoostacheError> {
+        self.render_to_string(
+            &(),
+            value,
+        )
+    }
+
+    /// Render this template given a type which impls
+    /// [`serde::Serialize`] and return result as a [`String`].
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    #[inline]
+    pub fn render_serializable_to_string<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, S: Serialize>(
+        &self,
+        loader: &T,
+        serializable: &S,
+    ) -> Result<String, T::Error> {
+        let value = serde_json::to_value(serializable)
+            .map_err(|err| MoostacheError::SerializationError(err.to_string()))?;
+        self.render_to_string(
+            loader,
+            &value,
+        )
+    }
+
+    /// Render this template given a type which impls
+    /// [`serde::Serialize`], assume it has no partials,
+    /// and return result as a [`String`].
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns a [`MoostacheError`] if the template contains a
+    /// partial, or serializing data during render fails for
+    /// whatever reason.
+    #[inline]
+    pub fn render_serializable_no_partials_to_string<S: Serialize>(
+        &self,
+        serializable: &S,
+    ) -> Result<String, MoostacheError> {
+        let value = serde_json::to_value(serializable)
+            .map_err(|err| MoostacheError::SerializationError(err.to_string()))?;
+        self.render_to_string(
+            &(),
+            &value,
+        )
+    }
+
+    /// Create a new template from this one where every partial
+    /// is replaced with the content of the template it refers to,
+    /// recursively, so the new template can be rendered without
+    /// a loader, e.g. with [`render_no_partials`](Template::render_no_partials).
+    /// Dynamic partials, e.g. {{>* variable }}, are kept as they
+    /// are since which template they refer to depends on the
+    /// value being rendered. Partials are resolved the same as
+    /// when rendering this template with the loader, including
+    /// [`RenderOptions::relative_partials`], where this template
+    /// is anonymous so its own partials are looked up at the root.
+    /// Kept dynamic partials are always looked up at the root when
+    /// the new template is rendered, since it's anonymous too.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns [`MoostacheError::LoaderErrorCyclicPartial`] if
+    /// some partial includes itself, directly or via other
+    /// partials. If using [`HashMapLoader`] or [`FileLoader`] this
+    /// function can also return any other enum variant of
+    /// [`MoostacheError`] returned while loading partials.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{HashMapLoader, Template};
+    /// use maplit::hashmap;
+    /// use serde_json::json;
+    /// 
+    /// let loader = HashMapLoader::try_from(hashmap! {
+    ///     "greet" => "hello {{name}}",
+    /// }).unwrap();
+    /// let template = Template::parse("{{> greet }}!").unwrap();
+    /// let flattened = template.flatten(&loader).unwrap();
+    /// let rendered = flattened
+    ///     .render_no_partials_to_string(&json!({"name": "John"}))
+    ///     .unwrap();
+    /// assert_eq!(rendered, "hello John!");
+    /// ```
+    pub fn flatten<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+    ) -> Result<Template, T::Error> {
+        // fragments can't borrow from the templates they came
+        // from, since loaded partials may be dropped before
+        // we're done, so copy their strs into a new source
+        let mut source = String::new();
+        let mut parts = Vec::new();
+        let mut skips = Vec::new();
+        _flatten(
+            self.frags(),
+            &self.skips,
+            loader,
+            &mut Vec::new(),
+            &mut source,
+            &mut parts,
+            &mut skips,
+        )?;
+        let fragments = Yoke::attach_to_cart(source, |source| {
+            Fragments(
+                parts
+                    .into_iter()
+                    .map(|(frag, range)| frag.with_str(&source[range]))
+                    .collect()
+            )
+        });
+        Ok(Template { fragments: TemplateFragments::Owned(fragments), skips, delimiters: self.delimiters })
+    }
+
+    /// Render this template twice, once with `old_value` and
+    /// once with `new_value`, and report which top-level fragments
+    /// of the template produced different output. A section,
+    /// including everything nested inside it, counts as a single
+    /// top-level fragment.
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    /// 
+    /// let template = Template::parse("a: {{a}}, b: {{b}}").unwrap();
+    /// let diff = template.render_diff(
+    ///     &(),
+    ///     &json!({"a": 1, "b": 2}),
+    ///     &json!({"a": 1, "b": 30}),
+    /// ).unwrap();
+    /// assert_eq!(diff.rendered, "a: 1, b: 30");
+    /// assert_eq!(diff.changed.len(), 1);
+    /// assert_eq!(diff.changed[0].old, 9..10);
+    /// assert_eq!(diff.changed[0].new, 9..11);
+    /// ```
+    pub fn render_diff<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        old_value: &serde_json::Value,
+        new_value: &serde_json::Value,
+    ) -> Result<RenderDiff, T::Error> {
+        let mut old_writer = Vec::<u8>::new();
+        let old_spans = self.render_spans(loader, old_value, &mut old_writer)?;
+        let mut new_writer = Vec::<u8>::new();
+        let new_spans = self.render_spans(loader, new_value, &mut new_writer)?;
+        let changed = old_spans
+            .into_iter()
+            .zip(new_spans)
+            .enumerate()
+            .filter(|(_, (old, new))| old_writer[old.clone()] != new_writer[new.clone()])
+            .map(|(fragment_index, (old, new))| ChangedFragment {
+                fragment_index,
+                old,
+                new,
+            })
+            .collect();
+        let rendered = rendered_utf8(loader, new_writer)?;
+        Ok(RenderDiff {
+            rendered,
+            changed,
+        })
+    }
+
+    /// Walk this template against `value` like a render would,
+    /// but instead of writing any output return the path of every
+    /// variable and section which resolved to null, i.e. data the
+    /// template expected but didn't get. Paths are returned in the
+    /// order they're first found, without duplicates.
+    ///
+    /// Content of sections which wouldn't be rendered isn't walked,
+    /// and inverted sections aren't reported since they're expected
+    /// to be falsy sometimes, but their content is walked if it
+    /// would be rendered.
+    ///
+    /// ### Errors
+    ///
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    ///
+    /// let template = Template::parse("{{a}} {{#b}}{{c}}{{/b}} {{d}}").unwrap();
+    /// let missing = template.missing_paths(
+    ///     &(),
+    ///     &json!({"a": 1, "b": true}),
+    /// ).unwrap();
+    /// assert_eq!(missing, vec!["c", "d"]);
+    /// ```
+    pub fn missing_paths<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+    ) -> Result<Vec<String>, T::Error> {
+        let mut missing = Vec::new();
+        let mut scopes = take_scratch_scopes();
+        scopes.push(value);
+        let result = _missing_paths(
+            self.frags(),
+            &self.skips,
+            loader,
+            &mut scopes,
+            &mut missing,
+        );
+        return_scratch_scopes(scopes);
+        result.map(|()| missing)
+    }
+
+    /// Check that every variable and section this template
+    /// references resolves against the default value of `S`,
+    /// e.g. in a test, to catch templates and the types rendered
+    /// with them drifting apart. See [`Template::missing_paths`],
+    /// the same caveats apply, e.g. content of sections over
+    /// empty [`Vec`]s isn't checked, and fields which are
+    /// [`None`] by default are reported as missing.
+    /// 
+    /// The loader's types are inferred, so only `S` has to be
+    /// named, e.g. `template.check_against::<User, _, _>(&())`.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns [`MoostacheError::CheckErrorMissingPaths`] if any
+    /// paths don't resolve, [`MoostacheError::SerializationError`]
+    /// if `S` can't be serialized, and if using [`HashMapLoader`]
+    /// or [`FileLoader`] can return any other enum variant of
+    /// [`MoostacheError`] while loading partials.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{MoostacheError, Template};
+    /// 
+    /// #[derive(Default, serde_derive::Serialize)]
+    /// struct User {
+    ///     name: String,
+    /// }
+    /// 
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// template.check_against::<User, _, _>(&()).unwrap();
+    /// 
+    /// let template = Template::parse("hello {{nickname}}!").unwrap();
+    /// let err = template.check_against::<User, _, _>(&()).unwrap_err();
+    /// assert_eq!(err, MoostacheError::CheckErrorMissingPaths(vec!["nickname".into()]));
+    /// ```
+    pub fn check_against<S: Serialize + Default, K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+    ) -> Result<(), T::Error> {
+        let value = serde_json::to_value(S::default())
+            .map_err(|err| MoostacheError::SerializationError(err.to_string()))?;
+        let missing = self.missing_paths(loader, &value)?;
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(MoostacheError::CheckErrorMissingPaths(missing).into())
+        }
+    }
+
+    /// Find the keys of `value`, if it's an object, which nothing
+    /// in this template or its partials references, i.e. data the
+    /// template ignores, e.g. to catch fields of a data contract
+    /// which went stale. The inverse of [`Template::missing_paths`].
+    /// Keys are returned in the order they're in the object.
+    ///
+    /// A key counts as referenced if any variable or section path
+    /// starts with it, in any section, e.g. both posts and title
+    /// are referenced by {{# posts }}{{ title }}{{/ posts }} since
+    /// title is looked up in the enclosing scopes if a post doesn't
+    /// have it. Paths which don't start with a key, e.g. {{ . }},
+    /// don't reference anything, and dynamic partials are followed
+    /// by resolving their names against `value`.
+    ///
+    /// ### Errors
+    ///
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`] while
+    /// loading partials.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use moostache::Template;
+    /// use serde_json::json;
+    ///
+    /// let template = Template::parse("{{a}} {{#b}}{{c}}{{/b}}").unwrap();
+    /// let unused = template.unused_keys(
+    ///     &(),
+    ///     &json!({"a": 1, "b": [{"c": 2}], "c": 3, "d": 4}),
+    /// ).unwrap();
+    /// assert_eq!(unused, vec!["d"]);
+    /// ```
+    pub fn unused_keys<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+    ) -> Result<Vec<String>, T::Error> {
+        let serde_json::Value::Object(object) = value else {
+            return Ok(Vec::new());
+        };
+        let mut referenced = HashSet::new();
+        _referenced_keys(self.frags(), loader, value, &mut HashSet::new(), &mut referenced)?;
+        Ok(object
+            .keys()
+            .filter(|key| !referenced.contains(key.as_str()))
+            .cloned()
+            .collect())
+    }
+
+    /// Check that this template, or its partials, references every
+    /// key of `value`, e.g. after rendering it, to error on data
+    /// with fields the template ignores. See [`Template::unused_keys`].
+    ///
+    /// ### Errors
+    ///
+    /// Returns [`MoostacheError::CheckErrorUnusedKeys`] if any keys
+    /// aren't referenced, and if using [`HashMapLoader`] or
+    /// [`FileLoader`] can return any other enum variant of
+    /// [`MoostacheError`] while loading partials.
+    ///
+    /// ### Examples
+    ///
+    /// ```rust
+    /// use moostache::{MoostacheError, Template};
+    /// use serde_json::json;
+    ///
+    /// let template = Template::parse("hello {{name}}!").unwrap();
+    /// template.check_unused_keys(&(), &json!({"name": "John"})).unwrap();
+    ///
+    /// let err = template.check_unused_keys(&(), &json!({"name": "John", "age": 30})).unwrap_err();
+    /// assert_eq!(err, MoostacheError::CheckErrorUnusedKeys(vec!["age".into()]));
+    /// ```
+    pub fn check_unused_keys<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+    ) -> Result<(), T::Error> {
+        let unused = self.unused_keys(loader, value)?;
+        if unused.is_empty() {
+            Ok(())
+        } else {
+            Err(MoostacheError::CheckErrorUnusedKeys(unused).into())
+        }
+    }
+
+    // renders each top-level fragment one at a time, returning
+    // the byte range of the output each one produced
+    fn render_spans<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+        &self,
+        loader: &T,
+        value: &serde_json::Value,
+        writer: &mut Vec<u8>,
+    ) -> Result<Vec<Range<usize>>, T::Error> {
+        let frags = self.frags();
+        let skips = &self.skips;
+        let options = loader.render_options();
+        let ctx = RenderContext::new(loader, &options);
+        let mut spans = Vec::new();
+        let mut scopes = take_scratch_scopes();
+        scopes.push(value);
+        let mut frag_idx = 0;
+        let mut section_idx = 0;
+        while frag_idx < frags.len() {
+            let (mut end_frag, mut end_section) = match frags[frag_idx] {
+                Fragment::Section(_) |
+                Fragment::InvertedSection(_) |
+                Fragment::MissingSection(_) |
+                Fragment::CompareSection(_) |
+                Fragment::AliasSection(_) |
+                Fragment::RangeSection(_) |
+                Fragment::PluralSection(_) |
+                Fragment::WithSection(_) => (
+                    frag_idx + 1 + skips[section_idx].nested_fragments as usize,
+                    section_idx + 1 + skips[section_idx].nested_sections as usize,
+                ),
+                _ => (frag_idx + 1, section_idx),
+            };
+            // a section and its else are rendered together
+            // since the else depends on the section
+            if let Some(Fragment::SectionElse(_)) = frags.get(end_frag) {
+                end_frag += 1 + skips[end_section].nested_fragments as usize;
+                end_section += 1 + skips[end_section].nested_sections as usize;
+            }
+            let start = writer.len();
+            let result = match options.max_output_bytes {
+                Some(max) => _render(
+                    &frags[frag_idx..end_frag],
+                    &skips[section_idx..end_section],
+                    &ctx,
+                    &mut scopes,
+                    &mut OutputLimitWriter {
+                        writer: &mut *writer,
+                        remaining: max.saturating_sub(start),
+                    },
+                ),
+                None => _render(
+                    &frags[frag_idx..end_frag],
+                    &skips[section_idx..end_section],
+                    &ctx,
+                    &mut scopes,
+                    writer,
+                ),
+            };
+            if let Err(err) = result {
+                return_scratch_scopes(scopes);
+                return Err(err);
+            }
+            spans.push(start..writer.len());
+            frag_idx = end_frag;
+            section_idx = end_section;
+        }
+        return_scratch_scopes(scopes);
+        Ok(spans)
+    }
+}
+
+/// Returned by [`Template::render_diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderDiff {
+    /// Output of rendering the template with the new value.
+    pub rendered: String,
+    /// Top-level fragments of the template whose output
+    /// changed between the old value and the new value.
+    pub changed: Vec<ChangedFragment>,
+}
+
+/// A top-level template fragment whose output changed
+/// between two renders. See [`Template::render_diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangedFragment {
+    /// Index of the fragment among the template's
+    /// top-level fragments.
+    pub fragment_index: usize,
+    /// Byte range of the fragment's output in the
+    /// old render.
+    pub old: Range<usize>,
+    /// Byte range of the fragment's output in the
+    /// new render, i.e. in [`RenderDiff::rendered`].
+    pub new: Range<usize>,
+}
+
+// copies a single fragment into parts, see _flatten
+fn copy_fragment(
+    frag: &Fragment<'_>,
+    source: &mut String,
+    parts: &mut Vec<(Fragment<'static>, Range<usize>)>,
+) {
+    let s = frag.as_str();
+    let range = source.len()..source.len() + s.len();
+    source.push_str(s);
+    parts.push((frag.with_str(""), range));
+}
+
+// copies a list of fragments into parts, where each part is
+// a fragment holding an empty str and the range of source its
+// actual str was copied to, replacing partials with their
+// content and recalculating section skips along the way
+// since inlining partials changes how long sections are
+fn _flatten<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    loader: &T,
+    partials: &mut Vec<String>,
+    source: &mut String,
+    parts: &mut Vec<(Fragment<'static>, Range<usize>)>,
+    out_skips: &mut Vec<SectionSkip>,
+) -> Result<(), T::Error> {
+    let mut frag_idx = 0;
+    let mut section_idx = 0;
+    while frag_idx < frags.len() {
+        let frag = &frags[frag_idx];
+        match frag {
+            Fragment::Section(_) |
+            Fragment::InvertedSection(_) |
+            Fragment::MissingSection(_) |
+            Fragment::CompareSection(_) |
+            Fragment::AliasSection(_) |
+            Fragment::RangeSection(_) |
+            Fragment::PluralSection(_) |
+            Fragment::WithSection(_) |
+            Fragment::SectionElse(_) => {
+                let start_frag = frag_idx + 1;
+                let end_frag = start_frag + skips[section_idx].nested_fragments as usize;
+                let start_section = section_idx + 1;
+                let end_section = start_section + skips[section_idx].nested_sections as usize;
+                copy_fragment(frag, source, parts);
+                let skip_idx = out_skips.len();
+                out_skips.push(SectionSkip {
+                    nested_sections: 0,
+                    nested_fragments: 0,
+                    has_aggregates: false,
+                });
+                let parts_before = parts.len();
+                let skips_before = out_skips.len();
+                _flatten(
+                    &frags[start_frag..end_frag],
+                    &skips[start_section..end_section],
+                    loader,
+                    partials,
+                    source,
+                    parts,
+                    out_skips,
+                )?;
+                out_skips[skip_idx] = SectionSkip {
+                    nested_sections: u16::try_from(out_skips.len() - skips_before)
+                        .map_err(|_| MoostacheError::ParseErrorTooManySections(String::new()))?,
+                    nested_fragments: u16::try_from(parts.len() - parts_before)
+                        .map_err(|_| MoostacheError::ParseErrorTooManyFragments(String::new()))?,
+                    // inlined partials can add aggregates to the section
+                    has_aggregates: parts[parts_before..].iter().any(|(frag, range)| {
+                        matches!(frag, Fragment::EscapedVariable(_) | Fragment::UnescapedVariable(_))
+                            && is_aggregate(split_width(&source[range.clone()]).0)
+                    }),
+                };
+                frag_idx = end_frag;
+                section_idx = end_section;
+            },
+            Fragment::Partial(path) => {
+                // partials are resolved the same as during render,
+                // where the template being flattened is anonymous
+                let template_name = partials.last().map_or("", String::as_str);
+                let relative_partials = loader.render_options().relative_partials;
+                let (template, relative) = get_partial(loader, relative_partials, template_name, path)?;
+                let name = relative.unwrap_or_else(|| (*path).to_owned());
+                if partials.contains(&name) {
+                    return Err(MoostacheError::LoaderErrorCyclicPartial(name).into());
+                }
+                partials.push(name);
+                _flatten(
+                    template.frags(),
+                    &template.skips,
+                    loader,
+                    partials,
+                    source,
+                    parts,
+                    out_skips,
+                )?;
+                partials.pop();
+                frag_idx += 1;
+            },
+            _ => {
+                copy_fragment(frag, source, parts);
+                frag_idx += 1;
+            },
+        }
+    }
+    Ok(())
+}
+
+type SectionItems<'a> = Box<dyn Iterator<Item = Result<serde_json::Value, serde_json::Error>> + 'a>;
+
+/// Sections whose items are pulled lazily from iterators during
+/// render, one at a time, so they never have to be collected
+/// into a [`serde_json::Value::Array`]. Passed to
+/// [`Template::render_with_iter_sections`].
+/// 
+/// Every item is serialized into a [`serde_json::Value`] right
+/// before the section content is rendered with it, and is dropped
+/// right after. An iterator can only be consumed once, so if its
+/// section is rendered more than once then only the first render
+/// gets its items and the later ones are empty. An inverted section
+/// with the same name renders if the iterator has no items left.
+pub struct IterSections<'a> {
+    sections: HashMap<&'a str, RefCell<Peekable<SectionItems<'a>>>>,
+}
+
+impl<'a> IterSections<'a> {
+    /// Create an empty [`IterSections`].
+    #[must_use]
+    pub fn new() -> Self {
+        IterSections {
+            sections: HashMap::new(),
+        }
+    }
+
+    /// Bind the section `name`, e.g. {{# name }}, to the items
+    /// of `items`. Replaces any iterator previously bound to
+    /// the same name.
+    pub fn insert<I: IntoIterator<Item = S>, S: Serialize>(&mut self, name: &'a str, items: I)
+    where
+        I::IntoIter: 'a,
+    {
+        let items: SectionItems<'a> = Box::new(
+            items.into_iter().map(|item| serde_json::to_value(item))
+        );
+        self.sections.insert(name, RefCell::new(items.peekable()));
+    }
+
+    // items of the section bound to name
+    fn get(&self, name: &str) -> Option<&RefCell<Peekable<SectionItems<'a>>>> {
+        self.sections.get(name)
+    }
+}
+
+impl Default for IterSections<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for IterSections<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IterSections")
+            .field("sections", &self.sections.keys())
+            .finish()
+    }
+}
+
+/// Renders the same template over and over with different
+/// data, reusing the same output buffer every time, which is
+/// useful if it's re-rendered frequently, e.g. for a status
+/// page refreshed every second. Renders are the same as
+/// [`Template::render_to_string`], including applying
+/// [`RenderOptions::trailing_whitespace`] and
+/// [`TemplateLoader::post_render`].
+/// 
+/// The buffer is never shrunk, so it stays as large as the
+/// largest render so far.
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::{RenderSession, Template};
+/// use serde_json::json;
+/// 
+/// let template = Template::parse("{{up}}/{{total}} up").unwrap();
+/// let mut session = RenderSession::new(&template, &());
+/// assert_eq!(session.render(&json!({"up": 3, "total": 4})).unwrap(), "3/4 up");
+/// assert_eq!(session.render(&json!({"up": 4, "total": 4})).unwrap(), "4/4 up");
+/// ```
+pub struct RenderSession<'a, 'src, T: ?Sized> {
+    template: &'a BorrowedTemplate<'src>,
+    loader: &'a T,
+    // output of the last render, its allocation
+    // is reused by the next render
+    buffer: String,
+}
+
+impl<'a, 'src, T: ?Sized> RenderSession<'a, 'src, T> {
+    /// Create a new [`RenderSession`] which renders `template`
+    /// using `loader` to load partials. Use `&()` as the loader
+    /// if the template has no partials.
+    #[must_use]
+    pub fn new(template: &'a BorrowedTemplate<'src>, loader: &'a T) -> Self {
+        RenderSession {
+            template,
+            loader,
+            buffer: String::new(),
+        }
+    }
+
+    /// Render the template with `value` and return the output,
+    /// which is borrowed from the session until the next render.
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    pub fn render<K: Borrow<str> + Eq + Hash>(
+        &mut self,
+        value: &serde_json::Value,
+    ) -> Result<&str, <T as TemplateLoader<K>>::Error>
+    where
+        T: TemplateLoader<K>,
+    {
+        let mut writer = std::mem::take(&mut self.buffer).into_bytes();
+        writer.clear();
+        if let Err(err) = self.template.render(self.loader, value, &mut writer) {
+            self.keep_buffer(writer);
+            return Err(err);
+        }
+        let rendered = if self.loader.value_renderer().is_some() {
+            match String::from_utf8(writer) {
+                Ok(rendered) => rendered,
+                Err(err) => {
+                    self.keep_buffer(err.into_bytes());
+                    return Err(MoostacheError::RenderErrorInvalidUtf8.into());
+                },
+            }
+        } else {
+            rendered_utf8(self.loader, writer)?
+        };
+        let rendered = self.loader.render_options().trailing_whitespace.apply(rendered);
+        self.buffer = self.loader.post_render(rendered);
+        Ok(&self.buffer)
+    }
+
+    // drops the output of a failed render, keeping
+    // the allocation for the next render
+    fn keep_buffer(&mut self, mut writer: Vec<u8>) {
+        writer.clear();
+        self.buffer = String::from_utf8(writer).unwrap_or_default();
+    }
+}
+
+/// A fragment of a parsed template. Returned by
+/// [`Template::inspect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentInfo {
+    /// What kind of fragment it is.
+    pub kind: FragmentKind,
+    /// Byte range of the fragment in the template's source,
+    /// for tags this includes the braces, e.g. {{ variable }}.
+    pub span: Range<usize>,
+}
+
+/// Kinds of fragments a template is parsed into.
+/// See [`FragmentInfo`].
+/// 
+/// New variants may be added in future versions,
+/// so matching on this enum requires a wildcard arm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FragmentKind {
+    /// Literal text, including the content of raw blocks.
+    Literal(String),
+    /// A variable, e.g. {{ variable }} or {{{ variable }}}.
+    Variable {
+        /// Path of the variable, e.g. some.variable.path.
+        name: String,
+        /// If the variable is html escaped, i.e. uses
+        /// two braces instead of three.
+        escaped: bool,
+    },
+    /// The start of a section, e.g. {{# section }}
+    /// or {{^ section }}.
+    SectionStart {
+        /// Path of the section, e.g. some.section.path.
+        name: String,
+        /// If it's an inverted section, i.e. {{^ section }}.
+        inverted: bool,
+        /// How many of the fragments after this one are
+        /// nested within the section.
+        nested: usize,
+    },
+    /// The start of a missing section, e.g. {{^? section }},
+    /// which is only rendered if the section doesn't exist.
+    MissingSectionStart {
+        /// Path of the section, e.g. some.section.path.
+        name: String,
+        /// How many of the fragments after this one are
+        /// nested within the section.
+        nested: usize,
+    },
+    /// The else of a section, e.g. {{|}} in
+    /// {{# section }} ... {{|}} ... {{/ section }}.
+    SectionElse {
+        /// How many of the fragments after this one are
+        /// nested within the else, i.e. rendered if the
+        /// section wasn't.
+        nested: usize,
+    },
+    /// The start of an equality section, e.g.
+    /// {{# eq status "active" }} or {{# ne status "active" }},
+    /// which is only rendered if the section's value is equal,
+    /// or not equal, to the literal. Requires the `helpers`
+    /// feature.
+    CompareSectionStart {
+        /// Path of the section, e.g. some.section.path.
+        name: String,
+        /// The literal the section's value is compared to,
+        /// as it appears in the template, e.g. "active" with
+        /// the quotes, -1.5, true, or null.
+        literal: String,
+        /// If it's a {{# ne ... }}, i.e. only rendered if
+        /// the section's value isn't equal to the literal.
+        negated: bool,
+        /// How many of the fragments after this one are
+        /// nested within the section.
+        nested: usize,
+    },
+    /// The start of an alias section, e.g.
+    /// {{# posts as post }}, where each item of the section is
+    /// rendered in a scope of its own in which it's the value
+    /// of the alias, e.g. {{ post.title }}.
+    AliasSectionStart {
+        /// Path of the section, e.g. some.section.path.
+        name: String,
+        /// Name each item of the section is given.
+        alias: String,
+        /// How many of the fragments after this one are
+        /// nested within the section.
+        nested: usize,
+    },
+    /// The start of a range section, e.g. {{# range 1 5 }},
+    /// whose content is rendered once per number from the
+    /// start up to but not including the end, with the number
+    /// as the scope, e.g. 1234. Requires the `helpers` feature.
+    RangeSectionStart {
+        /// Start of the range, an integer, e.g. 1, or the
+        /// path of a variable holding one.
+        start: String,
+        /// End of the range, which isn't included, an integer,
+        /// e.g. 5, or the path of a variable holding one.
+        end: String,
+        /// How many of the fragments after this one are
+        /// nested within the section.
+        nested: usize,
+    },
+    /// The start of a plural section, e.g.
+    /// {{# plural count }} items {{|}} item {{/ plural }},
+    /// which is only rendered if the section's value isn't a
+    /// number whose magnitude is 1, e.g. 1 or -1, so its else
+    /// is rendered if it is. Requires the `helpers` feature.
+    PluralSectionStart {
+        /// Path of the section, e.g. some.section.path.
+        name: String,
+        /// How many of the fragments after this one are
+        /// nested within the section.
+        nested: usize,
+    },
+    /// The start of a with section, e.g.
+    /// {{# with user }} {{ name }} {{/ with }}, which is
+    /// rendered once with the section's value as the scope,
+    /// even if it's an array or falsy, unless it's null or
+    /// missing, so its else is rendered if it is. Requires
+    /// the `helpers` feature.
+    WithSectionStart {
+        /// Path of the section, e.g. some.section.path.
+        name: String,
+        /// How many of the fragments after this one are
+        /// nested within the section.
+        nested: usize,
+    },
+    /// A partial, e.g. {{> partial }}.
+    Partial(String),
+    /// A dynamic partial, e.g. {{>* variable }}, where the
+    /// name of the partial is the value of the variable.
+    DynamicPartial(String),
+}
+
+/// A non-fatal issue with a template, which parses and
+/// renders fine but is probably not what its author meant.
+/// Returned by [`Template::parse_with_warnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// A section with nothing in it, e.g. {{# section }}{{/ section }},
+    /// which never renders anything.
+    EmptySection {
+        /// Path of the section, e.g. some.section.path.
+        name: String,
+        /// Byte range of the section's start tag in the
+        /// template's source.
+        span: Range<usize>,
+    },
+    /// A partial which was already included earlier in the
+    /// same template, e.g. the second {{> partial }} in
+    /// {{> partial }}{{> partial }}, which is fine if it's
+    /// intentional but is often a copy and paste mistake.
+    DuplicatePartial {
+        /// Name of the partial.
+        name: String,
+        /// Byte range of the repeated partial tag in the
+        /// template's source.
+        span: Range<usize>,
+    },
+}
+
+impl Warning {
+    /// Byte range of the warning's tag in the template's source.
+    #[must_use]
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Warning::EmptySection { span, .. }
+            | Warning::DuplicatePartial { span, .. } => span.clone(),
+        }
+    }
+}
+
+/// A lexical token of a template's source. Returned by
+/// [`tokenize`] and [`tokenize_with_options`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    /// What kind of token it is.
+    pub kind: TokenKind,
+    /// Byte range of the token in the template's source.
+    pub span: Range<usize>,
+}
+
+/// Kinds of tokens a template's source is split into.
+/// See [`Token`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Text outside of tags, including the content of raw
+    /// blocks and anything which isn't a complete tag, e.g.
+    /// a {{ which is never closed.
+    Text,
+    /// The opening braces of a tag, i.e. {{ or {{{, or the open
+    /// delimiter if it isn't {{, see [`Delimiters`].
+    TagOpen,
+    /// The closing braces of a tag, i.e. }} or }}}, or the close
+    /// delimiter if it isn't }}, see [`Delimiters`].
+    TagClose,
+    /// Whitespace within a tag.
+    Whitespace,
+    /// The # of a section start, e.g. {{# section }}.
+    SectionStart,
+    /// The ^ of an inverted section start, e.g. {{^ section }},
+    /// or the ^ and ? of a missing section start, e.g.
+    /// {{^? section }}.
+    InvertedSectionStart,
+    /// The / of a section end, e.g. {{/ section }}.
+    SectionEnd,
+    /// The | of a section else, i.e. {{|}}.
+    SectionElse,
+    /// The > of a partial, e.g. {{> partial }}, or the > and *
+    /// of a dynamic partial, e.g. {{>* variable }}.
+    Partial,
+    /// The % signs of a raw block's tags, e.g. {{% raw %}}.
+    RawBlock,
+    /// A comment, including its !, e.g. ! comment in
+    /// {{! comment }}.
+    Comment,
+    /// A variable path, partial name, or any other word within
+    /// a tag, e.g. some.variable in {{ some.variable }}.
+    Name,
+}
+
+/// Splits a template's source into lexical tokens, e.g. for
+/// syntax highlighting, without parsing it into a [`Template`],
+/// so invalid templates can be tokenized too. Tokens are in
+/// order and every byte of the source is in exactly one token,
+/// so their spans cover the whole source.
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::{tokenize, TokenKind};
+/// 
+/// let source = "hi {{# names }}";
+/// let tokens: Vec<_> = tokenize(source)
+///     .into_iter()
+///     .map(|token| (token.kind, &source[token.span]))
+///     .collect();
+/// assert_eq!(tokens, [
+///     (TokenKind::Text, "hi "),
+///     (TokenKind::TagOpen, "{{"),
+///     (TokenKind::SectionStart, "#"),
+///     (TokenKind::Whitespace, " "),
+///     (TokenKind::Name, "names"),
+///     (TokenKind::Whitespace, " "),
+///     (TokenKind::TagClose, "}}"),
+/// ]);
+/// ```
+#[must_use]
+pub fn tokenize(source: &str) -> Vec<Token> {
+    tokenize_with_options(source, &ParseOptions::default())
+}
+
+/// Splits a template's source into lexical tokens, like
+/// [`tokenize`], using the given [`ParseOptions`], e.g. to
+/// tokenize templates which use other [`Delimiters`].
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::{tokenize_with_options, Delimiters, ParseOptions, TokenKind};
+/// 
+/// let options = ParseOptions {
+///     delimiters: Delimiters { open: "[[", close: "]]" },
+///     ..ParseOptions::default()
+/// };
+/// let source = "{{ vue }} [[name]]";
+/// let tokens: Vec<_> = tokenize_with_options(source, &options)
+///     .into_iter()
+///     .map(|token| (token.kind, &source[token.span]))
+///     .collect();
+/// assert_eq!(tokens, [
+///     (TokenKind::Text, "{{ vue }} "),
+///     (TokenKind::TagOpen, "[["),
+///     (TokenKind::Name, "name"),
+///     (TokenKind::TagClose, "]]"),
+/// ]);
+/// ```
+#[must_use]
+pub fn tokenize_with_options(source: &str, options: &ParseOptions) -> Vec<Token> {
+    let Delimiters { open: open_delimiter, close: close_delimiter } = options.delimiters;
+    let mut tokens = Vec::new();
+    // empty delimiters would match everywhere, and
+    // since they can't be parsed everything is text
+    if open_delimiter.is_empty() || close_delimiter.is_empty() {
+        push_token(&mut tokens, TokenKind::Text, 0..source.len());
+        return tokens;
+    }
+    // text is merged into a single token until the next tag
+    let mut text_start = 0;
+    let mut offset = 0;
+    while let Some(index) = source[offset..].find(open_delimiter) {
+        let open = offset + index;
+        // a {{{ which is never closed by }}} could still
+        // be a {{ closed by }}
+        let triple_open = source[open + open_delimiter.len()..].starts_with('{');
+        let triple_close = triple_open
+            .then(|| find_triple_close(&source[open + open_delimiter.len() + 1..], close_delimiter))
+            .flatten();
+        let (open_len, close_len, inner_len) = match triple_close {
+            Some(inner_len) => (open_delimiter.len() + 1, close_delimiter.len() + 1, inner_len),
+            None => match source[open + open_delimiter.len()..].find(close_delimiter) {
+                Some(inner_len) => (open_delimiter.len(), close_delimiter.len(), inner_len),
+                None => break,
+            },
+        };
+        let inner_start = open + open_len;
+        let close = inner_start + inner_len;
+        push_token(&mut tokens, TokenKind::Text, text_start..open);
+        push_token(&mut tokens, TokenKind::TagOpen, open..inner_start);
+        tokenize_tag(&source[inner_start..close], inner_start, &mut tokens);
+        push_token(&mut tokens, TokenKind::TagClose, close..close + close_len);
+        text_start = close + close_len;
+        offset = text_start;
+        // the content of a raw block is text up until its end tag
+        if is_raw_tag(&source[inner_start..close], "raw") {
+            match find_raw_end_tag(&source[offset..], options.delimiters) {
+                Some(index) => offset += index,
+                None => break,
+            }
+        }
+    }
+    push_token(&mut tokens, TokenKind::Text, text_start..source.len());
+    tokens
+}
+
+// finds where the } before the close delimiter of an
+// unescaped variable is, i.e. the first } of }}}
+fn find_triple_close(source: &str, close: &str) -> Option<usize> {
+    let mut offset = 0;
+    while let Some(index) = source[offset..].find(close) {
+        let start = offset + index;
+        if start > 0 && source[..start].ends_with('}') {
+            return Some(start - 1);
+        }
+        // the close delimiter could overlap with itself, e.g.
+        // the last two braces of }}} are also a }}
+        offset = start + close.chars().next().map_or(1, char::len_utf8);
+    }
+    None
+}
+
+// pushes a token unless it's empty
+fn push_token(tokens: &mut Vec<Token>, kind: TokenKind, span: Range<usize>) {
+    if !span.is_empty() {
+        tokens.push(Token { kind, span });
+    }
+}
+
+// checks if the inside of a tag, i.e. between its braces,
+// is a raw block tag with the given name, e.g. % raw %
+fn is_raw_tag(inner: &str, name: &str) -> bool {
+    inner.strip_prefix('%')
+        .and_then(|inner| inner.strip_suffix('%'))
+        .is_some_and(|inner| inner.trim_matches(is_multispace) == name)
+}
+
+// finds where the end tag of a raw block starts, e.g.
+// {{% endraw %}}, see parse_raw_block
+fn find_raw_end_tag(source: &str, delimiters: Delimiters) -> Option<usize> {
+    let Delimiters { open, close } = delimiters;
+    let mut offset = 0;
+    while let Some(index) = source[offset..].find(open) {
+        let start = offset + index;
+        offset = start + open.len();
+        if !source[offset..].starts_with('%') {
+            continue;
+        }
+        let is_end_tag = source[offset + 1..]
+            .find(close)
+            .is_some_and(|inner_len| is_raw_tag(&source[offset..offset + 1 + inner_len], "endraw"));
+        if is_end_tag {
+            return Some(start);
+        }
+    }
+    None
+}
+
+// splits the inside of a tag, i.e. between its braces, into
+// tokens, start is the offset of the inside in the source
+fn tokenize_tag(inner: &str, start: usize, tokens: &mut Vec<Token>) {
+    let mut pos = inner.len() - inner.trim_start_matches(is_multispace).len();
+    push_token(tokens, TokenKind::Whitespace, start..start + pos);
+    let (kind, suffix) = match inner[pos..].chars().next() {
+        Some('#') => (TokenKind::SectionStart, None),
+        Some('^') => (TokenKind::InvertedSectionStart, Some('?')),
+        Some('/') => (TokenKind::SectionEnd, None),
+        Some('|') => (TokenKind::SectionElse, None),
+        Some('>') => (TokenKind::Partial, Some('*')),
+        Some('%') => (TokenKind::RawBlock, None),
+        Some('!') => {
+            push_token(tokens, TokenKind::Comment, start + pos..start + inner.len());
+            return;
+        },
+        _ => (TokenKind::Name, None),
+    };
+    let mut end = inner.len();
+    if kind != TokenKind::Name {
+        push_token(tokens, kind, start + pos..start + pos + 1);
+        pos += 1;
+        // the second char of ^? and >* can come after whitespace
+        let trimmed = inner[pos..].trim_start_matches(is_multispace);
+        if let Some(suffix) = suffix.filter(|&suffix| trimmed.starts_with(suffix)) {
+            let suffix_pos = inner.len() - trimmed.len();
+            push_token(tokens, TokenKind::Whitespace, start + pos..start + suffix_pos);
+            pos = suffix_pos + suffix.len_utf8();
+            push_token(tokens, kind, start + suffix_pos..start + pos);
+        }
+        // raw block tags end with a % too
+        if kind == TokenKind::RawBlock && inner.len() > pos && inner.ends_with('%') {
+            end -= 1;
+        }
+    }
+    // the rest is alternating whitespace and names
+    while pos < end {
+        let is_space = inner[pos..].starts_with(is_multispace);
+        let len = inner[pos..end]
+            .find(|c| is_multispace(c) != is_space)
+            .unwrap_or(end - pos);
+        let kind = if is_space { TokenKind::Whitespace } else { TokenKind::Name };
+        push_token(tokens, kind, start + pos..start + pos + len);
+        pos += len;
+    }
+    push_token(tokens, TokenKind::RawBlock, start + end..start + inner.len());
+}
+
+// note: can't do impl<S: Into<ImmutableStr> TryFrom<S> below
+// because compiler complains that generic impl overlaps
+// with another generic impl in the std lib, so we do separate
+// impls for &'static str and String
+
+impl<'src> TryFrom<&'src str> for BorrowedTemplate<'src> {
+    type Error = MoostacheError;
+    fn try_from(source: &'src str) -> Result<Self, Self::Error> {
+        Self::parse(source)
+    }
+}
+
+impl TryFrom<String> for BorrowedTemplate<'_> {
+    type Error = MoostacheError;
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Self::parse(source)
+    }
+}
+
+impl<'src> TryFrom<Cow<'src, str>> for BorrowedTemplate<'src> {
+    type Error = MoostacheError;
+    fn try_from(source: Cow<'src, str>) -> Result<Self, Self::Error> {
+        Self::parse(source)
+    }
+}
+
+//////////////////////
+// TEMPLATE LOADERS //
+//////////////////////
+
+/// Loads templates and renders them.
+pub trait TemplateLoader<K: Borrow<str> + Eq + Hash = String> {
+    /// Output type of the [`get`](TemplateLoader::get) method.
+    type Output<'a>: Deref<Target = Template> + 'a where Self: 'a;
+    /// Error type of the [`get`](TemplateLoader::get) and render methods.
+    type Error: From<MoostacheError>;
+    
+    /// Get a template by name.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns an [`Error`](TemplateLoader::Error) if getting
+    /// the template fails for whatever reason. In [`HashMapLoader`]
+    /// this would only ever return
+    /// [`MoostacheError::LoaderErrorTemplateNotFound`] since it
+    /// either has the template or it doesn't. In [`FileLoader`] it
+    /// can return almost any enum variant of [`MoostacheError`]
+    /// since it lazily loads and compiles templates on-demand.
+    fn get<'a>(&'a self, name: &str) -> Result<Self::Output<'a>, Self::Error>;
+
+    /// Insert a template by name.
+    fn insert(&mut self, name: K, value: Template) -> Option<Template>;
+
+    /// Remove a template by name.
+    fn remove(&mut self, name: &str) -> Option<Template>;
+
+    /// Options used when rendering with this loader, unless
+    /// other options are passed to the render, e.g. with
+    /// [`Template::render_with_options`]. Returns
+    /// [`RenderOptions::default`] unless overridden.
+    #[inline]
+    fn render_options(&self) -> RenderOptions {
+        RenderOptions::default()
+    }
+
+    /// Transforms the whole output of renders which return a
+    /// [`String`], e.g. [`render_to_string`](TemplateLoader::render_to_string),
+    /// after rendering with this loader, e.g. to minify HTML.
+    /// Returns the output unchanged unless overridden.
+    #[inline]
+    fn post_render(&self, rendered: String) -> String {
+        rendered
+    }
+
+    /// Renderer which gets the first chance to write the value
+    /// of every variable rendered with this loader, e.g. to
+    /// format dates. Returns [`None`] unless overridden.
+    /// See [`ValueRenderer`].
+    #[inline]
+    fn value_renderer(&self) -> Option<&dyn ValueRenderer> {
+        None
+    }
+
+    /// Name of the template rendered instead of a template
+    /// which fails to render by name, e.g. with
+    /// [`render`](TemplateLoader::render), because it fails to
+    /// parse or uses a missing partial. Returns [`None`] unless
+    /// overridden. Set with a loader's `on_error_render`, e.g.
+    /// [`HashMapLoader::on_error_render`].
+    #[inline]
+    fn error_template(&self) -> Option<&str> {
+        None
+    }
+
+    /// If an error returned by [`get`](TemplateLoader::get) means
+    /// the template doesn't exist, rather than that it exists but
+    /// failed to load, e.g. to parse. Partials are only looked up
+    /// by their name as-is after their relative name, see
+    /// [`RenderOptions::relative_partials`], if this is true.
+    /// Returns true for every error unless overridden.
+    #[inline]
+    fn is_not_found(&self, _err: &Self::Error) -> bool {
+        true
+    }
+    
+    /// Render a template by name, using a [`serde_json::Value`]
+    /// as data and writing output to a [`&mut impl Write`](std::io::Write).
+    /// 
+    /// If the loader has an
+    /// [`error_template`](TemplateLoader::error_template) then
+    /// the output is buffered, so nothing is written if the
+    /// render fails, and the error template is rendered with
+    /// the same data instead.
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`]. If the
+    /// loader has an error template then only errors from
+    /// rendering the error template, or from writing the output,
+    /// are returned.
+    #[inline]
+    fn render<W: Write>(
+        &self,
+        name: &str,
+        value: &serde_json::Value,
+        writer: &mut W,
+    ) -> Result<(), Self::Error> {
+        let Some(error_template) = self.error_template() else {
+            let template = self.get(name)?;
+            return template.render_named(self, name, value, writer);
+        };
+        let mut buffer = Vec::new();
+        let rendered = self.get(name)
+            .and_then(|template| template.render_named(self, name, value, &mut buffer));
+        if rendered.is_ok() {
+            return writer.write_all(&buffer)
+                .map_err(|err| MoostacheError::from_write_io(err).into());
+        }
+        let template = self.get(error_template)?;
+        template.render_named(self, error_template, value, writer)
+    }
+
+    /// Render a template by name, using a type which impls
+    /// [`serde::Serialize`] as data and writing output to a
+    /// [`&mut impl Write`](std::io::Write).
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    #[inline]
+    fn render_serializable<W: Write, S: Serialize>(
+        &self,
+        name: &str,
+        serializeable: &S,
+        writer: &mut W,
+    ) -> Result<(), Self::Error> {
+        let value = serde_json::to_value(serializeable)
+            .map_err(|err| MoostacheError::SerializationError(err.to_string()))?;
+        self.render(
+            name,
+            &value,
+            writer
+        )
+    }
+
+    /// Renders a template by name, using a [`serde_json::Value`]
+    /// as data and returning the output as a [`String`].
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    #[inline]
+    fn render_to_string(
+        &self,
+        name: &str,
+        value: &serde_json::Value,
+    ) -> Result<String, Self::Error> {
+        let mut writer = Vec::<u8>::new();
+        self.render(
+            name,
+            value,
+            &mut writer
+        )?;
+        let rendered = rendered_utf8(self, writer)?;
+        let rendered = self.render_options().trailing_whitespace.apply(rendered);
+        Ok(self.post_render(rendered))
+    }
+
+    /// Renders a template by name, using a type which impls
+    /// [`serde::Serialize`] as data and returning the output
+    /// as a [`String`].
+    /// 
+    /// ### Errors
+    /// 
+    /// If using [`HashMapLoader`] or [`FileLoader`] this function
+    /// can return any enum variant of [`MoostacheError`].
+    #[inline]
+    fn render_serializable_to_string<S: Serialize>(
+        &self,
+        name: &str,
+        serializable: &S,
+    ) -> Result<String, Self::Error> {
+        let value = serde_json::to_value(serializable)
+            .map_err(|err| MoostacheError::SerializationError(err.to_string()))?;
+        self.render_to_string(
+            name,
+            &value,
+        )
+    }
+}
+
+// function set with a loader's set_post_render,
+// wrapped so loaders can still derive Debug
+struct PostRender(Box<dyn Fn(String) -> String>);
+
+impl Debug for PostRender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "PostRender")
+    }
+}
+
+/// Custom rendering of variables' values, consulted before
+/// a value is written the default way, e.g. to write dates
+/// or numbers in a localized format without changing the
+/// data being rendered. Set with a loader's `set_value_renderer`,
+/// e.g. [`HashMapLoader::set_value_renderer`].
+/// 
+/// It's implemented for closures with the same signature as
+/// [`render`](ValueRenderer::render).
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::{HashMapLoader, TemplateLoader};
+/// use maplit::hashmap;
+/// use serde_json::{json, Value};
+/// use std::io::Write;
+/// 
+/// let mut loader = HashMapLoader::try_from(hashmap! {
+///     "price" => "{{ price }} {{ name }}",
+/// }).unwrap();
+/// loader.set_value_renderer(|value: &Value, _escaped: bool, writer: &mut dyn Write| {
+///     let price = value.as_f64()?;
+///     Some(write!(writer, "${price:.2}"))
+/// });
+/// let rendered = loader.render_to_string(
+///     "price",
+///     &json!({"price": 3, "name": "apples"}),
+/// ).unwrap();
+/// assert_eq!(rendered, "$3.00 apples");
+/// ```
+pub trait ValueRenderer {
+    /// Write the value of a variable, or return [`None`] to
+    /// write it the default way instead, in which case nothing
+    /// should be written to the writer. `escaped` is if the
+    /// variable is escaped, i.e. {{ variable }} rather than
+    /// {{{ variable }}}, but either way the writer already
+    /// escapes what's written to it if needed, so renderers
+    /// don't have to. Values of variables with widths, e.g.
+    /// {{ variable:10 }}, are padded or truncated after
+    /// being written.
+    /// 
+    /// Only used for renders with [`serde_json::Value`]s, e.g.
+    /// not [`Template::render_flat_serializable`].
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns [`Some`] [`std::io::Error`] if writing fails,
+    /// which fails the render with
+    /// [`MoostacheError::IoError`]. Renders which return a
+    /// [`String`], e.g. [`Template::render_to_string`], validate
+    /// their output if the loader has a value renderer, so
+    /// writing invalid utf-8 fails them with
+    /// [`MoostacheError::RenderErrorInvalidUtf8`].
+    fn render(&self, value: &serde_json::Value, escaped: bool, writer: &mut dyn Write) -> Option<std::io::Result<()>>;
+}
+
+impl<F: Fn(&serde_json::Value, bool, &mut dyn Write) -> Option<std::io::Result<()>>> ValueRenderer for F {
+    #[inline]
+    fn render(&self, value: &serde_json::Value, escaped: bool, writer: &mut dyn Write) -> Option<std::io::Result<()>> {
+        self(value, escaped, writer)
+    }
+}
+
+// renderer set with a loader's set_value_renderer,
+// wrapped so loaders can still derive Debug
+struct DynValueRenderer(Box<dyn ValueRenderer>);
+
+impl Debug for DynValueRenderer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DynValueRenderer")
+    }
+}
+
+// names of the templates gotten from a loader, in order of
+// first use, while recording, see HashMapLoader::start_recording
+#[derive(Debug, Default)]
+struct Recording(RefCell<Option<Vec<String>>>);
+
+impl Recording {
+    fn start(&self) {
+        *self.0.borrow_mut() = Some(Vec::new());
+    }
+    fn stop(&self) -> Vec<String> {
+        self.0.borrow_mut().take().unwrap_or_default()
+    }
+    #[inline]
+    fn record(&self, name: &str) {
+        if let Some(names) = self.0.borrow_mut().as_mut() {
+            if !names.iter().any(|recorded| recorded == name) {
+                names.push(name.to_owned());
+            }
+        }
+    }
+}
+
+/// Useful struct for creating [`HashMapLoader`]s or
+/// [`FileLoader`]s.
+/// 
+/// ### Examples
+/// 
+/// Creating a [`HashMapLoader`]:
+/// 
+/// ```rust
+/// use moostache::{LoaderConfig, HashMapLoader};
+/// 
+/// let loader = HashMapLoader::try_from(LoaderConfig::default()).unwrap();
+/// ```
+/// 
+/// Creating a [`FileLoader`]:
+/// 
+/// ```rust
+/// use moostache::{LoaderConfig, FileLoader};
+/// 
+/// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+/// ```
+/// 
+/// Creating a [`FileLoader`] from owned [`String`]s, e.g.
+/// read from environment variables:
+/// 
+/// ```rust
+/// use moostache::{LoaderConfig, FileLoader};
+/// 
+/// let dir = std::env::var("TEMPLATES_DIR")
+///     .unwrap_or_else(|_| "./templates/".to_owned());
+/// let loader = FileLoader::try_from(LoaderConfig {
+///     templates_directory: dir.into(),
+///     ..LoaderConfig::default()
+/// }).unwrap();
+/// ```
+/// 
+/// Reading a [`LoaderConfig`] from a config file, which requires
+/// the `serde` feature, any missing fields are set to their
+/// default values:
+/// 
+/// ```rust
+/// # #[cfg(feature = "serde")] {
+/// use moostache::LoaderConfig;
+/// 
+/// let config: LoaderConfig = serde_json::from_str(r#"{
+///     "templates_directory": "./templates/",
+///     "cache_size": 50
+/// }"#).unwrap();
+/// assert_eq!(config.cache_size, 50);
+/// assert_eq!(config.templates_extension, ".html");
+/// # }
+/// ```
+/// 
+/// [`LoaderConfig`] default values:
+/// 
+/// ```rust
+/// use moostache::{LoaderConfig, ParseOptions};
+/// 
+/// assert_eq!(
+///     LoaderConfig::default(),
+///     LoaderConfig {
+///         templates_directory: "./templates/".into(),
+///         templates_extension: ".html".into(),
+///         fallback_extensions: Vec::new(),
+///         case_insensitive_extension: false,
+///         cache_size: 200,
+///         cache_bytes: None,
+///         parse_options: ParseOptions::default(),
+///     },
+/// );
+/// ```
+#[cfg(feature = "fs")]
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize), serde(default))]
+pub struct LoaderConfig<'a> {
+    /// Directory to load templates from.
+    pub templates_directory: Cow<'a, str>,
+    /// File extension of template files.
+    pub templates_extension: Cow<'a, str>,
+    /// File extensions tried in order, after `templates_extension`,
+    /// for templates which don't have a file with that extension,
+    /// e.g. `[".htm", ".mustache"]` to keep loading templates
+    /// with legacy extensions while preferring `.html` ones.
+    pub fallback_extensions: Vec<Cow<'a, str>>,
+    /// Match the file extension of template files ignoring
+    /// ASCII case, e.g. so ".html" also matches ".HTML".
+    pub case_insensitive_extension: bool,
+    /// Max number of compiled templates to cache in memory.
+    pub cache_size: usize,
+    /// Max approximate bytes of memory compiled templates in the
+    /// cache can use, if limited, in addition to `cache_size`,
+    /// since templates can vary a lot in size. A template's size
+    /// is its source plus its parsed fragments, and templates
+    /// larger than the limit on their own aren't cached at all.
+    /// Only used by [`FileLoader`].
+    pub cache_bytes: Option<usize>,
+    /// Options every template is parsed with, e.g. to parse them
+    /// with other [`Delimiters`]. Isn't read from config files,
+    /// since delimiters are `&'static str`s, so it's always the
+    /// default when deserialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub parse_options: ParseOptions,
+}
+
+#[cfg(all(feature = "fs", windows))]
+const DEFAULT_TEMPLATES_DIRECTORY: &str = ".\\templates\\";
+
+#[cfg(all(feature = "fs", not(windows)))]
+const DEFAULT_TEMPLATES_DIRECTORY: &str = "./templates/";
+
+#[cfg(feature = "fs")]
+impl Default for LoaderConfig<'_> {
+    fn default() -> Self {
+        Self {
+            templates_directory: Cow::Borrowed(DEFAULT_TEMPLATES_DIRECTORY),
+            templates_extension: Cow::Borrowed(".html"),
+            fallback_extensions: Vec::new(),
+            case_insensitive_extension: false,
+            cache_size: 200,
+            cache_bytes: None,
+            parse_options: ParseOptions::default(),
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl TryFrom<LoaderConfig<'_>> for HashMapLoader {
+    type Error = MoostacheError;
+    fn try_from(config: LoaderConfig<'_>) -> Result<Self, MoostacheError> {
+        let exts = config_extensions(&config);
+        let mut dir: String = config.templates_directory.into();
+        if !dir.ends_with(MAIN_SEPARATOR_STR) {
+            dir.push_str(MAIN_SEPARATOR_STR);
+        }
+        let dir_path: &Path = dir.as_ref();
+        let max_size = NonZeroUsize::new(config.cache_size)
+            .ok_or(MoostacheError::ConfigErrorNonPositiveCacheSize)?;
+        let max_size: usize = max_size.into();
+
+        if !dir_path.is_dir() {
+            return Err(MoostacheError::ConfigErrorInvalidTemplatesDirectory(dir_path.into()));
+        }
+
+        let mut current_size = 0usize;
+        let mut templates: HashMap<String, Template, FnvBuildHasher> = HashMap::default();
+        // priority of the extension each template was loaded
+        // with, so files with fallback extensions don't
+        // replace templates loaded with earlier extensions
+        let mut priorities: HashMap<String, usize, FnvBuildHasher> = HashMap::default();
+        for entry in WalkDir::new(dir_path).into_iter().filter_map(Result::ok) {
+            if entry.file_type().is_file() {
+                let entry_path = entry.path();
+                let entry_path_str = entry_path
+                    .to_str()
+                    .ok_or_else(|| MoostacheError::LoaderErrorNonUtf8FilePath(entry_path.into()))?;
+                let stripped = strip_extensions(entry_path_str, &exts, config.case_insensitive_extension);
+                if let Some((stripped, priority)) = stripped {
+                    let name = stripped
+                        .strip_prefix(&dir)
+                        .unwrap()
+                        .to_string();
+                    if priorities.get(&name).is_some_and(|&loaded| loaded < priority) {
+                        continue;
+                    }
+                    let source = fs::read_to_string(entry_path)
+                        .map_err(|err| MoostacheError::from_io(err, name.clone()))?;
+                    let template = Template::parse_with_options(source, &config.parse_options)
+                        .map_err(|err| err.set_name(&name))?;
+                    priorities.insert(name.clone(), priority);
+                    if templates.insert(name, template).is_none() {
+                        current_size += 1;
+                    }
+                    if current_size > max_size {
+                        return Err(MoostacheError::ConfigErrorTooManyTemplates);
+                    }
+                }
+            }
+        }
+
+        Ok(HashMapLoader {
+            templates,
+            options: RenderOptions::default(),
+            post_render: None,
+            value_renderer: None,
+            error_template: None,
+            recording: Recording::default(),
+        })
+    }
+}
+
+/// Stores all templates in memory.
+/// 
+/// ### Examples
+/// 
+/// Creating a [`HashMapLoader`] from a hashmap:
+/// 
+/// ```rust
+/// use moostache::HashMapLoader;
+/// use maplit::hashmap;
+/// 
+/// let loader = HashMapLoader::try_from(hashmap! {
+///    "greet" => "hello {{name}}!",
+/// }).unwrap();
+/// ```
+/// 
+/// Creating a [`HashMapLoader`] from a [`LoaderConfig`]:
+/// 
+/// ```rust
+/// # #[cfg(feature = "fs")] {
+/// use moostache::{LoaderConfig, HashMapLoader};
+/// 
+/// let loader = HashMapLoader::try_from(LoaderConfig::default()).unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct HashMapLoader<K: Borrow<str> + Eq + Hash = String, H: BuildHasher + Default = FnvBuildHasher> {
+    templates: HashMap<K, Template, H>,
+    options: RenderOptions,
+    post_render: Option<PostRender>,
+    value_renderer: Option<DynValueRenderer>,
+    error_template: Option<String>,
+    recording: Recording,
+}
+
+impl<K: Borrow<str> + Eq + Hash, H: BuildHasher + Default> TemplateLoader<K> for HashMapLoader<K, H> {
+    type Output<'a> = &'a Template where K: 'a, H: 'a;
+    type Error = MoostacheError;
+    fn get(&self, name: &str) -> Result<&Template, MoostacheError> {
+        let template = self.templates.get(name)
+            .ok_or_else(|| MoostacheError::LoaderErrorTemplateNotFound(name.into()))?;
+        self.recording.record(name);
+        Ok(template)
+    }
+    fn insert(&mut self, name: K, value: Template) -> Option<Template> {
+        self.templates.insert(name, value)
+    }
+    fn remove(&mut self, name: &str) -> Option<Template> {
+        self.templates.remove(name)
+    }
+    fn render_options(&self) -> RenderOptions {
+        self.options.clone()
+    }
+    fn post_render(&self, rendered: String) -> String {
+        match &self.post_render {
+            Some(post_render) => (post_render.0)(rendered),
+            None => rendered,
+        }
+    }
+    fn value_renderer(&self) -> Option<&dyn ValueRenderer> {
+        self.value_renderer.as_ref().map(|renderer| &*renderer.0)
+    }
+    fn error_template(&self) -> Option<&str> {
+        self.error_template.as_deref()
+    }
+    fn is_not_found(&self, err: &MoostacheError) -> bool {
+        err.is_not_found()
+    }
+}
+
+impl<K: Borrow<str> + Eq + Hash, H: BuildHasher + Default> HashMapLoader<K, H> {
+    /// Set the options used when rendering with this loader.
+    /// See [`TemplateLoader::render_options`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{Escaper, HashMapLoader, RenderOptions, TemplateLoader};
+    /// use maplit::hashmap;
+    /// use serde_json::json;
+    /// 
+    /// let mut loader = HashMapLoader::try_from(hashmap! {
+    ///     "message" => r#"{"message": "{{message}}"}"#,
+    /// }).unwrap();
+    /// loader.set_render_options(RenderOptions {
+    ///     escaper: Escaper::Json,
+    ///     ..RenderOptions::default()
+    /// });
+    /// let rendered = loader.render_to_string(
+    ///     "message",
+    ///     &json!({"message": "say \"hi\""}),
+    /// ).unwrap();
+    /// assert_eq!(rendered, r#"{"message": "say \"hi\""}"#);
+    /// ```
+    pub fn set_render_options(&mut self, options: RenderOptions) {
+        self.options = options;
+    }
+
+    /// Set the function which transforms the output of renders
+    /// with this loader. See [`TemplateLoader::post_render`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{HashMapLoader, TemplateLoader};
+    /// use maplit::hashmap;
+    /// use serde_json::json;
+    /// 
+    /// let mut loader = HashMapLoader::try_from(hashmap! {
+    ///     "greet" => "  hello {{name}}!  ",
+    /// }).unwrap();
+    /// loader.set_post_render(|rendered| rendered.trim().to_owned());
+    /// let rendered = loader.render_to_string(
+    ///     "greet",
+    ///     &json!({"name": "John"}),
+    /// ).unwrap();
+    /// assert_eq!(rendered, "hello John!");
+    /// ```
+    pub fn set_post_render<F: Fn(String) -> String + 'static>(&mut self, post_render: F) {
+        self.post_render = Some(PostRender(Box::new(post_render)));
+    }
+
+    /// Set the renderer which gets the first chance to write
+    /// variables' values when rendering with this loader.
+    /// See [`ValueRenderer`].
+    pub fn set_value_renderer<V: ValueRenderer + 'static>(&mut self, value_renderer: V) {
+        self.value_renderer = Some(DynValueRenderer(Box::new(value_renderer)));
+    }
+
+    /// Set the name of the template rendered instead of
+    /// templates which fail to render by name with this
+    /// loader. See [`TemplateLoader::error_template`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{HashMapLoader, TemplateLoader};
+    /// use maplit::hashmap;
+    /// use serde_json::json;
+    /// 
+    /// let mut loader = HashMapLoader::try_from(hashmap! {
+    ///     "page" => "hello {{> missing }}",
+    ///     "error" => "something went wrong",
+    /// }).unwrap();
+    /// loader.on_error_render("error");
+    /// let rendered = loader.render_to_string("page", &json!({})).unwrap();
+    /// assert_eq!(rendered, "something went wrong");
+    /// ```
+    pub fn on_error_render<S: Into<String>>(&mut self, name: S) {
+        self.error_template = Some(name.into());
+    }
+
+    /// Start recording the names of all templates gotten from
+    /// this loader, including partials, until
+    /// [`stop_recording`](HashMapLoader::stop_recording) is
+    /// called, e.g. to record which templates a representative
+    /// render uses so they can be preloaded with
+    /// [`FileLoader::preload`]. Starting again discards any
+    /// names recorded so far.
+    pub fn start_recording(&self) {
+        self.recording.start();
+    }
+
+    /// Stop recording and return the names of all templates
+    /// gotten from this loader since recording started, in
+    /// order of first use. Returns an empty [`Vec`] if
+    /// recording wasn't started.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{HashMapLoader, TemplateLoader};
+    /// use maplit::hashmap;
+    /// use serde_json::json;
+    /// 
+    /// let loader = HashMapLoader::try_from(hashmap! {
+    ///     "page" => "{{> header }}{{> footer }}{{> header }}",
+    ///     "header" => "hi",
+    ///     "footer" => "bye",
+    /// }).unwrap();
+    /// loader.start_recording();
+    /// loader.render_to_string("page", &json!({})).unwrap();
+    /// assert_eq!(loader.stop_recording(), ["page", "header", "footer"]);
+    /// ```
+    #[must_use]
+    pub fn stop_recording(&self) -> Vec<String> {
+        self.recording.stop()
+    }
+
+    /// Moves all templates from `other` into this loader. If
+    /// both loaders have a template with the same name then the
+    /// template from `other` wins and replaces the one in this
+    /// loader.
+    /// 
+    /// Returns the names of the templates which were replaced,
+    /// in no particular order.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{HashMapLoader, TemplateLoader};
+    /// use maplit::hashmap;
+    /// use serde_json::json;
+    /// 
+    /// let mut theme = HashMapLoader::try_from(hashmap! {
+    ///     "header" => "theme header",
+    ///     "footer" => "theme footer",
+    /// }).unwrap();
+    /// let app = HashMapLoader::try_from(hashmap! {
+    ///     "header" => "app header",
+    /// }).unwrap();
+    /// let replaced = theme.merge(app);
+    /// assert_eq!(replaced, vec!["header"]);
+    /// assert_eq!(theme.render_to_string("header", &json!(null)).unwrap(), "app header");
+    /// assert_eq!(theme.render_to_string("footer", &json!(null)).unwrap(), "theme footer");
+    /// ```
+    pub fn merge<H2: BuildHasher + Default>(&mut self, other: HashMapLoader<K, H2>) -> Vec<String> {
+        let mut replaced = Vec::new();
+        self.templates.reserve(other.templates.len());
+        for (name, template) in other.templates {
+            if self.templates.contains_key(name.borrow()) {
+                replaced.push(name.borrow().to_owned());
+            }
+            self.templates.insert(name, template);
+        }
+        replaced
+    }
+
+    /// Checks if any templates include each other via partials
+    /// in a cycle, e.g. "a" includes "b" which includes "a",
+    /// which would recurse infinitely during render.
+    /// 
+    /// Partials which refer to templates that aren't in the
+    /// loader are ignored.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns the chain of template names which form the
+    /// first cycle found, starting and ending with the same
+    /// name, e.g. `["a", "b", "a"]`.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::HashMapLoader;
+    /// use maplit::hashmap;
+    /// 
+    /// let loader = HashMapLoader::try_from(hashmap! {
+    ///     "a" => "{{> b }}",
+    ///     "b" => "{{> a }}",
+    /// }).unwrap();
+    /// let cycle = loader.detect_cycles().unwrap_err();
+    /// assert_eq!(cycle, vec!["a", "b", "a"]);
+    /// ```
+    pub fn detect_cycles(&self) -> Result<(), Vec<String>> {
+        // names of templates we've fully explored and
+        // know aren't part of any cycle
+        let mut done: HashSet<&str> = HashSet::new();
+        // current chain of templates we're exploring
+        let mut chain: Vec<&str> = Vec::new();
+        // sort names so the reported cycle is deterministic
+        let mut names: Vec<&str> = self.templates.keys().map(Borrow::borrow).collect();
+        names.sort_unstable();
+        for name in names {
+            self.detect_cycles_from(name, &mut done, &mut chain)?;
+        }
+        Ok(())
+    }
+
+    /// Names of all partials the template with the given
+    /// name includes, directly or via other partials, i.e.
+    /// every template needed to render it. Partials which
+    /// include each other in a cycle are only visited once,
+    /// and if the template is part of a cycle its own name
+    /// is included too.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns [`MoostacheError::LoaderErrorTemplateNotFound`]
+    /// if the template, or any partial it needs, isn't in the
+    /// loader.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::HashMapLoader;
+    /// use maplit::{hashmap, hashset};
+    /// 
+    /// let loader = HashMapLoader::try_from(hashmap! {
+    ///     "page" => "{{> header }} hello",
+    ///     "header" => "{{> logo }}",
+    ///     "logo" => "moo",
+    ///     "unused" => "moo",
+    /// }).unwrap();
+    /// let partials = loader.transitive_partials("page").unwrap();
+    /// assert_eq!(partials, hashset! {"header".to_owned(), "logo".to_owned()});
+    /// ```
+    pub fn transitive_partials(&self, name: &str) -> Result<HashSet<String>, MoostacheError> {
+        let mut partials = HashSet::new();
+        let mut unvisited = vec![self.get(name)?];
+        while let Some(template) = unvisited.pop() {
+            for partial in template.partials() {
+                if !partials.contains(partial) {
+                    partials.insert(partial.to_owned());
+                    unvisited.push(self.get(partial)?);
+                }
+            }
+        }
+        Ok(partials)
+    }
+
+    // depth-first search through partials starting from name
+    fn detect_cycles_from<'a>(
+        &'a self,
+        name: &'a str,
+        done: &mut HashSet<&'a str>,
+        chain: &mut Vec<&'a str>,
+    ) -> Result<(), Vec<String>> {
+        if done.contains(name) {
+            return Ok(());
+        }
+        if let Some(start) = chain.iter().position(|visited| *visited == name) {
+            let mut cycle: Vec<String> = chain[start..]
+                .iter()
+                .map(|visited| (*visited).to_owned())
+                .collect();
+            cycle.push(name.to_owned());
+            return Err(cycle);
+        }
+        let Some(template) = self.templates.get(name) else {
+            return Ok(());
+        };
+        chain.push(name);
+        for partial in template.partials() {
+            self.detect_cycles_from(partial, done, chain)?;
+        }
+        chain.pop();
+        done.insert(name);
+        Ok(())
+    }
+}
+
+/// Lazily loads templates on-demand during render. Caches
+/// some compiled templates in memory. Templates which are
+/// used by most renders can be pinned so they're never
+/// evicted from the cache, see [`FileLoader::pin`].
+/// 
+/// ### Examples
+/// 
+/// Creating a [`FileLoader`] from a [`LoaderConfig`]:
+/// 
+/// ```rust
+/// use moostache::{LoaderConfig, FileLoader};
+/// 
+/// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+/// ```
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+pub struct FileLoader<H: BuildHasher + Default = FnvBuildHasher> {
+    templates_directory: String,
+    // templates_extension followed by any fallback_extensions
+    templates_extensions: Vec<String>,
+    case_insensitive_extension: bool,
+    parse_options: ParseOptions,
+    path_buf: RefCell<String>,
+    templates: RefCell<LruCache<String, Rc<Template>, H>>,
+    // approximate bytes used by the templates in the cache,
+    // and the most they can use, see LoaderConfig::cache_bytes
+    cached_bytes: Cell<usize>,
+    max_cached_bytes: Option<usize>,
+    pinned: RefCell<HashMap<String, Rc<Template>, H>>,
+    stats: Cell<CacheStats>,
+    options: RenderOptions,
+    post_render: Option<PostRender>,
+    value_renderer: Option<DynValueRenderer>,
+    error_template: Option<String>,
+    recording: Recording,
+}
+
+/// Counters of how effective the cache of a [`FileLoader`]
+/// is. Returned by [`FileLoader::stats`].
+/// 
+/// If there are a lot of evictions and about as many
+/// misses as gets then the cache size is probably too
+/// small for the templates being rendered.
+#[cfg(feature = "fs")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of times [`get`](TemplateLoader::get) was called,
+    /// including the calls made to load partials during renders.
+    pub gets: u64,
+    /// Number of gets which found the template in the cache.
+    pub hits: u64,
+    /// Number of gets which had to load the template from
+    /// the filesystem.
+    pub misses: u64,
+    /// Number of templates which were removed from the cache
+    /// to make room for another template.
+    pub evictions: u64,
+}
+
+#[cfg(feature = "fs")]
+impl<H: BuildHasher + Default> FileLoader<H> {
+    /// Returns the cache counters of this loader.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{CacheStats, FileLoader, LoaderConfig, TemplateLoader};
+    /// 
+    /// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+    /// loader.get("greet").unwrap();
+    /// loader.get("greet").unwrap();
+    /// assert_eq!(
+    ///     loader.stats(),
+    ///     CacheStats {
+    ///         gets: 2,
+    ///         hits: 1,
+    ///         misses: 1,
+    ///         evictions: 0,
+    ///     },
+    /// );
+    /// ```
+    #[must_use]
+    pub fn stats(&self) -> CacheStats {
+        self.stats.get()
+    }
+
+    /// The most templates the cache can hold, i.e. the
+    /// [`LoaderConfig::cache_size`] this loader was created with.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.templates.borrow().cap().get()
+    }
+
+    /// How many templates are currently in the cache, which
+    /// is never more than [`FileLoader::capacity`]. Pinned
+    /// templates aren't counted. See [`FileLoader::pin`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{FileLoader, LoaderConfig, TemplateLoader};
+    /// 
+    /// let loader = FileLoader::try_from(LoaderConfig {
+    ///     cache_size: 1,
+    ///     ..LoaderConfig::default()
+    /// }).unwrap();
+    /// assert_eq!(loader.len(), 0);
+    /// loader.get("greet").unwrap();
+    /// loader.get("nested/person").unwrap();
+    /// assert_eq!(loader.len(), loader.capacity());
+    /// ```
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.templates.borrow().len()
+    }
+
+    /// Returns `true` if no templates are in the cache.
+    /// See [`FileLoader::len`].
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.templates.borrow().is_empty()
+    }
+
+    /// Approximately how many bytes of memory the templates in
+    /// the cache use, which is never more than
+    /// [`LoaderConfig::cache_bytes`] if it's set. Pinned templates
+    /// aren't counted. See [`FileLoader::pin`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{FileLoader, LoaderConfig, TemplateLoader};
+    /// 
+    /// let loader = FileLoader::try_from(LoaderConfig {
+    ///     cache_bytes: Some(4096),
+    ///     ..LoaderConfig::default()
+    /// }).unwrap();
+    /// assert_eq!(loader.cached_bytes(), 0);
+    /// loader.get("greet").unwrap();
+    /// assert!(loader.cached_bytes() > 0);
+    /// ```
+    #[must_use]
+    pub fn cached_bytes(&self) -> usize {
+        self.cached_bytes.get()
+    }
+
+    /// Removes all templates from the cache, so they're read
+    /// from the filesystem again the next time they're used,
+    /// e.g. after deploying changed templates. Pinned templates
+    /// aren't removed. See [`FileLoader::pin`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{FileLoader, LoaderConfig, TemplateLoader};
+    /// 
+    /// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+    /// loader.get("greet").unwrap();
+    /// loader.clear_cache();
+    /// assert!(loader.is_empty());
+    /// loader.get("greet").unwrap();
+    /// assert_eq!(loader.stats().misses, 2);
+    /// ```
+    pub fn clear_cache(&self) {
+        self.templates.borrow_mut().clear();
+        self.cached_bytes.set(0);
+    }
+
+    /// Set the options used when rendering with this loader.
+    /// See [`TemplateLoader::render_options`].
+    pub fn set_render_options(&mut self, options: RenderOptions) {
+        self.options = options;
+    }
+
+    /// Set the function which transforms the output of renders
+    /// with this loader. See [`TemplateLoader::post_render`].
+    pub fn set_post_render<F: Fn(String) -> String + 'static>(&mut self, post_render: F) {
+        self.post_render = Some(PostRender(Box::new(post_render)));
+    }
+
+    /// Set the renderer which gets the first chance to write
+    /// variables' values when rendering with this loader.
+    /// See [`ValueRenderer`].
+    pub fn set_value_renderer<V: ValueRenderer + 'static>(&mut self, value_renderer: V) {
+        self.value_renderer = Some(DynValueRenderer(Box::new(value_renderer)));
+    }
+
+    /// Set the name of the template rendered instead of
+    /// templates which fail to render by name with this
+    /// loader. See [`TemplateLoader::error_template`].
+    pub fn on_error_render<S: Into<String>>(&mut self, name: S) {
+        self.error_template = Some(name.into());
+    }
+
+    /// Start recording the names of all templates gotten from
+    /// this loader, including partials, until
+    /// [`stop_recording`](FileLoader::stop_recording) is
+    /// called. See [`HashMapLoader::start_recording`].
+    pub fn start_recording(&self) {
+        self.recording.start();
+    }
+
+    /// Stop recording and return the names of all templates
+    /// gotten from this loader since recording started, in
+    /// order of first use, which can be passed to
+    /// [`preload`](FileLoader::preload). Returns an empty
+    /// [`Vec`] if recording wasn't started.
+    #[must_use]
+    pub fn stop_recording(&self) -> Vec<String> {
+        self.recording.stop()
+    }
+
+    /// Load a template and keep it in memory outside of the
+    /// cache, so it's never evicted no matter how many other
+    /// templates are loaded, e.g. a layout template which
+    /// every page uses. Pinned templates don't count towards
+    /// the cache size.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns a [`MoostacheError`] if loading the
+    /// template fails for whatever reason.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{FileLoader, LoaderConfig, TemplateLoader};
+    /// 
+    /// let loader = FileLoader::try_from(LoaderConfig {
+    ///     cache_size: 1,
+    ///     ..LoaderConfig::default()
+    /// }).unwrap();
+    /// loader.pin("greet").unwrap();
+    /// loader.get("nested/person").unwrap();
+    /// loader.get("greet").unwrap();
+    /// assert_eq!(loader.stats().hits, 1);
+    /// ```
+    pub fn pin(&self, name: &str) -> Result<(), MoostacheError> {
+        if self.is_pinned(name) {
+            return Ok(());
+        }
+        let cached = self.cache_pop(&mut self.templates.borrow_mut(), name);
+        let template = match cached {
+            Some(template) => template,
+            None => Rc::new(self.load(name)?),
+        };
+        self.pinned.borrow_mut().insert(name.into(), template);
+        Ok(())
+    }
+
+    /// Moves a pinned template back into the cache, where
+    /// it can be evicted again. Returns `false` if the
+    /// template wasn't pinned.
+    pub fn unpin(&self, name: &str) -> bool {
+        let unpinned = self.pinned.borrow_mut().remove(name);
+        match unpinned {
+            Some(template) => {
+                self.cache_put(&mut self.templates.borrow_mut(), name.into(), template);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the template is pinned.
+    /// See [`FileLoader::pin`].
+    #[must_use]
+    pub fn is_pinned(&self, name: &str) -> bool {
+        self.pinned.borrow().contains_key(name)
+    }
+
+    /// Returns the most recent time the file of a template, or
+    /// of any partial it includes, directly or through other
+    /// partials, was modified, e.g. to set `Last-Modified`
+    /// headers when serving rendered pages. Files are always
+    /// read from the filesystem, never from the cache, so the
+    /// partials are up-to-date too. Dynamic partials, e.g.
+    /// {{>* variable }}, aren't included since their names
+    /// depend on the data being rendered.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns a [`MoostacheError`] if any of the files can't
+    /// be read or parsed, or if getting their modified time
+    /// isn't supported on this platform.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{FileLoader, LoaderConfig};
+    /// use std::time::SystemTime;
+    /// 
+    /// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+    /// let modified = loader.last_modified("greet").unwrap();
+    /// assert!(modified <= SystemTime::now());
+    /// ```
+    pub fn last_modified(&self, name: &str) -> Result<SystemTime, MoostacheError> {
+        let mut last_modified = SystemTime::UNIX_EPOCH;
+        let mut visited = HashSet::from([name.to_owned()]);
+        let mut unvisited = vec![name.to_owned()];
+        while let Some(name) = unvisited.pop() {
+            let (modified, source) = self.read_first(&name, |path| {
+                Ok((fs::metadata(path)?.modified()?, fs::read_to_string(path)?))
+            })?;
+            last_modified = last_modified.max(modified);
+            let template = Template::parse_with_options(source, &self.parse_options)
+                .map_err(|err| err.set_name(&name))?;
+            for partial in template.partials() {
+                if visited.insert(partial.to_owned()) {
+                    unvisited.push(partial.to_owned());
+                }
+            }
+        }
+        Ok(last_modified)
+    }
+
+    /// Returns the names of all templates in the templates
+    /// directory which match a glob pattern, in sorted order,
+    /// without loading them. In patterns `*` matches any chars
+    /// besides path separators, `**` matches any chars, and `?`
+    /// matches any single char besides path separators.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns [`MoostacheError::LoaderErrorNonUtf8FilePath`] if
+    /// a file in the templates directory has a non-utf8 path.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{FileLoader, LoaderConfig};
+    /// 
+    /// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+    /// let names = loader.find("emails/*").unwrap();
+    /// assert_eq!(names, ["emails/reset-password", "emails/welcome"]);
+    /// ```
+    pub fn find(&self, pattern: &str) -> Result<Vec<String>, MoostacheError> {
+        let mut names = Vec::new();
+        for entry in WalkDir::new(&self.templates_directory).into_iter().filter_map(Result::ok) {
+            if entry.file_type().is_file() {
+                let entry_path = entry.path();
+                let entry_path_str = entry_path
+                    .to_str()
+                    .ok_or_else(|| MoostacheError::LoaderErrorNonUtf8FilePath(entry_path.into()))?;
+                let name = strip_extensions(entry_path_str, &self.templates_extensions, self.case_insensitive_extension)
+                    .and_then(|(stripped, _)| stripped.strip_prefix(&self.templates_directory));
+                if let Some(name) = name {
+                    if glob_matches(pattern, name) {
+                        names.push(name.to_owned());
+                    }
+                }
+            }
+        }
+        names.sort_unstable();
+        // the same template can have files with different extensions
+        names.dedup();
+        Ok(names)
+    }
+
+    // reads a template from the filesystem and parses it,
+    // trying each extension in order until a file is found
+    fn load(&self, name: &str) -> Result<Template, MoostacheError> {
+        let source = self.read_first(name, |path| fs::read_to_string(path))?;
+        Template::parse_with_options(source, &self.parse_options)
+            .map_err(|err| err.set_name(name))
+    }
+
+    // reads the file of a template using the first extension,
+    // in order of priority, which the file exists with
+    fn read_first<R>(
+        &self,
+        name: &str,
+        read: impl Fn(&Path) -> io::Result<R>,
+    ) -> Result<R, MoostacheError> {
+        let mut result = Err(io::ErrorKind::NotFound.into());
+        for ext in &self.templates_extensions {
+            result = self.read(name, ext, &read);
+            if !matches!(&result, Err(err) if err.kind() == io::ErrorKind::NotFound) {
+                break;
+            }
+        }
+        result.map_err(|err| MoostacheError::from_io(err, name.into()))
+    }
+
+    // reads the file of a template with the given extension
+    fn read<R>(
+        &self,
+        name: &str,
+        ext: &str,
+        read: impl Fn(&Path) -> io::Result<R>,
+    ) -> io::Result<R> {
+        let mut path_buf = self.path_buf.borrow_mut();
+        path_buf.clear();
+        path_buf.push_str(&self.templates_directory);
+        path_buf.push_str(name);
+        path_buf.push_str(ext);
+        let mut result = read(path_buf.as_ref());
+        if self.case_insensitive_extension {
+            if let Err(err) = &result {
+                if err.kind() == io::ErrorKind::NotFound {
+                    if let Some(path) = find_case_insensitive_extension(&path_buf, ext) {
+                        result = read(&path);
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    // puts a template in the cache, evicting the least recently
+    // used templates if there's too many of them or they use too
+    // many bytes, returns the template it replaced, if any
+    fn cache_put(
+        &self,
+        templates: &mut LruCache<String, Rc<Template>, H>,
+        name: String,
+        template: Rc<Template>,
+    ) -> Option<Rc<Template>> {
+        let bytes = template.approx_bytes();
+        // a template too large to ever fit isn't cached, and
+        // any older version of it is removed
+        if self.max_cached_bytes.is_some_and(|max| bytes > max) {
+            return self.cache_pop(templates, &name);
+        }
+        let replacing = templates.contains(&name);
+        let mut replaced = None;
+        let mut cached_bytes = self.cached_bytes.get() + bytes;
+        // if the name isn't in the cache then pushing it
+        // returns whichever template it evicted, if any
+        if let Some((_, template)) = templates.push(name, template) {
+            cached_bytes -= template.approx_bytes();
+            if replacing {
+                replaced = Some(template);
+            } else {
+                self.update_stats(|stats| stats.evictions += 1);
+            }
+        }
+        while self.max_cached_bytes.is_some_and(|max| cached_bytes > max) {
+            let Some((_, template)) = templates.pop_lru() else {
+                break;
+            };
+            cached_bytes -= template.approx_bytes();
+            self.update_stats(|stats| stats.evictions += 1);
+        }
+        self.cached_bytes.set(cached_bytes);
+        replaced
+    }
+
+    // removes a template from the cache
+    fn cache_pop(
+        &self,
+        templates: &mut LruCache<String, Rc<Template>, H>,
+        name: &str,
+    ) -> Option<Rc<Template>> {
+        let template = templates.pop(name)?;
+        self.cached_bytes.set(self.cached_bytes.get() - template.approx_bytes());
+        Some(template)
+    }
+
+    // updates the cache counters
+    fn update_stats(&self, update: impl FnOnce(&mut CacheStats)) {
+        let mut stats = self.stats.get();
+        update(&mut stats);
+        self.stats.set(stats);
+    }
+}
+
+#[cfg(feature = "fs")]
+impl FileLoader {
+    /// Load templates into the cache, in order, e.g. the names
+    /// returned by [`stop_recording`](FileLoader::stop_recording)
+    /// after a representative render, so the first renders
+    /// which use them don't have to load them. Loading more
+    /// templates than fit in the cache evicts the ones loaded
+    /// first.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns a [`MoostacheError`] if any template can't be
+    /// loaded, in which case the templates before it were
+    /// still loaded.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{FileLoader, LoaderConfig};
+    /// 
+    /// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+    /// loader.preload(["greet"]).unwrap();
+    /// assert_eq!(loader.len(), 1);
+    /// ```
+    pub fn preload<I: IntoIterator<Item = S>, S: AsRef<str>>(&self, names: I) -> Result<(), MoostacheError> {
+        for name in names {
+            self.get(name.as_ref())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "fs")]
+impl TemplateLoader for FileLoader {
+    type Output<'a> = Rc<Template>;
+    type Error = MoostacheError;
+    fn get(&self, name: &str) -> Result<Rc<Template>, MoostacheError> {
+        let mut templates = self.templates.borrow_mut();
+        let pinned = self.pinned.borrow();
+        let template = pinned
+            .get(name)
+            .or_else(|| templates.get(name));
+        if let Some(template) = template {
+            self.update_stats(|stats| {
+                stats.gets += 1;
+                stats.hits += 1;
+            });
+            self.recording.record(name);
+            return Ok(Rc::clone(template));
+        }
+        self.update_stats(|stats| {
+            stats.gets += 1;
+            stats.misses += 1;
+        });
+        let template = Rc::new(self.load(name)?);
+        self.cache_put(&mut templates, name.into(), Rc::clone(&template));
+        self.recording.record(name);
+        Ok(template)
+    }
+    fn insert(&mut self, name: String, value: Template) -> Option<Template> {
+        // pinned templates stay pinned when replaced
+        if let Some(template) = self.pinned.get_mut().get_mut(&name) {
+            let replaced = std::mem::replace(template, Rc::new(value));
+            return Rc::into_inner(replaced);
+        }
+        let option = self.cache_put(&mut self.templates.borrow_mut(), name, Rc::new(value));
+        match option {
+            Some(template) => {
+                Rc::into_inner(template)
+            },
+            None => None,
+        }
+    }
+    fn remove(&mut self, name: &str) -> Option<Template> {
+        let option = self.pinned
+            .get_mut()
+            .remove(name)
+            .or_else(|| self.cache_pop(&mut self.templates.borrow_mut(), name));
+        match option {
+            Some(template) => {
+                Rc::into_inner(template)
+            },
+            None => None,
+        }
+    }
+    fn render_options(&self) -> RenderOptions {
+        self.options.clone()
+    }
+    fn post_render(&self, rendered: String) -> String {
+        match &self.post_render {
+            Some(post_render) => (post_render.0)(rendered),
+            None => rendered,
+        }
+    }
+    fn value_renderer(&self) -> Option<&dyn ValueRenderer> {
+        self.value_renderer.as_ref().map(|renderer| &*renderer.0)
+    }
+    fn error_template(&self) -> Option<&str> {
+        self.error_template.as_deref()
+    }
+    fn is_not_found(&self, err: &MoostacheError) -> bool {
+        err.is_not_found()
+    }
+}
+
+#[cfg(feature = "fs")]
+impl TryFrom<LoaderConfig<'_>> for FileLoader {
+    type Error = MoostacheError;
     fn try_from(config: LoaderConfig<'_>) -> Result<Self, MoostacheError> {
+        let exts = config_extensions(&config);
         let mut dir: String = config.templates_directory.into();
         if !dir.ends_with(MAIN_SEPARATOR_STR) {
             dir.push_str(MAIN_SEPARATOR_STR);
         }
-        let dir_path: &Path = dir.as_ref();
-        let mut ext: String = config.templates_extension.into();
-        if !ext.starts_with('.') {
-            ext.insert(0, '.');
+        let dir_path: &Path = dir.as_ref();
+        let max_size = NonZeroUsize::new(config.cache_size)
+            .ok_or(MoostacheError::ConfigErrorNonPositiveCacheSize)?;
+
+        if !dir_path.is_dir() {
+            return Err(MoostacheError::ConfigErrorInvalidTemplatesDirectory(dir_path.into()));
+        }
+
+        let templates = RefCell::new(LruCache::with_hasher(max_size, BuildHasherDefault::default()));
+
+        Ok(FileLoader {
+            templates_directory: dir,
+            templates_extensions: exts,
+            case_insensitive_extension: config.case_insensitive_extension,
+            parse_options: config.parse_options,
+            path_buf: RefCell::new(String::new()),
+            templates,
+            cached_bytes: Cell::new(0),
+            max_cached_bytes: config.cache_bytes,
+            pinned: RefCell::new(HashMap::default()),
+            stats: Cell::new(CacheStats::default()),
+            options: RenderOptions::default(),
+            post_render: None,
+            value_renderer: None,
+            error_template: None,
+            recording: Recording::default(),
+        })
+    }
+}
+
+// the extensions of a LoaderConfig in the order they're
+// tried, i.e. templates_extension and then any fallback
+// extensions, each starting with a dot
+#[cfg(feature = "fs")]
+fn config_extensions(config: &LoaderConfig<'_>) -> Vec<String> {
+    std::iter::once(&config.templates_extension)
+        .chain(&config.fallback_extensions)
+        .map(|ext| match ext.strip_prefix('.') {
+            Some(_) => ext.to_string(),
+            None => format!(".{ext}"),
+        })
+        .collect()
+}
+
+// strips the first of the extensions the path has, returns
+// the stripped path and the index of the extension, i.e.
+// its priority, see config_extensions
+#[cfg(feature = "fs")]
+fn strip_extensions<'a>(path: &'a str, exts: &[String], case_insensitive: bool) -> Option<(&'a str, usize)> {
+    exts.iter()
+        .enumerate()
+        .find_map(|(priority, ext)| Some((strip_extension(path, ext, case_insensitive)?, priority)))
+}
+
+// strips the file extension from a path, optionally
+// ignoring the ascii case of the extension, returns
+// None if the path doesn't have the extension
+#[cfg(feature = "fs")]
+fn strip_extension<'a>(path: &'a str, ext: &str, case_insensitive: bool) -> Option<&'a str> {
+    if !case_insensitive {
+        return path.strip_suffix(ext);
+    }
+    let split = path.len().checked_sub(ext.len())?;
+    if !path.is_char_boundary(split) {
+        return None;
+    }
+    let (stripped, suffix) = path.split_at(split);
+    if suffix.eq_ignore_ascii_case(ext) {
+        Some(stripped)
+    } else {
+        None
+    }
+}
+
+// matches a template name against a glob pattern,
+// see FileLoader::find
+#[cfg(feature = "fs")]
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let mut pattern_chars = pattern.chars();
+    let mut name_chars = name.chars();
+    match pattern_chars.next() {
+        None => name.is_empty(),
+        // ** can match past path separators
+        Some('*') if pattern_chars.as_str().starts_with('*') => {
+            let rest = &pattern_chars.as_str()[1..];
+            (0..=name.len())
+                .filter(|idx| name.is_char_boundary(*idx))
+                .any(|idx| glob_matches(rest, &name[idx..]))
+        },
+        Some('*') => {
+            let rest = pattern_chars.as_str();
+            let end = name.find(['/', MAIN_SEPARATOR]).unwrap_or(name.len());
+            (0..=end)
+                .filter(|idx| name.is_char_boundary(*idx))
+                .any(|idx| glob_matches(rest, &name[idx..]))
+        },
+        Some('?') => {
+            matches!(name_chars.next(), Some(c) if c != '/' && c != MAIN_SEPARATOR)
+                && glob_matches(pattern_chars.as_str(), name_chars.as_str())
+        },
+        Some(c) => {
+            name_chars.next() == Some(c)
+                && glob_matches(pattern_chars.as_str(), name_chars.as_str())
+        },
+    }
+}
+
+// looks for a file in the same directory as path whose
+// name only differs from it by the ascii case of its
+// file extension, e.g. "greet.HTML" for "greet.html"
+#[cfg(feature = "fs")]
+fn find_case_insensitive_extension(path: &str, ext: &str) -> Option<PathBuf> {
+    let path: &Path = path.as_ref();
+    let file_name = path.file_name()?.to_str()?;
+    let stem = file_name.strip_suffix(ext)?;
+    fs::read_dir(path.parent()?)
+        .ok()?
+        .filter_map(Result::ok)
+        .find(|entry| {
+            entry.file_name()
+                .to_str()
+                .and_then(|entry_name| strip_extension(entry_name, ext, true))
+                == Some(stem)
+        })
+        .map(|entry| entry.path())
+}
+
+/// Wraps a [`FileLoader`] with templates kept in memory which
+/// are used instead of the ones with the same names on disk,
+/// e.g. to try out a variant of a partial for a single request
+/// without touching the filesystem or the [`FileLoader`]'s
+/// cache. Templates which aren't overlaid are loaded by the
+/// [`FileLoader`] as usual, and the [`FileLoader`]'s render
+/// options, post render, value renderer, and error template
+/// are used for all renders.
+/// 
+/// Templates are overlaid with [`insert`](TemplateLoader::insert)
+/// and removed from the overlay with [`remove`](TemplateLoader::remove),
+/// neither of which change the [`FileLoader`].
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::{FileLoader, LoaderConfig, OverlayLoader, Template, TemplateLoader};
+/// use serde_json::json;
+/// 
+/// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+/// let mut overlay = OverlayLoader::new(&loader);
+/// overlay.insert("nested/person".into(), Template::parse("<b>{{name}}</b>").unwrap());
+/// let rendered = overlay.render_to_string("greet", &json!({"name": "John"})).unwrap();
+/// assert_eq!(rendered, "hello <b>John</b>");
+/// let rendered = loader.render_to_string("greet", &json!({"name": "John"})).unwrap();
+/// assert_eq!(rendered, "hello John");
+/// ```
+#[cfg(feature = "fs")]
+#[derive(Debug)]
+pub struct OverlayLoader<'a> {
+    loader: &'a FileLoader,
+    overlay: HashMap<String, Rc<Template>, FnvBuildHasher>,
+}
+
+#[cfg(feature = "fs")]
+impl<'a> OverlayLoader<'a> {
+    /// Create a new [`OverlayLoader`] over `loader`
+    /// with no overlaid templates.
+    #[must_use]
+    pub fn new(loader: &'a FileLoader) -> Self {
+        OverlayLoader {
+            loader,
+            overlay: HashMap::default(),
+        }
+    }
+
+    /// Returns true if a template with
+    /// this name is overlaid.
+    #[must_use]
+    pub fn is_overlaid(&self, name: &str) -> bool {
+        self.overlay.contains_key(name)
+    }
+}
+
+#[cfg(feature = "fs")]
+impl TemplateLoader for OverlayLoader<'_> {
+    type Output<'b> = Rc<Template> where Self: 'b;
+    type Error = MoostacheError;
+    fn get(&self, name: &str) -> Result<Rc<Template>, MoostacheError> {
+        match self.overlay.get(name) {
+            Some(template) => Ok(Rc::clone(template)),
+            None => self.loader.get(name),
+        }
+    }
+    fn insert(&mut self, name: String, value: Template) -> Option<Template> {
+        self.overlay
+            .insert(name, Rc::new(value))
+            .and_then(Rc::into_inner)
+    }
+    fn remove(&mut self, name: &str) -> Option<Template> {
+        self.overlay
+            .remove(name)
+            .and_then(Rc::into_inner)
+    }
+    fn render_options(&self) -> RenderOptions {
+        self.loader.render_options()
+    }
+    fn post_render(&self, rendered: String) -> String {
+        self.loader.post_render(rendered)
+    }
+    fn value_renderer(&self) -> Option<&dyn ValueRenderer> {
+        self.loader.value_renderer()
+    }
+    fn error_template(&self) -> Option<&str> {
+        self.loader.error_template()
+    }
+    fn is_not_found(&self, err: &MoostacheError) -> bool {
+        self.loader.is_not_found(err)
+    }
+}
+
+/// Lazily loads templates from a zip archive, e.g. to ship
+/// all of an app's templates as a single file, and caches
+/// them in memory the same way [`FileLoader`] does.
+/// 
+/// Requires the `zip` feature.
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::{LoaderConfig, TemplateLoader, ZipLoader};
+/// use serde_json::json;
+/// use std::io::{Cursor, Write};
+/// use zip::{write::SimpleFileOptions, ZipWriter};
+/// 
+/// let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+/// archive.start_file("templates/greet.html", SimpleFileOptions::default()).unwrap();
+/// archive.write_all(b"hello {{name}}!").unwrap();
+/// let archive = archive.finish().unwrap();
+/// 
+/// let loader = ZipLoader::new(archive, LoaderConfig::default()).unwrap();
+/// let rendered = loader.render_to_string("greet", &json!({"name": "John"})).unwrap();
+/// assert_eq!(rendered, "hello John!");
+/// ```
+#[cfg(feature = "zip")]
+#[derive(Debug)]
+pub struct ZipLoader<R: Read + Seek> {
+    archive: RefCell<ZipArchive<R>>,
+    // template names mapped to
+    // their index in the archive
+    entries: HashMap<String, usize, FnvBuildHasher>,
+    parse_options: ParseOptions,
+    templates: RefCell<LruCache<String, Rc<Template>, FnvBuildHasher>>,
+    options: RenderOptions,
+    post_render: Option<PostRender>,
+    value_renderer: Option<DynValueRenderer>,
+    error_template: Option<String>,
+}
+
+#[cfg(feature = "zip")]
+impl<R: Read + Seek> ZipLoader<R> {
+    /// Create a [`ZipLoader`] from a zip archive, e.g. a
+    /// [`Cursor`](std::io::Cursor) over the bytes of one or a
+    /// [`File`](std::fs::File). The templates directory in
+    /// `config` is a directory within the archive, where a
+    /// leading `./` is ignored, and an empty string means
+    /// templates are at the root of the archive.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns [`MoostacheError::ConfigErrorNonPositiveCacheSize`]
+    /// if the cache size is zero, or [`MoostacheError::IoError`]
+    /// if the archive can't be read or isn't a valid zip archive.
+    pub fn new(archive: R, config: LoaderConfig<'_>) -> Result<Self, MoostacheError> {
+        let mut dir = config.templates_directory.replace('\\', "/");
+        if let Some(stripped) = dir.strip_prefix("./") {
+            dir = stripped.to_owned();
+        }
+        if !dir.is_empty() && !dir.ends_with('/') {
+            dir.push('/');
+        }
+        let exts = config_extensions(&config);
+        let max_size = NonZeroUsize::new(config.cache_size)
+            .ok_or(MoostacheError::ConfigErrorNonPositiveCacheSize)?;
+
+        let archive = ZipArchive::new(archive)
+            .map_err(|err| MoostacheError::from_io(err.into(), String::new()))?;
+        let mut entries = HashMap::default();
+        // priority of the extension of each entry, so entries
+        // with fallback extensions don't replace entries with
+        // earlier extensions
+        let mut priorities: HashMap<String, usize, FnvBuildHasher> = HashMap::default();
+        for index in 0..archive.len() {
+            let name = archive.name_for_index(index)
+                .and_then(|path| path.strip_prefix(&dir))
+                .and_then(|path| strip_extensions(path, &exts, config.case_insensitive_extension));
+            if let Some((name, priority)) = name {
+                if priorities.get(name).is_none_or(|&entry| entry >= priority) {
+                    priorities.insert(name.to_owned(), priority);
+                    entries.insert(name.to_owned(), index);
+                }
+            }
+        }
+
+        Ok(ZipLoader {
+            archive: RefCell::new(archive),
+            entries,
+            parse_options: config.parse_options,
+            templates: RefCell::new(LruCache::with_hasher(max_size, FnvBuildHasher::default())),
+            options: RenderOptions::default(),
+            post_render: None,
+            value_renderer: None,
+            error_template: None,
+        })
+    }
+
+    /// Set the options used when rendering with this loader.
+    /// See [`TemplateLoader::render_options`].
+    pub fn set_render_options(&mut self, options: RenderOptions) {
+        self.options = options;
+    }
+
+    /// Set the function which transforms the output of renders
+    /// with this loader. See [`TemplateLoader::post_render`].
+    pub fn set_post_render<F: Fn(String) -> String + 'static>(&mut self, post_render: F) {
+        self.post_render = Some(PostRender(Box::new(post_render)));
+    }
+
+    /// Set the renderer which gets the first chance to write
+    /// variables' values when rendering with this loader.
+    /// See [`ValueRenderer`].
+    pub fn set_value_renderer<V: ValueRenderer + 'static>(&mut self, value_renderer: V) {
+        self.value_renderer = Some(DynValueRenderer(Box::new(value_renderer)));
+    }
+
+    /// Set the name of the template rendered instead of
+    /// templates which fail to render by name with this
+    /// loader. See [`TemplateLoader::error_template`].
+    pub fn on_error_render<S: Into<String>>(&mut self, name: S) {
+        self.error_template = Some(name.into());
+    }
+}
+
+#[cfg(feature = "zip")]
+impl<R: Read + Seek> TemplateLoader for ZipLoader<R> {
+    type Output<'a> = Rc<Template> where R: 'a;
+    type Error = MoostacheError;
+    fn get(&self, name: &str) -> Result<Rc<Template>, MoostacheError> {
+        let mut templates = self.templates.borrow_mut();
+        if let Some(template) = templates.get(name) {
+            return Ok(Rc::clone(template));
+        }
+        let index = *self.entries
+            .get(name)
+            .ok_or_else(|| MoostacheError::LoaderErrorTemplateNotFound(name.into()))?;
+        let mut archive = self.archive.borrow_mut();
+        let mut source = String::new();
+        archive.by_index(index)
+            .map_err(io::Error::from)
+            .and_then(|mut entry| entry.read_to_string(&mut source))
+            .map_err(|err| MoostacheError::from_io(err, name.into()))?;
+        let template = Template::parse_with_options(source, &self.parse_options)
+            .map_err(|err| err.set_name(name))?;
+        let template = Rc::new(template);
+        templates.put(name.into(), Rc::clone(&template));
+        Ok(template)
+    }
+    fn insert(&mut self, name: String, value: Template) -> Option<Template> {
+        let option = self.templates
+            .borrow_mut()
+            .put(name, Rc::new(value));
+        match option {
+            Some(template) => {
+                Rc::into_inner(template)
+            },
+            None => None,
+        }
+    }
+    fn remove(&mut self, name: &str) -> Option<Template> {
+        let option = self.templates
+            .borrow_mut()
+            .pop(name);
+        match option {
+            Some(template) => {
+                Rc::into_inner(template)
+            },
+            None => None,
+        }
+    }
+    fn render_options(&self) -> RenderOptions {
+        self.options.clone()
+    }
+    fn post_render(&self, rendered: String) -> String {
+        match &self.post_render {
+            Some(post_render) => (post_render.0)(rendered),
+            None => rendered,
+        }
+    }
+    fn value_renderer(&self) -> Option<&dyn ValueRenderer> {
+        self.value_renderer.as_ref().map(|renderer| &*renderer.0)
+    }
+    fn error_template(&self) -> Option<&str> {
+        self.error_template.as_deref()
+    }
+    fn is_not_found(&self, err: &MoostacheError) -> bool {
+        err.is_not_found()
+    }
+}
+
+impl<K: Borrow<str> + Eq + Hash> HashMapLoader<K> {
+    /// Create a [`HashMapLoader`] from template names and their
+    /// sources, parsed using the given options, e.g. to parse
+    /// them with other [`Delimiters`]. Templates are parsed in
+    /// order, so if parsing fails the error names the first
+    /// invalid template. If a name appears more than once the
+    /// last template with that name wins.
+    /// 
+    /// ### Errors
+    /// 
+    /// Returns a [`MoostacheError`] parse error enum variant
+    /// if parsing any template fails.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{Delimiters, HashMapLoader, ParseOptions, TemplateLoader};
+    /// use serde_json::json;
+    /// 
+    /// let options = ParseOptions {
+    ///     delimiters: Delimiters { open: "[[", close: "]]" },
+    ///     ..ParseOptions::default()
+    /// };
+    /// let loader = HashMapLoader::try_from_with_options([
+    ///     ("page", "<div v-if=\"{{ ok }}\">[[> greet ]]</div>"),
+    ///     ("greet", "hello [[ name ]]"),
+    /// ], &options).unwrap();
+    /// let rendered = loader.render_to_string("page", &json!({"name": "John"})).unwrap();
+    /// assert_eq!(rendered, "<div v-if=\"{{ ok }}\">hello John</div>");
+    /// ```
+    pub fn try_from_with_options<I: IntoIterator<Item = (K, V)>, V: Into<Cow<'static, str>>>(
+        entries: I,
+        options: &ParseOptions,
+    ) -> Result<Self, MoostacheError> {
+        let templates = entries
+            .into_iter()
+            .map(|(key, value)| {
+                match parse(value, *options) {
+                    Ok(template) => Ok((key, template)),
+                    Err(err) => Err(MoostacheError::from_internal(err, key.borrow().to_owned())),
+                }
+            })
+            .collect::<Result<_, _>>();
+        templates.map(|templates| HashMapLoader {
+            templates,
+            options: RenderOptions::default(),
+            post_render: None,
+            value_renderer: None,
+            error_template: None,
+            recording: Recording::default(),
+        })
+    }
+}
+
+impl<K: Borrow<str> + Eq + Hash, V: Into<Cow<'static, str>>> TryFrom<HashMap<K, V>> for HashMapLoader<K> {
+    type Error = MoostacheError;
+    fn try_from(map: HashMap<K, V>) -> Result<Self, Self::Error> {
+        Self::try_from_with_options(map, &ParseOptions::default())
+    }
+}
+
+/// Templates are parsed in order, so if parsing fails the error
+/// names the first invalid template. If a name appears more than
+/// once the last template with that name wins.
+impl<K: Borrow<str> + Eq + Hash, V: Into<Cow<'static, str>>> TryFrom<Vec<(K, V)>> for HashMapLoader<K> {
+    type Error = MoostacheError;
+    fn try_from(entries: Vec<(K, V)>) -> Result<Self, Self::Error> {
+        Self::try_from_with_options(entries, &ParseOptions::default())
+    }
+}
+
+impl TemplateLoader<&'static str> for () {
+    type Output<'a> = &'a Template;
+    type Error = MoostacheError;
+    fn get(&self, name: &str) -> Result<&Template, MoostacheError> {
+        Err(MoostacheError::LoaderErrorTemplateNotFound(name.into()))
+    }
+    fn insert(&mut self, _: &'static str, _: Template) -> Option<Template> {
+        None
+    }
+    fn remove(&mut self, _: &str) -> Option<Template> {
+        None
+    }
+}
+
+///////////////
+// RENDERING //
+///////////////
+
+thread_local! {
+    // Every render needs a stack of scopes, so instead of
+    // allocating a new Vec per render we keep one around per
+    // thread and reuse its allocation. It's always empty while
+    // it sits in here, which is why the 'static lifetime is fine.
+    static SCRATCH_SCOPES: RefCell<Vec<&'static serde_json::Value>> = const {
+        RefCell::new(Vec::new())
+    };
+}
+
+// empties a scope stack and changes the lifetime of the
+// references it can hold, reusing the same allocation, std
+// collects a Vec's IntoIter into a Vec of a same sized type
+// in place but doesn't guarantee it, so it's checked here
+fn recycle_scopes<'a>(mut scopes: Vec<&serde_json::Value>) -> Vec<&'a serde_json::Value> {
+    scopes.clear();
+    let capacity = scopes.capacity();
+    let recycled: Vec<_> = scopes.into_iter().map(|_| unreachable!()).collect();
+    debug_assert_eq!(recycled.capacity(), capacity);
+    recycled
+}
+
+// takes the scratch scope stack out of the thread-local, if
+// we're already rendering on this thread (e.g. a loader which
+// renders other templates) this just returns a new Vec
+fn take_scratch_scopes<'a>() -> Vec<&'a serde_json::Value> {
+    recycle_scopes(SCRATCH_SCOPES.with_borrow_mut(std::mem::take))
+}
+
+// puts the scratch scope stack back into the thread-local so
+// the next render can reuse it
+fn return_scratch_scopes(scopes: Vec<&serde_json::Value>) {
+    let scopes = recycle_scopes(scopes);
+    SCRATCH_SCOPES.with_borrow_mut(|scratch| {
+        if scopes.capacity() > scratch.capacity() {
+            *scratch = scopes;
+        }
+    });
+}
+
+thread_local! {
+    // Buffer values are serialized into before they're
+    // written out. See write_value.
+    static SCRATCH_JSON: RefCell<Vec<u8>> = const {
+        RefCell::new(Vec::new())
+    };
+}
+
+// SCRATCH_JSON is freed if it grows larger than this
+const MAX_SCRATCH_JSON_CAPACITY: usize = 64 * 1024;
+
+// checks if serde_json::Value is truthy
+fn is_truthy(value: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(_) => value != &json!(0),
+        Value::String(string) => !string.is_empty(),
+        Value::Array(array) => !array.is_empty(),
+        Value::Object(object) => !object.is_empty(),
+    }
+}
+
+// splits the comparison of an equality section, e.g.
+// ne status "active", into if it's negated, the path,
+// and the literal, see parse_compare_section_start
+fn split_comparison(comparison: &str) -> (bool, &str, &str) {
+    let negated = comparison.starts_with("ne");
+    let rest = comparison[2..].trim_start();
+    let (path, literal) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    (negated, path, literal.trim_start())
+}
+
+// checks if serde_json::Value is equal to the literal of an
+// equality section, strings are only equal to quoted literals,
+// so 1 isn't equal to "1", and numbers are compared by value,
+// so 1 is equal to 1.0
+fn equals_literal(value: &serde_json::Value, literal: &str) -> bool {
+    use serde_json::Value;
+    if let Some(quoted) = literal.strip_prefix('"') {
+        return value.as_str() == quoted.strip_suffix('"');
+    }
+    match (value, literal) {
+        (Value::Null, "null") => true,
+        (Value::Bool(b), "true" | "false") => *b == (literal == "true"),
+        (Value::Number(number), _) => match (number.as_i64(), literal.parse::<i64>()) {
+            (Some(a), Ok(b)) => a == b,
+            _ => number.as_f64()
+                .zip(literal.parse::<f64>().ok())
+                .is_some_and(|(a, b)| a.total_cmp(&b).is_eq()),
+        },
+        _ => false,
+    }
+}
+
+// resolves the path of an equality, plural, or with section,
+// returns the value and if the section's content should be rendered
+fn helper_section<'a>(
+    frag: &Fragment<'_>,
+    scopes: &[&'a serde_json::Value],
+    root: Option<&'a MapRoot<'a>>,
+) -> (&'a serde_json::Value, bool) {
+    match frag {
+        Fragment::PluralSection(plural) => {
+            let resolved_value = resolve_value(plural_path(plural), scopes, root);
+            (resolved_value, !is_singular(resolved_value))
+        },
+        Fragment::WithSection(with) => {
+            let resolved_value = resolve_value(with_path(with), scopes, root);
+            (resolved_value, !resolved_value.is_null())
+        },
+        _ => compare_section(frag.as_str(), scopes, root),
+    }
+}
+
+// the path of an equality, plural, or with section and the
+// literal it's compared to, which is empty if there isn't one
+fn helper_section_path<'src>(frag: &Fragment<'src>) -> (&'src str, &'src str) {
+    match frag {
+        Fragment::CompareSection(comparison) => {
+            let (_, path, literal) = split_comparison(comparison);
+            (path, literal)
+        },
+        Fragment::WithSection(with) => (with_path(with), ""),
+        _ => (plural_path(frag.as_str()), ""),
+    }
+}
+
+// resolves the path of an equality section and compares
+// its value to the literal, returns the value and if the
+// section's content should be rendered
+fn compare_section<'a>(
+    comparison: &str,
+    scopes: &[&'a serde_json::Value],
+    root: Option<&'a MapRoot<'a>>,
+) -> (&'a serde_json::Value, bool) {
+    let (negated, path, literal) = split_comparison(comparison);
+    let resolved_value = resolve_value(path, scopes, root);
+    (resolved_value, equals_literal(resolved_value, literal) != negated)
+}
+
+// the path of a plural section, e.g. count for plural count
+fn plural_path(plural: &str) -> &str {
+    plural["plural".len()..].trim_start()
+}
+
+// the path of a with section, e.g. user for with user
+fn with_path(with: &str) -> &str {
+    with["with".len()..].trim_start()
+}
+
+// checks if serde_json::Value is a number whose magnitude is
+// 1, e.g. 1, -1, or 1.0, which a plural section is singular for,
+// any other value, including strings like "1", is plural
+fn is_singular(value: &serde_json::Value) -> bool {
+    value.as_f64().is_some_and(|number| number.abs().total_cmp(&1.0).is_eq())
+}
+
+// splits the bounds of a range section, e.g.
+// range 1 5, into its start and end
+fn split_range(range: &str) -> (&str, &str) {
+    let mut bounds = range.split_whitespace().skip(1);
+    (bounds.next().unwrap_or_default(), bounds.next().unwrap_or_default())
+}
+
+// resolves the bounds of a range section, an integer bound is
+// used as is and any other is resolved from the scopes, if a
+// bound isn't an integer the range is empty, also returns the
+// paths of bounds which resolved to null
+fn range_bounds<'r>(
+    range: &'r str,
+    scopes: &[&serde_json::Value],
+    root: Option<&MapRoot<'_>>,
+) -> (std::ops::Range<i64>, Vec<&'r str>) {
+    let (start, end) = split_range(range);
+    let mut missing = Vec::new();
+    let mut resolve = |bound: &'r str| bound.parse::<i64>().ok().or_else(|| {
+        let resolved_value = resolve_value(bound, scopes, root);
+        if resolved_value.is_null() {
+            missing.push(bound);
+        }
+        resolved_value.as_i64()
+    });
+    match (resolve(start), resolve(end)) {
+        (Some(start), Some(end)) => (start..end, missing),
+        _ => (0..0, missing),
+    }
+}
+
+/// Options which change how templates are rendered.
+/// 
+/// ### Examples
+/// 
+/// [`RenderOptions`] default values:
+/// 
+/// ```rust
+/// use moostache::{BoolStrings, Escaper, HtmlEscapeConfig, RenderOptions, TrailingWhitespace};
+/// 
+/// assert_eq!(
+///     RenderOptions::default(),
+///     RenderOptions {
+///         escaper: Escaper::Html(HtmlEscapeConfig::default()),
+///         whole_floats_as_integers: false,
+///         sort_object_keys: false,
+///         repeat_number_sections: false,
+///         array_sections_as_scopes: false,
+///         scalar_sections_keep_scope: false,
+///         relative_partials: false,
+///         bool_strings: BoolStrings::default(),
+///         max_output_bytes: None,
+///         strict_container_variables: false,
+///         collapse_whitespace: false,
+///         trailing_whitespace: TrailingWhitespace::Keep,
+///         memoize_paths: false,
+///     },
+/// );
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RenderOptions {
+    /// How escaped variables, e.g. {{ variable }}, are escaped.
+    pub escaper: Escaper,
+    /// If floats with no fractional part are rendered like
+    /// integers, e.g. 2.0 as 2. Otherwise they're rendered
+    /// the same as [`serde_json`] would serialize them. Floats
+    /// within arrays or objects are always rendered the same
+    /// as [`serde_json`] would serialize them.
+    pub whole_floats_as_integers: bool,
+    /// If the keys of objects, e.g. {{{ object }}}, are always
+    /// rendered in sorted order. Otherwise they're rendered in
+    /// the order of [`serde_json::Map`], which is insertion order
+    /// if [`serde_json`]'s `preserve_order` feature is enabled,
+    /// so enabling this keeps output stable, e.g. in snapshot tests,
+    /// regardless of which [`serde_json`] features are enabled.
+    pub sort_object_keys: bool,
+    /// If sections over numbers, e.g. {{# count }} ... {{/ count }},
+    /// render their content that many times, like a loop, with the
+    /// index of each repeat available as {{ @index }}. Fractional
+    /// numbers are rounded down and negative numbers render the
+    /// content zero times, in which case the section's else, e.g.
+    /// {{|}}, is rendered instead. Otherwise sections over non-zero
+    /// numbers render their content once.
+    pub repeat_number_sections: bool,
+    /// If sections over non-empty arrays, e.g. {{# items }} ...
+    /// {{/ items }}, render their content once with the array
+    /// itself as the scope, like sections over objects do, so
+    /// items can be referenced by index, e.g. {{ 0 }}. This
+    /// isn't standard mustache, which renders the content once
+    /// per item. Aggregates, e.g. {{ @count }}, aren't available
+    /// within these sections.
+    pub array_sections_as_scopes: bool,
+    /// If sections over truthy booleans, numbers, or strings, e.g.
+    /// {{# admin }} ... {{/ admin }}, render their content within
+    /// the enclosing scope. Otherwise, like in standard mustache,
+    /// the value is pushed as the scope of the content, so e.g.
+    /// {{ . }} renders `true` within a section over `true` instead
+    /// of the enclosing value. Sections over arrays and objects
+    /// are unaffected.
+    pub scalar_sections_keep_scope: bool,
+    /// If partials, e.g. {{> header }}, are looked up relative
+    /// to the directory of the template including them first,
+    /// e.g. as blog/header when rendering blog/post, before
+    /// falling back to looking them up by their name as-is.
+    /// The directory of a template is only known if it was
+    /// rendered by name, e.g. with [`TemplateLoader::render`],
+    /// or it's a partial.
+    pub relative_partials: bool,
+    /// How booleans, e.g. {{ admin }}, are rendered. Booleans
+    /// within arrays or objects are always rendered the same as
+    /// [`serde_json`] would serialize them.
+    pub bool_strings: BoolStrings,
+    /// The most bytes a render can write, if limited. Renders
+    /// which would write more are aborted with
+    /// [`MoostacheError::RenderErrorOutputTooLarge`], which
+    /// protects against templates and data which multiply into
+    /// huge outputs, e.g. nested sections over large arrays.
+    /// Output written before the limit was reached isn't undone.
+    pub max_output_bytes: Option<usize>,
+    /// If variables, e.g. {{ variable }}, which resolve to arrays
+    /// or objects fail the render with
+    /// [`MoostacheError::RenderErrorCannotStringifyContainer`],
+    /// since rendering them is usually a template bug, e.g.
+    /// forgetting to index into an object. Otherwise they're
+    /// rendered the same as [`serde_json`] would serialize them.
+    /// Sections over arrays or objects are unaffected.
+    pub strict_container_variables: bool,
+    /// If runs of whitespace, e.g. spaces, tabs, or newlines, in
+    /// the literal text of templates are collapsed into single
+    /// spaces, which makes rendered HTML smaller. Collapsing isn't
+    /// aware of HTML, so whitespace within elements where it's
+    /// significant, e.g. `<pre>` or `<textarea>`, is collapsed too.
+    /// Whitespace within rendered variables is never collapsed,
+    /// and every literal is collapsed on its own, e.g. the
+    /// whitespace around {{# section }} becomes two spaces.
+    pub collapse_whitespace: bool,
+    /// What happens to whitespace, e.g. newlines, at the end of
+    /// renders which return a [`String`], e.g.
+    /// [`render_to_string`](TemplateLoader::render_to_string),
+    /// since templates from files often end with inconsistent
+    /// newlines. It's normalized before
+    /// [`post_render`](TemplateLoader::post_render) is applied.
+    pub trailing_whitespace: TrailingWhitespace,
+    /// If the values paths resolve to, e.g. {{ user.profile.name }},
+    /// are remembered for the rest of the fragments rendered with
+    /// the same scopes, so repeating a path within a section, or at
+    /// the top level of a template, only resolves it once. This
+    /// costs a lookup per variable and section, so it's only faster
+    /// for templates which repeat the same deep paths many times.
+    pub memoize_paths: bool,
+}
+
+/// The strings booleans are rendered as. See [`RenderOptions`].
+/// 
+/// ### Examples
+/// 
+/// [`BoolStrings`] default values:
+/// 
+/// ```rust
+/// use moostache::BoolStrings;
+/// 
+/// assert_eq!(
+///     BoolStrings::default(),
+///     BoolStrings {
+///         true_str: "true".into(),
+///         false_str: "false".into(),
+///     },
+/// );
+/// ```
+/// 
+/// Rendering booleans as yes or no:
+/// 
+/// ```rust
+/// use moostache::{BoolStrings, RenderOptions};
+/// 
+/// let options = RenderOptions {
+///     bool_strings: BoolStrings {
+///         true_str: "yes".into(),
+///         false_str: "no".into(),
+///     },
+///     ..RenderOptions::default()
+/// };
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoolStrings {
+    /// What `true` is rendered as.
+    pub true_str: Cow<'static, str>,
+    /// What `false` is rendered as.
+    pub false_str: Cow<'static, str>,
+}
+
+impl Default for BoolStrings {
+    fn default() -> Self {
+        BoolStrings {
+            true_str: Cow::Borrowed("true"),
+            false_str: Cow::Borrowed("false"),
+        }
+    }
+}
+
+/// How escaped variables, e.g. {{ variable }}, are escaped
+/// during renders. See [`RenderOptions`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Escaper {
+    /// Escape HTML chars. See [`HtmlEscapeWriter`].
+    Html(HtmlEscapeConfig),
+    /// Escape chars the same way they would be escaped
+    /// within a JSON string, e.g. `"` becomes `\"`, so
+    /// variables can be rendered inside JSON strings.
+    Json,
+    /// Hex escape every char below U+0100 which isn't ASCII
+    /// alphanumeric, e.g. `<` becomes `\3c `, following OWASP's
+    /// CSS escaping rules, so variables can be rendered inside
+    /// `<style>` elements or style attributes.
+    Css,
+    /// Don't escape anything, so {{ variable }} renders the
+    /// same as {{{ variable }}}, for templates which don't
+    /// render HTML, e.g. config files or code. To render all
+    /// templates of a loader this way set it in the loader's
+    /// render options.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{Escaper, HashMapLoader, RenderOptions, TemplateLoader};
+    /// use maplit::hashmap;
+    /// use serde_json::json;
+    /// 
+    /// let mut loader = HashMapLoader::try_from(hashmap! {
+    ///     "config" => "if a < b {{ op }} c > d",
+    /// }).unwrap();
+    /// loader.set_render_options(RenderOptions {
+    ///     escaper: Escaper::None,
+    ///     ..RenderOptions::default()
+    /// });
+    /// let rendered = loader.render_to_string("config", &json!({"op": "&&"})).unwrap();
+    /// assert_eq!(rendered, "if a < b && c > d");
+    /// ```
+    None,
+}
+
+impl Default for Escaper {
+    fn default() -> Self {
+        Escaper::Html(HtmlEscapeConfig::default())
+    }
+}
+
+/// Configures which chars [`HtmlEscapeWriter`] escapes
+/// and which entities it uses to escape them.
+/// 
+/// ### Examples
+/// 
+/// [`HtmlEscapeConfig`] default values:
+/// 
+/// ```rust
+/// use moostache::{ApostropheEntity, EscapedChars, HtmlEscapeConfig, QuoteEntity};
+/// 
+/// assert_eq!(
+///     HtmlEscapeConfig::default(),
+///     HtmlEscapeConfig {
+///         apostrophe: ApostropheEntity::Hex,
+///         quote: QuoteEntity::Named,
+///         attribute_safe: false,
+///         chars: EscapedChars::ALL,
+///     },
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HtmlEscapeConfig {
+    /// Entity used to escape `'`.
+    pub apostrophe: ApostropheEntity,
+    /// Entity used to escape `"`.
+    pub quote: QuoteEntity,
+    /// If whitespace, `` ` ``, and `=` are also escaped, as
+    /// recommended by OWASP for variables rendered within
+    /// unquoted or single-quoted HTML attribute values,
+    /// e.g. `<div class={{ class }}>`. Otherwise they're only
+    /// safe to render in HTML text or double-quoted attribute
+    /// values.
+    pub attribute_safe: bool,
+    /// Which of `&`, `<`, `>`, `"`, and `'` are escaped.
+    /// Escaping fewer than all of them is only safe in
+    /// contexts where the others can't be misinterpreted,
+    /// e.g. quotes within HTML text.
+    pub chars: EscapedChars,
+}
+
+/// Set of the chars `&`, `<`, `>`, `"`, and `'` which a
+/// [`HtmlEscapeWriter`] escapes. Sets can be combined
+/// with `|`. See [`HtmlEscapeConfig`].
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::{EscapedChars, HtmlEscapeConfig, HtmlEscapeWriter};
+/// use std::io::Write;
+/// 
+/// let config = HtmlEscapeConfig {
+///     chars: EscapedChars::AMPERSAND | EscapedChars::LESS_THAN | EscapedChars::GREATER_THAN,
+///     ..HtmlEscapeConfig::default()
+/// };
+/// let mut escaped = Vec::new();
+/// HtmlEscapeWriter::with_config(&mut escaped, config)
+///     .write_all(br#"<q>"it's"</q>"#)
+///     .unwrap();
+/// assert_eq!(escaped, br#"&lt;q&gt;"it's"&lt;/q&gt;"#);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EscapedChars(u8);
+
+impl EscapedChars {
+    /// No chars.
+    pub const NONE: EscapedChars = EscapedChars(0);
+    /// The `&` char.
+    pub const AMPERSAND: EscapedChars = EscapedChars(1);
+    /// The `<` char.
+    pub const LESS_THAN: EscapedChars = EscapedChars(1 << 1);
+    /// The `>` char.
+    pub const GREATER_THAN: EscapedChars = EscapedChars(1 << 2);
+    /// The `"` char.
+    pub const QUOTE: EscapedChars = EscapedChars(1 << 3);
+    /// The `'` char.
+    pub const APOSTROPHE: EscapedChars = EscapedChars(1 << 4);
+    /// All five chars, the default.
+    pub const ALL: EscapedChars = EscapedChars(0b1_1111);
+
+    /// If all chars in `other` are also in this set.
+    #[must_use]
+    pub const fn contains(self, other: EscapedChars) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // the set a byte belongs to, if it's one of the five
+    fn of(byte: u8) -> Option<EscapedChars> {
+        match byte {
+            b'&' => Some(EscapedChars::AMPERSAND),
+            b'<' => Some(EscapedChars::LESS_THAN),
+            b'>' => Some(EscapedChars::GREATER_THAN),
+            b'"' => Some(EscapedChars::QUOTE),
+            b'\'' => Some(EscapedChars::APOSTROPHE),
+            _ => None,
+        }
+    }
+}
+
+impl Default for EscapedChars {
+    fn default() -> Self {
+        EscapedChars::ALL
+    }
+}
+
+impl BitOr for EscapedChars {
+    type Output = EscapedChars;
+    fn bitor(self, rhs: EscapedChars) -> EscapedChars {
+        EscapedChars(self.0 | rhs.0)
+    }
+}
+
+/// What happens to whitespace at the end of renders which
+/// return a [`String`]. See [`RenderOptions`].
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::{HashMapLoader, RenderOptions, TemplateLoader, TrailingWhitespace};
+/// use maplit::hashmap;
+/// use serde_json::json;
+/// 
+/// let mut loader = HashMapLoader::try_from(hashmap! {
+///     "greet" => "hello {{name}}!\n\n\n",
+/// }).unwrap();
+/// loader.set_render_options(RenderOptions {
+///     trailing_whitespace: TrailingWhitespace::Newline,
+///     ..RenderOptions::default()
+/// });
+/// let rendered = loader.render_to_string("greet", &json!({"name": "John"})).unwrap();
+/// assert_eq!(rendered, "hello John!\n");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrailingWhitespace {
+    /// Keep it as it is.
+    #[default]
+    Keep,
+    /// Remove all of it.
+    Trim,
+    /// Replace it with exactly one newline, unless
+    /// the output is empty or only whitespace, in
+    /// which case it's removed.
+    Newline,
+}
+
+impl TrailingWhitespace {
+    // normalizes the whitespace at the end of a render
+    #[inline]
+    fn apply(self, mut rendered: String) -> String {
+        if self == TrailingWhitespace::Keep {
+            return rendered;
+        }
+        rendered.truncate(rendered.trim_end().len());
+        if self == TrailingWhitespace::Newline && !rendered.is_empty() {
+            rendered.push('\n');
+        }
+        rendered
+    }
+}
+
+/// Entity used to escape the `'` char.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ApostropheEntity {
+    /// `&#x27;`, valid everywhere.
+    #[default]
+    Hex,
+    /// `&#39;`, valid everywhere.
+    Decimal,
+    /// `&apos;`, valid in XML, XHTML, and HTML5,
+    /// but not in HTML4.
+    Named,
+}
+
+impl ApostropheEntity {
+    #[inline]
+    fn as_bytes(self) -> &'static [u8] {
+        match self {
+            ApostropheEntity::Hex => b"&#x27;",
+            ApostropheEntity::Decimal => b"&#39;",
+            ApostropheEntity::Named => b"&apos;",
+        }
+    }
+}
+
+/// Entity used to escape the `"` char.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QuoteEntity {
+    /// `&quot;`, valid everywhere.
+    #[default]
+    Named,
+    /// `&#x22;`, valid everywhere.
+    Hex,
+    /// `&#34;`, valid everywhere.
+    Decimal,
+}
+
+impl QuoteEntity {
+    #[inline]
+    fn as_bytes(self) -> &'static [u8] {
+        match self {
+            QuoteEntity::Named => b"&quot;",
+            QuoteEntity::Hex => b"&#x22;",
+            QuoteEntity::Decimal => b"&#34;",
+        }
+    }
+}
+
+// lookup tables of which bytes HtmlEscapeWriter escapes,
+// checking a table is a lot faster than matching on every
+// byte when scanning for the next byte to escape
+const fn escaped_bytes(attribute_safe: bool) -> [bool; 256] {
+    let mut table = [false; 256];
+    let mut byte: u8 = 0;
+    loop {
+        table[byte as usize] = matches!(byte, b'&' | b'<' | b'>' | b'"' | b'\'')
+            || (attribute_safe && matches!(byte, b' ' | b'\t' | b'\n' | b'\x0C' | b'\r' | b'`' | b'='));
+        if byte == u8::MAX {
+            return table;
+        }
+        byte += 1;
+    }
+}
+
+static HTML_ESCAPED_BYTES: [bool; 256] = escaped_bytes(false);
+static ATTRIBUTE_ESCAPED_BYTES: [bool; 256] = escaped_bytes(true);
+
+/// Wraps a [`Write`] type and escapes HTML
+/// chars before writing them to the inner writer. This is
+/// what moostache uses to escape variables, e.g. {{ variable }},
+/// during renders.
+/// 
+/// As recommended by OWASP the chars `&`, `<`, `>`, `"`,
+/// and `'` are escaped. Which of them are escaped, and which
+/// entities are used for `"` and `'`, can be changed with a
+/// [`HtmlEscapeConfig`], which can also make it escape the
+/// extra chars which need escaping within HTML attribute
+/// values.
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::HtmlEscapeWriter;
+/// use std::io::Write;
+/// 
+/// let mut escaped = Vec::new();
+/// HtmlEscapeWriter::new(&mut escaped)
+///     .write_all(br#"<a href="/?a=1&b='2'">"#)
+///     .unwrap();
+/// assert_eq!(
+///     String::from_utf8(escaped).unwrap(),
+///     "&lt;a href=&quot;/?a=1&amp;b=&#x27;2&#x27;&quot;&gt;",
+/// );
+/// ```
+pub struct HtmlEscapeWriter<'a, W: Write> {
+    writer: &'a mut W,
+    config: HtmlEscapeConfig,
+    // which bytes need escaping, see escaped_bytes
+    escaped: [bool; 256],
+    // rest of an entity the inner writer
+    // only wrote part of
+    pending: &'static [u8],
+}
+
+impl<'a, W: Write> HtmlEscapeWriter<'a, W> {
+    /// Create a new [`HtmlEscapeWriter`] which writes
+    /// escaped output to `writer`.
+    #[inline]
+    pub fn new(writer: &'a mut W) -> Self {
+        Self::with_config(writer, HtmlEscapeConfig::default())
+    }
+
+    /// Create a new [`HtmlEscapeWriter`] which writes
+    /// escaped output to `writer` using the entities
+    /// in `config`.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{ApostropheEntity, HtmlEscapeConfig, HtmlEscapeWriter};
+    /// use std::io::Write;
+    /// 
+    /// let config = HtmlEscapeConfig {
+    ///     apostrophe: ApostropheEntity::Named,
+    ///     ..HtmlEscapeConfig::default()
+    /// };
+    /// let mut escaped = Vec::new();
+    /// HtmlEscapeWriter::with_config(&mut escaped, config)
+    ///     .write_all(b"it's")
+    ///     .unwrap();
+    /// assert_eq!(escaped, b"it&apos;s");
+    /// ```
+    #[inline]
+    pub fn with_config(writer: &'a mut W, config: HtmlEscapeConfig) -> Self {
+        let mut escaped = if config.attribute_safe {
+            ATTRIBUTE_ESCAPED_BYTES
+        } else {
+            HTML_ESCAPED_BYTES
+        };
+        for byte in *b"&<>\"'" {
+            if EscapedChars::of(byte).is_some_and(|chars| !config.chars.contains(chars)) {
+                escaped[byte as usize] = false;
+            }
+        }
+        HtmlEscapeWriter {
+            writer,
+            config,
+            escaped,
+            pending: b"",
+        }
+    }
+
+    // entity a byte is escaped as, if it needs escaping
+    #[inline]
+    fn entity(&self, byte: u8) -> Option<&'static [u8]> {
+        if !self.escaped[byte as usize] {
+            return None;
+        }
+        match byte {
+            b'&' => Some(b"&amp;"),
+            b'<' => Some(b"&lt;"),
+            b'>' => Some(b"&gt;"),
+            b'"' => Some(self.config.quote.as_bytes()),
+            b'\'' => Some(self.config.apostrophe.as_bytes()),
+            b' ' if self.config.attribute_safe => Some(b"&#x20;"),
+            b'\t' if self.config.attribute_safe => Some(b"&#x9;"),
+            b'\n' if self.config.attribute_safe => Some(b"&#xA;"),
+            b'\x0C' if self.config.attribute_safe => Some(b"&#xC;"),
+            b'\r' if self.config.attribute_safe => Some(b"&#xD;"),
+            b'`' if self.config.attribute_safe => Some(b"&#x60;"),
+            b'=' if self.config.attribute_safe => Some(b"&#x3D;"),
+            _ => None,
+        }
+    }
+
+    // writes the rest of an entity which the inner writer
+    // only wrote part of, if it fails what's left of the
+    // entity is kept so it can be retried
+    fn write_pending(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            match self.writer.write(self.pending) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => self.pending = &self.pending[written..],
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {},
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HtmlEscapeWriter<'_, W> {
+    // writes either a run of bytes which don't need escaping,
+    // or a single byte which does, so the returned count is
+    // always how many bytes of buf were actually consumed, if
+    // the inner writer only writes part of an entity the rest
+    // of it is written before anything else
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_pending()?;
+        let Some(&first) = buf.first() else {
+            return Ok(0);
+        };
+        if let Some(entity) = self.entity(first) {
+            let written = self.writer.write(entity)?;
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.pending = &entity[written..];
+            return Ok(1);
+        }
+        let end = buf.iter()
+            .position(|byte| self.escaped[*byte as usize])
+            .unwrap_or(buf.len());
+        self.writer.write(&buf[..end])
+    }
+    fn write_all(&mut self, mut buf: &[u8]) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => buf = &buf[written..],
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {},
+                Err(err) => return Err(err),
+            }
+        }
+        self.write_pending()
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.writer.flush()
+    }
+}
+
+/// Escapes HTML chars in a string exactly the same way
+/// escaped variables, e.g. {{ variable }}, are escaped
+/// during renders by default. To escape with a different
+/// [`HtmlEscapeConfig`], or directly into a writer, use a
+/// [`HtmlEscapeWriter`].
+/// 
+/// ### Examples
+/// 
+/// ```rust
+/// use moostache::escape_html;
+/// 
+/// assert_eq!(
+///     escape_html("<a href=\"/?a=1&b='2'\">"),
+///     "&lt;a href=&quot;/?a=1&amp;b=&#x27;2&#x27;&quot;&gt;",
+/// );
+/// ```
+#[must_use]
+pub fn escape_html(s: &str) -> String {
+    let mut escaped = Vec::with_capacity(s.len());
+    // writing to a Vec can't fail
+    let _ = HtmlEscapeWriter::new(&mut escaped).write_all(s.as_bytes());
+    // only ascii chars are replaced, with
+    // ascii entities, so it's still utf-8
+    String::from_utf8(escaped).unwrap_or_default()
+}
+
+// Wraps a Write type and fails writes once more
+// than `remaining` bytes would be written to the
+// inner writer. See RenderOptions::max_output_bytes.
+struct OutputLimitWriter<'a, W: Write> {
+    writer: &'a mut W,
+    remaining: usize,
+}
+
+// the error OutputLimitWriter fails writes with,
+// which from_write_io turns into a MoostacheError
+#[derive(Debug)]
+struct OutputTooLarge;
+
+impl Display for OutputTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "output too large")
+    }
+}
+
+impl std::error::Error for OutputTooLarge {}
+
+impl<W: Write> Write for OutputLimitWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = buf.len();
+        self.write_all(buf)
+            .map(|()| written)
+    }
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if buf.len() > self.remaining {
+            return Err(io::Error::other(OutputTooLarge));
+        }
+        self.remaining -= buf.len();
+        self.writer.write_all(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Wraps a Write type and escapes chars the same
+// way they would be escaped within a JSON string
+// before writing them to the inner writer.
+struct JsonEscapeWriter<'a, W: Write> {
+    writer: &'a mut W,
+}
+
+impl<W: Write> Write for JsonEscapeWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = buf.len();
+        self.write_all(buf)
+            .map(|()| written)
+    }
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        let mut start = 0;
+        for (end, byte) in buf.iter().enumerate() {
+            let escaped: &[u8] = match byte {
+                b'"' => b"\\\"",
+                b'\\' => b"\\\\",
+                b'\n' => b"\\n",
+                b'\r' => b"\\r",
+                b'\t' => b"\\t",
+                0x00..=0x1f => {
+                    // other control chars have no short escape
+                    if start < end {
+                        self.writer.write_all(&buf[start..end])?;
+                    }
+                    start = end + 1;
+                    write!(self.writer, "\\u{byte:04x}")?;
+                    continue;
+                },
+                _ => continue,
+            };
+            if start < end {
+                self.writer.write_all(&buf[start..end])?;
+            }
+            start = end + 1;
+            self.writer.write_all(escaped)?;
+        }
+        if start < buf.len() {
+            self.writer.write_all(&buf[start..])?;
+        }
+        Ok(())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Wraps a Write type and hex escapes chars below U+0100
+// which aren't ASCII alphanumeric, e.g. < becomes \3c
+// followed by a space, which terminates the escape,
+// before writing them to the inner writer.
+struct CssEscapeWriter<'a, W: Write> {
+    writer: &'a mut W,
+}
+
+impl<W: Write> Write for CssEscapeWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = buf.len();
+        self.write_all(buf)
+            .map(|()| written)
+    }
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        let mut start = 0;
+        let mut end = 0;
+        while end < buf.len() {
+            let byte = buf[end];
+            let (char, len) = match (byte, buf.get(end + 1)) {
+                _ if byte.is_ascii_alphanumeric() => {
+                    end += 1;
+                    continue;
+                },
+                (0x00..=0x7f, _) => (u32::from(byte), 1),
+                // two byte utf-8 encodings of U+0080 to U+00FF
+                (0xc2 | 0xc3, Some(next)) => (u32::from(byte & 0x1f) << 6 | u32::from(next & 0x3f), 2),
+                _ => {
+                    end += 1;
+                    continue;
+                },
+            };
+            if start < end {
+                self.writer.write_all(&buf[start..end])?;
+            }
+            write!(self.writer, "\\{char:x} ")?;
+            end += len;
+            start = end;
+        }
+        if start < buf.len() {
+            self.writer.write_all(&buf[start..])?;
         }
-        let max_size = NonZeroUsize::new(config.cache_size)
-            .ok_or(MoostacheError::ConfigErrorNonPositiveCacheSize)?;
-        let max_size: usize = max_size.into();
+        Ok(())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
 
-        if !dir_path.is_dir() {
-            return Err(MoostacheError::ConfigErrorInvalidTemplatesDirectory(dir_path.into()));
+// serializes a serde_json::Value but with the keys of
+// every object in it sorted, see RenderOptions
+struct SortedKeys<'a>(&'a serde_json::Value);
+
+impl Serialize for SortedKeys<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{SerializeMap, SerializeSeq};
+        use serde_json::Value;
+        match self.0 {
+            Value::Array(array) => {
+                let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                for value in array {
+                    seq.serialize_element(&SortedKeys(value))?;
+                }
+                seq.end()
+            },
+            Value::Object(object) => {
+                let mut entries: Vec<_> = object.iter().collect();
+                entries.sort_unstable_by_key(|(key, _)| *key);
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, &SortedKeys(value))?;
+                }
+                map.end()
+            },
+            value => value.serialize(serializer),
         }
+    }
+}
 
-        let mut current_size = 0usize;
-        let mut templates: HashMap<String, Template, FnvBuildHasher> = HashMap::with_hasher(BuildHasherDefault::default());
-        for entry in WalkDir::new(dir_path).into_iter().filter_map(Result::ok) {
-            if entry.file_type().is_file() {
-                let entry_path = entry.path();
-                let entry_path_str = entry_path
-                    .to_str()
-                    .ok_or_else(|| MoostacheError::LoaderErrorNonUtf8FilePath(entry_path.into()))?;
-                if entry_path_str.ends_with(&ext) {
-                    let name = entry_path_str
-                        .strip_prefix(&dir)
-                        .and_then(|path| path.strip_suffix(&ext))
-                        .unwrap()
-                        .to_string();
-                    let source = fs::read_to_string(entry_path)
-                        .map_err(|err| MoostacheError::from_io(err, name.clone()))?;
-                    let template = Template::parse(source)
-                        .map_err(|err| err.set_name(&name))?;
-                    templates.insert(name, template);
-                    current_size += 1;
-                    if current_size > max_size {
-                        return Err(MoostacheError::ConfigErrorTooManyTemplates);
+// is number a float with no fractional part, e.g. 2.0
+fn is_whole_float(number: &serde_json::Number) -> bool {
+    number.is_f64() && number.as_f64().is_some_and(|float| float.fract() == 0.0)
+}
+
+// converts rendered output into a String, validating
+// it's utf8, see Template::render_to_string_checked
+fn checked_utf8(rendered: Vec<u8>) -> Result<String, MoostacheError> {
+    String::from_utf8(rendered).map_err(|_| MoostacheError::RenderErrorInvalidUtf8)
+}
+
+// converts rendered output into a String, it's only validated
+// if the loader has a value renderer, since those can write
+// any bytes, otherwise templates are utf8 and values are utf8
+// so we know templates + values will also be utf8
+fn rendered_utf8<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+    loader: &T,
+    rendered: Vec<u8>,
+) -> Result<String, MoostacheError> {
+    if loader.value_renderer().is_some() {
+        return checked_utf8(rendered);
+    }
+    Ok(unsafe {
+        // SAFETY: see above
+        debug_assert!(str::from_utf8(&rendered).is_ok());
+        String::from_utf8_unchecked(rendered)
+    })
+}
+
+// writes a literal, collapsing runs of whitespace
+// in it if enabled, see RenderOptions
+#[inline]
+fn write_literal<W: Write>(
+    literal: &str,
+    options: &RenderOptions,
+    writer: &mut W,
+) -> Result<(), MoostacheError> {
+    if options.collapse_whitespace {
+        write_collapsed_literal(literal, writer)
+    } else {
+        writer.write_all(literal.as_bytes())
+            .map_err(MoostacheError::from_write_io)
+    }
+}
+
+// writes a literal with every run of ascii whitespace
+// in it replaced by a single space
+fn write_collapsed_literal<W: Write>(
+    literal: &str,
+    writer: &mut W,
+) -> Result<(), MoostacheError> {
+    let bytes = literal.as_bytes();
+    let mut start = 0;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if !bytes[idx].is_ascii_whitespace() {
+            idx += 1;
+            continue;
+        }
+        let end = idx;
+        while idx < bytes.len() && bytes[idx].is_ascii_whitespace() {
+            idx += 1;
+        }
+        // a lone space is already collapsed
+        if idx - end == 1 && bytes[end] == b' ' {
+            continue;
+        }
+        writer.write_all(&bytes[start..end])
+            .and_then(|()| writer.write_all(b" "))
+            .map_err(MoostacheError::from_write_io)?;
+        start = idx;
+    }
+    writer.write_all(&bytes[start..])
+        .map_err(MoostacheError::from_write_io)
+}
+
+// serializes a serde_json::Value, escaping it
+// using the given escaper
+#[inline]
+fn write_escaped_value<W: Write>(
+    value: &serde_json::Value,
+    options: &RenderOptions,
+    writer: &mut W,
+) -> Result<(), MoostacheError> {
+    match options.escaper {
+        Escaper::Html(config) => write_value(value, options, &mut HtmlEscapeWriter::with_config(writer, config)),
+        Escaper::Json => write_value(value, options, &mut JsonEscapeWriter { writer }),
+        Escaper::Css => write_value(value, options, &mut CssEscapeWriter { writer }),
+        Escaper::None => write_value(value, options, writer),
+    }
+}
+
+// How many chars a variable with a width, e.g. {{ name:10 }},
+// is padded with spaces or truncated to. Chars are counted,
+// not bytes, and they're counted before being escaped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Width {
+    chars: usize,
+    // pad on the left, e.g. {{ name:>10 }}, instead of
+    // on the right, e.g. {{ name:10 }} or {{ name:<10 }}
+    align_right: bool,
+}
+
+// splits the name of a variable into its path and its
+// width, if it has one, e.g. name:>10 into name and 10
+#[inline]
+fn split_width(name: &str) -> (&str, Option<Width>) {
+    // widths always end with a digit, so most names
+    // can be ruled out without searching for the colon
+    if !name.ends_with(|c: char| c.is_ascii_digit()) {
+        return (name, None);
+    }
+    // aggregates have a colon too, e.g. @sum:price1
+    let Some((path, width)) = name.rsplit_once(':') else {
+        return (name, None);
+    };
+    let (digits, align_right) = match width.strip_prefix('>') {
+        Some(digits) => (digits, true),
+        None => (width.strip_prefix('<').unwrap_or(width), false),
+    };
+    // the parser only allows digits which fit in a u16
+    match digits.parse() {
+        Ok(chars) => (path, Some(Width {
+            chars,
+            align_right,
+        })),
+        Err(_) => (name, None),
+    }
+}
+
+// writes the value of a variable, escaping it if it's
+// escaped and padding it if it has a width, giving the
+// value renderer, if any, the first chance to write it
+#[inline]
+fn write_variable<W: Write>(
+    value: &serde_json::Value,
+    width: Option<Width>,
+    escaped: bool,
+    renderer: Option<&dyn ValueRenderer>,
+    options: &RenderOptions,
+    writer: &mut W,
+) -> Result<(), MoostacheError> {
+    if let (Some(renderer), None) = (renderer, width) {
+        let result = match (escaped, options.escaper) {
+            (false, _) | (true, Escaper::None) => renderer.render(value, escaped, writer),
+            (true, Escaper::Html(config)) => renderer.render(value, escaped, &mut HtmlEscapeWriter::with_config(&mut *writer, config)),
+            (true, Escaper::Json) => renderer.render(value, escaped, &mut JsonEscapeWriter { writer: &mut *writer }),
+            (true, Escaper::Css) => renderer.render(value, escaped, &mut CssEscapeWriter { writer: &mut *writer }),
+        };
+        if let Some(result) = result {
+            return result.map_err(MoostacheError::from_write_io);
+        }
+    }
+    match width {
+        Some(width) => write_padded_value(value, width, escaped, renderer, options, writer),
+        None if escaped => write_escaped_value(value, options, writer),
+        None => write_value(value, options, writer),
+    }
+}
+
+// same as write_value or write_escaped_value, but pads the
+// value with spaces or truncates it to the width
+#[cold]
+fn write_padded_value<W: Write>(
+    value: &serde_json::Value,
+    width: Width,
+    escaped: bool,
+    renderer: Option<&dyn ValueRenderer>,
+    options: &RenderOptions,
+    writer: &mut W,
+) -> Result<(), MoostacheError> {
+    let mut bytes = Vec::new();
+    match renderer.and_then(|renderer| renderer.render(value, escaped, &mut bytes)) {
+        Some(result) => result.map_err(MoostacheError::from_write_io)?,
+        None => write_value(value, options, &mut bytes)?,
+    }
+    // values are utf8 so they're written as utf8, but
+    // value renderers could write anything
+    let text = String::from_utf8(bytes)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned());
+    let (text, chars) = match text.char_indices().nth(width.chars) {
+        Some((end, _)) => (&text[..end], width.chars),
+        None => (text.as_str(), text.chars().count()),
+    };
+    let padding = width.chars - chars;
+    if width.align_right {
+        write!(writer, "{:padding$}", "")
+            .map_err(MoostacheError::from_write_io)?;
+    }
+    match (escaped, options.escaper) {
+        (false, _) | (true, Escaper::None) => writer.write_all(text.as_bytes()),
+        (true, Escaper::Html(config)) => HtmlEscapeWriter::with_config(&mut *writer, config).write_all(text.as_bytes()),
+        (true, Escaper::Json) => JsonEscapeWriter { writer: &mut *writer }.write_all(text.as_bytes()),
+        (true, Escaper::Css) => CssEscapeWriter { writer: &mut *writer }.write_all(text.as_bytes()),
+    }.map_err(MoostacheError::from_write_io)?;
+    if !width.align_right {
+        write!(writer, "{:padding$}", "")
+            .map_err(MoostacheError::from_write_io)?;
+    }
+    Ok(())
+}
+
+// serializes a serde_json::Value
+fn write_value<W: Write>(
+    value: &serde_json::Value,
+    options: &RenderOptions,
+    writer: &mut W,
+) -> Result<(), MoostacheError> {
+    use serde_json::Value;
+    match value {
+        Value::Null => {
+            // serde_json serializes null as
+            // "null" but we want this to be
+            // an empty string instead
+        },
+        Value::String(string) => {
+            // serde_json serializes strings
+            // wrapped with quotes, but we
+            // want to write them without quotes
+            writer.write_all(string.as_bytes())
+                .map_err(MoostacheError::from_write_io)?;
+        },
+        Value::Bool(b) => {
+            let string = if *b {
+                &options.bool_strings.true_str
+            } else {
+                &options.bool_strings.false_str
+            };
+            writer.write_all(string.as_bytes())
+                .map_err(MoostacheError::from_write_io)?;
+        },
+        // serde_json serializes whole floats
+        // with a trailing .0, e.g. 2.0, but
+        // Display writes them without it
+        Value::Number(number) if options.whole_floats_as_integers && is_whole_float(number) => {
+            let float = number.as_f64().unwrap_or_default();
+            write!(writer, "{float}")
+                .map_err(MoostacheError::from_write_io)?;
+        },
+        // let serde_json handle the rest, it writes
+        // lots of tiny pieces, e.g. a single quote or
+        // comma, which is slow if the writer does any
+        // work per write (like escaping), so we serialize
+        // into a reusable buffer and write it all at once
+        _ => {
+            SCRATCH_JSON.with_borrow_mut(|scratch| {
+                scratch.clear();
+                let mut serializer = serde_json::Serializer::new(&mut *scratch);
+                if options.sort_object_keys {
+                    SortedKeys(value).serialize(&mut serializer)
+                } else {
+                    value.serialize(&mut serializer)
+                }.map_err(|err| MoostacheError::SerializationError(err.to_string()))?;
+                let result = writer.write_all(scratch)
+                    .map_err(MoostacheError::from_write_io);
+                // don't hold onto the memory of huge values
+                if scratch.capacity() > MAX_SCRATCH_JSON_CAPACITY {
+                    *scratch = Vec::new();
+                }
+                result
+            })?;
+        },
+    }
+    Ok(())
+}
+
+// the fields of a type which serialized into a map of
+// strings, numbers, bools, and nulls, used for rendering
+// it without serializing it into a serde_json::Value,
+// see Template::render_flat_serializable
+#[derive(Default)]
+struct FlatFields {
+    // keys and string values are written in here
+    // so there's no allocation per field
+    buffer: Vec<u8>,
+    // key and value of every field, in the same order
+    // they were serialized in
+    fields: Vec<(Range<usize>, FlatValue)>,
+    // last serialized map key, waiting for its value
+    key: Range<usize>,
+}
+
+enum FlatValue {
+    // range of the string in FlatFields' buffer
+    Str(Range<usize>),
+    // a number, bool, or null, these don't allocate
+    Other(serde_json::Value),
+}
+
+impl FlatFields {
+    // like resolve_value, if the same key was serialized
+    // more than once the last one wins, like in a serde_json::Map
+    fn get(&self, name: &str) -> Option<&FlatValue> {
+        self.fields.iter()
+            .rev()
+            .find(|(key, _)| &self.buffer[key.clone()] == name.as_bytes())
+            .map(|(_, value)| value)
+    }
+    // renders a flat template, i.e. one with only
+    // literals and variables without dots
+    fn render<W: Write>(
+        &self,
+        frags: &[Fragment<'_>],
+        options: &RenderOptions,
+        writer: &mut W,
+    ) -> Result<(), MoostacheError> {
+        for frag in frags {
+            match frag {
+                Fragment::Literal(literal) => {
+                    write_literal(literal, options, writer)?;
+                },
+                Fragment::EscapedVariable(name) => {
+                    self.write_escaped_field(name, options, writer)?;
+                },
+                Fragment::UnescapedVariable(name) => {
+                    self.write_field(name, options, writer)?;
+                },
+                _ => unreachable!(),
+            }
+        }
+        Ok(())
+    }
+    // same as write_escaped_value but for a field
+    fn write_escaped_field<W: Write>(
+        &self,
+        name: &str,
+        options: &RenderOptions,
+        writer: &mut W,
+    ) -> Result<(), MoostacheError> {
+        match options.escaper {
+            Escaper::Html(config) => self.write_field(name, options, &mut HtmlEscapeWriter::with_config(writer, config)),
+            Escaper::Json => self.write_field(name, options, &mut JsonEscapeWriter { writer }),
+            Escaper::Css => self.write_field(name, options, &mut CssEscapeWriter { writer }),
+            Escaper::None => self.write_field(name, options, writer),
+        }
+    }
+    // same as write_value but for a field
+    fn write_field<W: Write>(
+        &self,
+        name: &str,
+        options: &RenderOptions,
+        writer: &mut W,
+    ) -> Result<(), MoostacheError> {
+        match self.get(name) {
+            None => Ok(()),
+            Some(FlatValue::Str(range)) => {
+                writer.write_all(&self.buffer[range.clone()])
+                    .map_err(MoostacheError::from_write_io)
+            },
+            Some(FlatValue::Other(value)) => write_value(value, options, writer),
+        }
+    }
+}
+
+// returned by the FlatFields serializers if a type doesn't
+// serialize into a flat map, we fall back to serializing
+// it into a serde_json::Value when that happens
+#[derive(Debug)]
+struct NotFlat;
+
+impl Display for NotFlat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "can't serialize into flat fields")
+    }
+}
+
+impl std::error::Error for NotFlat {}
+
+impl serde::ser::Error for NotFlat {
+    fn custom<M: Display>(_msg: M) -> Self {
+        NotFlat
+    }
+}
+
+// serializes the top level map or struct
+impl Serializer for &mut FlatFields {
+    type Ok = ();
+    type Error = NotFlat;
+    type SerializeSeq = Impossible<(), NotFlat>;
+    type SerializeTuple = Impossible<(), NotFlat>;
+    type SerializeTupleStruct = Impossible<(), NotFlat>;
+    type SerializeTupleVariant = Impossible<(), NotFlat>;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), NotFlat>;
+
+    fn serialize_bool(self, _v: bool) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_i8(self, _v: i8) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_i16(self, _v: i16) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_i32(self, _v: i32) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_i64(self, _v: i64) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_u8(self, _v: u8) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_u16(self, _v: u16) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_u32(self, _v: u32) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_u64(self, _v: u64) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_f32(self, _v: f32) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_f64(self, _v: f64) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_char(self, _v: char) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_str(self, _v: &str) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_none(self) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_unit(self) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, _variant: &'static str) -> Result<(), NotFlat> { Err(NotFlat) }
+    fn serialize_some<V: Serialize + ?Sized>(self, value: &V) -> Result<(), NotFlat> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_struct<V: Serialize + ?Sized>(self, _name: &'static str, value: &V) -> Result<(), NotFlat> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<V: Serialize + ?Sized>(self, _name: &'static str, _index: u32, _variant: &'static str, _value: &V) -> Result<(), NotFlat> {
+        Err(NotFlat)
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, NotFlat> { Err(NotFlat) }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, NotFlat> { Err(NotFlat) }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, NotFlat> { Err(NotFlat) }
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, NotFlat> { Err(NotFlat) }
+    fn serialize_map(self, len: Option<usize>) -> Result<Self, NotFlat> {
+        self.fields.reserve(len.unwrap_or_default());
+        Ok(self)
+    }
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self, NotFlat> {
+        self.fields.reserve(len);
+        Ok(self)
+    }
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, NotFlat> { Err(NotFlat) }
+}
+
+impl serde::ser::SerializeMap for &mut FlatFields {
+    type Ok = ();
+    type Error = NotFlat;
+
+    fn serialize_key<V: Serialize + ?Sized>(&mut self, key: &V) -> Result<(), NotFlat> {
+        match key.serialize(FlatValueSerializer { buffer: &mut self.buffer })? {
+            FlatValue::Str(range) => {
+                self.key = range;
+                Ok(())
+            },
+            // serde_json turns some non-string keys into
+            // strings but it's simpler to fall back for those
+            FlatValue::Other(_) => Err(NotFlat),
+        }
+    }
+    fn serialize_value<V: Serialize + ?Sized>(&mut self, value: &V) -> Result<(), NotFlat> {
+        let value = value.serialize(FlatValueSerializer { buffer: &mut self.buffer })?;
+        self.fields.push((self.key.clone(), value));
+        Ok(())
+    }
+    fn end(self) -> Result<(), NotFlat> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeStruct for &mut FlatFields {
+    type Ok = ();
+    type Error = NotFlat;
+
+    fn serialize_field<V: Serialize + ?Sized>(&mut self, key: &'static str, value: &V) -> Result<(), NotFlat> {
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(key.as_bytes());
+        let key = start..self.buffer.len();
+        let value = value.serialize(FlatValueSerializer { buffer: &mut self.buffer })?;
+        self.fields.push((key, value));
+        Ok(())
+    }
+    fn end(self) -> Result<(), NotFlat> {
+        Ok(())
+    }
+}
+
+// serializes the values of the top level map or struct,
+// numbers are serialized by serde_json so they come out
+// exactly the same as they would in a serde_json::Value
+struct FlatValueSerializer<'b> {
+    buffer: &'b mut Vec<u8>,
+}
+
+impl FlatValueSerializer<'_> {
+    fn str(self, v: &str) -> FlatValue {
+        let start = self.buffer.len();
+        self.buffer.extend_from_slice(v.as_bytes());
+        FlatValue::Str(start..self.buffer.len())
+    }
+}
+
+// a value serialized by serde_json's serializer,
+// only used for values which don't allocate
+fn json_value(value: Result<serde_json::Value, serde_json::Error>) -> Result<FlatValue, NotFlat> {
+    value.map(FlatValue::Other).map_err(|_| NotFlat)
+}
+
+impl Serializer for FlatValueSerializer<'_> {
+    type Ok = FlatValue;
+    type Error = NotFlat;
+    type SerializeSeq = Impossible<FlatValue, NotFlat>;
+    type SerializeTuple = Impossible<FlatValue, NotFlat>;
+    type SerializeTupleStruct = Impossible<FlatValue, NotFlat>;
+    type SerializeTupleVariant = Impossible<FlatValue, NotFlat>;
+    type SerializeMap = Impossible<FlatValue, NotFlat>;
+    type SerializeStruct = Impossible<FlatValue, NotFlat>;
+    type SerializeStructVariant = Impossible<FlatValue, NotFlat>;
+
+    fn serialize_bool(self, v: bool) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_bool(v)) }
+    fn serialize_i8(self, v: i8) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_i8(v)) }
+    fn serialize_i16(self, v: i16) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_i16(v)) }
+    fn serialize_i32(self, v: i32) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_i32(v)) }
+    fn serialize_i64(self, v: i64) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_i64(v)) }
+    fn serialize_i128(self, v: i128) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_i128(v)) }
+    fn serialize_u8(self, v: u8) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_u8(v)) }
+    fn serialize_u16(self, v: u16) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_u16(v)) }
+    fn serialize_u32(self, v: u32) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_u32(v)) }
+    fn serialize_u64(self, v: u64) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_u64(v)) }
+    fn serialize_u128(self, v: u128) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_u128(v)) }
+    fn serialize_f32(self, v: f32) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_f32(v)) }
+    fn serialize_f64(self, v: f64) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_f64(v)) }
+    fn serialize_char(self, v: char) -> Result<FlatValue, NotFlat> {
+        Ok(self.str(v.encode_utf8(&mut [0; 4])))
+    }
+    fn serialize_str(self, v: &str) -> Result<FlatValue, NotFlat> {
+        Ok(self.str(v))
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<FlatValue, NotFlat> { Err(NotFlat) }
+    fn serialize_none(self) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_none()) }
+    fn serialize_unit(self) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_unit()) }
+    fn serialize_unit_struct(self, name: &'static str) -> Result<FlatValue, NotFlat> { json_value(JsonSerializer.serialize_unit_struct(name)) }
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<FlatValue, NotFlat> {
+        Ok(self.str(variant))
+    }
+    fn serialize_some<V: Serialize + ?Sized>(self, value: &V) -> Result<FlatValue, NotFlat> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_struct<V: Serialize + ?Sized>(self, _name: &'static str, value: &V) -> Result<FlatValue, NotFlat> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<V: Serialize + ?Sized>(self, _name: &'static str, _index: u32, _variant: &'static str, _value: &V) -> Result<FlatValue, NotFlat> {
+        Err(NotFlat)
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, NotFlat> { Err(NotFlat) }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, NotFlat> { Err(NotFlat) }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, NotFlat> { Err(NotFlat) }
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, NotFlat> { Err(NotFlat) }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, NotFlat> { Err(NotFlat) }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, NotFlat> { Err(NotFlat) }
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, _variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, NotFlat> { Err(NotFlat) }
+}
+
+// returned when resolving a variable path that doesn't
+// point to anything
+static NULL: serde_json::Value = serde_json::Value::Null;
+
+// marks where an alias scope, e.g. for {{# posts as post }},
+// starts in a scope stack, it's followed by the alias as a
+// string and then the item it names, so items are borrowed
+// instead of cloned into an {"post": item} object, markers
+// are recognized by their address so data can't look like one
+static ALIAS_SCOPE: serde_json::Value = serde_json::Value::Null;
+
+// a serde_json::Map rendered as the outermost scope, below
+// every value in the scope stack, so the caller's map doesn't
+// have to be moved or cloned into a serde_json::Value first
+struct MapRoot<'a> {
+    map: &'a serde_json::Map<String, serde_json::Value>,
+    // the map as a value, only cloned if the template needs
+    // the root scope itself rather than one of its keys, e.g.
+    // for {{.}} or a section over the root
+    value: OnceCell<serde_json::Value>,
+}
+
+impl<'a> MapRoot<'a> {
+    fn new(map: &'a serde_json::Map<String, serde_json::Value>) -> Self {
+        MapRoot {
+            map,
+            value: OnceCell::new(),
+        }
+    }
+    fn value(&self) -> &serde_json::Value {
+        self.value.get_or_init(|| serde_json::Value::Object(self.map.clone()))
+    }
+    // looks up the first key of the path in the map, and
+    // the rest of the path in the value it points to
+    fn resolve<'r>(&'r self, path: &str) -> Option<&'r serde_json::Value> {
+        let (key, rest) = match path.find(['.', '[']) {
+            Some(idx) => (&path[..idx], Some(&path[idx + 1..])),
+            None => (path, None),
+        };
+        let value = self.map.get(key.strip_suffix(']').unwrap_or(key))?;
+        match rest {
+            Some(rest) => try_resolve_value(rest, &[value], None),
+            None => Some(value),
+        }
+    }
+}
+
+// given a variable path, e.g. variable.path, and a list of scopes,
+// e.g. serde_json::Values, it resolves the path to the specific
+// serde_json::Value it points to, or returns None if it cannot be
+// found, which is different from finding a serde_json::Value::Null
+//
+// the first key of the path is looked up starting from the innermost
+// scope: an object scope claims the key only if it has it, otherwise
+// we go to the parent scope, but an array scope claims any key that
+// parses as an index, even if it's out of bounds, so numeric keys
+// like "0" can match an object's "0" key or an array's 0th item
+// depending on which scope gets to it first
+//
+// paths starting with ../ skip the innermost scope, so they're
+// looked up starting from the scope enclosing it
+//
+// keys can also be in brackets, e.g. items[0].name or [0], which
+// are looked up the same as items.0.name or 0
+//
+// alias scopes, see ALIAS_SCOPE, only claim their alias as a key,
+// and the rest of the path is looked up in the item it names
+//
+// if there's a map root, see MapRoot, it's the outermost scope
+// and is only checked after every scope in the stack
+fn try_resolve_value<'a>(
+    path: &str,
+    scopes: &[&'a serde_json::Value],
+    root: Option<&'a MapRoot<'a>>,
+) -> Option<&'a serde_json::Value> {
+    use serde_json::Value;
+    if let Some(path) = path.strip_prefix("../") {
+        let parents = &scopes[..scopes.len() - innermost_scope_len(scopes)];
+        return match (scopes, parents, root) {
+            ([], _, _) | (_, [], None) => None,
+            _ => try_resolve_value(path, parents, root),
+        };
+    }
+    if path == "." {
+        return match scopes.last() {
+            Some(&value) => Some(value),
+            None => root.map(MapRoot::value),
+        };
+    }
+    let path = path.strip_prefix('[').unwrap_or(path);
+    let mut remaining = scopes;
+    'parent: while let Some((&value, parents)) = remaining.split_last() {
+        remaining = parents;
+        let mut resolved_value = value;
+        let mut keys = path
+            .split(['.', '['])
+            .map(|key| key.strip_suffix(']').unwrap_or(key));
+        let mut first_key = true;
+        if let Some(alias) = scope_alias(parents) {
+            remaining = &parents[..parents.len() - 2];
+            if keys.next() != Some(alias) {
+                // go to parent scope
+                continue 'parent;
+            }
+            first_key = false;
+        }
+        for key in keys {
+            match resolved_value {
+                Value::Array(array) => {
+                    // if we're in this branch assume
+                    // the key is an integer index
+                    let parsed_index = key.parse::<usize>();
+                    if let Ok(index) = parsed_index {
+                        let get_option = array.get(index);
+                        match get_option {
+                            Some(get) => {
+                                resolved_value = get;
+                            },
+                            None => {
+                                return None;
+                            },
+                        }
+                    } else {
+                        // key doesn't exist in this scope
+                        if first_key {
+                            // go to parent scope
+                            continue 'parent;
+                        }
+                        return None;
+                    }
+                },
+                Value::Object(object) => {
+                    let get_option = object.get(key);
+                    if let Some(get) = get_option {
+                        resolved_value = get;
+                    } else {
+                        // key doesn't exist in this scope
+                        if first_key {
+                            // go to parent scope
+                            continue 'parent;
+                        }
+                        return None;
                     }
+                },
+                // we got a null, string, or number
+                // none of which are keyed, return null
+                _ => {
+                    // key doesn't exist in this scope
+                    if first_key {
+                        // go to parent scope
+                        continue 'parent;
+                    }
+                    return None;
                 }
             }
+            first_key = false;
         }
-
-        Ok(HashMapLoader {
-            templates
-        })
+        return Some(resolved_value);
     }
+    // no scope has the first key
+    root.and_then(|root| root.resolve(path))
 }
 
-/// Stores all templates in memory.
-/// 
-/// ### Examples
-/// 
-/// Creating a [`HashMapLoader`] from a hashmap:
-/// 
-/// ```rust
-/// use moostache::HashMapLoader;
-/// use maplit::hashmap;
-/// 
-/// let loader = HashMapLoader::try_from(hashmap! {
-///    "greet" => "hello {{name}}!",
-/// }).unwrap();
-/// ```
-/// 
-/// Creating a [`HashMapLoader`] from a [`LoaderConfig`]:
-/// 
-/// ```rust
-/// use moostache::{LoaderConfig, HashMapLoader};
-/// 
-/// let loader = HashMapLoader::try_from(LoaderConfig::default()).unwrap();
-/// ```
-#[derive(Debug)]
-pub struct HashMapLoader<K: Borrow<str> + Eq + Hash = String, H: BuildHasher + Default = FnvBuildHasher> {
-    templates: HashMap<K, Template, H>,
+// the alias of the innermost scope, if it's an alias scope,
+// given the scopes enclosing it, see ALIAS_SCOPE
+#[inline]
+fn scope_alias<'a>(parents: &[&'a serde_json::Value]) -> Option<&'a str> {
+    match parents {
+        [.., marker, alias] if std::ptr::eq(*marker, &raw const ALIAS_SCOPE) => alias.as_str(),
+        _ => None,
+    }
 }
 
-impl<K: Borrow<str> + Eq + Hash, H: BuildHasher + Default> TemplateLoader<K> for HashMapLoader<K, H> {
-    type Output<'a> = &'a Template where K: 'a, H: 'a;
-    type Error = MoostacheError;
-    fn get(&self, name: &str) -> Result<&Template, MoostacheError> {
-        self.templates.get(name)
-            .ok_or_else(|| MoostacheError::LoaderErrorTemplateNotFound(name.into()))
-    }
-    fn insert(&mut self, name: K, value: Template) -> Option<Template> {
-        self.templates.insert(name, value)
+// how many values of the scope stack the innermost scope
+// takes up, see ALIAS_SCOPE
+#[inline]
+fn innermost_scope_len(scopes: &[&serde_json::Value]) -> usize {
+    match scopes.split_last() {
+        Some((_, parents)) if scope_alias(parents).is_some() => 3,
+        Some(_) => 1,
+        None => 0,
     }
-    fn remove(&mut self, name: &str) -> Option<Template> {
-        self.templates.remove(name)
+}
+
+// same as try_resolve_value but returns serde_json::Value::Null
+// if the path cannot be found
+#[inline]
+fn resolve_value<'a>(
+    path: &str,
+    scopes: &[&'a serde_json::Value],
+    root: Option<&'a MapRoot<'a>>,
+) -> &'a serde_json::Value {
+    try_resolve_value(path, scopes, root).unwrap_or(&NULL)
+}
+
+// values paths resolved to, for as long as the
+// scopes they were resolved with don't change
+type PathMemo<'p, 'a> = HashMap<&'p str, &'a serde_json::Value, FnvBuildHasher>;
+
+// resolves a path like resolve_value, remembering the
+// value if paths are memoized, see memoize_paths
+#[inline]
+fn resolve_memoized<'p, 'a>(
+    path: &'p str,
+    scopes: &[&'a serde_json::Value],
+    root: Option<&'a MapRoot<'a>>,
+    memo: &mut Option<PathMemo<'p, 'a>>,
+) -> &'a serde_json::Value {
+    match memo {
+        Some(memo) => memo.entry(path).or_insert_with(|| resolve_value(path, scopes, root)),
+        None => resolve_value(path, scopes, root),
     }
 }
 
-/// Lazily loads templates on-demand during render. Caches
-/// some compiled templates in memory.
-/// 
-/// ### Examples
-/// 
-/// Creating a [`FileLoader`] from a [`LoaderConfig`]:
-/// 
-/// ```rust
-/// use moostache::{LoaderConfig, FileLoader};
-/// 
-/// let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
-/// ```
-#[derive(Debug)]
-pub struct FileLoader<H: BuildHasher + Default = FnvBuildHasher> {
-    templates_directory: String,
-    templates_extension: String,
-    path_buf: RefCell<String>,
-    templates: RefCell<LruCache<String, Rc<Template>, H>>,
+// everything _render needs besides the fragments, scopes, and
+// writer, bundled together so it's easy to pass down through
+// recursive calls
+struct RenderContext<'a, 'i, 'r, T: ?Sized> {
+    loader: &'a T,
+    options: &'a RenderOptions,
+    // partials passed with the render, these are
+    // checked before falling back to the loader
+    inline_partials: Option<&'a HashMap<&'a str, &'a Template>>,
+    // checked before rendering each fragment, the
+    // render is stopped early once it's set to true
+    cancel: Option<&'a AtomicBool>,
+    // sections whose items are pulled from iterators,
+    // these are checked before resolving section values
+    iter_sections: Option<&'a IterSections<'i>>,
+    // names of partials rendered so far, if
+    // the caller wants to know which ones
+    loaded_partials: Option<&'a RefCell<HashSet<String>>>,
+    // how many times the content of each section was
+    // rendered so far, if the caller wants to know
+    section_counts: Option<&'a RefCell<HashMap<String, usize>>>,
+    // name of the template being rendered, empty if
+    // it's anonymous, see RenderOptions::relative_partials
+    template_name: &'a str,
+    // map rendered as the outermost scope, if the
+    // template is rendered with a serde_json::Map
+    root: Option<&'r MapRoot<'r>>,
 }
 
-impl TemplateLoader for FileLoader {
-    type Output<'a> = Rc<Template>;
-    type Error = MoostacheError;
-    fn get(&self, name: &str) -> Result<Rc<Template>, MoostacheError> {
-        let mut templates = self.templates.borrow_mut();
-        let template = templates.get(name);
-        if let Some(template) = template {
-            return Ok(Rc::clone(template));
+impl<'a, T: ?Sized> RenderContext<'a, '_, '_, T> {
+    // a context with nothing besides the loader and options
+    fn new(loader: &'a T, options: &'a RenderOptions) -> Self {
+        RenderContext {
+            loader,
+            options,
+            inline_partials: None,
+            cancel: None,
+            iter_sections: None,
+            loaded_partials: None,
+            section_counts: None,
+            template_name: "",
+            root: None,
         }
-        let mut path_buf = self.path_buf.borrow_mut();
-        path_buf.clear();
-        path_buf.push_str(&self.templates_directory);
-        path_buf.push_str(name);
-        path_buf.push_str(&self.templates_extension);
-        let source = fs::read_to_string::<&Path>(path_buf.as_ref())
-            .map_err(|err| MoostacheError::from_io(err, name.into()))?;
-        let template = Template::parse(source)
-            .map_err(|err| err.set_name(name))?;
-        let template = Rc::new(template);
-        templates.put(name.into(), Rc::clone(&template));
-        Ok(template)
     }
-    fn insert(&mut self, name: String, value: Template) -> Option<Template> {
-        let option = self.templates
-            .borrow_mut()
-            .put(name, Rc::new(value));
-        match option {
-            Some(template) => {
-                Rc::into_inner(template)
-            },
-            None => None,
+}
+
+impl<'r, T: ?Sized> RenderContext<'_, '_, 'r, T> {
+    // checks if the render was cancelled, a relaxed load
+    // is as cheap as a regular load so it's fine to call
+    // this before rendering every fragment
+    #[inline]
+    fn cancelled(&self) -> bool {
+        self.cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+    // checks if a section's value doesn't exist, sections
+    // whose items come from an iterator always exist
+    fn is_missing(&self, name: &str, scopes: &[&serde_json::Value]) -> bool {
+        self.iter_sections.is_none_or(|sections| sections.get(name).is_none())
+            && try_resolve_value(name, scopes, self.root).is_none()
+    }
+    // resolves a section's value if it's falsey, sections whose
+    // items come from an iterator are falsey once the iterator
+    // has no items left
+    fn falsey_value(&self, name: &str, scopes: &[&'r serde_json::Value]) -> Option<&'r serde_json::Value> {
+        let resolved_value = resolve_value(name, scopes, self.root);
+        let is_falsey = match self.iter_sections.and_then(|sections| sections.get(name)) {
+            Some(items) => items.borrow_mut().peek().is_none(),
+            None => !is_truthy(resolved_value),
+        };
+        is_falsey.then_some(resolved_value)
+    }
+    // records how many times a section's content was
+    // rendered, if the caller wants to know
+    fn rendered_section(&self, name: &str, renders: usize) {
+        if let Some(section_counts) = self.section_counts {
+            let mut section_counts = section_counts.borrow_mut();
+            match section_counts.get_mut(name) {
+                Some(count) => *count += renders,
+                None => {
+                    section_counts.insert(name.to_owned(), renders);
+                },
+            }
         }
     }
-    fn remove(&mut self, name: &str) -> Option<Template> {
-        let option = self.templates
-            .borrow_mut()
-            .pop(name);
-        match option {
-            Some(template) => {
-                Rc::into_inner(template)
-            },
-            None => None,
+    // records that a partial was rendered, if the
+    // caller wants to know which ones were
+    fn loaded_partial(&self, name: &str) {
+        if let Some(loaded_partials) = self.loaded_partials {
+            let mut loaded_partials = loaded_partials.borrow_mut();
+            if !loaded_partials.contains(name) {
+                loaded_partials.insert(name.to_owned());
+            }
         }
     }
 }
 
-impl TryFrom<LoaderConfig<'_>> for FileLoader {
-    type Error = MoostacheError;
-    fn try_from(config: LoaderConfig<'_>) -> Result<Self, MoostacheError> {
-        let mut dir: String = config.templates_directory.into();
-        if !dir.ends_with(MAIN_SEPARATOR_STR) {
-            dir.push_str(MAIN_SEPARATOR_STR);
+// renders fragments, and their section skips, with the
+// given context and values as the root scopes, the first
+// value is the innermost root scope, if the context has a
+// map root it's the outermost scope, below all the values,
+// and isn't in the scope stack, see try_resolve_value
+fn _render_root<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, '_, T>,
+    values: &[&serde_json::Value],
+    writer: &mut W,
+) -> Result<(), T::Error> {
+    let mut scopes = take_scratch_scopes();
+    scopes.extend(values.iter().rev());
+    let result = match ctx.options.max_output_bytes {
+        Some(remaining) => _render(
+            frags,
+            skips,
+            ctx,
+            &mut scopes,
+            &mut OutputLimitWriter { writer, remaining },
+        ),
+        None => _render(
+            frags,
+            skips,
+            ctx,
+            &mut scopes,
+            writer,
+        ),
+    };
+    return_scratch_scopes(scopes);
+    result
+}
+
+// the fragments and section skips nested within the
+// section which starts at the given indexes
+#[inline]
+fn section_content<'f, 'src>(
+    frags: &'f [Fragment<'src>],
+    skips: &'f [SectionSkip],
+    frag_idx: usize,
+    section_idx: usize,
+) -> (&'f [Fragment<'src>], &'f [SectionSkip]) {
+    let start_frag = frag_idx + 1;
+    let end_frag = start_frag + skips[section_idx].nested_fragments as usize;
+    let start_section = section_idx + 1;
+    let end_section = start_section + skips[section_idx].nested_sections as usize;
+    (&frags[start_frag..end_frag], &skips[start_section..end_section])
+}
+
+// this function iterates over a list of fragments and writes
+// each one out to the writer, will call itself recursively
+// to render sections and partials
+fn _render<'a, K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, 'a, T>,
+    scopes: &mut Vec<&'a serde_json::Value>,
+    writer: &mut W,
+) -> Result<(), T::Error> {
+    let mut frag_idx = 0;
+    let mut section_idx = 0;
+    // value of the last section, if it wasn't rendered,
+    // for the section's else to render with
+    let mut else_value = None;
+    // every fragment here is rendered with the same scopes,
+    // sections restore them when they're done, so resolved
+    // paths stay valid until this returns, see memoize_paths
+    let mut memo = ctx.options.memoize_paths.then(PathMemo::default);
+    while frag_idx < frags.len() {
+        if ctx.cancelled() {
+            return Err(MoostacheError::RenderErrorCancelled.into());
         }
-        let dir_path: &Path = dir.as_ref();
-        let mut ext: String = config.templates_extension.into();
-        if !ext.starts_with('.') {
-            ext.insert(0, '.');
+        let frag = &frags[frag_idx];
+        match frag {
+            // write literal to writer
+            Fragment::Literal(literal) => {
+                write_literal(literal, ctx.options, writer)?;
+                frag_idx += 1;
+            },
+            // write variable value to writer, escape any html chars
+            // if it's escaped, and pad or truncate it if it has a width,
+            // arrays and objects are written as json unless strict
+            Fragment::EscapedVariable(name) | Fragment::UnescapedVariable(name) => {
+                let escaped = matches!(frag, Fragment::EscapedVariable(_));
+                let (path, width) = split_width(name);
+                let resolved_value = resolve_memoized(path, scopes, ctx.root, &mut memo);
+                if ctx.options.strict_container_variables && (resolved_value.is_array() || resolved_value.is_object()) {
+                    return Err(MoostacheError::RenderErrorCannotStringifyContainer(path.to_owned()).into());
+                }
+                write_variable(resolved_value, width, escaped, ctx.loader.value_renderer(), ctx.options, writer)?;
+                frag_idx += 1;
+            },
+            // check if section value is truthy, if not skip it,
+            // otherwise render the section content, see _render_section
+            Fragment::Section(name) | Fragment::AliasSection(name) => {
+                let resolved_value = resolve_memoized(section_path(name), scopes, ctx.root, &mut memo);
+                let (section_frags, section_skips) = section_content(frags, skips, frag_idx, section_idx);
+                let aggregates = skips[section_idx].has_aggregates;
+                let renders = _render_section(name, resolved_value, aggregates, section_frags, section_skips, ctx, scopes, writer)?;
+                else_value = (renders == 0).then_some(resolved_value);
+                frag_idx += 1 + skips[section_idx].nested_fragments as usize;
+                section_idx += 1 + skips[section_idx].nested_sections as usize;
+            },
+            // render section content once per number of the range,
+            // see _render_range_section
+            Fragment::RangeSection(range) => {
+                let (section_frags, section_skips) = section_content(frags, skips, frag_idx, section_idx);
+                let renders = _render_range_section(range, section_frags, section_skips, ctx, scopes, writer)?;
+                else_value = (renders == 0).then_some(&NULL);
+                frag_idx += 1 + skips[section_idx].nested_fragments as usize;
+                section_idx += 1 + skips[section_idx].nested_sections as usize;
+            },
+            // check if invertedsection value is falsey, if missing
+            // section value doesn't exist, if equality section value
+            // matches its literal, if with section value isn't null,
+            // or if the section before an else wasn't rendered, if
+            // not skip it, otherwise render inner content once
+            Fragment::InvertedSection(_) |
+            Fragment::MissingSection(_) |
+            Fragment::CompareSection(_) |
+            Fragment::PluralSection(_) |
+            Fragment::WithSection(_) |
+            Fragment::SectionElse(_) => {
+                let falsey_value = match frag {
+                    Fragment::MissingSection(name) => ctx.is_missing(name, scopes).then_some(&NULL),
+                    Fragment::CompareSection(_) | Fragment::PluralSection(_) | Fragment::WithSection(_) => {
+                        let (resolved_value, matches) = helper_section(frag, scopes, ctx.root);
+                        else_value = (!matches).then_some(resolved_value);
+                        matches.then_some(resolved_value)
+                    },
+                    Fragment::InvertedSection(name) => ctx.falsey_value(name, scopes),
+                    _ => else_value.take(),
+                };
+                if let Some(value) = falsey_value {
+                    let (section_frags, section_skips) = section_content(frags, skips, frag_idx, section_idx);
+                    scopes.push(value);
+                    _render(section_frags, section_skips, ctx, scopes, writer)?;
+                    scopes.pop();
+                }
+                frag_idx += 1 + skips[section_idx].nested_fragments as usize;
+                section_idx += 1 + skips[section_idx].nested_sections as usize;
+            },
+            // render partial by loading its content via a TemplateLoader,
+            // unless it was passed inline with the render, the names
+            // of dynamic partials are resolved from the scopes first
+            Fragment::Partial(_) | Fragment::DynamicPartial(_) => {
+                let path = partial_name(frag, scopes, ctx.root)?;
+                _render_partial(path, ctx, scopes, writer)?;
+                frag_idx += 1;
+            },
         }
-        let max_size = NonZeroUsize::new(config.cache_size)
-            .ok_or(MoostacheError::ConfigErrorNonPositiveCacheSize)?;
+    }
+    Ok(())
+}
 
-        if !dir_path.is_dir() {
-            return Err(MoostacheError::ConfigErrorInvalidTemplatesDirectory(dir_path.into()));
+// renders the partial with the given name, preferring
+// partials passed inline with the render over the loader
+fn _render_partial<'a, K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    path: &str,
+    ctx: &RenderContext<'_, '_, 'a, T>,
+    scopes: &mut Vec<&'a serde_json::Value>,
+    writer: &mut W,
+) -> Result<(), T::Error> {
+    ctx.loaded_partial(path);
+    let loaded;
+    let mut relative = None;
+    let inline = ctx.inline_partials
+        .and_then(|partials| partials.get(path));
+    let template: &Template = if let Some(template) = inline {
+        template
+    } else {
+        (loaded, relative) = get_partial(ctx.loader, ctx.options.relative_partials, ctx.template_name, path)?;
+        &loaded
+    };
+    let ctx = RenderContext {
+        template_name: relative.as_deref().unwrap_or(path),
+        ..*ctx
+    };
+    _render(
+        template.frags(),
+        &template.skips,
+        &ctx,
+        scopes,
+        writer,
+    )
+}
+
+// gets a partial from the loader, if relative partials are
+// enabled it's looked up relative to the directory of the
+// template including it first, and if it's found there its
+// relative name is returned too, it's only looked up by its
+// name as-is if the relative template doesn't exist, so a
+// relative template which fails to load isn't hidden
+fn get_partial<'l, K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+    loader: &'l T,
+    relative_partials: bool,
+    template_name: &str,
+    path: &str,
+) -> Result<(T::Output<'l>, Option<String>), T::Error> {
+    if relative_partials {
+        if let Some(slash) = template_name.rfind('/') {
+            let relative = format!("{}{path}", &template_name[..=slash]);
+            match loader.get(&relative) {
+                Ok(template) => return Ok((template, Some(relative))),
+                Err(err) if !loader.is_not_found(&err) => return Err(err),
+                Err(_) => {},
+            }
         }
+    }
+    Ok((loader.get(path)?, None))
+}
 
-        let templates = RefCell::new(LruCache::with_hasher(max_size, BuildHasherDefault::default()));
+// name of the template a partial refers to, for dynamic
+// partials that's the value of their variable, which has
+// to be a non-empty string
+fn partial_name<'a>(
+    frag: &Fragment<'a>,
+    scopes: &[&'a serde_json::Value],
+    root: Option<&'a MapRoot<'a>>,
+) -> Result<&'a str, MoostacheError> {
+    match frag {
+        Fragment::DynamicPartial(name) => match resolve_value(name, scopes, root) {
+            serde_json::Value::String(path) if !path.is_empty() => Ok(path),
+            _ => Err(MoostacheError::RenderErrorInvalidDynamicPartial((*name).to_owned())),
+        },
+        _ => Ok(frag.as_str()),
+    }
+}
 
-        Ok(FileLoader {
-            templates_directory: dir,
-            templates_extension: ext,
-            path_buf: RefCell::new(String::new()),
-            templates,
-        })
+// creates an "implicit iterator" over the resolved value of a
+// section and renders the section content that many times, a
+// truthy non-array value renders exactly like a one-item array
+// containing it would, unless the section's items come from
+// an iterator or it has a wildcard, returns how many times
+// the content was rendered
+#[allow(clippy::too_many_arguments)]
+fn _render_section<'a, K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    name: &str,
+    value: &'a serde_json::Value,
+    aggregates: bool,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, 'a, T>,
+    scopes: &mut Vec<&'a serde_json::Value>,
+    writer: &mut W,
+) -> Result<usize, T::Error> {
+    let (name, alias) = split_alias(name);
+    let renders = if let Some(alias) = alias {
+        _render_alias_section(alias, value, frags, skips, ctx, scopes, writer)?
+    } else if name.ends_with(".*") {
+        _render_wildcard_section(value, frags, skips, ctx, scopes, writer)?
+    } else if let Some(items) = ctx.iter_sections.and_then(|sections| sections.get(name)) {
+        _render_iter_section(items, frags, skips, ctx, scopes, writer)?
+    } else {
+        _render_value_section(value, aggregates, frags, skips, ctx, scopes, writer)?
+    };
+    ctx.rendered_section(name, renders);
+    Ok(renders)
+}
+
+// renders section content once per item of an alias section,
+// see alias_items, returns how many times it was rendered
+fn _render_alias_section<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    alias: &str,
+    value: &serde_json::Value,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, '_, T>,
+    scopes: &[&serde_json::Value],
+    writer: &mut W,
+) -> Result<usize, T::Error> {
+    let items = alias_items(value);
+    if items.is_empty() {
+        return Ok(0);
+    }
+    // the alias only lives as long as this section, which is
+    // shorter than the references in scopes can live, so the
+    // section gets a copy of scopes, once rather than per item
+    let alias = serde_json::Value::from(alias);
+    let mut alias_scopes = Vec::with_capacity(scopes.len() + 3);
+    alias_scopes.extend_from_slice(scopes);
+    alias_scopes.extend([&ALIAS_SCOPE, &alias]);
+    for item in items {
+        alias_scopes.push(item);
+        _render(frags, skips, ctx, &mut alias_scopes, writer)?;
+        alias_scopes.pop();
+    }
+    Ok(items.len())
+}
+
+// renders section content once per number of a range section,
+// see range_bounds, with the number as the scope, the numbers
+// aren't collected up front so large ranges don't allocate,
+// returns how many times the content was rendered
+fn _render_range_section<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    range: &str,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, '_, T>,
+    scopes: &[&serde_json::Value],
+    writer: &mut W,
+) -> Result<usize, T::Error> {
+    // each number only lives for one iteration, which is shorter
+    // than the references in scopes can live, so every number
+    // gets a copy of scopes, reusing the same allocation
+    let mut recycled: Vec<&serde_json::Value> = Vec::with_capacity(scopes.len() + 1);
+    let mut renders = 0;
+    for number in range_bounds(range, scopes, ctx.root).0 {
+        let number = serde_json::Value::from(number);
+        let mut number_scopes = recycle_scopes(recycled);
+        number_scopes.extend_from_slice(scopes);
+        number_scopes.push(&number);
+        _render(frags, skips, ctx, &mut number_scopes, writer)?;
+        recycled = recycle_scopes(number_scopes);
+        renders += 1;
+    }
+    Ok(renders)
+}
+
+// splits the name of a section into its path and alias, if
+// it's an alias section, e.g. posts as post into posts and
+// post, the names of other sections never have whitespace
+#[inline]
+fn split_alias(name: &str) -> (&str, Option<&str>) {
+    match name.split_once(is_multispace) {
+        Some((path, rest)) => (path, rest.split_whitespace().last()),
+        None => (name, None),
+    }
+}
+
+// the path a section's value is resolved from, e.g.
+// posts for {{# posts.* }} or {{# posts as post }}
+#[inline]
+fn section_path(name: &str) -> &str {
+    let path = split_alias(name).0;
+    path.strip_suffix(".*").unwrap_or(path)
+}
+
+// the items the content of an alias section is rendered with,
+// each item of its value if it's an array, or the value itself
+// if it's any other truthy value
+fn alias_items(value: &serde_json::Value) -> &[serde_json::Value] {
+    use serde_json::Value;
+    match value {
+        _ if !is_truthy(value) => &[],
+        Value::Array(array) => array.as_slice(),
+        _ => std::slice::from_ref(value),
+    }
+}
+
+// renders section content once per item of the section's
+// value if it's truthy, returns how many times it was rendered,
+// aggregates is if the section content has any aggregates
+fn _render_value_section<'a, K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    value: &'a serde_json::Value,
+    aggregates: bool,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, 'a, T>,
+    scopes: &mut Vec<&'a serde_json::Value>,
+    writer: &mut W,
+) -> Result<usize, T::Error> {
+    use serde_json::Value;
+    if let (Value::Number(number), true) = (value, ctx.options.repeat_number_sections) {
+        return _render_repeat_section(repeat_count(number), frags, skips, ctx, scopes, writer);
+    }
+    if !is_truthy(value) {
+        return Ok(0);
+    }
+    if ctx.options.scalar_sections_keep_scope && !value.is_array() && !value.is_object() {
+        _render(frags, skips, ctx, scopes, writer)?;
+        return Ok(1);
+    }
+    if let (Value::Array(array), false, true) = (value, ctx.options.array_sections_as_scopes, aggregates) {
+        if let Some(aggregates) = section_aggregates(frags, array) {
+            return _render_aggregate_section(array, &aggregates, frags, skips, ctx, scopes, writer);
+        }
+    }
+    let values = match value {
+        Value::Array(array) if !ctx.options.array_sections_as_scopes => array.as_slice(),
+        _ => std::slice::from_ref(value),
+    };
+    for value in values {
+        scopes.push(value);
+        _render(frags, skips, ctx, scopes, writer)?;
+        scopes.pop();
     }
+    Ok(values.len())
 }
 
-impl<K: Borrow<str> + Eq + Hash, V: Into<Cow<'static, str>>> TryFrom<HashMap<K, V>> for HashMapLoader<K> {
-    type Error = MoostacheError;
-    fn try_from(map: HashMap<K, V>) -> Result<Self, Self::Error> {
-        let templates = map
-            .into_iter()
-            .map(|(key, value)| {
-                match parse(value) {
-                    Ok(template) => Ok((key, template)),
-                    Err(err) => Err(MoostacheError::from_internal(err, key.borrow().to_owned())),
-                }
-            })
-            .collect::<Result<_, _>>();
-        templates.map(|templates| HashMapLoader {
-            templates,
-        })
+// computes every aggregate, e.g. {{ @sum:price }}, within
+// the content of a section over an array, only called for
+// sections which were marked as having aggregates when they
+// were parsed, see SectionSkip
+fn section_aggregates(frags: &[Fragment<'_>], array: &[serde_json::Value]) -> Option<serde_json::Value> {
+    let mut aggregates = serde_json::Map::new();
+    for frag in frags {
+        if let Fragment::EscapedVariable(name) | Fragment::UnescapedVariable(name) = frag {
+            let path = split_width(name).0;
+            if let Some(value) = aggregate(path, array) {
+                aggregates.insert(path.to_owned(), value);
+            }
+        }
     }
+    (!aggregates.is_empty()).then_some(serde_json::Value::Object(aggregates))
 }
 
-impl TemplateLoader<&'static str> for () {
-    type Output<'a> = &'a Template;
-    type Error = MoostacheError;
-    fn get(&self, name: &str) -> Result<&Template, MoostacheError> {
-        Err(MoostacheError::LoaderErrorTemplateNotFound(name.into()))
+// if a variable path is an aggregate, see aggregate
+fn is_aggregate(path: &str) -> bool {
+    path == "@count" || path
+        .strip_prefix('@')
+        .and_then(|path| path.split_once(':'))
+        .is_some_and(|(func, _)| matches!(func, "sum" | "min" | "max"))
+}
+
+// computes an aggregate over the items of an array, @count
+// is how many items there are, and @sum:field, @min:field, and
+// @max:field are over the numbers in the field of every item,
+// items where the field isn't a number are skipped, returns
+// None if path isn't an aggregate
+fn aggregate(path: &str, array: &[serde_json::Value]) -> Option<serde_json::Value> {
+    use serde_json::Value;
+    if path == "@count" {
+        return Some(array.len().into());
     }
-    fn insert(&mut self, _: &'static str, _: Template) -> Option<Template> {
-        None
+    let (func, field) = path.strip_prefix('@')?.split_once(':')?;
+    let mut numbers = array.iter()
+        .filter_map(|item| item.get(field).and_then(Value::as_number));
+    match func {
+        "sum" => {
+            // integers are summed as integers unless they overflow
+            let int_sum = numbers.clone()
+                .try_fold(0i64, |sum, number| sum.checked_add(number.as_i64()?));
+            Some(match int_sum {
+                Some(sum) => sum.into(),
+                None => numbers.filter_map(serde_json::Number::as_f64).sum::<f64>().into(),
+            })
+        },
+        "min" | "max" => {
+            let first = numbers.next()?;
+            let extreme = numbers.fold(first, |extreme, number| {
+                let (a, b) = (number.as_f64(), extreme.as_f64());
+                if (func == "min" && a < b) || (func == "max" && a > b) {
+                    number
+                } else {
+                    extreme
+                }
+            });
+            Some(Value::Number(extreme.clone()))
+        },
+        _ => None,
     }
-    fn remove(&mut self, _: &str) -> Option<Template> {
-        None
+}
+
+// renders section content once per item of an array, each
+// time with the section's aggregates in scope underneath the
+// item, see section_aggregates, returns how many items there were
+#[cold]
+fn _render_aggregate_section<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    array: &[serde_json::Value],
+    aggregates: &serde_json::Value,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, '_, T>,
+    scopes: &[&serde_json::Value],
+    writer: &mut W,
+) -> Result<usize, T::Error> {
+    // the aggregates scope doesn't live as long as the
+    // references in scopes can, so every item gets a copy
+    // of scopes, reusing the same allocation
+    let mut recycled: Vec<&serde_json::Value> = Vec::with_capacity(scopes.len() + 2);
+    for item in array {
+        let mut item_scopes = recycle_scopes(recycled);
+        item_scopes.extend_from_slice(scopes);
+        item_scopes.push(aggregates);
+        item_scopes.push(item);
+        _render(frags, skips, ctx, &mut item_scopes, writer)?;
+        recycled = recycle_scopes(item_scopes);
     }
+    Ok(array.len())
 }
 
-///////////////
-// RENDERING //
-///////////////
+// renders section content once for every value of an
+// object, for sections with a wildcard, e.g. {{# object.* }},
+// returns how many values the object had
+fn _render_wildcard_section<'a, K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    value: &'a serde_json::Value,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, 'a, T>,
+    scopes: &mut Vec<&'a serde_json::Value>,
+    writer: &mut W,
+) -> Result<usize, T::Error> {
+    let serde_json::Value::Object(object) = value else {
+        return Ok(0);
+    };
+    for value in object.values() {
+        scopes.push(value);
+        _render(frags, skips, ctx, scopes, writer)?;
+        scopes.pop();
+    }
+    Ok(object.len())
+}
 
-// checks if serde_json::Value is truthy
-fn is_truthy(value: &serde_json::Value) -> bool {
-    use serde_json::Value;
-    match value {
-        Value::Null => false,
-        Value::Bool(b) => *b,
-        Value::Number(_) => value != &json!(0),
-        Value::String(string) => !string.is_empty(),
-        Value::Array(array) => !array.is_empty(),
-        Value::Object(object) => !object.is_empty(),
+// how many times a section over a number repeats its
+// content, see RenderOptions::repeat_number_sections
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn repeat_count(number: &serde_json::Number) -> u64 {
+    if let Some(count) = number.as_u64() {
+        return count;
     }
+    // float to int casts saturate, and negative numbers
+    // and NaN become 0
+    number.as_f64().map_or(0, |float| float.floor() as u64)
 }
 
-// wraps a Write type and escapes HTML chars
-// before writing to the inner Write
-struct EscapeHtml<'a, W: Write>(&'a mut W);
+// renders section content count times, each time with
+// an {"@index": index} scope, returns count
+fn _render_repeat_section<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    count: u64,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, '_, T>,
+    scopes: &[&serde_json::Value],
+    writer: &mut W,
+) -> Result<usize, T::Error> {
+    // the index scope is changed between repeats, which
+    // the references in scopes can't outlive, so every repeat
+    // gets a copy of scopes, reusing the same allocation
+    let mut index = json!({"@index": 0});
+    let mut recycled: Vec<&serde_json::Value> = Vec::with_capacity(scopes.len() + 1);
+    for i in 0..count {
+        index["@index"] = i.into();
+        let mut repeat_scopes = recycle_scopes(recycled);
+        repeat_scopes.extend_from_slice(scopes);
+        repeat_scopes.push(&index);
+        _render(frags, skips, ctx, &mut repeat_scopes, writer)?;
+        recycled = recycle_scopes(repeat_scopes);
+    }
+    Ok(usize::try_from(count).unwrap_or(usize::MAX))
+}
 
-// as recommended by OWASP the chars "&", "<",
-// ">", "\"", and "'" are escaped
-impl<W: Write> Write for EscapeHtml<'_, W> {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let written = buf.len();
-        self.write_all(buf)
-            .map(|()| written)
+// renders section content once per item pulled from an
+// iterator, see IterSections, returns how many items there were
+fn _render_iter_section<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+    items: &RefCell<Peekable<SectionItems<'_>>>,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    ctx: &RenderContext<'_, '_, '_, T>,
+    scopes: &[&serde_json::Value],
+    writer: &mut W,
+) -> Result<usize, T::Error> {
+    // items only live for one iteration, which is shorter
+    // than the references in scopes can live, so every item
+    // gets a copy of scopes, reusing the same allocation
+    let mut recycled: Vec<&serde_json::Value> = Vec::with_capacity(scopes.len() + 1);
+    // don't hold the borrow while rendering since the
+    // section content may refer to the same section
+    let mut renders = 0;
+    while let Some(item) = items.borrow_mut().next() {
+        renders += 1;
+        let value = item.map_err(|err| MoostacheError::SerializationError(err.to_string()))?;
+        let mut item_scopes = recycle_scopes(recycled);
+        item_scopes.extend_from_slice(scopes);
+        item_scopes.push(&value);
+        _render(frags, skips, ctx, &mut item_scopes, writer)?;
+        recycled = recycle_scopes(item_scopes);
     }
-    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        let mut start = 0;
-        let mut end = 0;
-        for byte in buf {
-            match byte {
-                b'&' => {
-                    if start < end {
-                        self.0.write_all(&buf[start..end])?;
-                    }
-                    end += 1;
-                    start = end;
-                    self.0.write_all(b"&amp;")?;
-                },
-                b'<' => {
-                    if start < end {
-                        self.0.write_all(&buf[start..end])?;
-                    }
-                    end += 1;
-                    start = end;
-                    self.0.write_all(b"&lt;")?;
-                },
-                b'>' => {
-                    if start < end {
-                        self.0.write_all(&buf[start..end])?;
-                    }
-                    end += 1;
-                    start = end;
-                    self.0.write_all(b"&gt;")?;
-                },
-                b'"' => {
-                    if start < end {
-                        self.0.write_all(&buf[start..end])?;
-                    }
-                    end += 1;
-                    start = end;
-                    self.0.write_all(b"&quot;")?;
-                },
-                b'\'' => {
-                    if start < end {
-                        self.0.write_all(&buf[start..end])?;
-                    }
-                    end += 1;
-                    start = end;
-                    self.0.write_all(b"&#x27;")?;
-                },
-                _ => {
-                    end += 1;
-                },
-            }
-        }
-        if start < end {
-            self.0.write_all(&buf[start..end])?;
-        }
-        Ok(())
+    Ok(renders)
+}
+
+// same as _missing_paths but for the content of an alias
+// section, returns the section's value if its else, if any,
+// would be rendered
+fn _missing_alias_paths<'a, K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+    aliased: &str,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    loader: &T,
+    scopes: &[&'a serde_json::Value],
+    missing: &mut Vec<String>,
+) -> Result<Option<&'a serde_json::Value>, T::Error> {
+    let (path, alias) = split_alias(aliased);
+    let resolved_value = resolve_value(path, scopes, None);
+    if resolved_value.is_null() {
+        record_missing_path(path, missing);
     }
-    fn flush(&mut self) -> io::Result<()> {
-        self.0.flush()
+    let items = alias_items(resolved_value);
+    if items.is_empty() {
+        return Ok(Some(resolved_value));
+    }
+    let alias = serde_json::Value::from(alias.unwrap_or_default());
+    let mut alias_scopes = Vec::with_capacity(scopes.len() + 3);
+    alias_scopes.extend_from_slice(scopes);
+    alias_scopes.extend([&ALIAS_SCOPE, &alias]);
+    for item in items {
+        alias_scopes.push(item);
+        _missing_paths(frags, skips, loader, &mut alias_scopes, missing)?;
+        alias_scopes.pop();
     }
+    Ok(None)
 }
 
-// serializes a serde_json::Value
-fn write_value<W: Write>(
+// same as _missing_paths but for the content of a range
+// section, the numbers of a range don't change which paths
+// are missing so its content is walked only once, returns
+// null if its else, if any, would be rendered
+fn _missing_range_paths<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+    range: &str,
+    frags: &[Fragment<'_>],
+    skips: &[SectionSkip],
+    loader: &T,
+    scopes: &[&serde_json::Value],
+    missing: &mut Vec<String>,
+) -> Result<Option<&'static serde_json::Value>, T::Error> {
+    let (numbers, bounds) = range_bounds(range, scopes, None);
+    for bound in bounds {
+        record_missing_path(bound, missing);
+    }
+    if numbers.is_empty() {
+        return Ok(Some(&NULL));
+    }
+    let number = serde_json::Value::from(numbers.start);
+    let mut scopes = scopes.to_vec();
+    scopes.push(&number);
+    _missing_paths(frags, skips, loader, &mut scopes, missing)?;
+    Ok(None)
+}
+
+// records the first key of every path referenced by a list of
+// fragments and the partials they include, partials are only
+// walked once, and dynamic partials whose names don't resolve
+// can't be walked at all, see Template::unused_keys
+fn _referenced_keys<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
+    frags: &[Fragment<'_>],
+    loader: &T,
     value: &serde_json::Value,
-    writer: &mut W,
-) -> Result<(), MoostacheError> {
-    use serde_json::Value;
-    match value {
-        Value::Null => {
-            // serde_json serializes null as
-            // "null" but we want this to be
-            // an empty string instead
-        },
-        Value::String(string) => {
-            // serde_json serializes strings
-            // wrapped with quotes, but we
-            // want to write them without quotes
-            writer.write_all(string.as_bytes())
-                .map_err(|err| MoostacheError::from_io(err, String::new()))?;
-        },
-        // let serde_json handle the rest
-        _ => {
-            let mut serializer = serde_json::Serializer::new(writer);
-            value.serialize(&mut serializer)
-                .map_err(|_| MoostacheError::SerializationError)?;
-        },
+    partials: &mut HashSet<String>,
+    referenced: &mut HashSet<String>,
+) -> Result<(), T::Error> {
+    for frag in frags {
+        let paths = match frag {
+            Fragment::Literal(_) | Fragment::SectionElse(_) | Fragment::Partial(_) => [None, None],
+            Fragment::EscapedVariable(name) | Fragment::UnescapedVariable(name) => [Some(split_width(name).0), None],
+            Fragment::Section(name) |
+            Fragment::InvertedSection(name) |
+            Fragment::MissingSection(name) |
+            Fragment::AliasSection(name) => [Some(section_path(name)), None],
+            Fragment::CompareSection(comparison) => [Some(split_comparison(comparison).1), None],
+            Fragment::PluralSection(plural) => [Some(plural_path(plural)), None],
+            Fragment::WithSection(with) => [Some(with_path(with)), None],
+            // integer bounds aren't paths
+            Fragment::RangeSection(range) => {
+                let (start, end) = split_range(range);
+                [start, end].map(|bound| bound.parse::<i64>().is_err().then_some(bound))
+            },
+            Fragment::DynamicPartial(name) => [Some(*name), None],
+        };
+        referenced.extend(paths.into_iter().flatten().filter_map(first_key).map(str::to_owned));
+        if let Fragment::Partial(_) | Fragment::DynamicPartial(_) = frag {
+            let Ok(name) = partial_name(frag, &[value], None) else {
+                continue;
+            };
+            if partials.insert(name.to_owned()) {
+                let template = loader.get(name)?;
+                _referenced_keys(template.frags(), loader, value, partials, referenced)?;
+            }
+        }
     }
     Ok(())
 }
 
-// given a variable path, e.g. variable.path, and a list of scopes,
-// e.g. serde_json::Values, it resolves the path to the specific
-// serde_json::Value it points to, or returns serde_json::Value::Null
-// if it cannot be found
-fn resolve_value<'a>(path: &str, scopes: &[&'a serde_json::Value]) -> &'a serde_json::Value {
-    use serde_json::Value;
-    if path == "." {
-        return scopes[scopes.len() - 1];
+// the key a path starts with, e.g. items for ../items[0].name,
+// or None if it doesn't start with one, e.g. . or @count
+fn first_key(path: &str) -> Option<&str> {
+    let mut path = path;
+    while let Some(rest) = path.strip_prefix("../") {
+        path = rest;
     }
-    let mut resolved_value = &Value::Null;
-    'parent: for value in scopes.iter().rev() {
-        resolved_value = *value;
-        for (idx, key) in path.split('.').enumerate() {
-            match resolved_value {
-                Value::Array(array) => {
-                    // if we're in this branch assume
-                    // the key is an integer index
-                    let parsed_index = key.parse::<usize>();
-                    if let Ok(index) = parsed_index {
-                        let get_option = array.get(index);
-                        match get_option {
-                            Some(get) => {
-                                resolved_value = get;
-                            },
-                            None => {
-                                return &Value::Null;
-                            },
-                        }
-                    } else {
-                        // key doesn't exist in this scope
-                        if idx == 0 {
-                            // go to parent scope
-                            continue 'parent;
-                        }
-                        return &Value::Null;
-                    }
-                },
-                Value::Object(object) => {
-                    let get_option = object.get(key);
-                    if let Some(get) = get_option {
-                        resolved_value = get;
-                    } else {
-                        // key doesn't exist in this scope
-                        if idx == 0 {
-                            // go to parent scope
-                            continue 'parent;
-                        }
-                        return &Value::Null;
-                    }
-                },
-                // we got a null, string, or number
-                // none of which are keyed, return null
-                _ => {
-                    // key doesn't exist in this scope
-                    if idx == 0 {
-                        // go to parent scope
-                        continue 'parent;
-                    }
-                    return &Value::Null;
-                }
-            }
-        }
-        return resolved_value;
+    let path = path.strip_prefix('[').unwrap_or(path);
+    let key = path.split(['.', '[', ']']).next().unwrap_or_default();
+    (!key.is_empty() && !key.starts_with('@')).then_some(key)
+}
+
+// records a path which resolved to null, unless it was
+// already recorded
+fn record_missing_path(path: &str, missing: &mut Vec<String>) {
+    if !missing.iter().any(|recorded| recorded == path) {
+        missing.push(path.to_owned());
     }
-    resolved_value
 }
 
-// this function iterates over a list of fragments and writes
-// each one out to the writer, will call itself recursively
-// to render sections and partials
-fn _render<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
+// walks a list of fragments the same way _render does, but
+// instead of writing anything it records which variable and
+// section paths resolved to null
+fn _missing_paths<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized>(
     frags: &[Fragment<'_>],
     skips: &[SectionSkip],
     loader: &T,
     scopes: &mut Vec<&serde_json::Value>,
-    writer: &mut W,
+    missing: &mut Vec<String>,
 ) -> Result<(), T::Error> {
     use serde_json::Value;
     let mut frag_idx = 0;
     let mut section_idx = 0;
+    let mut else_value = None;
     while frag_idx < frags.len() {
         let frag = &frags[frag_idx];
         match frag {
-            // write literal to writer
-            Fragment::Literal(literal) => {
-                writer.write_all(literal.as_bytes())
-                    .map_err(|err| MoostacheError::from_io(err, String::new()))?;
+            Fragment::Literal(_) => {
                 frag_idx += 1;
             },
-            // write variable value to writer, escape any html chars
-            Fragment::EscapedVariable(name) => {
-                let resolved_value = resolve_value(name, scopes);
-                write_value(resolved_value, &mut EscapeHtml(writer))?;
+            // aggregates, e.g. @sum:price, aren't data so they
+            // aren't reported, @min and @max can be null anyway
+            Fragment::EscapedVariable(name) | Fragment::UnescapedVariable(name) => {
+                let path = split_width(name).0;
+                if !path.starts_with('@') && resolve_value(path, scopes, None).is_null() {
+                    record_missing_path(path, missing);
+                }
                 frag_idx += 1;
             },
-            // write variable value to writer
-            Fragment::UnescapedVariable(name) => {
-                let resolved_value = resolve_value(name, scopes);
-                write_value(resolved_value, writer)?;
-                frag_idx += 1;
+            Fragment::AliasSection(aliased) => {
+                let (section_frags, section_skips) = section_content(frags, skips, frag_idx, section_idx);
+                else_value = _missing_alias_paths(aliased, section_frags, section_skips, loader, scopes, missing)?;
+                frag_idx += 1 + skips[section_idx].nested_fragments as usize;
+                section_idx += 1 + skips[section_idx].nested_sections as usize;
             },
-            // check if section value is truthy, if not skip it,
-            // otherwise create an "implicit iterator" over
-            // the resolved value and render the section content
-            // that many times
-            Fragment::Section(name) => {
-                let resolved_value = resolve_value(name, scopes);
-                let start_frag = frag_idx + 1;
-                let end_frag = start_frag + skips[section_idx].nested_fragments as usize;
-                let start_section = section_idx + 1;
-                let end_section = start_section + skips[section_idx].nested_sections as usize;
-                if is_truthy(resolved_value) {
-                    if let Value::Array(array) = resolved_value {
-                        for value in array {
-                            scopes.push(value);
-                            _render(
-                                &frags[start_frag..end_frag],
-                                &skips[start_section..end_section],
-                                loader,
-                                scopes,
-                                writer,
-                            )?;
-                            scopes.pop();
-                        }
-                    } else {
-                        scopes.push(resolved_value);
-                        _render(
-                            &frags[start_frag..end_frag],
-                            &skips[start_section..end_section],
-                            loader,
-                            scopes,
-                            writer,
-                        )?;
+            Fragment::RangeSection(range) => {
+                let (section_frags, section_skips) = section_content(frags, skips, frag_idx, section_idx);
+                else_value = _missing_range_paths(range, section_frags, section_skips, loader, scopes, missing)?;
+                frag_idx += 1 + skips[section_idx].nested_fragments as usize;
+                section_idx += 1 + skips[section_idx].nested_sections as usize;
+            },
+            Fragment::Section(name) | Fragment::InvertedSection(name) => {
+                let wildcard = name.strip_suffix(".*");
+                let resolved_value = resolve_value(wildcard.unwrap_or(name), scopes, None);
+                let inverted = matches!(frag, Fragment::InvertedSection(_));
+                if !inverted && resolved_value.is_null() {
+                    record_missing_path(wildcard.unwrap_or(name), missing);
+                }
+                let (section_frags, section_skips) = section_content(frags, skips, frag_idx, section_idx);
+                let rendered = match (resolved_value, wildcard) {
+                    (Value::Object(object), Some(_)) => !object.is_empty(),
+                    (_, Some(_)) => false,
+                    _ => is_truthy(resolved_value) != inverted,
+                };
+                if rendered {
+                    let values = match resolved_value {
+                        Value::Object(object) if wildcard.is_some() => object.values().collect(),
+                        Value::Array(array) if !inverted => array.iter().collect(),
+                        _ => vec![resolved_value],
+                    };
+                    for value in values {
+                        scopes.push(value);
+                        _missing_paths(section_frags, section_skips, loader, scopes, missing)?;
                         scopes.pop();
                     }
                 }
+                else_value = (!inverted && !rendered).then_some(resolved_value);
                 frag_idx += 1 + skips[section_idx].nested_fragments as usize;
                 section_idx += 1 + skips[section_idx].nested_sections as usize;
             },
-            // check if invertedsection value is falsey, if not
-            // skip it, otherwise render inner content
-            Fragment::InvertedSection(name) => {
-                let resolved_value = resolve_value(name, scopes);
-                let start_frag = frag_idx + 1;
-                let end_frag = start_frag + skips[section_idx].nested_fragments as usize;
-                let start_section = section_idx + 1;
-                let end_section = start_section + skips[section_idx].nested_sections as usize;
-                if !is_truthy(resolved_value) {
-                    scopes.push(resolved_value);
-                    _render(
-                        &frags[start_frag..end_frag],
-                        &skips[start_section..end_section],
-                        loader,
-                        scopes,
-                        writer,
-                    )?;
+            // missing sections are expected to be missing
+            // sometimes, so like inverted sections they
+            // aren't reported
+            Fragment::MissingSection(_) | Fragment::CompareSection(_) | Fragment::PluralSection(_) | Fragment::WithSection(_) | Fragment::SectionElse(_) => {
+                let value = match frag {
+                    Fragment::MissingSection(name) => try_resolve_value(name, scopes, None).is_none().then_some(&NULL),
+                    Fragment::CompareSection(_) | Fragment::PluralSection(_) | Fragment::WithSection(_) => {
+                        let (path, literal) = helper_section_path(frag);
+                        let (resolved_value, matches) = helper_section(frag, scopes, None);
+                        if resolved_value.is_null() && literal != "null" {
+                            record_missing_path(path, missing);
+                        }
+                        else_value = (!matches).then_some(resolved_value);
+                        matches.then_some(resolved_value)
+                    },
+                    _ => else_value.take(),
+                };
+                if let Some(value) = value {
+                    let (section_frags, section_skips) = section_content(frags, skips, frag_idx, section_idx);
+                    scopes.push(value);
+                    _missing_paths(section_frags, section_skips, loader, scopes, missing)?;
                     scopes.pop();
                 }
                 frag_idx += 1 + skips[section_idx].nested_fragments as usize;
                 section_idx += 1 + skips[section_idx].nested_sections as usize;
             },
-            // render partial by loading its content via a TemplateLoader
-            Fragment::Partial(path) => {
-                let template = loader.get(path)?;
-                _render(
-                    &template.fragments.get().0,
-                    &template.skips,
-                    loader,
-                    scopes,
-                    writer,
-                )?;
+            Fragment::DynamicPartial(name) if resolve_value(name, scopes, None).is_null() => {
+                record_missing_path(name, missing);
+                frag_idx += 1;
+            },
+            Fragment::Partial(_) | Fragment::DynamicPartial(_) => {
+                let template = loader.get(partial_name(frag, scopes, None)?)?;
+                _missing_paths(template.frags(), &template.skips, loader, scopes, missing)?;
                 frag_idx += 1;
             },
         }
@@ -1429,8 +8195,12 @@ fn _render<K: Borrow<str> + Eq + Hash, T: TemplateLoader<K> + ?Sized, W: Write>(
 /// 
 /// The [`String`] in almost every enum variant
 /// is the name of the template which produced
-/// the error.
+/// the error, see [`MoostacheError::template_name`].
+/// 
+/// New variants may be added in future versions,
+/// so matching on this enum requires a wildcard arm.
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum MoostacheError {
     /// Reading from the filesystem, or writing
     /// to a writer, failed for whatever reason.
@@ -1463,9 +8233,33 @@ pub enum MoostacheError {
     ParseErrorInvalidInvertedSectionStartTag(String),
     /// Some partial tag, e.g. {{> partial }}, is invalid.
     ParseErrorInvalidPartialTag(String),
+    /// Some raw block, e.g. {{% raw %}} ... {{% endraw %}},
+    /// is invalid or was never closed.
+    ParseErrorInvalidRawBlock(String),
+    /// Some section else tag, e.g. {{|}}, is invalid.
+    ParseErrorInvalidElseTag(String),
+    /// Some section else tag isn't directly within a
+    /// section, e.g. {{# section }} ... {{|}} ... {{/ section }}.
+    /// Else tags can't be used in inverted sections.
+    ParseErrorElseOutsideSection(String),
+    /// Some section has more than one else tag.
+    ParseErrorDuplicateElse(String),
+    /// Some section has more than 65,535 sections nested
+    /// within it, which is the most a section can have.
+    ParseErrorTooManySections(String),
+    /// Some section has more than 65,535 fragments, e.g.
+    /// literals, variables, or sections, nested within it,
+    /// which is the most a section can have.
+    ParseErrorTooManyFragments(String),
+    /// Some delimiter in [`ParseOptions::delimiters`] is empty
+    /// or has whitespace in it.
+    ParseErrorInvalidDelimiters(String),
     /// Loader tried to load a template but couldn't find it by
     /// its name.
     LoaderErrorTemplateNotFound(String),
+    /// Some partial includes itself, directly or via other
+    /// partials, so it can't be inlined. See [`Template::flatten`].
+    LoaderErrorCyclicPartial(String),
     /// [`FileLoader`] tried to load a template but its filepath wasn't
     /// valid utf-8.
     LoaderErrorNonUtf8FilePath(PathBuf),
@@ -1479,11 +8273,149 @@ pub enum MoostacheError {
     /// increase your cache size or switch to [`FileLoader`].
     ConfigErrorTooManyTemplates,
     /// moostache uses [`serde_json`] internally, and if [`serde_json`] fails
-    /// to serialize anything for any reason this error will be returned.
-    SerializationError,
+    /// to serialize anything for any reason this error will be returned,
+    /// along with the message of the [`serde_json::Error`], e.g. which
+    /// key of a map couldn't be serialized.
+    SerializationError(String),
+    /// The writer passed to a render returned an
+    /// [`ErrorKind::WouldBlock`](std::io::ErrorKind::WouldBlock) error,
+    /// which usually means it's non-blocking and isn't ready to be written
+    /// to yet. Some of the output may have already been written before
+    /// this happened, and renders can't be resumed, so the render has to
+    /// be retried from the start, e.g. into a buffer.
+    RenderErrorWouldBlock,
+    /// The render was cancelled. See [`Template::render_with_cancel`].
+    RenderErrorCancelled,
+    /// The render would have written more bytes than allowed by
+    /// [`RenderOptions::max_output_bytes`] so it was aborted.
+    RenderErrorOutputTooLarge,
+    /// The variable of a dynamic partial, e.g. {{>* variable }},
+    /// didn't resolve to a non-empty string, so it can't be used
+    /// as the name of a partial. The [`String`] is the variable's
+    /// path.
+    RenderErrorInvalidDynamicPartial(String),
+    /// Some variable, e.g. {{ variable }}, resolved to an array or
+    /// object while [`RenderOptions::strict_container_variables`]
+    /// was enabled. The [`String`] is the variable's path.
+    RenderErrorCannotStringifyContainer(String),
+    /// The output of a render wasn't valid utf-8, e.g. because
+    /// a [`ValueRenderer`] wrote invalid utf-8. See
+    /// [`Template::render_to_string_checked`].
+    RenderErrorInvalidUtf8,
+    /// The range of fragments passed to [`Template::render_range`]
+    /// was out of bounds or split a section. The [`Range`] is the
+    /// range of fragments.
+    RenderErrorInvalidFragmentRange(Range<usize>),
+    /// Some variables or sections of a template don't resolve
+    /// against the default value of a type. See
+    /// [`Template::check_against`]. The [`Vec`] has their paths.
+    CheckErrorMissingPaths(Vec<String>),
+    /// Some keys of the data a template is checked against aren't
+    /// referenced by the template. See [`Template::check_unused_keys`].
+    /// The [`Vec`] has the keys.
+    CheckErrorUnusedKeys(Vec<String>),
 }
 
 impl MoostacheError {
+    /// If this is one of the `ParseError*` variants, i.e.
+    /// parsing a template failed.
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::Template;
+    /// 
+    /// let err = Template::parse("{{# unclosed }}").unwrap_err();
+    /// assert!(err.is_parse_error());
+    /// ```
+    #[must_use]
+    pub fn is_parse_error(&self) -> bool {
+        use MoostacheError::*;
+        matches!(
+            self,
+            ParseErrorGeneric(_) |
+            ParseErrorNoContent(_) |
+            ParseErrorUnclosedSectionTags(_) |
+            ParseErrorInvalidEscapedVariableTag(_) |
+            ParseErrorInvalidUnescapedVariableTag(_) |
+            ParseErrorInvalidSectionEndTag(_) |
+            ParseErrorMismatchedSectionEndTag(_) |
+            ParseErrorInvalidCommentTag(_) |
+            ParseErrorInvalidSectionStartTag(_) |
+            ParseErrorInvalidInvertedSectionStartTag(_) |
+            ParseErrorInvalidPartialTag(_) |
+            ParseErrorInvalidRawBlock(_) |
+            ParseErrorInvalidElseTag(_) |
+            ParseErrorElseOutsideSection(_) |
+            ParseErrorDuplicateElse(_) |
+            ParseErrorTooManySections(_) |
+            ParseErrorTooManyFragments(_) |
+            ParseErrorInvalidDelimiters(_)
+        )
+    }
+    /// If reading from the filesystem, or writing to a writer,
+    /// failed, i.e. this is [`MoostacheError::IoError`] or
+    /// [`MoostacheError::RenderErrorWouldBlock`].
+    #[must_use]
+    pub fn is_io_error(&self) -> bool {
+        matches!(self, MoostacheError::IoError(..) | MoostacheError::RenderErrorWouldBlock)
+    }
+    /// If a template doesn't exist, i.e. this is
+    /// [`MoostacheError::LoaderErrorTemplateNotFound`] or an
+    /// [`MoostacheError::IoError`] of kind [`NotFound`](io::ErrorKind::NotFound).
+    #[must_use]
+    pub fn is_not_found(&self) -> bool {
+        matches!(
+            self,
+            MoostacheError::LoaderErrorTemplateNotFound(_) |
+            MoostacheError::IoError(_, io::ErrorKind::NotFound)
+        )
+    }
+    /// Name of the template which produced the error, if
+    /// the variant has one and the template isn't anonymous,
+    /// e.g. parsed directly via [`Template::parse`].
+    /// 
+    /// ### Examples
+    /// 
+    /// ```rust
+    /// use moostache::{HashMapLoader, MoostacheError, TemplateLoader};
+    /// use maplit::hashmap;
+    /// 
+    /// let loader = HashMapLoader::try_from(hashmap! {
+    ///     "greet" => "hello {{name}}!",
+    /// }).unwrap();
+    /// let err = loader.get("missing").unwrap_err();
+    /// assert_eq!(err.template_name(), Some("missing"));
+    /// assert_eq!(MoostacheError::SerializationError("key must be a string".into()).template_name(), None);
+    /// ```
+    #[must_use]
+    pub fn template_name(&self) -> Option<&str> {
+        use MoostacheError::*;
+        match self {
+            ParseErrorGeneric(s) |
+            ParseErrorNoContent(s) |
+            ParseErrorUnclosedSectionTags(s) |
+            ParseErrorInvalidEscapedVariableTag(s) |
+            ParseErrorInvalidUnescapedVariableTag(s) |
+            ParseErrorInvalidSectionEndTag(s) |
+            ParseErrorMismatchedSectionEndTag(s) |
+            ParseErrorInvalidCommentTag(s) |
+            ParseErrorInvalidSectionStartTag(s) |
+            ParseErrorInvalidInvertedSectionStartTag(s) |
+            ParseErrorInvalidPartialTag(s) |
+            ParseErrorInvalidRawBlock(s) |
+            ParseErrorInvalidElseTag(s) |
+            ParseErrorElseOutsideSection(s) |
+            ParseErrorDuplicateElse(s) |
+            ParseErrorTooManySections(s) |
+            ParseErrorTooManyFragments(s) |
+            ParseErrorInvalidDelimiters(s) |
+            IoError(s, _) |
+            LoaderErrorTemplateNotFound(s) |
+            LoaderErrorCyclicPartial(s) if !s.is_empty() => Some(s),
+            _ => None,
+        }
+    }
     fn from_internal(internal: InternalError, s: String) -> Self {
         match internal {
             InternalError::ParseErrorGeneric => MoostacheError::ParseErrorGeneric(s),
@@ -1497,8 +8429,16 @@ impl MoostacheError {
             InternalError::ParseErrorInvalidSectionStartTag => MoostacheError::ParseErrorInvalidSectionStartTag(s),
             InternalError::ParseErrorInvalidInvertedSectionStartTag => MoostacheError::ParseErrorInvalidInvertedSectionStartTag(s),
             InternalError::ParseErrorInvalidPartialTag => MoostacheError::ParseErrorInvalidPartialTag(s),
+            InternalError::ParseErrorInvalidRawBlock => MoostacheError::ParseErrorInvalidRawBlock(s),
+            InternalError::ParseErrorInvalidElseTag => MoostacheError::ParseErrorInvalidElseTag(s),
+            InternalError::ParseErrorElseOutsideSection => MoostacheError::ParseErrorElseOutsideSection(s),
+            InternalError::ParseErrorDuplicateElse => MoostacheError::ParseErrorDuplicateElse(s),
+            InternalError::ParseErrorTooManySections => MoostacheError::ParseErrorTooManySections(s),
+            InternalError::ParseErrorTooManyFragments => MoostacheError::ParseErrorTooManyFragments(s),
+            InternalError::ParseErrorInvalidDelimiters => MoostacheError::ParseErrorInvalidDelimiters(s),
         }
     }
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
     fn set_name(mut self, name: &str) -> Self {
         use MoostacheError::*;
         match &mut self {
@@ -1513,19 +8453,37 @@ impl MoostacheError {
             ParseErrorInvalidSectionStartTag(s) |
             ParseErrorInvalidInvertedSectionStartTag(s) |
             ParseErrorInvalidPartialTag(s) |
+            ParseErrorInvalidRawBlock(s) |
+            ParseErrorInvalidElseTag(s) |
+            ParseErrorElseOutsideSection(s) |
+            ParseErrorDuplicateElse(s) |
+            ParseErrorTooManySections(s) |
+            ParseErrorTooManyFragments(s) |
+            ParseErrorInvalidDelimiters(s) |
             IoError(s, _) |
             LoaderErrorTemplateNotFound(s) => {
                 s.clear();
                 s.push_str(name);
             },
             _ => unreachable!("trying to set name for parse error"),
-        };
+        }
         self
     }
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
     fn from_io(io: std::io::Error, s: String) -> Self {
         let kind = io.kind();
         MoostacheError::IoError(s, kind)
     }
+    // like from_io, but for errors from the writer during renders
+    fn from_write_io(io: std::io::Error) -> Self {
+        if io.get_ref().is_some_and(|inner| inner.downcast_ref::<OutputTooLarge>().is_some()) {
+            return MoostacheError::RenderErrorOutputTooLarge;
+        }
+        match io.kind() {
+            io::ErrorKind::WouldBlock => MoostacheError::RenderErrorWouldBlock,
+            kind => MoostacheError::IoError(String::new(), kind),
+        }
+    }
 }
 
 impl std::error::Error for MoostacheError {}
@@ -1551,13 +8509,30 @@ impl Display for MoostacheError {
             ParseErrorInvalidSectionStartTag(s) => write!(f, "error parsing {} template: invalid section start tag, expected {{{{# section }}}}", template_name(s)),
             ParseErrorInvalidInvertedSectionStartTag(s) => write!(f, "error parsing {} template: invalid inverted section start tag, expected {{{{^ section }}}}", template_name(s)),
             ParseErrorInvalidPartialTag(s) => write!(f, "error parsing {} template: invalid partial tag, expected {{{{> partial }}}}", template_name(s)),
+            ParseErrorInvalidRawBlock(s) => write!(f, "error parsing {} template: invalid raw block, expected {{{{% raw %}}}} ... {{{{% endraw %}}}}", template_name(s)),
+            ParseErrorInvalidElseTag(s) => write!(f, "error parsing {} template: invalid else tag, expected {{{{|}}}}", template_name(s)),
+            ParseErrorElseOutsideSection(s) => write!(f, "error parsing {} template: else tag outside of section, expected {{{{# section }}}} ... {{{{|}}}} ... {{{{/ section }}}}", template_name(s)),
+            ParseErrorDuplicateElse(s) => write!(f, "error parsing {} template: section has more than one else tag", template_name(s)),
+            ParseErrorTooManySections(s) => write!(f, "error parsing {} template: section has more than 65535 nested sections", template_name(s)),
+            ParseErrorTooManyFragments(s) => write!(f, "error parsing {} template: section has more than 65535 nested fragments", template_name(s)),
+            ParseErrorInvalidDelimiters(s) => write!(f, "error parsing {} template: invalid delimiters, expected non-empty delimiters without whitespace", template_name(s)),
             IoError(s, error_kind) => write!(f, "error reading {} template: {}", template_name(s), error_kind),
             LoaderErrorTemplateNotFound(s) => write!(f, "loader error: {} template not found", template_name(s)),
+            LoaderErrorCyclicPartial(s) => write!(f, "loader error: {} template includes itself via partials", template_name(s)),
             LoaderErrorNonUtf8FilePath(s) => write!(f, "loader error: can't load non-utf8 file path: {}", s.display()),
             ConfigErrorNonPositiveCacheSize => write!(f, "config error: cache size must be positive"),
             ConfigErrorInvalidTemplatesDirectory(s) => write!(f, "config error: invalid templates directory: {}", s.display()),
             ConfigErrorTooManyTemplates => write!(f, "config error: templates in directory exceeds cache size"),
-            SerializationError => write!(f, "serialization error: could not serialize data to serde_json::Value"),
+            SerializationError(s) => write!(f, "serialization error: could not serialize data to serde_json::Value: {s}"),
+            RenderErrorWouldBlock => write!(f, "render error: writer would block"),
+            RenderErrorCancelled => write!(f, "render error: render was cancelled"),
+            RenderErrorOutputTooLarge => write!(f, "render error: output exceeds max output bytes"),
+            RenderErrorInvalidDynamicPartial(s) => write!(f, "render error: dynamic partial \"{s}\" isn't a non-empty string"),
+            RenderErrorCannotStringifyContainer(s) => write!(f, "render error: variable \"{s}\" is an array or object"),
+            RenderErrorInvalidUtf8 => write!(f, "render error: output isn't valid utf-8"),
+            RenderErrorInvalidFragmentRange(range) => write!(f, "render error: fragment range {range:?} is out of bounds or splits a section"),
+            CheckErrorMissingPaths(paths) => write!(f, "check error: paths don't resolve: {}", paths.join(", ")),
+            CheckErrorUnusedKeys(keys) => write!(f, "check error: keys aren't used: {}", keys.join(", ")),
         }
     }
 }
@@ -1583,6 +8558,13 @@ enum InternalError {
     ParseErrorInvalidSectionStartTag,
     ParseErrorInvalidInvertedSectionStartTag,
     ParseErrorInvalidPartialTag,
+    ParseErrorInvalidRawBlock,
+    ParseErrorInvalidElseTag,
+    ParseErrorElseOutsideSection,
+    ParseErrorDuplicateElse,
+    ParseErrorTooManySections,
+    ParseErrorTooManyFragments,
+    ParseErrorInvalidDelimiters,
 }
 
 impl std::error::Error for InternalError {}
@@ -1602,14 +8584,24 @@ impl Display for InternalError {
             ParseErrorInvalidSectionStartTag => write!(f, "parse error: invalid section start tag, expected {{{{# section }}}}"),
             ParseErrorInvalidInvertedSectionStartTag => write!(f, "parse error: invalid inverted section start tag, expected {{{{^ section }}}}"),
             ParseErrorInvalidPartialTag => write!(f, "parse error: invalid partial tag, expected {{{{> partial }}}}"),
+            ParseErrorInvalidRawBlock => write!(f, "parse error: invalid raw block, expected {{{{% raw %}}}} ... {{{{% endraw %}}}}"),
+            ParseErrorInvalidElseTag => write!(f, "parse error: invalid else tag, expected {{{{|}}}}"),
+            ParseErrorElseOutsideSection => write!(f, "parse error: else tag outside of section, expected {{{{# section }}}} ... {{{{|}}}} ... {{{{/ section }}}}"),
+            ParseErrorDuplicateElse => write!(f, "parse error: section has more than one else tag"),
+            ParseErrorTooManySections => write!(f, "parse error: section has more than 65535 nested sections"),
+            ParseErrorTooManyFragments => write!(f, "parse error: section has more than 65535 nested fragments"),
+            ParseErrorInvalidDelimiters => write!(f, "parse error: invalid delimiters, expected non-empty delimiters without whitespace"),
         }
     }
 }
 
-// need to impl this so InternalError plays nice with winnow
+// need to impl this so InternalError plays nice with winnow,
+// from_error_kind is deprecated since winnow 0.6.26 but it's
+// still a required method of ParserError in winnow 0.6
+#[allow(deprecated)]
 impl<I: Stream> WParserError<I> for InternalError {
     #[inline]
-    fn from_error_kind(_input: &I, _kind: ErrorKind) -> Self {
+    fn from_error_kind(_input: &I, _kind: winnow::error::ErrorKind) -> Self {
         InternalError::ParseErrorGeneric
     }
 
@@ -1618,7 +8610,7 @@ impl<I: Stream> WParserError<I> for InternalError {
         self,
         _input: &I,
         _token_start: &<I as Stream>::Checkpoint,
-        _kind: ErrorKind,
+        _kind: winnow::error::ErrorKind,
     ) -> Self {
         self
     }
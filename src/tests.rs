@@ -4,6 +4,12 @@ use super::*;
 // run tests with
 // cargo test
 
+// check everything besides FileLoader still builds and
+// works without filesystem access, doctests included, with
+// cargo test --no-default-features
+// and that it compiles for wasm with
+// cargo check --no-default-features --target wasm32-unknown-unknown
+
 // run miri (fully isolated) with
 // cargo +nightly miri test miri_iso
 
@@ -12,15 +18,57 @@ use super::*;
 
 fn temp_no_skips(frags: Vec<Fragment<'static>>) -> Template {
     Template {
-        fragments: Yoke::new_owned(Fragments(frags)),
+        fragments: TemplateFragments::Borrowed("", frags),
         skips: Vec::new(),
+        delimiters: Delimiters::default(),
     }
 }
 
 fn temp(frags: Vec<Fragment<'static>>, skips: Vec<SectionSkip>) -> Template {
     Template {
-        fragments: Yoke::new_owned(Fragments(frags)),
+        fragments: TemplateFragments::Borrowed("", frags),
         skips,
+        delimiters: Delimiters::default(),
+    }
+}
+
+// a templates directory in the system's temp directory,
+// unique to the test and process, which is removed when
+// dropped, even if the test panics before the end
+#[cfg(feature = "fs")]
+struct TempDir(PathBuf);
+
+#[cfg(feature = "fs")]
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("moostache-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    fn path(&self, file: &str) -> PathBuf {
+        self.0.join(file)
+    }
+
+    // writes a file, creating its parent directories
+    fn write(&self, file: &str, contents: impl AsRef<[u8]>) {
+        let path = self.path(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    fn config(&self) -> LoaderConfig<'static> {
+        LoaderConfig {
+            templates_directory: self.0.to_str().unwrap().to_owned().into(),
+            ..LoaderConfig::default()
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
     }
 }
 
@@ -30,9 +78,9 @@ fn temp(frags: Vec<Fragment<'static>>, skips: Vec<SectionSkip>) -> Template {
 
 #[test]
 fn miri_iso_parse_empty() {
-    let source = "".to_owned();
+    let source = String::new();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorNoContent("".to_owned());
+    let expected = MoostacheError::ParseErrorNoContent(String::new());
     assert_eq!(err, expected);
 }
 
@@ -40,7 +88,7 @@ fn miri_iso_parse_empty() {
 fn miri_iso_parse_invalid_escaped_variable() {
     let source = "{{ dfg%jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidEscapedVariableTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -48,7 +96,7 @@ fn miri_iso_parse_invalid_escaped_variable() {
 fn miri_iso_parse_invalid_escaped_variable_utf8() {
     let source = "{{ dfg🦀jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidEscapedVariableTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -56,7 +104,7 @@ fn miri_iso_parse_invalid_escaped_variable_utf8() {
 fn miri_iso_parse_unclosed_escaped_variable() {
     let source = "{{ dfg.jgf }".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidEscapedVariableTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -64,7 +112,7 @@ fn miri_iso_parse_unclosed_escaped_variable() {
 fn miri_iso_parse_invalid_unescaped_variable() {
     let source = "{{{ dfg%jgf }}}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidUnescapedVariableTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidUnescapedVariableTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -72,7 +120,7 @@ fn miri_iso_parse_invalid_unescaped_variable() {
 fn miri_iso_parse_invalid_unescaped_variable_utf8() {
     let source = "{{{ dfg🦀jgf }}}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidUnescapedVariableTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidUnescapedVariableTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -80,7 +128,7 @@ fn miri_iso_parse_invalid_unescaped_variable_utf8() {
 fn miri_iso_parse_unclosed_unescaped_variable() {
     let source = "{{{ dfg.jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidUnescapedVariableTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidUnescapedVariableTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -88,7 +136,7 @@ fn miri_iso_parse_unclosed_unescaped_variable() {
 fn miri_iso_parse_unclosed_comment() {
     let source = "{{! comment }".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidCommentTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidCommentTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -96,7 +144,7 @@ fn miri_iso_parse_unclosed_comment() {
 fn miri_iso_parse_unclosed_comment_utf8() {
     let source = "{{! 🦀 }".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidCommentTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidCommentTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -104,7 +152,7 @@ fn miri_iso_parse_unclosed_comment_utf8() {
 fn miri_iso_parse_invalid_section_start() {
     let source = "{{# dfg%jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidSectionStartTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidSectionStartTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -112,7 +160,7 @@ fn miri_iso_parse_invalid_section_start() {
 fn miri_iso_parse_invalid_section_start_utf8() {
     let source = "{{# dfg🦀jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidSectionStartTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidSectionStartTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -120,7 +168,7 @@ fn miri_iso_parse_invalid_section_start_utf8() {
 fn miri_iso_parse_unclosed_section_start() {
     let source = "{{# dfg.jgf }".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidSectionStartTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidSectionStartTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -128,7 +176,7 @@ fn miri_iso_parse_unclosed_section_start() {
 fn miri_iso_parse_unclosed_section_missing_end() {
     let source = "{{# dfg.jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorUnclosedSectionTags("".to_owned());
+    let expected = MoostacheError::ParseErrorUnclosedSectionTags(String::new());
     assert_eq!(err, expected);
 }
 
@@ -136,7 +184,7 @@ fn miri_iso_parse_unclosed_section_missing_end() {
 fn miri_iso_parse_unclosed_section_mismatched_end() {
     let source = "{{# dfg.jgf }} lol {{/ not.same }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag("".to_owned());
+    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -144,7 +192,7 @@ fn miri_iso_parse_unclosed_section_mismatched_end() {
 fn miri_iso_parse_unclosed_section_mismatched_end_utf8() {
     let source = "{{# dfg.jgf }} 🦀🦀 {{/ not.same }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag("".to_owned());
+    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -152,7 +200,7 @@ fn miri_iso_parse_unclosed_section_mismatched_end_utf8() {
 fn miri_iso_parse_invalid_inverted_section_start() {
     let source = "{{^ dfg%jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidInvertedSectionStartTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidInvertedSectionStartTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -160,7 +208,7 @@ fn miri_iso_parse_invalid_inverted_section_start() {
 fn miri_iso_parse_invalid_inverted_section_start_utf8() {
     let source = "{{^ dfg🦀jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidInvertedSectionStartTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidInvertedSectionStartTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -168,7 +216,7 @@ fn miri_iso_parse_invalid_inverted_section_start_utf8() {
 fn miri_iso_parse_unclosed_inverted_section_start() {
     let source = "{{^ dfg.jgf }".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidInvertedSectionStartTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidInvertedSectionStartTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -176,7 +224,7 @@ fn miri_iso_parse_unclosed_inverted_section_start() {
 fn miri_iso_parse_unclosed_inverted_section_missing_end() {
     let source = "{{^ dfg.jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorUnclosedSectionTags("".to_owned());
+    let expected = MoostacheError::ParseErrorUnclosedSectionTags(String::new());
     assert_eq!(err, expected);
 }
 
@@ -184,7 +232,7 @@ fn miri_iso_parse_unclosed_inverted_section_missing_end() {
 fn miri_iso_parse_unclosed_inverted_section_mismatched_end() {
     let source = "{{^ dfg.jgf }} lol {{/ not.same }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag("".to_owned());
+    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -192,7 +240,7 @@ fn miri_iso_parse_unclosed_inverted_section_mismatched_end() {
 fn miri_iso_parse_unclosed_inverted_section_mismatched_end_utf8() {
     let source = "{{^ dfg.jgf }} 🦀🦀 {{/ not.same }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag("".to_owned());
+    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -200,7 +248,7 @@ fn miri_iso_parse_unclosed_inverted_section_mismatched_end_utf8() {
 fn miri_iso_parse_invalid_section_end() {
     let source = "{{/ dfg%jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidSectionEndTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidSectionEndTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -208,7 +256,7 @@ fn miri_iso_parse_invalid_section_end() {
 fn miri_iso_parse_unclosed_section_end() {
     let source = "{{/ dfg.jgf }".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidSectionEndTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidSectionEndTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -216,7 +264,7 @@ fn miri_iso_parse_unclosed_section_end() {
 fn miri_iso_parse_section_end_without_start() {
     let source = "{{/ dfg.jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag("".to_owned());
+    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -224,7 +272,7 @@ fn miri_iso_parse_section_end_without_start() {
 fn miri_iso_parse_invalid_partial() {
     let source = "{{> dfg\"jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidPartialTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidPartialTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -232,7 +280,7 @@ fn miri_iso_parse_invalid_partial() {
 fn miri_iso_parse_invalid_partial_utf8() {
     let source = "{{> dfg🦀jgf }}".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidPartialTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidPartialTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -240,7 +288,122 @@ fn miri_iso_parse_invalid_partial_utf8() {
 fn miri_iso_parse_unclosed_partial() {
     let source = "{{> dfg/jgf }".to_owned();
     let err = Template::parse(source).unwrap_err();
-    let expected = MoostacheError::ParseErrorInvalidPartialTag("".to_owned());
+    let expected = MoostacheError::ParseErrorInvalidPartialTag(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_unclosed_raw_block() {
+    let source = "{{% raw %}} {{ lol }} {{% endraw }}".to_owned();
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorInvalidRawBlock(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_invalid_raw_block_start() {
+    let source = "{{% rawr %}} lol {{% endraw %}}".to_owned();
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorInvalidRawBlock(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_else_outside_section() {
+    let source = "lol {{|}} lol".to_owned();
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorElseOutsideSection(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_else_after_section() {
+    let source = "{{# dfg }} lol {{/ dfg }}{{|}}".to_owned();
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorElseOutsideSection(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_else_in_inverted_section() {
+    let source = "{{^ dfg }} lol {{|}} lol {{/ dfg }}".to_owned();
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorElseOutsideSection(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_invalid_missing_section_start() {
+    let err = Template::parse("{{^? }}").unwrap_err();
+    let expected = MoostacheError::ParseErrorInvalidInvertedSectionStartTag(String::new());
+    assert_eq!(err, expected);
+    let err = Template::parse("{{^? a }} lol {{|}} lol {{/ a }}").unwrap_err();
+    let expected = MoostacheError::ParseErrorElseOutsideSection(String::new());
+    assert_eq!(err, expected);
+    let err = Template::parse("{{^? a }} lol {{/ b }}").unwrap_err();
+    let expected = MoostacheError::ParseErrorMismatchedSectionEndTag(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_duplicate_else() {
+    let source = "{{# dfg }} lol {{|}} lol {{|}} lol {{/ dfg }}".to_owned();
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorDuplicateElse(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_too_many_nested_sections() {
+    // outermost section has 65536 nested sections
+    let depth = usize::from(u16::MAX) + 2;
+    let source = "{{#a}}".repeat(depth) + &"{{/a}}".repeat(depth);
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorTooManySections(String::new());
+    assert_eq!(err, expected);
+    // one less is fine
+    let depth = usize::from(u16::MAX) + 1;
+    let source = "{{#a}}".repeat(depth) + &"{{/a}}".repeat(depth);
+    assert!(Template::parse(source).is_ok());
+}
+
+#[test]
+fn miri_iso_parse_too_many_nested_fragments() {
+    let count = usize::from(u16::MAX) + 1;
+    let source = "{{#a}}".to_owned() + &"{{b}}".repeat(count) + "{{/a}}";
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorTooManyFragments(String::new());
+    assert_eq!(err, expected);
+    // same thing but closed by an else
+    let source = "{{#a}}".to_owned() + &"{{b}}".repeat(count) + "{{|}}{{/a}}";
+    let err = Template::parse(source).unwrap_err();
+    assert_eq!(err, expected);
+    // top level has no limit
+    let source = "{{b}}".repeat(count);
+    assert!(Template::parse(source).is_ok());
+}
+
+#[test]
+fn miri_iso_parse_unclosed_else() {
+    let source = "{{# dfg }} lol {{| } lol {{/ dfg }}".to_owned();
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorInvalidElseTag(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_invalid_dynamic_partial() {
+    let source = "{{>* dfg/jgf }}".to_owned();
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorInvalidPartialTag(String::new());
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn miri_iso_parse_unclosed_dynamic_partial() {
+    let source = "{{>* dfg.jgf }".to_owned();
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorInvalidPartialTag(String::new());
     assert_eq!(err, expected);
 }
 
@@ -270,6 +433,20 @@ fn miri_iso_parse_literal_static() {
     assert_eq!(template, expected_template);
 }
 
+#[test]
+fn miri_iso_parse_literal_cow() {
+    let source = "hello {{ name }}";
+    let expected_template = Template::parse(source).unwrap();
+    for cow in [Cow::Borrowed(source), Cow::Owned(source.to_owned())] {
+        let template = Template::parse(cow.clone())
+            .expect("template parsed successfully");
+        assert_eq!(template, expected_template);
+        let template = Template::try_from(cow)
+            .expect("template parsed successfully");
+        assert_eq!(template, expected_template);
+    }
+}
+
 #[test]
 fn miri_iso_parse_literal_utf8() {
     let source = "hello world 🦀";
@@ -337,6 +514,7 @@ fn miri_iso_parse_section() {
     let expected_skips = vec![SectionSkip {
         nested_sections: 0,
         nested_fragments: 1,
+        has_aggregates: false,
     }];
     let expected_template = temp(
         expected_frags,
@@ -357,6 +535,7 @@ fn miri_iso_parse_section_utf8() {
     let expected_skips = vec![SectionSkip {
         nested_sections: 0,
         nested_fragments: 1,
+        has_aggregates: false,
     }];
     let expected_template = temp(
         expected_frags,
@@ -393,7 +572,7 @@ fn miri_iso_parse_partial_nested() {
     let template = Template::parse(source.to_owned())
         .expect("Fragment parsed successfully");
     let expected_template = temp_no_skips(
-        vec![Fragment::Partial("name/in/nested/dir".into())],
+        vec![Fragment::Partial("name/in/nested/dir")],
     );
     assert_eq!(template, expected_template);
 }
@@ -404,7 +583,73 @@ fn miri_iso_parse_partial_nested_padded() {
     let template = Template::parse(source.to_owned())
         .expect("Fragment parsed successfully");
     let expected_template = temp_no_skips(
-        vec![Fragment::Partial("name/in/nested/dir".into())],
+        vec![Fragment::Partial("name/in/nested/dir")],
+    );
+    assert_eq!(template, expected_template);
+}
+
+#[test]
+fn miri_iso_parse_raw_block() {
+    let source = "a {{%raw%}}{{# b }}{{ c }}{{/ b }}{{{ d }}}{{! e }}{{> f }}{{%  endraw  %}} g";
+    let template = Template::parse(source.to_owned())
+        .expect("Fragment parsed successfully");
+    let expected_template = temp_no_skips(
+        vec![
+            Fragment::Literal("a "),
+            Fragment::Literal("{{# b }}{{ c }}{{/ b }}{{{ d }}}{{! e }}{{> f }}"),
+            Fragment::Literal(" g"),
+        ],
+    );
+    assert_eq!(template, expected_template);
+}
+
+#[test]
+fn miri_iso_parse_raw_block_empty() {
+    let source = "a{{% raw %}}{{% endraw %}}b";
+    let template = Template::parse(source.to_owned())
+        .expect("Fragment parsed successfully");
+    let expected_template = temp_no_skips(
+        vec![
+            Fragment::Literal("a"),
+            Fragment::Literal("b"),
+        ],
+    );
+    assert_eq!(template, expected_template);
+}
+
+#[test]
+fn miri_iso_parse_raw_block_not_nested() {
+    // raw blocks don't nest, the first end tag
+    // ends the block and the second one is invalid
+    let source = "{{% raw %}}{{% raw %}}{{ a }}{{% endraw %}}{{% endraw %}}";
+    let err = Template::parse(source).unwrap_err();
+    let expected = MoostacheError::ParseErrorInvalidRawBlock(String::new());
+    assert_eq!(err, expected);
+
+    let source = "{{% raw %}}{{% raw %}}{{ a }}{{% endraw %}}";
+    let template = Template::parse(source.to_owned())
+        .expect("Fragment parsed successfully");
+    let expected_template = temp_no_skips(
+        vec![Fragment::Literal("{{% raw %}}{{ a }}")],
+    );
+    assert_eq!(template, expected_template);
+}
+
+#[test]
+fn miri_iso_parse_raw_block_in_section() {
+    let source = "{{# a }}{{% raw %}}{{ b }}{{% endraw %}}{{/ a }}";
+    let template = Template::parse(source.to_owned())
+        .expect("Fragment parsed successfully");
+    let expected_template = temp(
+        vec![
+            Fragment::Section("a"),
+            Fragment::Literal("{{ b }}"),
+        ],
+        vec![SectionSkip {
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        }],
     );
     assert_eq!(template, expected_template);
 }
@@ -416,9 +661,9 @@ fn miri_iso_parse_v1_features() {
         .expect("template parsed successfully");
     let expected_template = temp_no_skips(
         vec![
-            Fragment::Literal("prefix ".into()),
-            Fragment::EscapedVariable("escaped".into()),
-            Fragment::Literal("!".into()),
+            Fragment::Literal("prefix "),
+            Fragment::EscapedVariable("escaped"),
+            Fragment::Literal("!"),
         ],
     );
     assert_eq!(template, expected_template);
@@ -431,11 +676,11 @@ fn miri_iso_parse_v2_features() {
         .expect("template parsed successfully");
     let expected_template = temp_no_skips(
         vec![
-            Fragment::Literal("prefix ".into()),
-            Fragment::EscapedVariable("escaped".into()),
-            Fragment::Literal(" ".into()),
-            Fragment::UnescapedVariable("unescaped".into()),
-            Fragment::Literal("!".into()),
+            Fragment::Literal("prefix "),
+            Fragment::EscapedVariable("escaped"),
+            Fragment::Literal(" "),
+            Fragment::UnescapedVariable("unescaped"),
+            Fragment::Literal("!"),
         ],
     );
     assert_eq!(template, expected_template);
@@ -460,6 +705,7 @@ fn miri_iso_parse_v3_features() {
     ];
     let expected_skips = vec![SectionSkip {
         nested_fragments: 3,
+        has_aggregates: false,
         nested_sections: 0,
     }];
     let expected_template = temp(
@@ -492,10 +738,12 @@ fn miri_iso_parse_v4_features() {
     let expected_skips = vec![
         SectionSkip {
             nested_fragments: 3,
+            has_aggregates: false,
             nested_sections: 0,
         },
         SectionSkip {
             nested_fragments: 1,
+            has_aggregates: false,
             nested_sections: 0,
         },
     ];
@@ -529,10 +777,12 @@ fn miri_iso_parse_v4_features_utf8() {
     let expected_skips = vec![
         SectionSkip {
             nested_fragments: 3,
+            has_aggregates: false,
             nested_sections: 0,
         },
         SectionSkip {
             nested_fragments: 1,
+            has_aggregates: false,
             nested_sections: 0,
         },
     ];
@@ -576,38 +826,47 @@ fn miri_iso_parse_heavy_section_nesting() {
         SectionSkip { // s1
             nested_sections: 8,
             nested_fragments: 19,
+            has_aggregates: false,
         },
         SectionSkip { // s1a
             nested_sections: 4,
             nested_fragments: 9,
+            has_aggregates: false,
         },
         SectionSkip { // s1aa
             nested_sections: 0,
             nested_fragments: 1,
+            has_aggregates: false,
         },
         SectionSkip { // ^s1aa
             nested_sections: 0,
             nested_fragments: 1,
+            has_aggregates: false,
         },
         SectionSkip { // s1ab
             nested_sections: 0,
             nested_fragments: 1,
+            has_aggregates: false,
         },
         SectionSkip { // ^s1ab
             nested_sections: 0,
             nested_fragments: 1,
+            has_aggregates: false,
         },
         SectionSkip { // ^s1a
             nested_sections: 0,
             nested_fragments: 1,
+            has_aggregates: false,
         },
         SectionSkip { // s1b
             nested_sections: 0,
             nested_fragments: 1,
+            has_aggregates: false,
         },
         SectionSkip { // ^s1b
             nested_sections: 0,
             nested_fragments: 1,
+            has_aggregates: false,
         },
     ];
     let expected_template = temp(
@@ -617,68 +876,571 @@ fn miri_iso_parse_heavy_section_nesting() {
     assert_eq!(template, expected_template);
 }
 
-/////////////////////////////
-// TEST JSON TRUTHY VALUES //
-/////////////////////////////
-
-#[test]
-fn miri_iso_truthy_value_null() {
-    assert_eq!(is_truthy(&json!(null)), false);
-}
-
 #[test]
-fn miri_iso_truthy_value_false() {
-    assert_eq!(is_truthy(&json!(false)), false);
+fn miri_iso_parse_section_else() {
+    let source = "{{# items }}{{ . }}{{# . }}!{{/ . }}{{ | }}no items{{^ more }}?{{/ more }}{{/ items }}";
+    let template = Template::parse(source.to_owned())
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::Section("items"),
+        Fragment::EscapedVariable("."),
+        Fragment::Section("."),
+        Fragment::Literal("!"),
+        Fragment::SectionElse("|"),
+        Fragment::Literal("no items"),
+        Fragment::InvertedSection("more"),
+        Fragment::Literal("?"),
+    ];
+    let expected_skips = vec![
+        SectionSkip { // items
+            nested_sections: 1,
+            nested_fragments: 3,
+            has_aggregates: false,
+        },
+        SectionSkip { // .
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // else
+            nested_sections: 1,
+            nested_fragments: 3,
+            has_aggregates: false,
+        },
+        SectionSkip { // ^more
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+    ];
+    let expected_template = temp(
+        expected_frags,
+        expected_skips,
+    );
+    assert_eq!(template, expected_template);
 }
 
 #[test]
-fn miri_iso_truthy_value_true() {
-    assert_eq!(is_truthy(&json!(true)), true);
+fn miri_iso_parse_section_else_nested_content() {
+    let source = "{{#items}}{{name}}{{|}}{{#fallbacks}}{{name}}{{|}}none{{/fallbacks}}{{> footer }}{{/items}}{{after}}";
+    let template = Template::parse(source)
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::Section("items"),
+        Fragment::EscapedVariable("name"),
+        Fragment::SectionElse("|"),
+        Fragment::Section("fallbacks"),
+        Fragment::EscapedVariable("name"),
+        Fragment::SectionElse("|"),
+        Fragment::Literal("none"),
+        Fragment::Partial("footer"),
+        Fragment::EscapedVariable("after"),
+    ];
+    let expected_skips = vec![
+        SectionSkip { // items
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // else of items
+            nested_sections: 2,
+            nested_fragments: 5,
+            has_aggregates: false,
+        },
+        SectionSkip { // fallbacks
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // else of fallbacks
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+    ];
+    let expected_template = temp(
+        expected_frags,
+        expected_skips,
+    );
+    assert_eq!(template, expected_template);
 }
 
 #[test]
-fn miri_iso_truthy_value_zero() {
-    assert_eq!(is_truthy(&json!(0)), false);
+fn miri_iso_parse_wildcard_section() {
+    let source = "{{# data.* }}{{ . }}{{/ data.* }}{{#a.b.*}}{{/a.b.*}}";
+    let template = Template::parse(source.to_owned())
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::Section("data.*"),
+        Fragment::EscapedVariable("."),
+        Fragment::Section("a.b.*"),
+    ];
+    let expected_skips = vec![
+        SectionSkip { // data.*
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // a.b.*
+            nested_sections: 0,
+            nested_fragments: 0,
+            has_aggregates: false,
+        },
+    ];
+    let expected_template = temp(
+        expected_frags,
+        expected_skips,
+    );
+    assert_eq!(template, expected_template);
+    // wildcards only end section paths
+    for (source, expected) in [
+        ("{{# data.* }}{{/ data }}", MoostacheError::ParseErrorMismatchedSectionEndTag(String::new())),
+        ("{{# data }}{{/ data.* }}", MoostacheError::ParseErrorMismatchedSectionEndTag(String::new())),
+        ("{{# .* }}{{/ .* }}", MoostacheError::ParseErrorInvalidSectionStartTag(String::new())),
+        ("{{# data.*.a }}{{/ data.*.a }}", MoostacheError::ParseErrorInvalidSectionStartTag(String::new())),
+        ("{{^ data.* }}{{/ data.* }}", MoostacheError::ParseErrorInvalidInvertedSectionStartTag(String::new())),
+        ("{{ data.* }}", MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new())),
+    ] {
+        assert_eq!(Template::parse(source).unwrap_err(), expected, "{source}");
+    }
 }
 
 #[test]
-fn miri_iso_truthy_value_nonzero() {
-    assert_eq!(is_truthy(&json!(1)), true);
+fn miri_iso_parse_variable_widths() {
+    let source = "{{ a:10 }}{{{ b.c:<3 }}}{{.:>0}}";
+    let template = Template::parse(source.to_owned())
+        .expect("template parsed successfully");
+    let expected_template = temp_no_skips(vec![
+        Fragment::EscapedVariable("a:10"),
+        Fragment::UnescapedVariable("b.c:<3"),
+        Fragment::EscapedVariable(".:>0"),
+    ]);
+    assert_eq!(template, expected_template);
+    assert_eq!(split_width("a:10"), ("a", Some(Width { chars: 10, align_right: false })));
+    assert_eq!(split_width("b.c:<3"), ("b.c", Some(Width { chars: 3, align_right: false })));
+    assert_eq!(split_width(".:>0"), (".", Some(Width { chars: 0, align_right: true })));
+    assert_eq!(split_width("a"), ("a", None));
+    for source in ["{{ a: }}", "{{ a:x }}", "{{ a:-1 }}", "{{ a :1 }}", "{{ a:65536 }}", "{{ a:>>1 }}"] {
+        let err = Template::parse(source).unwrap_err();
+        assert_eq!(err, MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new()), "{source}");
+    }
+    // widths are only for variables
+    let err = Template::parse("{{# a:1 }}{{/ a:1 }}").unwrap_err();
+    assert_eq!(err, MoostacheError::ParseErrorInvalidSectionStartTag(String::new()));
 }
 
 #[test]
-fn miri_iso_truthy_value_empty_string() {
-    assert_eq!(is_truthy(&json!("")), false);
+fn miri_iso_parse_enclosing_scope_paths() {
+    let source = "{{../a}}{{{ ../a.b }}}{{# ../c }}{{../.}}{{/ ../c }}";
+    let template = Template::parse(source)
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::EscapedVariable("../a"),
+        Fragment::UnescapedVariable("../a.b"),
+        Fragment::Section("../c"),
+        Fragment::EscapedVariable("../."),
+    ];
+    let expected_skips = vec![
+        SectionSkip {
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+    ];
+    assert_eq!(template, temp(expected_frags, expected_skips));
+    // only a single ../ is supported
+    for source in ["{{../../a}}", "{{a/../b}}", "{{a.../b}}", "{{..a}}", "{{../}}"] {
+        let err = Template::parse(source).unwrap_err();
+        assert_eq!(err, MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new()), "{source}");
+    }
 }
 
+#[cfg(feature = "helpers")]
 #[test]
-fn miri_iso_truthy_value_nonempty_string() {
-    assert_eq!(is_truthy(&json!("hello")), true);
+fn miri_iso_parse_compare_sections() {
+    let source = "{{#eq status \"active\"}}a{{|}}b{{/eq}}{{# ne  n -1.5 }}{{#eq}}c{{/eq}}{{/ne}}{{#eq a.b true}}{{/eq}}";
+    let template = Template::parse(source)
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::CompareSection("eq status \"active\""),
+        Fragment::Literal("a"),
+        Fragment::SectionElse("|"),
+        Fragment::Literal("b"),
+        Fragment::CompareSection("ne  n -1.5"),
+        // a section named eq is still a section
+        Fragment::Section("eq"),
+        Fragment::Literal("c"),
+        Fragment::CompareSection("eq a.b true"),
+    ];
+    let expected_skips = vec![
+        SectionSkip { // eq status
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // else of eq status
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // ne n
+            nested_sections: 1,
+            nested_fragments: 2,
+            has_aggregates: false,
+        },
+        SectionSkip { // eq
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // eq a.b
+            nested_sections: 0,
+            nested_fragments: 0,
+            has_aggregates: false,
+        },
+    ];
+    assert_eq!(template, temp(expected_frags, expected_skips));
+    for source in ["{{#eq a b}}{{/eq}}", "{{#eq a 'b'}}{{/eq}}", "{{#eq a \"b}}{{/eq}}", "{{#eq a 1}}{{/ne}}"] {
+        assert!(Template::parse(source).is_err(), "{source}");
+    }
 }
 
+#[cfg(not(feature = "helpers"))]
 #[test]
-fn miri_iso_truthy_value_nonempty_string_utf8() {
-    assert_eq!(is_truthy(&json!("🦀")), true);
+fn miri_iso_parse_compare_sections_without_helpers() {
+    let err = Template::parse("{{#eq status \"active\"}}{{/eq}}").unwrap_err();
+    assert_eq!(err, MoostacheError::ParseErrorInvalidSectionStartTag(String::new()));
 }
 
+#[cfg(feature = "helpers")]
 #[test]
-fn miri_iso_truthy_value_empty_array() {
-    assert_eq!(is_truthy(&json!([])), false);
+fn miri_iso_parse_range_sections() {
+    let source = "{{#range 1 5}}{{.}}{{|}}none{{/range}}{{# range  -2 page.count }}{{#range}}{{/range}}{{/ range }}";
+    let template = Template::parse(source)
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::RangeSection("range 1 5"),
+        Fragment::EscapedVariable("."),
+        Fragment::SectionElse("|"),
+        Fragment::Literal("none"),
+        Fragment::RangeSection("range  -2 page.count"),
+        // a section named range is still a section
+        Fragment::Section("range"),
+    ];
+    let expected_skips = vec![
+        SectionSkip { // range 1 5
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // else of range 1 5
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // range -2 page.count
+            nested_sections: 1,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // range
+            nested_sections: 0,
+            nested_fragments: 0,
+            has_aggregates: false,
+        },
+    ];
+    assert_eq!(template, temp(expected_frags, expected_skips));
+    assert_eq!(template.inspect()[4].kind, FragmentKind::RangeSectionStart {
+        start: "-2".into(),
+        end: "page.count".into(),
+        nested: 1,
+    });
+    for source in [
+        "{{#range 1}}{{/range}}",
+        "{{#range 1 5 7}}{{/range}}",
+        "{{#range 1 \"5\"}}{{/range}}",
+        "{{#range 1 5}}{{/ 1 }}",
+    ] {
+        assert!(Template::parse(source).is_err(), "{source}");
+    }
 }
 
+#[cfg(feature = "helpers")]
 #[test]
-fn miri_iso_truthy_value_nonempty_array() {
-    assert_eq!(is_truthy(&json!([1])), true);
-}
-
+fn miri_iso_parse_with_sections() {
+    let source = "{{#with user}}{{name}}{{|}}none{{/with}}{{# with  a.b }}{{#with}}{{/with}}{{/ with }}{{#with as w}}{{/with}}";
+    let template = Template::parse(source)
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::WithSection("with user"),
+        Fragment::EscapedVariable("name"),
+        Fragment::SectionElse("|"),
+        Fragment::Literal("none"),
+        Fragment::WithSection("with  a.b"),
+        // a section named with is still a section
+        Fragment::Section("with"),
+        // and so is an alias section over with
+        Fragment::AliasSection("with as w"),
+    ];
+    let expected_skips = vec![
+        SectionSkip { // with user
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // else of with user
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // with a.b
+            nested_sections: 1,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // with
+            nested_sections: 0,
+            nested_fragments: 0,
+            has_aggregates: false,
+        },
+        SectionSkip { // with as w
+            nested_sections: 0,
+            nested_fragments: 0,
+            has_aggregates: false,
+        },
+    ];
+    assert_eq!(template, temp(expected_frags, expected_skips));
+    assert_eq!(template.inspect()[4].kind, FragmentKind::WithSectionStart {
+        name: "a.b".into(),
+        nested: 1,
+    });
+    assert_eq!(template.debug_tree().lines().next(), Some("{{# with user }}"));
+    for source in ["{{#with user}}{{/user}}", "{{#with user 1}}{{/with}}", "{{#with user}}"] {
+        assert!(Template::parse(source).is_err(), "{source}");
+    }
+}
+
+#[cfg(feature = "helpers")]
+#[test]
+fn miri_iso_parse_plural_sections() {
+    let source = "{{#plural count}}items{{|}}item{{/plural}}{{# plural  a.b }}{{#plural}}{{/plural}}{{/ plural }}{{#plural as p}}{{/plural}}";
+    let template = Template::parse(source)
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::PluralSection("plural count"),
+        Fragment::Literal("items"),
+        Fragment::SectionElse("|"),
+        Fragment::Literal("item"),
+        Fragment::PluralSection("plural  a.b"),
+        // a section named plural is still a section
+        Fragment::Section("plural"),
+        // and so is an alias section over plural
+        Fragment::AliasSection("plural as p"),
+    ];
+    let expected_skips = vec![
+        SectionSkip { // plural count
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // else of plural count
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // plural a.b
+            nested_sections: 1,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // plural
+            nested_sections: 0,
+            nested_fragments: 0,
+            has_aggregates: false,
+        },
+        SectionSkip { // plural as p
+            nested_sections: 0,
+            nested_fragments: 0,
+            has_aggregates: false,
+        },
+    ];
+    assert_eq!(template, temp(expected_frags, expected_skips));
+    assert_eq!(template.inspect()[4].kind, FragmentKind::PluralSectionStart {
+        name: "a.b".into(),
+        nested: 1,
+    });
+    for source in ["{{#plural count}}{{/count}}", "{{#plural count 1}}{{/plural}}", "{{#plural count}}"] {
+        assert!(Template::parse(source).is_err(), "{source}");
+    }
+}
+
+#[test]
+fn miri_iso_parse_alias_sections() {
+    let source = "{{#posts as post}}{{post.title}}{{|}}none{{/posts}}{{# a.b  as  c }}{{#as}}{{/as}}{{/ a.b }}";
+    let template = Template::parse(source)
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::AliasSection("posts as post"),
+        Fragment::EscapedVariable("post.title"),
+        Fragment::SectionElse("|"),
+        Fragment::Literal("none"),
+        Fragment::AliasSection("a.b  as  c"),
+        // a section named as is still a section
+        Fragment::Section("as"),
+    ];
+    let expected_skips = vec![
+        SectionSkip { // posts as post
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // else of posts as post
+            nested_sections: 0,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // a.b as c
+            nested_sections: 1,
+            nested_fragments: 1,
+            has_aggregates: false,
+        },
+        SectionSkip { // as
+            nested_sections: 0,
+            nested_fragments: 0,
+            has_aggregates: false,
+        },
+    ];
+    assert_eq!(template, temp(expected_frags, expected_skips));
+    assert_eq!(
+        template.debug_tree(),
+        "{{# posts as post }}\n  {{ post.title }}\n{{|}}\n  \"none\"\n{{/ posts }}\n{{# a.b  as  c }}\n  {{# as }}\n  {{/ as }}\n{{/ a.b }}\n",
+    );
+    assert_eq!(template.inspect()[0].kind, FragmentKind::AliasSectionStart {
+        name: "posts".into(),
+        alias: "post".into(),
+        nested: 1,
+    });
+    for source in [
+        "{{#posts as}}{{/posts}}",
+        "{{#posts as a.b}}{{/posts}}",
+        "{{#posts as post}}{{/post}}",
+        "{{#posts as post}}{{/posts as post}}",
+        "{{#posts asd post}}{{/posts}}",
+    ] {
+        assert!(Template::parse(source).is_err(), "{source}");
+    }
+}
+
+#[test]
+fn miri_iso_parse_dynamic_partials() {
+    let source = "{{>*name}}{{> * some.name }}{{>some/name}}";
+    let template = Template::parse(source)
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::DynamicPartial("name"),
+        Fragment::DynamicPartial("some.name"),
+        Fragment::Partial("some/name"),
+    ];
+    let expected_template = temp_no_skips(expected_frags);
+    assert_eq!(template, expected_template);
+}
+
+#[test]
+fn miri_iso_template_source() {
+    let source = "hello {{ name }}!{{! comment }}{{# a }}{{/ a }}";
+    assert_eq!(Template::parse(source).unwrap().source(), source);
+    assert_eq!(Template::parse(source.to_owned()).unwrap().source(), source);
+    let owned = String::from(source);
+    assert_eq!(BorrowedTemplate::parse(owned.as_str()).unwrap().source(), source);
+    let loader = HashMapLoader::try_from(hashmap! {
+        "name" => "{{ name }}",
+    }).unwrap();
+    let flattened = Template::parse("hi {{> name }}").unwrap().flatten(&loader).unwrap();
+    assert_eq!(flattened.source(), "hi name");
+}
+
+#[test]
+fn miri_iso_parse_borrowed() {
+    let source = String::from("hello {{ name }}!{{# items }}{{.}}{{/ items }}");
+    let template = BorrowedTemplate::parse(source.as_str())
+        .expect("template parsed successfully");
+    // fragments point into the caller's string instead of a copy
+    assert_eq!(template.source().as_ptr(), source.as_ptr());
+    let Fragment::Literal(literal) = template.frags()[0] else {
+        panic!("expected literal");
+    };
+    assert_eq!(literal.as_ptr(), source.as_ptr());
+    let data = json!({"name": "John", "items": [1, 2]});
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, "hello John!12");
+    assert_eq!(template, Template::parse(source.clone()).unwrap());
+    assert_eq!(template, BorrowedTemplate::try_from(source.as_str()).unwrap());
+    let err = BorrowedTemplate::parse(&source[..8]).unwrap_err();
+    assert_eq!(err, MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new()));
+}
+
+/////////////////////////////
+// TEST JSON TRUTHY VALUES //
+/////////////////////////////
+
+#[test]
+fn miri_iso_truthy_value_null() {
+    assert!(!is_truthy(&json!(null)));
+}
+
+#[test]
+fn miri_iso_truthy_value_false() {
+    assert!(!is_truthy(&json!(false)));
+}
+
+#[test]
+fn miri_iso_truthy_value_true() {
+    assert!(is_truthy(&json!(true)));
+}
+
+#[test]
+fn miri_iso_truthy_value_zero() {
+    assert!(!is_truthy(&json!(0)));
+}
+
+#[test]
+fn miri_iso_truthy_value_nonzero() {
+    assert!(is_truthy(&json!(1)));
+}
+
+#[test]
+fn miri_iso_truthy_value_empty_string() {
+    assert!(!is_truthy(&json!("")));
+}
+
+#[test]
+fn miri_iso_truthy_value_nonempty_string() {
+    assert!(is_truthy(&json!("hello")));
+}
+
+#[test]
+fn miri_iso_truthy_value_nonempty_string_utf8() {
+    assert!(is_truthy(&json!("🦀")));
+}
+
+#[test]
+fn miri_iso_truthy_value_empty_array() {
+    assert!(!is_truthy(&json!([])));
+}
+
+#[test]
+fn miri_iso_truthy_value_nonempty_array() {
+    assert!(is_truthy(&json!([1])));
+}
+
 #[test]
 fn miri_iso_truthy_value_empty_object() {
-    assert_eq!(is_truthy(&json!({})), false);
+    assert!(!is_truthy(&json!({})));
 }
 
 #[test]
 fn miri_iso_truthy_value_nonempty_object() {
-    assert_eq!(is_truthy(&json!({"field": 1})), true);
+    assert!(is_truthy(&json!({"field": 1})));
 }
 
 ////////////////////////////////
@@ -691,6 +1453,7 @@ fn miri_iso_resolve_value_dot() {
         resolve_value(
             ".",
             &[&json!("hello")],
+            None,
         ),
         &json!("hello"),
     );
@@ -702,6 +1465,7 @@ fn miri_iso_resolve_value_dot_utf8() {
         resolve_value(
             ".",
             &[&json!("🦀")],
+            None,
         ),
         &json!("🦀"),
     );
@@ -713,6 +1477,7 @@ fn miri_iso_resolve_value_number() {
         resolve_value(
             "0",
             &[&json!(["hello"])],
+            None,
         ),
         &json!("hello"),
     );
@@ -724,6 +1489,7 @@ fn miri_iso_resolve_value_number_utf8() {
         resolve_value(
             "0",
             &[&json!(["🦀"])],
+            None,
         ),
         &json!("🦀"),
     );
@@ -735,6 +1501,7 @@ fn miri_iso_resolve_value_string() {
         resolve_value(
             "greeting",
             &[&json!({"greeting": "hello"})],
+            None,
         ),
         &json!("hello"),
     );
@@ -746,6 +1513,7 @@ fn miri_iso_resolve_value_string_utf8() {
         resolve_value(
             "greeting",
             &[&json!({"greeting": "🦀"})],
+            None,
         ),
         &json!("🦀"),
     );
@@ -757,6 +1525,7 @@ fn miri_iso_resolve_value_nested_numbers() {
         resolve_value(
             "1.1",
             &[&json!([1, [2, 3], 4])],
+            None,
         ),
         &json!(3),
     );
@@ -768,6 +1537,7 @@ fn miri_iso_resolve_value_nested_strings() {
         resolve_value(
             "a.b",
             &[&json!({"a": {"b": 1}})],
+            None,
         ),
         &json!(1),
     );
@@ -779,6 +1549,7 @@ fn miri_iso_resolve_value_nested_mixed() {
         resolve_value(
             "1.a.0.b",
             &[&json!([0, {"a": [{"b": 1}]}])],
+            None,
         ),
         &json!(1),
     );
@@ -790,6 +1561,7 @@ fn miri_iso_resolve_value_parent_scope_number() {
         resolve_value(
             "0",
             &[&json!([2]), &json!({"a": 1})],
+            None,
         ),
         &json!(2),
     );
@@ -799,6 +1571,7 @@ fn miri_iso_resolve_value_parent_scope_number() {
         resolve_value(
             "2",
             &[&json!([0, 1, 2]), &json!([0])],
+            None,
         ),
         &json!(null),
     );
@@ -810,124 +1583,1122 @@ fn miri_iso_resolve_value_parent_scope_string() {
         resolve_value(
             "a",
             &[&json!({"a": 1}), &json!([2])],
+            None,
         ),
         &json!(1),
     );
 }
 
 #[test]
-fn miri_iso_write_value_null() {
-    let mut writer = Vec::new();
-    let _ = write_value(&json!(null), &mut writer);
-    assert!(writer.is_empty());
+fn miri_iso_resolve_value_missing_in_all_scopes() {
+    assert_eq!(
+        resolve_value(
+            "b",
+            &[&json!({"a": 1}), &json!([2])],
+            None,
+        ),
+        &json!(null),
+    );
 }
 
-///////////////////////////////////////////////
-// TEST RENDERING TEMPLATES WITHOUT PARTIALS //
-///////////////////////////////////////////////
-
 #[test]
-fn miri_iso_render_section_object_parent_scope() {
-    let source = "{{ blogTitle }}, posts: {{# posts }}{{ postTitle }} by {{ author }}, {{/ posts}}";
-    let data = json!({
-        "blogTitle": "blog title",
-        "author": "chris",
-        "posts": [
-            {
-                "postTitle": "post 1"
-            },
-            {
-                "postTitle": "post 2"
-            }
-        ]
-    });
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "blog title, posts: post 1 by chris, post 2 by chris, ";
-    assert_eq!(rendered, expected);
+fn miri_iso_resolve_value_numeric_object_key() {
+    assert_eq!(
+        resolve_value(
+            "0",
+            &[&json!({"0": "x"})],
+            None,
+        ),
+        &json!("x"),
+    );
+    assert_eq!(
+        resolve_value(
+            "a.1.b",
+            &[&json!({"a": {"1": {"b": "y"}}})],
+            None,
+        ),
+        &json!("y"),
+    );
 }
 
 #[test]
-fn miri_iso_render_section_object_parent_scope_utf8() {
-    let source = "{{ blogTitle }}, posts: {{# posts }}{{ postTitle }} by {{ author }}, {{/ posts}}";
-    let data = json!({
-        "blogTitle": "🦀 title",
-        "author": "🦀",
-        "posts": [
-            {
-                "postTitle": "🦀 1"
-            },
-            {
-                "postTitle": "🦀 2"
-            }
-        ]
-    });
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "🦀 title, posts: 🦀 1 by 🦀, 🦀 2 by 🦀, ";
-    assert_eq!(rendered, expected);
+fn miri_iso_resolve_value_numeric_mixed_parent_scopes() {
+    // innermost array scope claims numeric keys
+    assert_eq!(
+        resolve_value(
+            "0",
+            &[&json!({"0": "object"}), &json!(["array"])],
+            None,
+        ),
+        &json!("array"),
+    );
+
+    // innermost object scope claims numeric keys it has
+    assert_eq!(
+        resolve_value(
+            "0",
+            &[&json!(["array"]), &json!({"0": "object"})],
+            None,
+        ),
+        &json!("object"),
+    );
+
+    // innermost object scope doesn't have the numeric
+    // key so it falls thru to the parent array scope
+    assert_eq!(
+        resolve_value(
+            "0",
+            &[&json!(["array"]), &json!({"a": "object"})],
+            None,
+        ),
+        &json!("array"),
+    );
+
+    // out of bounds array indexes still don't fall
+    // thru, even if a parent object has the key
+    assert_eq!(
+        resolve_value(
+            "0",
+            &[&json!({"0": "object"}), &json!([])],
+            None,
+        ),
+        &json!(null),
+    );
 }
 
 #[test]
-fn miri_iso_render_section_array_multi_escaped() {
-    let source = "{{# . }}{{ . }}{{/ . }}";
-    let data = json!(["&", "<", ">", "\"", "'"]);
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "&amp;&lt;&gt;&quot;&#x27;";
-    assert_eq!(rendered, expected);
+fn miri_iso_try_resolve_value_absent_vs_null() {
+    let scopes: &[&serde_json::Value] = &[&json!({"a": null, "b": [], "c": {"d": [1]}})];
+    assert_eq!(try_resolve_value("a", scopes, None), Some(&json!(null)));
+    assert_eq!(try_resolve_value("b", scopes, None), Some(&json!([])));
+    assert_eq!(try_resolve_value("c.d.0", scopes, None), Some(&json!(1)));
+    assert_eq!(try_resolve_value(".", scopes, None), Some(scopes[0]));
+    assert_eq!(try_resolve_value("missing", scopes, None), None);
+    assert_eq!(try_resolve_value("a.missing", scopes, None), None);
+    assert_eq!(try_resolve_value("b.0", scopes, None), None);
+    assert_eq!(try_resolve_value("c.d.1", scopes, None), None);
+    assert_eq!(try_resolve_value("c.d.e", scopes, None), None);
+    // resolve_value can't tell the difference
+    assert_eq!(resolve_value("a", scopes, None), &json!(null));
+    assert_eq!(resolve_value("missing", scopes, None), &json!(null));
 }
 
 #[test]
-fn miri_iso_render_section_array_multi_unescaped() {
-    let source = "{{# . }}{{{ . }}}{{/ . }}";
-    let data = json!(["&", "<", ">", "\"", "'"]);
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "&<>\"'";
-    assert_eq!(rendered, expected);
+fn miri_iso_resolve_value_enclosing_scope() {
+    let root = json!({"title": "root", "a": {"b": 1}});
+    let post = json!({"title": "post"});
+    let tag = json!("tag");
+    let scopes: &[&serde_json::Value] = &[&root, &post, &tag];
+    assert_eq!(resolve_value("title", scopes, None), &json!("post"));
+    assert_eq!(resolve_value("../title", scopes, None), &json!("post"));
+    assert_eq!(resolve_value("../.", scopes, None), &post);
+    assert_eq!(resolve_value("../a.b", scopes, None), &json!(1));
+    assert_eq!(resolve_value("../title", &scopes[..2], None), &json!("root"));
+    assert_eq!(try_resolve_value("../title", &scopes[..1], None), None);
+    assert_eq!(try_resolve_value("../.", &[], None), None);
 }
 
 #[test]
-fn miri_iso_render_section_array_multi_escaped_mixed() {
-    let source = "{{ . }}";
-    let data = json!("&g<>\"g'🦀g&<g>🦀\"'g");
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "&amp;g&lt;&gt;&quot;g&#x27;🦀g&amp;&lt;g&gt;🦀&quot;&#x27;g";
-    assert_eq!(rendered, expected);
+fn miri_iso_write_value_null() {
+    let mut writer = Vec::new();
+    let _ = write_value(&json!(null), &RenderOptions::default(), &mut writer);
+    assert!(writer.is_empty());
 }
 
-#[test]
-fn miri_iso_render_section_array_multi_unescaped_mixed() {
-    let source = "{{{ . }}}";
-    let data = json!("&g<>\"g'🦀g&<g>🦀\"'g");
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "&g<>\"g'🦀g&<g>🦀\"'g";
-    assert_eq!(rendered, expected);
+////////////////////////
+// TEST ESCAPING HTML //
+////////////////////////
+
+fn escape_html_with(config: HtmlEscapeConfig, source: &str) -> String {
+    let mut escaped = Vec::new();
+    HtmlEscapeWriter::with_config(&mut escaped, config)
+        .write_all(source.as_bytes())
+        .unwrap();
+    String::from_utf8(escaped).unwrap()
 }
 
 #[test]
-fn miri_iso_render_section_object() {
-    let source = "{{# . }}{{ . }}{{/ . }}";
-    let data = json!({ "some": "field" });
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "{&quot;some&quot;:&quot;field&quot;}";
-    assert_eq!(rendered, expected);
+fn miri_iso_escape_html_default_entities() {
+    let escaped = escape_html_with(HtmlEscapeConfig::default(), "<'🦀' & \"🦀\">");
+    assert_eq!(escaped, "&lt;&#x27;🦀&#x27; &amp; &quot;🦀&quot;&gt;");
 }
 
 #[test]
-fn miri_iso_render_section_object_utf8() {
-    let source = "{{# . }}{{ . }}{{/ . }}";
-    let data = json!({ "some": "🦀" });
-    let template = Template::parse(source.to_owned()).unwrap();
+fn miri_iso_escape_html_matches_render() {
+    let template = Template::parse("{{ . }}").unwrap();
+    for source in ["<a href=\"x\">", "<'🦀' & \"🦀\">", "no escaping", ""] {
+        let rendered = template.render_no_partials_to_string(&json!(source)).unwrap();
+        assert_eq!(escape_html(source), rendered);
+    }
+    assert_eq!(escape_html("<a href=\"x\">"), "&lt;a href=&quot;x&quot;&gt;");
+}
+
+#[test]
+fn miri_iso_escape_html_apostrophe_entities() {
+    let source = "it's";
+    let escaped = escape_html_with(HtmlEscapeConfig {
+        apostrophe: ApostropheEntity::Hex,
+        ..HtmlEscapeConfig::default()
+    }, source);
+    assert_eq!(escaped, "it&#x27;s");
+    let escaped = escape_html_with(HtmlEscapeConfig {
+        apostrophe: ApostropheEntity::Decimal,
+        ..HtmlEscapeConfig::default()
+    }, source);
+    assert_eq!(escaped, "it&#39;s");
+    let escaped = escape_html_with(HtmlEscapeConfig {
+        apostrophe: ApostropheEntity::Named,
+        ..HtmlEscapeConfig::default()
+    }, source);
+    assert_eq!(escaped, "it&apos;s");
+}
+
+#[test]
+fn miri_iso_escape_html_quote_entities() {
+    let source = "\"hi\"";
+    let escaped = escape_html_with(HtmlEscapeConfig {
+        quote: QuoteEntity::Named,
+        ..HtmlEscapeConfig::default()
+    }, source);
+    assert_eq!(escaped, "&quot;hi&quot;");
+    let escaped = escape_html_with(HtmlEscapeConfig {
+        quote: QuoteEntity::Hex,
+        ..HtmlEscapeConfig::default()
+    }, source);
+    assert_eq!(escaped, "&#x22;hi&#x22;");
+    let escaped = escape_html_with(HtmlEscapeConfig {
+        quote: QuoteEntity::Decimal,
+        ..HtmlEscapeConfig::default()
+    }, source);
+    assert_eq!(escaped, "&#34;hi&#34;");
+}
+
+#[test]
+fn miri_iso_escape_html_subset_of_chars() {
+    let source = "<a title=\"it's\">&</a>";
+    let config = HtmlEscapeConfig {
+        chars: EscapedChars::AMPERSAND | EscapedChars::LESS_THAN | EscapedChars::GREATER_THAN,
+        ..HtmlEscapeConfig::default()
+    };
+    let escaped = escape_html_with(config, source);
+    assert_eq!(escaped, "&lt;a title=\"it's\"&gt;&amp;&lt;/a&gt;");
+    let config = HtmlEscapeConfig {
+        chars: EscapedChars::QUOTE,
+        ..HtmlEscapeConfig::default()
+    };
+    let escaped = escape_html_with(config, source);
+    assert_eq!(escaped, "<a title=&quot;it's&quot;>&</a>");
+    let config = HtmlEscapeConfig {
+        chars: EscapedChars::NONE,
+        attribute_safe: true,
+        ..HtmlEscapeConfig::default()
+    };
+    let escaped = escape_html_with(config, "<a b>");
+    assert_eq!(escaped, "<a&#x20;b>");
+    assert_eq!(HtmlEscapeConfig::default().chars, EscapedChars::ALL);
+    assert!(EscapedChars::ALL.contains(EscapedChars::QUOTE | EscapedChars::APOSTROPHE));
+    assert!(!EscapedChars::AMPERSAND.contains(EscapedChars::ALL));
+}
+
+#[test]
+fn miri_iso_render_with_escape_config() {
+    let source = "{{ a }} {{{ a }}}";
+    let data = json!({"a": "'\""});
+    let template = Template::parse(source).unwrap();
+    let options = RenderOptions {
+        escaper: Escaper::Html(HtmlEscapeConfig {
+            apostrophe: ApostropheEntity::Named,
+            quote: QuoteEntity::Hex,
+            ..HtmlEscapeConfig::default()
+        }),
+        ..RenderOptions::default()
+    };
+    let mut rendered = Vec::new();
+    template.render_with_options(&(), &data, &options, &mut rendered).unwrap();
+    assert_eq!(rendered, b"&apos;&#x22; '\"");
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, "&#x27;&quot; '\"");
+}
+
+#[test]
+fn miri_iso_escape_html_attribute_safe() {
+    let source = "a` b=c\t\n\r\x0Cd";
+    let escaped = escape_html_with(HtmlEscapeConfig::default(), source);
+    assert_eq!(escaped, source);
+    let config = HtmlEscapeConfig {
+        attribute_safe: true,
+        ..HtmlEscapeConfig::default()
+    };
+    let escaped = escape_html_with(config, source);
+    assert_eq!(escaped, "a&#x60;&#x20;b&#x3D;c&#x9;&#xA;&#xD;&#xC;d");
+    let escaped = escape_html_with(config, "<'\"&>");
+    assert_eq!(escaped, "&lt;&#x27;&quot;&amp;&gt;");
+
+    let template = Template::parse("<div class={{ class }}>{{ class }}</div>").unwrap();
+    let data = json!({"class": "a onclick=alert(1)"});
+    let options = RenderOptions {
+        escaper: Escaper::Html(config),
+        ..RenderOptions::default()
+    };
+    let mut rendered = Vec::new();
+    template.render_with_options(&(), &data, &options, &mut rendered).unwrap();
+    assert_eq!(
+        String::from_utf8(rendered).unwrap(),
+        "<div class=a&#x20;onclick&#x3D;alert(1)>a&#x20;onclick&#x3D;alert(1)</div>",
+    );
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, "<div class=a onclick=alert(1)>a onclick=alert(1)</div>");
+}
+
+// only accepts up to limit bytes per write,
+// and if flaky fails every other write
+struct ShortWriter {
+    written: Vec<u8>,
+    limit: usize,
+    flaky: bool,
+    writes: usize,
+}
+
+impl Write for ShortWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes += 1;
+        if self.flaky && self.writes.is_multiple_of(2) {
+            return Err(io::ErrorKind::Other.into());
+        }
+        let written = buf.len().min(self.limit);
+        self.written.extend_from_slice(&buf[..written]);
+        Ok(written)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn miri_iso_escape_html_short_writes() {
+    let input = "<a href=\"/?a=1&b='🦀'\">&&</a>";
+    let expected = "&lt;a href=&quot;/?a=1&amp;b=&#x27;🦀&#x27;&quot;&gt;&amp;&amp;&lt;/a&gt;";
+    for limit in 1..=6 {
+        let mut writer = ShortWriter { written: Vec::new(), limit, flaky: false, writes: 0 };
+        HtmlEscapeWriter::new(&mut writer).write_all(input.as_bytes()).unwrap();
+        assert_eq!(String::from_utf8(writer.written).unwrap(), expected);
+        // same thing but via a render
+        let mut writer = ShortWriter { written: Vec::new(), limit, flaky: false, writes: 0 };
+        let template = Template::parse("{{ a }}").unwrap();
+        template.render_no_partials(&json!({"a": input}), &mut writer).unwrap();
+        assert_eq!(String::from_utf8(writer.written).unwrap(), expected);
+    }
+}
+
+#[test]
+fn miri_iso_escape_html_failed_writes() {
+    let input = b"a&<b>''";
+    let expected = b"a&amp;&lt;b&gt;&#x27;&#x27;";
+    for limit in 1..=3 {
+        let mut writer = ShortWriter { written: Vec::new(), limit, flaky: true, writes: 0 };
+        let mut escaper = HtmlEscapeWriter::new(&mut writer);
+        // retry after every error, if write reported
+        // wrong counts the output would lose or repeat bytes
+        let mut buf = &input[..];
+        let mut errors = 0;
+        while !buf.is_empty() {
+            match escaper.write(buf) {
+                Ok(written) => {
+                    assert!(written > 0 && written <= buf.len());
+                    buf = &buf[written..];
+                },
+                Err(_) => errors += 1,
+            }
+        }
+        while escaper.flush().is_err() {
+            errors += 1;
+        }
+        assert!(errors > 0);
+        assert_eq!(writer.written, expected);
+    }
+}
+
+#[test]
+fn miri_iso_render_whole_floats_as_integers() {
+    let source = "{{ a }} {{{ a }}} {{ b }} {{ c }} {{ d }}";
+    let data = json!({"a": 2.0, "b": 2.5, "c": 2, "d": [1.0, -3.0]});
+    let template = Template::parse(source).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, "2.0 2.0 2.5 2 [1.0,-3.0]");
+    let options = RenderOptions {
+        whole_floats_as_integers: true,
+        ..RenderOptions::default()
+    };
+    let mut rendered = Vec::new();
+    template.render_with_options(&(), &data, &options, &mut rendered).unwrap();
+    assert_eq!(rendered, b"2 2 2.5 2 [1.0,-3.0]");
+}
+
+#[test]
+fn miri_iso_render_sorted_object_keys() {
+    let source = "{{{ . }}}|{{ nested }}";
+    let mut object = serde_json::Map::new();
+    for key in ["b", "c", "a"] {
+        object.insert(key.to_owned(), json!(key));
+    }
+    object.insert("nested".to_owned(), json!([{"z": 1, "y": {"x": 2, "w": 3}}]));
+    let data = serde_json::Value::Object(object);
+    let template = Template::parse(source).unwrap();
+    let options = RenderOptions {
+        sort_object_keys: true,
+        ..RenderOptions::default()
+    };
+    let mut rendered = Vec::new();
+    template.render_with_options(&(), &data, &options, &mut rendered).unwrap();
+    assert_eq!(
+        String::from_utf8(rendered).unwrap(),
+        r#"{"a":"a","b":"b","c":"c","nested":[{"y":{"w":3,"x":2},"z":1}]}|[{&quot;y&quot;:{&quot;w&quot;:3,&quot;x&quot;:2},&quot;z&quot;:1}]"#,
+    );
+}
+
+#[test]
+fn miri_iso_render_repeat_number_sections() {
+    let options = RenderOptions {
+        repeat_number_sections: true,
+        ..RenderOptions::default()
+    };
+    let render = |source: &'static str, data: &serde_json::Value, options: &RenderOptions| {
+        let template = Template::parse(source).unwrap();
+        let mut rendered = Vec::new();
+        template.render_with_options(&(), data, options, &mut rendered).unwrap();
+        String::from_utf8(rendered).unwrap()
+    };
+    assert_eq!(render("{{#.}}*{{/.}}", &json!(3), &options), "***");
+    assert_eq!(render("{{#.}}*{{/.}}", &json!(3), &RenderOptions::default()), "*");
+    let source = "{{#count}}[{{@index}} {{name}}]{{|}}none{{/count}}";
+    for (count, expected) in [
+        (json!(3), "[0 🦀][1 🦀][2 🦀]"),
+        (json!(2.9), "[0 🦀][1 🦀]"),
+        (json!(0.5), "none"),
+        (json!(0), "none"),
+        (json!(-2), "none"),
+        (json!(-0.5), "none"),
+    ] {
+        let data = json!({"count": count, "name": "🦀"});
+        assert_eq!(render(source, &data, &options), expected);
+    }
+    // nested repeats see their own index, other numbers
+    // still render once when not used as a section
+    let source = "{{#rows}}{{#cols}}{{@index}}{{/cols}}{{^cols}}-{{/cols}}|{{/rows}}{{rows}}";
+    let data = json!({"rows": 2, "cols": 3});
+    assert_eq!(render(source, &data, &options), "012|012|2");
+    // @index is empty outside of repeated sections
+    let data = json!({"count": 2, "items": [1, 2]});
+    assert_eq!(render("{{#items}}[{{@index}}]{{/items}}", &data, &options), "[][]");
+    assert_eq!(render("{{#count}}[{{@index}}]{{/count}}", &data, &RenderOptions::default()), "[]");
+}
+
+#[test]
+fn miri_iso_render_array_sections_as_scopes() {
+    let options = RenderOptions {
+        array_sections_as_scopes: true,
+        ..RenderOptions::default()
+    };
+    let render = |source: &'static str, data: &serde_json::Value, options: &RenderOptions| {
+        let template = Template::parse(source).unwrap();
+        let mut rendered = Vec::new();
+        template.render_with_options(&(), data, options, &mut rendered).unwrap();
+        String::from_utf8(rendered).unwrap()
+    };
+    let data = json!({"items": ["a", "b"], "empty": [], "title": "🦀"});
+    assert_eq!(render("{{#items}}{{0}}{{/items}}", &data, &options), "a");
+    assert_eq!(render("{{#items}}{{0}}{{/items}}", &data, &RenderOptions::default()), "");
+    assert_eq!(render("{{#items}}{{.}}{{/items}}", &data, &RenderOptions::default()), "ab");
+    // other keys are still resolved from enclosing scopes,
+    // and empty arrays are still falsy
+    assert_eq!(render("{{#items}}{{1}} {{title}}{{/items}}", &data, &options), "b 🦀");
+    assert_eq!(render("{{#empty}}{{0}}{{|}}none{{/empty}}", &data, &options), "none");
+    // the array itself is the scope, and aggregates aren't available
+    assert_eq!(render("{{#items}}{{{.}}}|{{@count}}{{/items}}", &data, &options), "[\"a\",\"b\"]|");
+}
+
+#[test]
+fn miri_iso_render_enclosing_scope_paths() {
+    let template = Template::parse("{{#posts}}{{title}} in {{../title}}{{#tags}}, {{.}} of {{../title}}{{/tags}}; {{/posts}}").unwrap();
+    let data = json!({"title": "blog", "posts": [
+        {"title": "a", "tags": ["x", "y"]},
+        {"title": "b", "tags": []},
+    ]});
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, "a in blog, x of a, y of a; b in blog; ");
+    // nothing encloses the root scope
+    let rendered = Template::parse("[{{../title}}]").unwrap().render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, "[]");
+}
+
+#[test]
+fn miri_iso_render_scalar_sections_keep_scope() {
+    let options = RenderOptions {
+        scalar_sections_keep_scope: true,
+        ..RenderOptions::default()
+    };
+    let render = |source: &'static str, data: &serde_json::Value, options: &RenderOptions| {
+        let template = Template::parse(source).unwrap();
+        let mut rendered = Vec::new();
+        template.render_with_options(&(), data, options, &mut rendered).unwrap();
+        String::from_utf8(rendered).unwrap()
+    };
+    let data = json!({"user": {"flag": true, "count": 2, "name": "🦀", "tags": ["a"]}});
+    let source = "{{#user}}{{#flag}}{{{.}}}{{/flag}}|{{#count}}{{{.}}}{{/count}}|{{#name}}{{{.}}}{{/name}}{{/user}}";
+    // by default the section's value shadows the enclosing scope
+    assert_eq!(render(source, &data, &RenderOptions::default()), "true|2|🦀");
+    let user = r#"{"count":2,"flag":true,"name":"🦀","tags":["a"]}"#;
+    assert_eq!(render(source, &data, &options), format!("{user}|{user}|{user}"));
+    // other keys resolve the same either way, and sections
+    // over arrays and objects still push their values
+    let source = "{{#user.flag}}{{user.name}}{{#user.tags}}{{.}}{{/user.tags}}{{/user.flag}}";
+    assert_eq!(render(source, &data, &RenderOptions::default()), "🦀a");
+    assert_eq!(render(source, &data, &options), "🦀a");
+    // falsy values still aren't rendered
+    assert_eq!(render("{{#.}}x{{|}}none{{/.}}", &json!(false), &options), "none");
+}
+
+#[cfg(feature = "helpers")]
+#[test]
+fn miri_iso_render_compare_sections() {
+    let render = |source: &'static str, data: &serde_json::Value| {
+        Template::parse(source).unwrap().render_no_partials_to_string(data).unwrap()
+    };
+    let data = json!({"status": "active", "n": 1, "x": 1.5, "flag": false, "one": "1", "user": {"name": "John"}});
+    // equal
+    assert_eq!(render("{{#eq status \"active\"}}yes{{/eq}}", &data), "yes");
+    assert_eq!(render("{{#eq n 1}}yes{{/eq}}{{#eq n 1.0}}yes{{/eq}}{{#eq x 1.5}}yes{{/eq}}", &data), "yesyesyes");
+    assert_eq!(render("{{#eq flag false}}yes{{/eq}}{{#eq missing null}}yes{{/eq}}", &data), "yesyes");
+    assert_eq!(render("{{#ne status \"inactive\"}}yes{{/ne}}{{#ne one 1}}yes{{/ne}}", &data), "yesyes");
+    // unequal
+    assert_eq!(render("{{#eq status \"inactive\"}}yes{{/eq}}{{#eq n 2}}yes{{/eq}}{{#eq one 1}}yes{{/eq}}", &data), "");
+    assert_eq!(render("{{#eq flag true}}yes{{/eq}}{{#eq missing \"\"}}yes{{/eq}}{{#eq n true}}yes{{/eq}}", &data), "");
+    assert_eq!(render("{{#ne status \"active\"}}yes{{/ne}}{{#ne n 1}}yes{{/ne}}", &data), "");
+    // content is rendered once, with the enclosing scope, and
+    // the else is rendered if the section wasn't
+    assert_eq!(render("{{#eq status \"active\"}}{{user.name}} {{.}}{{|}}no{{/eq}}", &data), "John active");
+    assert_eq!(render("{{#eq status \"inactive\"}}yes{{|}}{{.}}{{/eq}}", &data), "active");
+}
+
+#[cfg(feature = "helpers")]
+#[test]
+fn miri_iso_render_range_sections() {
+    let render = |source: &'static str, data: &serde_json::Value| {
+        Template::parse(source).unwrap().render_no_partials_to_string(data).unwrap()
+    };
+    let data = json!({"pages": 3, "first": -1, "title": "blog", "name": "x"});
+    // ascending, the end isn't included
+    assert_eq!(render("{{#range 1 5}}{{.}}{{/range}}", &data), "1234");
+    assert_eq!(render("{{#range first pages}}{{.}},{{/range}}", &data), "-1,0,1,2,");
+    // other names are still looked up in the enclosing scopes
+    assert_eq!(render("{{#range 0 2}}{{title}}{{.}} {{/range}}", &data), "blog0 blog1 ");
+    assert_eq!(render("{{#range 0 2}}{{#range 0 2}}{{.}}{{/range}}{{/range}}", &data), "0101");
+    // empty ranges render the else
+    assert_eq!(render("{{#range 5 5}}{{.}}{{|}}none{{/range}}", &data), "none");
+    assert_eq!(render("{{#range 5 1}}{{.}}{{|}}none{{/range}}", &data), "none");
+    assert_eq!(render("{{#range 0 missing}}{{.}}{{|}}none{{/range}}", &data), "none");
+    assert_eq!(render("{{#range 0 name}}{{.}}{{|}}none{{/range}}", &data), "none");
+    // missing bounds are reported
+    let template = Template::parse("{{#range 0 missing}}{{/range}}{{#range 0 pages}}{{nope}}{{/range}}").unwrap();
+    assert_eq!(template.missing_paths(&(), &data).unwrap(), ["missing", "nope"]);
+}
+
+#[cfg(feature = "helpers")]
+#[test]
+fn miri_iso_render_with_sections() {
+    let render = |source: &'static str, data: &serde_json::Value| {
+        Template::parse(source).unwrap().render_no_partials_to_string(data).unwrap()
+    };
+    let source = "{{#with user.profile}}{{name}} ({{title}}){{|}}nobody{{/with}}";
+    let data = json!({"title": "admin", "user": {"profile": {"name": "John"}}});
+    assert_eq!(render(source, &data), "John (admin)");
+    // null and missing values render the else
+    assert_eq!(render(source, &json!({"user": {"profile": null}})), "nobody");
+    assert_eq!(render(source, &json!({})), "nobody");
+    // arrays aren't iterated, they're the scope
+    let source = "{{#with items}}{{length}}: {{#.}}{{.}}{{/.}}{{/with}}";
+    assert_eq!(render(source, &json!({"items": [1, 2, 3], "length": 3})), "3: 123");
+    assert_eq!(render("{{#with items}}[{{.}}]{{/with}}", &json!({"items": []})), "[[]]");
+    // falsy values are still rendered
+    assert_eq!(render("{{#with n}}n={{.}}{{/with}}", &json!({"n": 0})), "n=0");
+    assert_eq!(render("{{#with s}}s={{.}}{{/with}}", &json!({"s": ""})), "s=");
+    assert_eq!(render("{{#with b}}b={{.}}{{/with}}", &json!({"b": false})), "b=false");
+    // missing paths are reported
+    let template = Template::parse("{{#with u}}{{a}}{{|}}{{b}}{{/with}}").unwrap();
+    assert_eq!(template.missing_paths(&(), &json!({"u": {}})).unwrap(), ["a"]);
+    assert_eq!(template.missing_paths(&(), &json!({})).unwrap(), ["u", "b"]);
+}
+
+#[cfg(feature = "helpers")]
+#[test]
+fn miri_iso_render_plural_sections() {
+    let render = |source: &'static str, data: &serde_json::Value| {
+        Template::parse(source).unwrap().render_no_partials_to_string(data).unwrap()
+    };
+    let source = "{{count}} {{#plural count}}items{{|}}item{{/plural}}";
+    // singular
+    assert_eq!(render(source, &json!({"count": 1})), "1 item");
+    assert_eq!(render(source, &json!({"count": -1})), "-1 item");
+    assert_eq!(render(source, &json!({"count": 1.0})), "1.0 item");
+    // plural
+    assert_eq!(render(source, &json!({"count": 2})), "2 items");
+    assert_eq!(render(source, &json!({"count": 0})), "0 items");
+    assert_eq!(render(source, &json!({"count": 1.5})), "1.5 items");
+    assert_eq!(render(source, &json!({"count": "1"})), "1 items");
+    assert_eq!(render(source, &json!({})), " items");
+    // content is rendered with the count as the scope, and
+    // without an else nothing is rendered if it's singular
+    let data = json!({"cart": {"count": 3}, "name": "John"});
+    assert_eq!(render("{{#plural cart.count}}{{name}} has {{.}} items{{/plural}}", &data), "John has 3 items");
+    assert_eq!(render("{{#plural n}}items{{/plural}}", &json!({"n": 1})), "");
+    // missing paths are reported
+    let template = Template::parse("{{#plural n}}{{a}}{{|}}{{b}}{{/plural}}").unwrap();
+    assert_eq!(template.missing_paths(&(), &json!({"n": 1})).unwrap(), ["b"]);
+    assert_eq!(template.missing_paths(&(), &json!({})).unwrap(), ["n", "a"]);
+}
+
+#[test]
+fn miri_iso_render_alias_sections() {
+    let render = |source: &'static str, data: &serde_json::Value| {
+        Template::parse(source).unwrap().render_no_partials_to_string(data).unwrap()
+    };
+    let data = json!({
+        "title": "blog",
+        "posts": [{"title": "a"}, {"title": "b", "tags": ["x", "y"]}],
+        "user": {"name": "John"},
+        "empty": [],
+    });
+    // the alias is unambiguous, other names are still
+    // looked up in the enclosing scopes
+    assert_eq!(render("{{#posts as post}}{{post.title}} in {{title}}, {{/posts}}", &data), "a in blog, b in blog, ");
+    assert_eq!(
+        render("{{#posts as post}}{{#post.tags as tag}}{{post.title}}{{tag}}{{/post.tags}}{{/posts}}", &data),
+        "bxby",
+    );
+    assert_eq!(render("{{#user as u}}{{u.name}} {{title}}{{/user}}", &data), "John blog");
+    // the alias shadows the same name in enclosing scopes, {{ . }}
+    // is the item, and ../ skips to the scope enclosing the section
+    assert_eq!(render("{{#posts as title}}{{title.title}}{{/posts}}", &data), "ab");
+    assert_eq!(render("{{#posts.1.tags as tag}}{{.}}{{tag}}{{../title}}{{/posts.1.tags}}", &data), "xxblogyyblog");
+    assert_eq!(render("{{#posts as post}}{{post.missing}}{{post.title.x}}{{/posts}}", &data), "");
+    // falsy values render the else
+    assert_eq!(render("{{#empty as e}}{{e}}{{|}}none{{/empty}}", &data), "none");
+    assert_eq!(render("{{#missing as m}}{{m}}{{|}}none{{/missing}}", &data), "none");
+    // counted by path
+    let template = Template::parse("{{#posts as post}}{{/posts}}").unwrap();
+    let (_, counts) = template.render_counting_sections(&(), &data).unwrap();
+    assert_eq!(counts, HashMap::from([("posts".to_owned(), 2)]));
+    // missing paths see the alias
+    let template = Template::parse("{{#posts as post}}{{post.tags.0}}{{post.title}}{{/posts}}{{#nope as n}}{{/nope}}").unwrap();
+    assert_eq!(template.missing_paths(&(), &data).unwrap(), ["post.tags.0", "nope"]);
+}
+
+#[test]
+fn miri_iso_render_section_aggregates() {
+    let render = |source: &'static str, data: &serde_json::Value| {
+        Template::parse(source).unwrap().render_no_partials_to_string(data).unwrap()
+    };
+    let data = json!({"items": [
+        {"name": "a", "price": 3},
+        {"name": "b", "price": 10},
+        {"name": "c", "price": -2},
+        {"name": "d"},
+    ]});
+    assert_eq!(render("{{#items}}{{name}}={{price}}/{{@sum:price}} {{/items}}", &data), "a=3/11 b=10/11 c=-2/11 d=/11 ");
+    assert_eq!(render("{{#items}}[{{@count}} {{@min:price}} {{@max:price}}]{{/items}}", &data), "[4 -2 10][4 -2 10][4 -2 10][4 -2 10]");
+    // sections over objects don't have aggregates
+    assert_eq!(render("{{#items.0}}[{{@count}}{{@min:price}}]{{/items.0}}", &data), "[]");
+    // floats, and sums which overflow integers, are summed as floats
+    let data = json!({"items": [{"n": 1.5}, {"n": 2}], "big": [{"n": i64::MAX}, {"n": 1}]});
+    assert_eq!(render("{{#items}}{{@sum:n}} {{@min:n}} {{@max:n}}|{{/items}}", &data), "3.5 1.5 2|3.5 1.5 2|");
+    assert_eq!(render("{{#big}}{{@sum:n}}{{/big}}", &data), "9.223372036854776e+189.223372036854776e+18");
+    // empty or missing fields, and widths
+    let data = json!({"items": [{"a": "x"}], "nested": [{"rows": [{"n": 1}, {"n": 2}]}]});
+    assert_eq!(render("{{#items}}[{{@sum:n}}|{{@min:n}}|{{@max:n}}|{{@count:3}}|{{@sum:n:>2}}]{{/items}}", &data), "[0|||1  | 0]");
+    // the innermost array section's aggregates are used
+    assert_eq!(render("{{#nested}}{{#rows}}{{@count}}{{/rows}}{{@count}}{{/nested}}", &data), "221");
+    // aggregates are empty outside of array sections
+    assert_eq!(render("[{{@count}}{{@sum:n}}]", &data), "[]");
+    assert_eq!(
+        Template::parse("{{ @avg:price }}").unwrap_err(),
+        MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new()),
+    );
+}
+
+#[test]
+fn miri_iso_parse_section_aggregates() {
+    let has_aggregates = |template: &Template| -> Vec<bool> {
+        template.skips.iter().map(|skip| skip.has_aggregates).collect()
+    };
+    // every section an aggregate is nested in is marked
+    let template = Template::parse("{{#a}}{{#b}}{{@count}}{{/b}}{{#c}}{{n}}{{/c}}{{/a}}{{#d}}{{/d}}").unwrap();
+    assert_eq!(has_aggregates(&template), [true, true, false, false]);
+    let template = Template::parse("{{#a}}{{x}}{{|}}{{@sum:n:3}}{{/a}}").unwrap();
+    assert_eq!(has_aggregates(&template), [false, true]);
+    // including aggregates inlined from partials
+    let loader = HashMapLoader::try_from(hashmap! {
+        "row" => "{{name}} {{@count}}",
+    }).unwrap();
+    let template = Template::parse("{{#a}}{{> row }}{{/a}}").unwrap();
+    assert_eq!(has_aggregates(&template), [false]);
+    let flattened = template.flatten(&loader).unwrap();
+    assert_eq!(has_aggregates(&flattened), [true]);
+    let data = json!({"a": [{"name": "x"}, {"name": "y"}]});
+    assert_eq!(flattened.render_no_partials_to_string(&data).unwrap(), "x 2y 2");
+}
+
+#[test]
+fn miri_iso_render_bool_strings() {
+    let source = "{{ . }} {{{ . }}}";
+    let template = Template::parse(source).unwrap();
+    let rendered = template.render_no_partials_to_string(&json!(true)).unwrap();
+    assert_eq!(rendered, "true true");
+    let options = RenderOptions {
+        bool_strings: BoolStrings {
+            true_str: "yes".into(),
+            false_str: "<no>".into(),
+        },
+        ..RenderOptions::default()
+    };
+    let render = |data: &serde_json::Value| {
+        let mut rendered = Vec::new();
+        template.render_with_options(&(), data, &options, &mut rendered).unwrap();
+        String::from_utf8(rendered).unwrap()
+    };
+    assert_eq!(render(&json!(true)), "yes yes");
+    assert_eq!(render(&json!(false)), "&lt;no&gt; <no>");
+    // booleans within arrays or objects are unaffected
+    assert_eq!(render(&json!([true, {"a": false}])), r#"[true,{&quot;a&quot;:false}] [true,{"a":false}]"#);
+}
+
+#[test]
+fn miri_iso_render_trailing_whitespace() {
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "page" => "  hello {{name}}\n\n \t\r\n",
+        "empty" => "{{name}}\n\n",
+    }).unwrap();
+    let data = json!({"name": "🦀"});
+    let mut render = |trailing_whitespace| {
+        loader.set_render_options(RenderOptions {
+            trailing_whitespace,
+            ..RenderOptions::default()
+        });
+        let template = loader.get("page").unwrap();
+        let rendered = template.render_to_string(&loader, &data).unwrap();
+        // rendering by name is the same, and so
+        // are other renders which return a String
+        assert_eq!(rendered, loader.render_to_string("page", &data).unwrap());
+        assert_eq!(rendered, template.render_tracking_partials(&loader, &data).unwrap().0);
+        assert_eq!(rendered, template.render_counting_sections(&loader, &data).unwrap().0);
+        let empty = loader.render_to_string("empty", &json!({})).unwrap();
+        (rendered, empty)
+    };
+    assert_eq!(render(TrailingWhitespace::Keep), ("  hello 🦀\n\n \t\r\n".into(), "\n\n".into()));
+    assert_eq!(render(TrailingWhitespace::Trim), ("  hello 🦀".into(), String::new()));
+    assert_eq!(render(TrailingWhitespace::Newline), ("  hello 🦀\n".into(), String::new()));
+    // it's normalized before post_render
+    loader.set_post_render(|rendered| format!("{rendered}\n\n"));
+    assert_eq!(loader.render_to_string("page", &data).unwrap(), "  hello 🦀\n\n\n");
+    let template = loader.get("page").unwrap();
+    assert_eq!(template.render_tracking_partials(&loader, &data).unwrap().0, "  hello 🦀\n\n\n");
+    assert_eq!(template.render_counting_sections(&loader, &data).unwrap().0, "  hello 🦀\n\n\n");
+    // writers get the output as is
+    let mut rendered = Vec::new();
+    loader.render("page", &data, &mut rendered).unwrap();
+    assert_eq!(rendered, "  hello 🦀\n\n \t\r\n".as_bytes());
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn miri_iso_render_to_async_writer() {
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    // async writer which fails every write
+    struct Broken;
+    impl tokio::io::AsyncWrite for Broken {
+        fn poll_write(self: Pin<&mut Self>, _: &mut Context<'_>, _: &[u8]) -> Poll<io::Result<usize>> {
+            Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+        }
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+    let loader = HashMapLoader::try_from(hashmap! {
+        "page" => "<b>{{name}}</b>{{#items}}{{.}}{{/items}}",
+        "broken" => "{{name}}{{> missing }}",
+    }).unwrap();
+    let data = json!({"name": "🦀 & co", "items": [1, 2, 3]});
+    let template = loader.get("page").unwrap();
+    let mut writer = Vec::new();
+    runtime.block_on(template.render_to_async_writer(&loader, &data, &mut writer)).unwrap();
+    assert_eq!(String::from_utf8(writer).unwrap(), template.render_to_string(&loader, &data).unwrap());
+    // failed renders don't write anything
+    let mut writer = Vec::new();
+    let template = loader.get("broken").unwrap();
+    let err = runtime.block_on(template.render_to_async_writer(&loader, &data, &mut writer)).unwrap_err();
+    assert_eq!(err, MoostacheError::LoaderErrorTemplateNotFound("missing".into()));
+    assert!(writer.is_empty());
+    // and errors from the writer are io errors
+    let template = loader.get("page").unwrap();
+    let err = runtime.block_on(template.render_to_async_writer(&loader, &data, &mut Broken)).unwrap_err();
+    assert_eq!(err, MoostacheError::IoError(String::new(), io::ErrorKind::BrokenPipe));
+}
+
+#[test]
+fn miri_iso_render_append() {
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "first" => "<b>{{name}}</b>\n",
+    }).unwrap();
+    let data = json!({"name": "🦀 & co", "items": [1, 2, 3]});
+    let first = Template::parse("<b>{{name}}</b>\n").unwrap();
+    let second = Template::parse("{{> first }}{{#items}}{{.}}{{/items}}").unwrap();
+    let mut rendered = String::from("start ");
+    first.render_append(&loader, &data, &mut rendered).unwrap();
+    second.render_append(&loader, &data, &mut rendered).unwrap();
+    let expected = format!(
+        "start {}{}",
+        first.render_to_string(&loader, &data).unwrap(),
+        second.render_to_string(&loader, &data).unwrap(),
+    );
+    assert_eq!(rendered, expected);
+    assert_eq!(rendered, "start <b>🦀 &amp; co</b>\n<b>🦀 &amp; co</b>\n123");
+    // failed renders leave the string unchanged, even if
+    // they had written some output already
+    loader.set_render_options(RenderOptions {
+        max_output_bytes: Some(8),
+        ..RenderOptions::default()
+    });
+    let err = second.render_append(&loader, &data, &mut rendered).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorOutputTooLarge);
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_to_string_checked() {
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "page" => "<b>{{name}}</b> {{> greet }}\n",
+        "greet" => "hello {{{name}}}",
+    }).unwrap();
+    loader.set_render_options(RenderOptions {
+        trailing_whitespace: TrailingWhitespace::Trim,
+        ..RenderOptions::default()
+    });
+    let template = loader.get("page").unwrap();
+    let data = json!({"name": "🦀 & \u{10FFFF}"});
+    // same output as the unchecked render
+    let rendered = template.render_to_string_checked(&loader, &data).unwrap();
+    assert_eq!(rendered, template.render_to_string(&loader, &data).unwrap());
+    assert_eq!(rendered, "<b>🦀 &amp; \u{10FFFF}</b> hello 🦀 & \u{10FFFF}");
+    // invalid utf8, e.g. from a misbehaving escaper, is caught
+    assert_eq!(checked_utf8(b"ok".to_vec()), Ok("ok".to_owned()));
+    for bytes in [&b"\xff"[..], b"a\xc3", b"\xed\xa0\x80", b"\xf0\x9f\xa6"] {
+        assert_eq!(checked_utf8(bytes.to_vec()), Err(MoostacheError::RenderErrorInvalidUtf8));
+    }
+}
+
+#[test]
+fn miri_iso_render_collapse_whitespace() {
+    #[derive(serde_derive::Serialize)]
+    struct Flat {
+        a: u8,
+    }
+    let source = "<ul>\n    {{#items}}\n    <li>  {{.}}</li>\n{{/items}}\t\r\n</ul> <p>a b</p>\n";
+    let template = Template::parse(source).unwrap();
+    let data = json!({"items": ["x  y\n"]});
+    let options = RenderOptions {
+        collapse_whitespace: true,
+        ..RenderOptions::default()
+    };
+    let mut rendered = Vec::new();
+    template.render_with_options(&(), &data, &options, &mut rendered).unwrap();
+    // whitespace in variables isn't collapsed, and each
+    // literal is collapsed separately, so whitespace on
+    // both sides of a tag leaves a space on either side
+    assert_eq!(String::from_utf8(rendered).unwrap(), "<ul>  <li> x  y\n</li>  </ul> <p>a b</p> ");
+    // off by default
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, "<ul>\n    \n    <li>  x  y\n</li>\n\t\r\n</ul> <p>a b</p>\n");
+    // flat templates are collapsed too
+    let template = Template::parse("  {{a}}\n\n{{a}} ").unwrap();
+    let mut rendered = Vec::new();
+    template.render_flat_serializable(&(), &Flat { a: 1 }, &mut rendered).unwrap();
+    assert_eq!(rendered, b"  1\n\n1 ");
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "flat" => "  {{a}}\n\n{{a}} ",
+    }).unwrap();
+    loader.set_render_options(options);
+    let mut rendered = Vec::new();
+    loader.get("flat").unwrap().render_flat_serializable(&loader, &Flat { a: 1 }, &mut rendered).unwrap();
+    assert_eq!(rendered, b" 1 1 ");
+}
+
+#[test]
+fn miri_iso_render_strict_container_variables() {
+    let template = Template::parse("{{ . }}").unwrap();
+    let data = json!({"a": 1});
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, "{&quot;a&quot;:1}");
+    let options = RenderOptions {
+        strict_container_variables: true,
+        ..RenderOptions::default()
+    };
+    let render = |source: &'static str, data: &serde_json::Value| {
+        let template = Template::parse(source).unwrap();
+        let mut rendered = Vec::new();
+        template.render_with_options(&(), data, &options, &mut rendered)
+            .map(|()| String::from_utf8(rendered).unwrap())
+    };
+    assert_eq!(render("{{ . }}", &data), Err(MoostacheError::RenderErrorCannotStringifyContainer(".".into())));
+    let data = json!({"a": {"b": [1]}, "c": "d"});
+    assert_eq!(render("{{{ a.b }}}", &data), Err(MoostacheError::RenderErrorCannotStringifyContainer("a.b".into())));
+    assert_eq!(render("{{ a:5 }}", &data), Err(MoostacheError::RenderErrorCannotStringifyContainer("a".into())));
+    // sections over containers and other values are unaffected
+    assert_eq!(render("{{# a.b }}{{ . }}{{/ a.b }}{{ c }}", &data), Ok("1d".into()));
+}
+
+#[test]
+fn miri_iso_memoize_paths() {
+    let data = json!({
+        "x": "root",
+        "a": {"x": "a"},
+        "items": [{"x": 1}, {"y": 2}, {"x": 3}],
+        "empty": [],
+    });
+    let template = Template::parse(concat!(
+        "{{x}}{{#a}}{{x}}{{x}}{{/a}}{{x}}|",
+        "{{#items}}{{x}}{{x}}{{y}},{{/items}}|",
+        "{{#empty}}{{x}}{{|}}{{x}}{{/empty}}{{^empty}}{{x}}{{/empty}}|",
+        "{{#items}}{{#a}}{{x}}{{/a}}{{/items}}",
+    )).unwrap();
+    let render = |options: &RenderOptions| {
+        let mut rendered = Vec::new();
+        template.render_with_options(&(), &data, options, &mut rendered).unwrap();
+        String::from_utf8(rendered).unwrap()
+    };
+    let memoized = RenderOptions {
+        memoize_paths: true,
+        ..RenderOptions::default()
+    };
+    let expected = "rootaaroot|11,rootroot2,33,|rootroot|aaa";
+    assert_eq!(render(&RenderOptions::default()), expected);
+    // sections get their own memo, so paths
+    // resolved in one item don't leak into others
+    assert_eq!(render(&memoized), expected);
+}
+
+#[test]
+fn miri_iso_escape_json() {
+    let data = json!({"a": "\"🦀\\\n\t\u{1}<'>", "b": {"c": "\"d\""}});
+    let template = Template::parse("{{ a }}|{{ b }}").unwrap();
+    let options = RenderOptions {
+        escaper: Escaper::Json,
+        ..RenderOptions::default()
+    };
+    let mut rendered = Vec::new();
+    template.render_with_options(&(), &data, &options, &mut rendered).unwrap();
+    assert_eq!(
+        String::from_utf8(rendered).unwrap(),
+        r#"\"🦀\\\n\t\u0001<'>|{\"c\":\"\\\"d\\\"\"}"#,
+    );
+}
+
+#[test]
+fn miri_iso_escape_css() {
+    let data = json!({"a": "red}</style><script>", "b": "a-1 é🦀", "c": 1.5});
+    let template = Template::parse("{{ a }}|{{ b }}|{{ c }}|{{{ a }}}").unwrap();
+    let options = RenderOptions {
+        escaper: Escaper::Css,
+        ..RenderOptions::default()
+    };
+    let mut rendered = Vec::new();
+    template.render_with_options(&(), &data, &options, &mut rendered).unwrap();
+    assert_eq!(
+        String::from_utf8(rendered).unwrap(),
+        r"red\7d \3c \2f style\3e \3c script\3e |a\2d 1\20 \e9 🦀|1\2e 5|red}</style><script>",
+    );
+}
+
+#[test]
+fn miri_iso_escape_none() {
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "escaped" => "{{ a }}|{{ b }}|{{ c }}|{{ a:12 }}|{{> partial }}",
+        "unescaped" => "{{{ a }}}|{{{ b }}}|{{{ c }}}|{{{ a:12 }}}|{{> partial }}",
+        "partial" => "{{ a }}",
+    }).unwrap();
+    loader.set_render_options(RenderOptions {
+        escaper: Escaper::None,
+        ..RenderOptions::default()
+    });
+    let data = json!({"a": "<'&\"🦀\\>", "b": {"c": "<d>"}, "c": 1.5});
+    let escaped = loader.render_to_string("escaped", &data).unwrap();
+    let unescaped = loader.render_to_string("unescaped", &data).unwrap();
+    assert_eq!(escaped.as_bytes(), unescaped.as_bytes());
+    assert_eq!(escaped, r#"<'&"🦀\>|{"c":"<d>"}|1.5|<'&"🦀\>     |<'&"🦀\>"#);
+    let data = hashmap! {"a" => "<b>", "c" => "&"};
+    let escaped = loader.render_serializable_to_string("escaped", &data).unwrap();
+    let unescaped = loader.render_serializable_to_string("unescaped", &data).unwrap();
+    assert_eq!(escaped, unescaped);
+    assert_eq!(escaped, "<b>||&|<b>         |<b>");
+}
+
+#[test]
+fn miri_iso_render_with_loader_escaper() {
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "json" => r#"{"a": "{{a}}", "b": "{{> b }}"}"#,
+        "b" => "{{b}}",
+    }).unwrap();
+    loader.set_render_options(RenderOptions {
+        escaper: Escaper::Json,
+        ..RenderOptions::default()
+    });
+    for (a, b) in [("\"quoted\"", "<b>"), ("back\\slash", "say \"hi\"")] {
+        let data = json!({"a": a, "b": b});
+        let rendered = loader.render_to_string("json", &data).unwrap();
+        let parsed: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+        assert_eq!(parsed, data);
+    }
+    // options passed to the render still win
+    let template = loader.get("b").unwrap();
+    let mut rendered = Vec::new();
+    let data = json!({"b": "\"<b>\""});
+    template.render_with_options(&loader, &data, &RenderOptions::default(), &mut rendered).unwrap();
+    assert_eq!(rendered, b"&quot;&lt;b&gt;&quot;");
+}
+
+///////////////////////////////////////////////
+// TEST RENDERING TEMPLATES WITHOUT PARTIALS //
+///////////////////////////////////////////////
+
+#[test]
+fn miri_iso_render_section_object_parent_scope() {
+    let source = "{{ blogTitle }}, posts: {{# posts }}{{ postTitle }} by {{ author }}, {{/ posts}}";
+    let data = json!({
+        "blogTitle": "blog title",
+        "author": "chris",
+        "posts": [
+            {
+                "postTitle": "post 1"
+            },
+            {
+                "postTitle": "post 2"
+            }
+        ]
+    });
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "blog title, posts: post 1 by chris, post 2 by chris, ";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_section_object_parent_scope_utf8() {
+    let source = "{{ blogTitle }}, posts: {{# posts }}{{ postTitle }} by {{ author }}, {{/ posts}}";
+    let data = json!({
+        "blogTitle": "🦀 title",
+        "author": "🦀",
+        "posts": [
+            {
+                "postTitle": "🦀 1"
+            },
+            {
+                "postTitle": "🦀 2"
+            }
+        ]
+    });
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "🦀 title, posts: 🦀 1 by 🦀, 🦀 2 by 🦀, ";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_section_array_multi_escaped() {
+    let source = "{{# . }}{{ . }}{{/ . }}";
+    let data = json!(["&", "<", ">", "\"", "'"]);
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "&amp;&lt;&gt;&quot;&#x27;";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_section_array_multi_unescaped() {
+    let source = "{{# . }}{{{ . }}}{{/ . }}";
+    let data = json!(["&", "<", ">", "\"", "'"]);
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "&<>\"'";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_section_array_multi_escaped_mixed() {
+    let source = "{{ . }}";
+    let data = json!("&g<>\"g'🦀g&<g>🦀\"'g");
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "&amp;g&lt;&gt;&quot;g&#x27;🦀g&amp;&lt;g&gt;🦀&quot;&#x27;g";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_section_array_multi_unescaped_mixed() {
+    let source = "{{{ . }}}";
+    let data = json!("&g<>\"g'🦀g&<g>🦀\"'g");
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "&g<>\"g'🦀g&<g>🦀\"'g";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_section_object() {
+    let source = "{{# . }}{{ . }}{{/ . }}";
+    let data = json!({ "some": "field" });
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "{&quot;some&quot;:&quot;field&quot;}";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_section_object_utf8() {
+    let source = "{{# . }}{{ . }}{{/ . }}";
+    let data = json!({ "some": "🦀" });
+    let template = Template::parse(source.to_owned()).unwrap();
     let rendered = template.render_no_partials_to_string(&data).unwrap();
     let expected = "{&quot;some&quot;:&quot;🦀&quot;}";
     assert_eq!(rendered, expected);
 }
 
+#[test]
+fn miri_iso_render_section_object_same_as_single_item_array() {
+    let source = "{{#item}}[{{name}} {{parent}} {{#.}}{{name}}{{/.}}]{{/item}}";
+    let template = Template::parse(source).unwrap();
+    let object = json!({"parent": "p", "item": {"name": "a"}});
+    let array = json!({"parent": "p", "item": [{"name": "a"}]});
+    let rendered_object = template.render_no_partials_to_string(&object).unwrap();
+    let rendered_array = template.render_no_partials_to_string(&array).unwrap();
+    assert_eq!(rendered_object, "[a p a]");
+    assert_eq!(rendered_object, rendered_array);
+}
+
+#[test]
+fn miri_iso_render_section_scalar_same_as_single_item_array() {
+    let source = "{{#item}}[{{.}} {{parent}}]{{/item}}";
+    let template = Template::parse(source).unwrap();
+    let scalar = json!({"parent": "p", "item": 7});
+    let array = json!({"parent": "p", "item": [7]});
+    let rendered_scalar = template.render_no_partials_to_string(&scalar).unwrap();
+    let rendered_array = template.render_no_partials_to_string(&array).unwrap();
+    assert_eq!(rendered_scalar, "[7 p]");
+    assert_eq!(rendered_scalar, rendered_array);
+}
+
 #[test]
 fn miri_iso_render_section_array_single() {
     let source = "{{# . }}{{ . }}{{/ . }}";
@@ -1068,6 +2839,31 @@ fn miri_iso_render_inverted_section_null() {
     assert_eq!(rendered, expected);
 }
 
+#[test]
+fn miri_iso_render_missing_section() {
+    let source = "{{^? items }}absent{{/ items }}{{^ items }}empty{{/ items }}";
+    let template = Template::parse(source).unwrap();
+    for (data, expected) in [
+        (json!({}), "absentempty"),
+        (json!({"items": []}), "empty"),
+        (json!({"items": null}), "empty"),
+        (json!({"items": false}), "empty"),
+        (json!({"items": [1]}), ""),
+    ] {
+        let rendered = template.render_no_partials_to_string(&data).unwrap();
+        assert_eq!(rendered, expected);
+    }
+    // paths are looked up in parent scopes too
+    let source = "{{#users}}{{^? name }}{{^? title }}anonymous{{/ title }}{{/ name }}{{name}},{{/users}}";
+    let template = Template::parse(source).unwrap();
+    let data = json!({"users": [{"name": "🦀"}, {}, {"name": ""}]});
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, "🦀,anonymous,,");
+    let data = json!({"title": "admin", "users": [{}]});
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, ",");
+}
+
 #[test]
 fn miri_iso_render_section_empty_object() {
     let source = "{{# . }}lol{{/ . }}";
@@ -1178,6 +2974,16 @@ fn miri_iso_render_unescaped_nested_index_string() {
     assert_eq!(rendered, expected);
 }
 
+#[test]
+fn miri_iso_render_escaped_numeric_object_key() {
+    let source = "{{0}} {{ a.1 }}";
+    let data = json!({"0": "x", "a": {"1": "y"}});
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "x y";
+    assert_eq!(rendered, expected);
+}
+
 #[test]
 fn miri_iso_render_unescaped_key_string() {
     let source = "hello {{{ name }}}!";
@@ -1269,115 +3075,680 @@ fn miri_iso_render_unescaped_dot_object() {
 }
 
 #[test]
-fn miri_iso_render_unescaped_dot_object_utf8() {
-    let source = "hello {{{ . }}}!";
-    let data = json!({"some": "🦀"});
+fn miri_iso_render_unescaped_dot_object_utf8() {
+    let source = "hello {{{ . }}}!";
+    let data = json!({"some": "🦀"});
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "hello {\"some\":\"🦀\"}!";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_unescaped_dot_array() {
+    let source = "hello {{{ . }}}!";
+    let data = json!([1, "string", null]);
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "hello [1,\"string\",null]!";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_unescaped_dot_array_utf8() {
+    let source = "hello {{{ . }}}!";
+    let data = json!([1, "🦀", null]);
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "hello [1,\"🦀\",null]!";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_unescaped_dot_null() {
+    let source = "hello {{{ . }}}!";
+    let data = json!(null);
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "hello !";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_unescaped_dot_float() {
+    let source = "hello {{{ . }}}!";
+    let data = json!(123.5);
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "hello 123.5!";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_unescaped_dot_integer() {
+    let source = "hello {{{ . }}}!";
+    let data = json!(123);
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "hello 123!";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_unescaped_dot_string() {
+    let source = "hello {{{ . }}}!";
+    let data = json!("world");
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "hello world!";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_many_objects_same_as_serde_json() {
+    let mut items: Vec<_> = (0..100)
+        .map(|i| json!({"id": i, "name": format!("<item {i}>"), "tags": ["a", "'b'"]}))
+        .collect();
+    // larger than the reusable serialization buffer
+    items.push(json!({"big": "🦀".repeat(100 * 1024)}));
+    let data = json!({"items": items});
+    let template = Template::parse("{{#items}}{{{.}}}{{/items}}").unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected: Vec<String> = items.iter().map(ToString::to_string).collect();
+    assert_eq!(rendered, expected.concat());
+    let template = Template::parse("{{#items}}{{.}}{{/items}}").unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(rendered, escape_html_with(HtmlEscapeConfig::default(), &expected.concat()));
+}
+
+#[test]
+fn miri_iso_render_serializable_string() {
+    let source = "hello {{{ . }}}!";
+    let data = "world".to_owned();
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_serializable_no_partials_to_string(&data).unwrap();
+    let expected = "hello world!";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_serializable_struct() {
+    #[derive(serde_derive::Serialize)]
+    struct Person {
+        name: &'static str,
+    }
+    let source = "hello {{ name }}!";
+    let data = Person {
+        name: "homer"
+    };
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_serializable_no_partials_to_string(&data).unwrap();
+    let expected = "hello homer!";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_serializable_struct_utf8() {
+    #[derive(serde_derive::Serialize)]
+    struct Person {
+        name: &'static str,
+    }
+    let source = "hello {{ name }}!";
+    let data = Person {
+        name: "🦀"
+    };
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_serializable_no_partials_to_string(&data).unwrap();
+    let expected = "hello 🦀!";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_serializable_error_message() {
+    struct Secret;
+    impl serde::Serialize for Secret {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("secret can't be serialized"))
+        }
+    }
+    #[derive(serde_derive::Serialize)]
+    struct Account {
+        name: &'static str,
+        secret: Secret,
+    }
+    let data = Account {
+        name: "John",
+        secret: Secret,
+    };
+    let template = Template::parse("{{ name }}").unwrap();
+    let err = template.render_serializable_no_partials_to_string(&data).unwrap_err();
+    assert_eq!(err, MoostacheError::SerializationError("secret can't be serialized".into()));
+    assert!(err.to_string().ends_with(": secret can't be serialized"));
+
+    let data = hashmap! {(1, 2) => "a"};
+    let err = template.render_serializable_no_partials_to_string(&data).unwrap_err();
+    assert_eq!(err, MoostacheError::SerializationError("key must be a string".into()));
+}
+
+// renders with render_flat_serializable and checks
+// the output matches render_serializable's
+fn assert_flat_matches<K: Borrow<str> + Eq + Hash, S: serde::Serialize>(loader: &HashMapLoader<K>, source: &'static str, data: &S, expected: &str) {
+    let template = Template::parse(source).unwrap();
+    let mut flat = Vec::new();
+    template.render_flat_serializable(loader, data, &mut flat).unwrap();
+    let mut serialized = Vec::new();
+    template.render_serializable(loader, data, &mut serialized).unwrap();
+    assert_eq!(String::from_utf8(flat).unwrap(), expected);
+    assert_eq!(String::from_utf8(serialized).unwrap(), expected);
+}
+
+#[test]
+fn miri_iso_render_flat_serializable() {
+    #[derive(serde_derive::Serialize)]
+    enum Role {
+        Admin,
+    }
+    #[derive(serde_derive::Serialize)]
+    struct Person {
+        name: &'static str,
+        quote: String,
+        age: u8,
+        height: f64,
+        weight: f32,
+        alive: bool,
+        nickname: Option<&'static str>,
+        initial: char,
+        role: Role,
+        big: u128,
+    }
+    let data = Person {
+        name: "🦀",
+        quote: "<it's>".into(),
+        age: 39,
+        height: 1.5,
+        weight: 70.0,
+        alive: true,
+        nickname: None,
+        initial: 'H',
+        role: Role::Admin,
+        big: u128::from(u64::MAX),
+    };
+    let loader = HashMapLoader::try_from(HashMap::<&str, &str>::new()).unwrap();
+    let source = "{{name}} {{quote}} {{{quote}}} {{age}} {{height}} {{weight}} {{alive}} [{{nickname}}] {{initial}} {{role}} {{big}} [{{missing}}]";
+    let expected = "🦀 &lt;it&#x27;s&gt; <it's> 39 1.5 70.0 true [] H Admin 18446744073709551615 []";
+    assert_flat_matches(&loader, source, &data, expected);
+
+    let mut loader = HashMapLoader::try_from(HashMap::<&str, &str>::new()).unwrap();
+    loader.set_render_options(RenderOptions {
+        escaper: Escaper::Json,
+        whole_floats_as_integers: true,
+        ..RenderOptions::default()
+    });
+    let expected = "🦀 <it's> <it's> 39 1.5 70 true [] H Admin 18446744073709551615 []";
+    assert_flat_matches(&loader, source, &data, expected);
+
+    let map = hashmap! {"a" => "1", "b" => "<2>"};
+    assert_flat_matches(&HashMapLoader::try_from(HashMap::<&str, &str>::new()).unwrap(), "{{a}}{{b}}", &map, "1&lt;2&gt;");
+    let map = maplit::btreemap! {1 => "1", 2 => "2"};
+    assert_flat_matches(&HashMapLoader::try_from(HashMap::<&str, &str>::new()).unwrap(), "{{1}}{{2}}", &map, "12");
+}
+
+#[test]
+fn miri_iso_render_flat_serializable_fallback() {
+    #[derive(serde_derive::Serialize)]
+    struct Name {
+        first: &'static str,
+    }
+    #[derive(serde_derive::Serialize)]
+    struct Person {
+        name: Name,
+        tags: Vec<&'static str>,
+        admin: bool,
+    }
+    let data = Person {
+        name: Name {
+            first: "homer",
+        },
+        tags: vec!["a", "b"],
+        admin: true,
+    };
+    let loader = HashMapLoader::try_from(hashmap! {
+        "greet" => "hi {{name.first}}",
+    }).unwrap();
+    assert_flat_matches(&loader, "{{name.first}}", &data, "homer");
+    assert_flat_matches(&loader, "{{#tags}}{{.}}{{/tags}}", &data, "ab");
+    assert_flat_matches(&loader, "{{#admin}}admin{{/admin}}", &data, "admin");
+    assert_flat_matches(&loader, "{{> greet }}", &data, "hi homer");
+    assert_flat_matches(&loader, "{{admin}}", &data, "true");
+    assert_flat_matches(&loader, "{{0}}{{1}}", &("a", "b"), "ab");
+
+    let template = Template::parse("{{a}}").unwrap();
+    let mut writer = Vec::new();
+    let err = template.render_flat_serializable(&loader, &hashmap! {vec![1] => 1}, &mut writer).unwrap_err();
+    assert_eq!(err, MoostacheError::SerializationError("key must be a string".into()));
+}
+
+#[test]
+fn miri_iso_render_raw_block() {
+    let source = "{{#a}}{{% raw %}}{{#a}}{{b}}{{/a}}{{% endraw %}} {{b}}{{/a}}";
+    let data = json!({"a": true, "b": "🦀"});
+    let template = Template::parse(source.to_owned()).unwrap();
+    let rendered = template.render_no_partials_to_string(&data).unwrap();
+    let expected = "{{#a}}{{b}}{{/a}} 🦀";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_map() {
+    let sources = [
+        "{{greeting}} {{#people}}{{name}} {{/people}}{{{.}}}",
+        "{{people.0.name}}|{{people[1].name}}|{{missing.name}}|{{^missing}}none{{/missing}}",
+        "{{#people}}{{../greeting}} {{name}}{{greeting}},{{/people}}{{../greeting}}",
+        "{{#people as person}}{{person.name}}{{greeting}}{{/people}}",
+        "{{#.}}{{greeting}}{{/.}}|{{#nested}}{{#inner}}{{x}}{{../greeting}}{{/inner}}{{../.}}{{/nested}}",
+    ];
+    let data = json!({
+        "greeting": "hello",
+        "people": [{"name": "🦀"}, {"name": "<b>"}],
+        "nested": {"inner": {"x": 1}},
+    });
+    let serde_json::Value::Object(map) = &data else {
+        unreachable!();
+    };
+    for source in sources {
+        let template = Template::parse(source).unwrap();
+        let expected = template.render_no_partials_to_string(&serde_json::Value::Object(map.clone())).unwrap();
+        let mut rendered = Vec::new();
+        template.render_map(&(), map, &mut rendered).unwrap();
+        assert_eq!(String::from_utf8(rendered).unwrap(), expected, "{source}");
+    }
+}
+
+#[test]
+fn miri_iso_render_map_errors() {
+    let template = Template::parse("{{> missing }}").unwrap();
+    let mut map = serde_json::Map::new();
+    map.insert("a".to_owned(), json!(1));
+    let mut rendered = Vec::new();
+    let err = template.render_map(&(), &map, &mut rendered).unwrap_err();
+    assert_eq!(err, MoostacheError::LoaderErrorTemplateNotFound("missing".into()));
+}
+
+#[test]
+fn miri_iso_render_at() {
+    let data = json!({"user": {"name": "x", "pets": ["🦀"]}, "name": "root"});
+    let render_at = |source: &'static str, path: &str| {
+        let template = Template::parse(source).unwrap();
+        let mut rendered = Vec::new();
+        template.render_at(path, &(), &data, &mut rendered).unwrap();
+        String::from_utf8(rendered).unwrap()
+    };
+    assert_eq!(render_at("{{name}}", "user"), "x");
+    assert_eq!(render_at("{{0}}", "user.pets"), "🦀");
+    assert_eq!(render_at("{{.}}", "user.pets.0"), "🦀");
+    assert_eq!(render_at("{{name}}", "."), "root");
+    // the rest of the data isn't reachable from the sub-context
+    assert_eq!(render_at("[{{name}}]", "user.pets"), "[]");
+    assert_eq!(render_at("[{{name}}]", "missing"), "[]");
+}
+
+#[test]
+fn miri_iso_render_layered() {
+    let user = json!({"name": "x", "tags": ["a"]});
+    let site = json!({"name": "site", "title": "moo", "nav": {"home": "/"}});
+    let render_layered = |source: &'static str, values: &[&serde_json::Value]| {
+        let template = Template::parse(source).unwrap();
+        let mut rendered = Vec::new();
+        template.render_layered(&(), values, &mut rendered).unwrap();
+        String::from_utf8(rendered).unwrap()
+    };
+    // only in the second value
+    assert_eq!(render_layered("{{title}} {{nav.home}}", &[&user, &site]), "moo /");
+    // in both, the first value wins
+    assert_eq!(render_layered("{{name}}", &[&user, &site]), "x");
+    assert_eq!(render_layered("{{name}}", &[&site, &user]), "site");
+    // sections can still reach every value
+    assert_eq!(render_layered("{{#tags}}{{.}}{{title}}{{/tags}}", &[&user, &site]), "amoo");
+    assert_eq!(render_layered("[{{.}}]", &[&json!("a"), &site]), "[a]");
+    assert_eq!(render_layered("[{{.}}{{name}}]", &[]), "[]");
+}
+
+// writer which accepts a limited number of
+// bytes and then returns WouldBlock
+struct WouldBlockWriter {
+    written: Vec<u8>,
+    capacity: usize,
+}
+
+impl Write for WouldBlockWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.capacity - self.written.len());
+        if len == 0 {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+        self.written.extend_from_slice(&buf[..len]);
+        Ok(len)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn miri_iso_render_would_block() {
+    let data = json!({"a": "aaaa", "b": [1, 2, 3]});
+    // would block writing a literal
+    let template = Template::parse("hello world").unwrap();
+    let mut writer = WouldBlockWriter { written: Vec::new(), capacity: 5 };
+    let err = template.render_no_partials(&data, &mut writer).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorWouldBlock);
+    assert_eq!(writer.written, b"hello");
+    // would block writing a string value
+    let template = Template::parse("{{a}}").unwrap();
+    let mut writer = WouldBlockWriter { written: Vec::new(), capacity: 2 };
+    let err = template.render_no_partials(&data, &mut writer).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorWouldBlock);
+    // would block writing a serialized value
+    let template = Template::parse("{{{b}}}").unwrap();
+    let mut writer = WouldBlockWriter { written: Vec::new(), capacity: 2 };
+    let err = template.render_no_partials(&data, &mut writer).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorWouldBlock);
+}
+
+// writer which sets a flag once it's been
+// written to a certain number of times
+struct CancellingWriter<'a> {
+    written: Vec<u8>,
+    writes_left: usize,
+    cancel: &'a AtomicBool,
+}
+
+impl Write for CancellingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.extend_from_slice(buf);
+        self.writes_left -= 1;
+        if self.writes_left == 0 {
+            self.cancel.store(true, Ordering::Relaxed);
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn miri_iso_render_cancelled_mid_render() {
+    let template = Template::parse("{{#items}}<{{.}}>{{/items}}").unwrap();
+    let data = json!({"items": [1, 2, 3, 4, 5]});
+    let cancel = AtomicBool::new(false);
+    let mut writer = CancellingWriter {
+        written: Vec::new(),
+        writes_left: 4,
+        cancel: &cancel,
+    };
+    let err = template.render_with_cancel(&(), &data, &cancel, &mut writer).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorCancelled);
+    assert_eq!(writer.written, b"<1><");
+    // renders normally if never cancelled
+    let cancel = AtomicBool::new(false);
+    let mut writer = Vec::new();
+    template.render_with_cancel(&(), &data, &cancel, &mut writer).unwrap();
+    assert_eq!(writer, b"<1><2><3><4><5>");
+}
+
+#[test]
+fn miri_iso_render_max_output_bytes() {
+    // 10 * 10 rows of 2 bytes each, 200 bytes total
+    let template = Template::parse("{{#a}}{{#a}}{{x}}.{{/a}}{{/a}}").unwrap();
+    let data = json!({"a": [1, 2, 3, 4, 5, 6, 7, 8, 9, 10], "x": "<"});
+    let render = |max_output_bytes: Option<usize>| {
+        let options = RenderOptions {
+            max_output_bytes,
+            ..RenderOptions::default()
+        };
+        let mut rendered = Vec::new();
+        template.render_with_options(&(), &data, &options, &mut rendered)
+            .map(|()| rendered.len())
+    };
+    assert_eq!(render(None), Ok(500));
+    assert_eq!(render(Some(500)), Ok(500));
+    assert_eq!(render(Some(1000)), Ok(500));
+    assert_eq!(render(Some(499)), Err(MoostacheError::RenderErrorOutputTooLarge));
+    assert_eq!(render(Some(0)), Err(MoostacheError::RenderErrorOutputTooLarge));
+    // aborts early instead of writing everything
+    let options = RenderOptions {
+        max_output_bytes: Some(12),
+        ..RenderOptions::default()
+    };
+    let mut rendered = Vec::new();
+    let err = template.render_with_options(&(), &data, &options, &mut rendered).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorOutputTooLarge);
+    assert_eq!(rendered, b"&lt;.&lt;.");
+    // limits also apply to partials and loader options
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "main" => "{{#a}}{{> row }}{{/a}}",
+        "row" => "{{#a}}{{x}}.{{/a}}",
+        "flat" => "{{x}}{{x}}{{x}}",
+    }).unwrap();
+    loader.set_render_options(options);
+    let err = loader.render_to_string("main", &data).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorOutputTooLarge);
+    let flat = loader.get("flat").unwrap();
+    let err = flat.render_flat_serializable(&loader, &hashmap! {"x" => "<<"}, &mut Vec::new()).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorOutputTooLarge);
+    let err = flat.render_diff(&loader, &data, &json!({"x": "<<"})).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorOutputTooLarge);
+}
+
+#[test]
+fn miri_iso_render_iter_sections() {
+    struct PullsWriter<'a> {
+        written: Vec<u8>,
+        pulled: &'a std::cell::Cell<usize>,
+        pulls: Vec<usize>,
+    }
+    impl Write for PullsWriter<'_> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            self.pulls.push(self.pulled.get());
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+    #[derive(serde_derive::Serialize)]
+    struct Row {
+        id: usize,
+        name: String,
+    }
+    let template = Template::parse(
+        "{{title}}:{{#rows}} {{id}}={{name}}{{/rows}}{{^rows}} done{{/rows}}"
+    ).unwrap();
+    // count how many rows were pulled from the iterator
+    // every time the writer is written to
+    let pulled = std::cell::Cell::new(0);
+    let rows = (0..1000).map(|id| {
+        pulled.set(pulled.get() + 1);
+        Row { id, name: format!("row{id}") }
+    });
+    let mut sections = IterSections::new();
+    sections.insert("rows", rows);
+    let mut writer = PullsWriter {
+        written: Vec::new(),
+        pulled: &pulled,
+        pulls: Vec::new(),
+    };
+    let data = json!({"title": "rows", "rows": "ignored"});
+    template.render_with_iter_sections(&(), &data, &sections, &mut writer).unwrap();
+    let rows: Vec<String> = (0..1000).map(|id| format!("{id}=row{id}")).collect();
+    let expected = format!("rows: {} done", rows.join(" "));
+    assert_eq!(String::from_utf8(writer.written).unwrap(), expected);
+    // rows are pulled one at a time as they're rendered
+    assert_eq!(&writer.pulls[..6], &[0, 0, 1, 1, 1, 1]);
+    assert_eq!(&writer.pulls[6..10], &[2, 2, 2, 2]);
+    assert_eq!(pulled.get(), 1000);
+}
+
+#[test]
+fn miri_iso_render_reuses_scratch_scopes() {
+    let source = "{{#a}}{{#b}}{{#c}}{{d}}{{/c}}{{/b}}{{/a}}";
+    let data = json!({"a": {"b": {"c": {"d": "deep"}}}});
     let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "hello {\"some\":\"🦀\"}!";
-    assert_eq!(rendered, expected);
+    let first = template.render_no_partials_to_string(&data).unwrap();
+    let capacity = SCRATCH_SCOPES.with_borrow(Vec::capacity);
+    assert!(capacity >= 4);
+    let second = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(first, "deep");
+    assert_eq!(first, second);
+    assert_eq!(capacity, SCRATCH_SCOPES.with_borrow(Vec::capacity));
+    assert!(SCRATCH_SCOPES.with_borrow(Vec::is_empty));
 }
 
 #[test]
-fn miri_iso_render_unescaped_dot_array() {
-    let source = "hello {{{ . }}}!";
-    let data = json!([1, "string", null]);
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "hello [1,\"string\",null]!";
-    assert_eq!(rendered, expected);
+fn miri_iso_recycle_scopes_keeps_allocation() {
+    let value = json!(1);
+    let mut scopes = Vec::with_capacity(8);
+    scopes.extend([&value, &value]);
+    let ptr = scopes.as_ptr().cast::<u8>();
+    let recycled: Vec<&serde_json::Value> = recycle_scopes(scopes);
+    assert!(recycled.is_empty());
+    assert_eq!(recycled.capacity(), 8);
+    assert_eq!(recycled.as_ptr().cast::<u8>(), ptr);
 }
 
 #[test]
-fn miri_iso_render_unescaped_dot_array_utf8() {
-    let source = "hello {{{ . }}}!";
-    let data = json!([1, "🦀", null]);
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "hello [1,\"🦀\",null]!";
-    assert_eq!(rendered, expected);
+fn miri_iso_render_scratch_scopes_reentrant() {
+    let outer = take_scratch_scopes();
+    // scratch stack is already taken, so rendering
+    // must still work with a fresh scope stack
+    let template = Template::parse("hello {{name}}!").unwrap();
+    let rendered = template.render_no_partials_to_string(&json!({"name": "world"})).unwrap();
+    assert_eq!(rendered, "hello world!");
+    return_scratch_scopes(outer);
 }
 
 #[test]
-fn miri_iso_render_unescaped_dot_null() {
-    let source = "hello {{{ . }}}!";
-    let data = json!(null);
-    let template = Template::parse(source.to_owned()).unwrap();
+fn miri_iso_render_wildcard_section() {
+    let source = "{{#data.*}}[{{name}}:{{.}}]{{|}}none{{/data.*}}";
+    let template = Template::parse(source).unwrap();
+    let data = json!({
+        "name": "root",
+        "data": {"a": 1, "b": {"name": "🦀"}, "c": [1, 2]},
+    });
     let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "hello !";
-    assert_eq!(rendered, expected);
+    assert_eq!(rendered, "[root:1][🦀:{&quot;name&quot;:&quot;🦀&quot;}][root:[1,2]]");
+    // non-objects and empty objects are falsy, even if
+    // they would be truthy in a section without a wildcard
+    for data in [json!({}), json!({"data": {}}), json!({"data": [1, 2]}), json!({"data": "a"}), json!({"data": true})] {
+        let rendered = template.render_no_partials_to_string(&data).unwrap();
+        assert_eq!(rendered, "none");
+    }
+    let template = Template::parse("{{#a.b.*}}{{#.}}{{.}}{{/.}}{{/a.b.*}}").unwrap();
+    let data = json!({"a": {"b": {"x": [1, 2], "y": [3]}}});
+    assert_eq!(template.render_no_partials_to_string(&data).unwrap(), "123");
+    let missing = template.missing_paths(&(), &json!({"a": {}})).unwrap();
+    assert_eq!(missing, vec!["a.b"]);
 }
 
 #[test]
-fn miri_iso_render_unescaped_dot_float() {
-    let source = "hello {{{ . }}}!";
-    let data = json!(123.5);
-    let template = Template::parse(source.to_owned()).unwrap();
+fn miri_iso_render_variable_widths() {
+    let data = json!({"name": "crab🦀é", "n": 42, "tag": "<b>"});
+    let render = |source: &'static str| {
+        Template::parse(source).unwrap().render_no_partials_to_string(&data).unwrap()
+    };
+    assert_eq!(render("[{{name:8}}]"), "[crab🦀é  ]");
+    assert_eq!(render("[{{name:<8}}]"), "[crab🦀é  ]");
+    assert_eq!(render("[{{name:>8}}]"), "[  crab🦀é]");
+    assert_eq!(render("[{{name:6}}]"), "[crab🦀é]");
+    // truncated by chars, not bytes
+    assert_eq!(render("[{{name:5}}]"), "[crab🦀]");
+    assert_eq!(render("[{{name:>4}}]"), "[crab]");
+    assert_eq!(render("[{{name:0}}]"), "[]");
+    assert_eq!(render("[{{n:>5}}|{{missing:3}}]"), "[   42|   ]");
+    // width is counted before escaping
+    assert_eq!(render("[{{tag:2}}|{{{tag:2}}}|{{tag:>4}}]"), "[&lt;b|<b| &lt;b&gt;]");
+    let template = Template::parse("{{#.}}{{name:>6}}|{{n:3}}|\n{{/.}}").unwrap();
+    let data = json!([{"name": "a", "n": 1}, {"name": "bbb", "n": 22}]);
     let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "hello 123.5!";
-    assert_eq!(rendered, expected);
+    assert_eq!(rendered, "     a|1  |\n   bbb|22 |\n");
+    let missing = template.missing_paths(&(), &json!([{}])).unwrap();
+    assert_eq!(missing, vec!["name", "n"]);
+    // flat renders fall back to regular renders
+    let template = Template::parse("[{{a:>3}}]").unwrap();
+    let mut rendered = Vec::new();
+    template.render_flat_serializable(&(), &hashmap! {"a" => "x"}, &mut rendered).unwrap();
+    assert_eq!(rendered, b"[  x]");
 }
 
 #[test]
-fn miri_iso_render_unescaped_dot_integer() {
-    let source = "hello {{{ . }}}!";
-    let data = json!(123);
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "hello 123!";
-    assert_eq!(rendered, expected);
+fn miri_iso_render_section_else_truthy() {
+    let source = "{{#items}}<{{.}}>{{|}}no items{{/items}}";
+    let template = Template::parse(source).unwrap();
+    let rendered = template.render_no_partials_to_string(&json!({"items": [1, 2]})).unwrap();
+    assert_eq!(rendered, "<1><2>");
+    let rendered = template.render_no_partials_to_string(&json!({"items": "one"})).unwrap();
+    assert_eq!(rendered, "<one>");
 }
 
 #[test]
-fn miri_iso_render_unescaped_dot_string() {
-    let source = "hello {{{ . }}}!";
-    let data = json!("world");
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_no_partials_to_string(&data).unwrap();
-    let expected = "hello world!";
-    assert_eq!(rendered, expected);
+fn miri_iso_render_section_else_falsy() {
+    let source = "{{#items}}<{{.}}>{{|}}no {{items}} {{title}}{{/items}}";
+    let template = Template::parse(source).unwrap();
+    let rendered = template.render_no_partials_to_string(&json!({"items": [], "title": "items"})).unwrap();
+    assert_eq!(rendered, "no [] items");
+    let rendered = template.render_no_partials_to_string(&json!({"items": false, "title": "items"})).unwrap();
+    assert_eq!(rendered, "no false items");
+    let rendered = template.render_no_partials_to_string(&json!({"title": "items"})).unwrap();
+    assert_eq!(rendered, "no  items");
 }
 
 #[test]
-fn miri_iso_render_serializable_string() {
-    let source = "hello {{{ . }}}!";
-    let data = "world".to_owned();
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_serializable_no_partials_to_string(&data).unwrap();
-    let expected = "hello world!";
-    assert_eq!(rendered, expected);
+fn miri_iso_render_nested_section_else() {
+    let source = "{{#a}}{{#b}}b{{|}}not b{{#c}}, c{{|}}, not c{{/c}}{{/b}}{{|}}not a{{/a}}.";
+    let template = Template::parse(source).unwrap();
+    let render = |data| template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(render(json!({})), "not a.");
+    assert_eq!(render(json!({"a": true, "b": true})), "b.");
+    assert_eq!(render(json!({"a": true, "c": true})), "not b, c.");
+    assert_eq!(render(json!({"a": true})), "not b, not c.");
 }
 
 #[test]
-fn miri_iso_render_serializable_struct() {
-    let source = "hello {{ name }}!";
-    #[derive(serde_derive::Serialize)]
-    struct Person {
-        name: &'static str,
-    }
-    let data = Person {
-        name: "homer"
-    };
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_serializable_no_partials_to_string(&data).unwrap();
-    let expected = "hello homer!";
-    assert_eq!(rendered, expected);
+fn miri_iso_render_section_else_fallback_sections() {
+    let source = "{{#items}}<{{name}}>{{|}}{{#fallbacks}}[{{name}}]{{|}}none{{/fallbacks}}{{> footer }}{{/items}}{{after}}";
+    let template = Template::parse(source).unwrap();
+    let loader = HashMapLoader::try_from(hashmap! {
+        "footer" => "({{#fallbacks}}{{name}}{{/fallbacks}})",
+    }).unwrap();
+    let render = |data| template.render_to_string(&loader, &data).unwrap();
+    let fallbacks = json!([{"name": "x"}, {"name": "y"}]);
+    assert_eq!(
+        render(json!({"items": [{"name": "a"}, {"name": "b"}], "fallbacks": fallbacks, "after": "."})),
+        "<a><b>.",
+    );
+    assert_eq!(
+        render(json!({"items": [], "fallbacks": fallbacks, "after": "."})),
+        "[x][y](xy).",
+    );
+    assert_eq!(render(json!({"items": [], "fallbacks": [], "after": "."})), "none().");
+    assert_eq!(render(json!({"after": "."})), "none().");
 }
 
 #[test]
-fn miri_iso_render_serializable_struct_utf8() {
-    let source = "hello {{ name }}!";
-    #[derive(serde_derive::Serialize)]
-    struct Person {
-        name: &'static str,
-    }
-    let data = Person {
-        name: "🦀"
-    };
-    let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_serializable_no_partials_to_string(&data).unwrap();
-    let expected = "hello 🦀!";
-    assert_eq!(rendered, expected);
+fn miri_iso_render_iter_section_else() {
+    let template = Template::parse("{{#rows}}<{{.}}>{{|}}no rows{{/rows}}").unwrap();
+    let mut sections = IterSections::new();
+    sections.insert("rows", 1..3);
+    let mut rendered = Vec::new();
+    template.render_with_iter_sections(&(), &json!({}), &sections, &mut rendered).unwrap();
+    assert_eq!(rendered, b"<1><2>");
+    let mut sections = IterSections::new();
+    sections.insert("rows", 0..0);
+    let mut rendered = Vec::new();
+    template.render_with_iter_sections(&(), &json!({}), &sections, &mut rendered).unwrap();
+    assert_eq!(rendered, b"no rows");
 }
 
 ////////////////////////////////////////////
@@ -1388,11 +3759,11 @@ fn miri_iso_render_serializable_struct_utf8() {
 fn miri_iso_render_partial_hashmap() {
     let source = "{{>partial}}!";
     let data = json!(null);
-    let mut loader = HashMapLoader::try_from(hashmap! {
+    let loader = HashMapLoader::try_from(hashmap! {
         "partial" => "hello world".to_owned(),
     }).unwrap();
     let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_to_string(&mut loader, &data).unwrap();
+    let rendered = template.render_to_string(&loader, &data).unwrap();
     let expected = "hello world!";
     assert_eq!(rendered, expected);
 }
@@ -1401,11 +3772,11 @@ fn miri_iso_render_partial_hashmap() {
 fn miri_iso_render_partial_hashmap_utf8() {
     let source = "{{>partial}}!";
     let data = json!(null);
-    let mut loader = HashMapLoader::try_from(hashmap! {
+    let loader = HashMapLoader::try_from(hashmap! {
         "partial" => "hello 🦀".to_owned(),
     }).unwrap();
     let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_to_string(&mut loader, &data).unwrap();
+    let rendered = template.render_to_string(&loader, &data).unwrap();
     let expected = "hello 🦀!";
     assert_eq!(rendered, expected);
 }
@@ -1414,16 +3785,419 @@ fn miri_iso_render_partial_hashmap_utf8() {
 fn miri_iso_render_partial_padded_hashmap() {
     let source = "{{>  partial  }}!";
     let data = json!(null);
-    let mut loader = HashMapLoader::try_from(hashmap! {
+    let loader = HashMapLoader::try_from(hashmap! {
         "partial" => "hello world".to_owned(),
     }).unwrap();
     let template = Template::parse(source.to_owned()).unwrap();
-    let rendered = template.render_to_string(&mut loader, &data).unwrap();
+    let rendered = template.render_to_string(&loader, &data).unwrap();
     let expected = "hello world!";
     assert_eq!(rendered, expected);
 }
 
 #[test]
+fn miri_iso_render_dynamic_partial_hashmap() {
+    let source = "{{#shapes}}{{>*kind}} {{/shapes}}!";
+    let data = json!({
+        "shapes": [
+            {"kind": "circle", "radius": 1},
+            {"kind": "shapes/square", "side": 2},
+            {"kind": "circle", "radius": 3},
+        ],
+    });
+    let loader = HashMapLoader::try_from(hashmap! {
+        "circle" => "(r={{radius}})",
+        "shapes/square" => "[s={{side}}]",
+    }).unwrap();
+    let template = Template::parse(source).unwrap();
+    let rendered = template.render_to_string(&loader, &data).unwrap();
+    let expected = "(r=1) [s=2] (r=3) !";
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_dynamic_partial_invalid_name() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "partial" => "hello world",
+    }).unwrap();
+    let template = Template::parse("{{>* name }}!").unwrap();
+    for data in [json!({"name": ""}), json!({"name": 1}), json!({"name": ["partial"]}), json!({})] {
+        let err = template.render_to_string(&loader, &data).unwrap_err();
+        assert_eq!(err, MoostacheError::RenderErrorInvalidDynamicPartial("name".into()));
+    }
+    let err = template.render_to_string(&loader, &json!({"name": "missing"})).unwrap_err();
+    assert_eq!(err, MoostacheError::LoaderErrorTemplateNotFound("missing".into()));
+}
+
+#[test]
+fn miri_iso_render_tracking_partials() {
+    let source = "{{> header }}{{#user}}{{> card }}{{/user}}{{^user}}{{> login }}{{/user}}{{> header }}";
+    let loader = HashMapLoader::try_from(hashmap! {
+        "header" => "[{{> logo }}]",
+        "logo" => "moo",
+        "card" => "<{{name}}>",
+        "login" => "log in",
+    }).unwrap();
+    let template = Template::parse(source).unwrap();
+    let (rendered, partials) = template.render_tracking_partials(&loader, &json!({})).unwrap();
+    assert_eq!(rendered, "[moo]log in[moo]");
+    assert_eq!(partials, maplit::hashset! {"header".to_owned(), "logo".to_owned(), "login".to_owned()});
+    let data = json!({"user": {"name": "🦀"}});
+    let (rendered, partials) = template.render_tracking_partials(&loader, &data).unwrap();
+    assert_eq!(rendered, "[moo]<🦀>[moo]");
+    assert_eq!(partials, maplit::hashset! {"header".to_owned(), "logo".to_owned(), "card".to_owned()});
+}
+
+#[test]
+fn miri_iso_render_counting_sections() {
+    let source = "{{#items}}{{> item }}{{/items}}{{#user}}{{name}}{{|}}anon{{/user}}{{#props.*}}{{.}}{{/props.*}}";
+    let loader = HashMapLoader::try_from(hashmap! {
+        "item" => "{{#.}}<{{.}}>{{/.}}",
+    }).unwrap();
+    let template = Template::parse(source).unwrap();
+    let data = json!({"items": ["a", "b", "c"], "props": {"x": 1, "y": 2}});
+    let (rendered, counts) = template.render_counting_sections(&loader, &data).unwrap();
+    assert_eq!(rendered, template.render_to_string(&loader, &data).unwrap());
+    assert_eq!(rendered, "<a><b><c>anon12");
+    // sections within partials are counted too, and
+    // falsy sections are counted as rendered zero times
+    assert_eq!(counts, hashmap! {
+        "items".to_owned() => 3,
+        ".".to_owned() => 3,
+        "user".to_owned() => 0,
+        "props.*".to_owned() => 2,
+    });
+}
+
+#[test]
+fn miri_iso_render_relative_partials() {
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "blog/post" => "{{> header }}|{{> footer }}|{{>* name }}",
+        "blog/header" => "blog header {{> logo }}",
+        "blog/logo" => "blog logo",
+        "header" => "root header",
+        "footer" => "root footer {{> logo }}",
+        "logo" => "root logo",
+    }).unwrap();
+    let data = json!({"name": "logo"});
+    let rendered = loader.render_to_string("blog/post", &data).unwrap();
+    assert_eq!(rendered, "root header|root footer root logo|root logo");
+    loader.set_render_options(RenderOptions {
+        relative_partials: true,
+        ..RenderOptions::default()
+    });
+    // the root footer's logo is relative to the root
+    let rendered = loader.render_to_string("blog/post", &data).unwrap();
+    assert_eq!(rendered, "blog header blog logo|root footer root logo|blog logo");
+    // anonymous templates are at the root
+    let template = Template::parse("{{> header }}").unwrap();
+    assert_eq!(template.render_to_string(&loader, &data).unwrap(), "root header");
+    let err = loader.render_to_string("blog/missing", &data).unwrap_err();
+    assert_eq!(err, MoostacheError::LoaderErrorTemplateNotFound("blog/missing".into()));
+    // flattening resolves partials the same way
+    let template = Template::parse("{{> header }}|{{> blog/header }}").unwrap();
+    let flattened = template.flatten(&loader).unwrap();
+    assert_eq!(
+        flattened.render_no_partials_to_string(&data).unwrap(),
+        template.render_to_string(&loader, &data).unwrap(),
+    );
+    assert_eq!(flattened.render_no_partials_to_string(&data).unwrap(), "root header|blog header blog logo");
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_relative_partials() {
+    let mut loader = FileLoader::try_from(LoaderConfig {
+        templates_extension: "rel".into(),
+        ..LoaderConfig::default()
+    }).unwrap();
+    let rendered = loader.render_to_string("blog/post", &json!({})).unwrap();
+    assert_eq!(rendered, "root header|root footer");
+    loader.set_render_options(RenderOptions {
+        relative_partials: true,
+        ..RenderOptions::default()
+    });
+    let rendered = loader.render_to_string("blog/post", &json!({})).unwrap();
+    assert_eq!(rendered, "blog header blog logo|root footer");
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_relative_partials_errors() {
+    let dir = TempDir::new("relative-partials-errors");
+    dir.write("blog/post.html", "{{> header }}|{{> footer }}");
+    dir.write("blog/header.html", "{{# unclosed }}");
+    dir.write("header.html", "root header");
+    dir.write("footer.html", "root footer");
+    let mut loader = FileLoader::try_from(dir.config()).unwrap();
+    loader.set_render_options(RenderOptions {
+        relative_partials: true,
+        ..RenderOptions::default()
+    });
+    // a relative partial which fails to parse isn't
+    // replaced by the root partial with the same name
+    let err = loader.render_to_string("blog/post", &json!({})).unwrap_err();
+    assert!(err.is_parse_error());
+    assert_eq!(err.template_name(), Some("blog/header"));
+    // but missing relative partials fall back to the root
+    dir.write("blog/header.html", "blog header");
+    loader.clear_cache();
+    assert_eq!(loader.render_to_string("blog/post", &json!({})).unwrap(), "blog header|root footer");
+}
+
+#[test]
+fn miri_iso_render_post_render() {
+    use std::{cell::Cell, rc::Rc};
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "page" => "hello {{> name }}!",
+        "name" => "{{name}}",
+    }).unwrap();
+    let data = json!({"name": "world"});
+    assert_eq!(loader.render_to_string("page", &data).unwrap(), "hello world!");
+    loader.set_post_render(|rendered| rendered.to_uppercase());
+    assert_eq!(loader.render_to_string("page", &data).unwrap(), "HELLO WORLD!");
+    let rendered = loader.render_serializable_to_string("page", &hashmap! {"name" => "🦀"}).unwrap();
+    assert_eq!(rendered, "HELLO 🦀!");
+    let template = loader.get("page").unwrap();
+    assert_eq!(template.render_to_string(&loader, &data).unwrap(), "HELLO WORLD!");
+    // applied once to the whole output, not to each partial
+    let count = Rc::new(Cell::new(0));
+    let counter = Rc::clone(&count);
+    loader.set_post_render(move |rendered| {
+        counter.set(counter.get() + 1);
+        rendered
+    });
+    loader.render_to_string("page", &data).unwrap();
+    assert_eq!(count.get(), 1);
+    // renders which write to a writer aren't transformed
+    let mut rendered = Vec::new();
+    loader.render("page", &data, &mut rendered).unwrap();
+    assert_eq!(rendered, b"hello world!");
+    assert_eq!(count.get(), 1);
+}
+
+#[test]
+fn miri_iso_render_shared() {
+    use std::{rc::Rc, sync::Arc};
+    let loader = HashMapLoader::try_from(hashmap! {
+        "page" => "{{#items}}{{> item }}{{/items}} {{ title }}",
+        "item" => "<{{ name }}>",
+    }).unwrap();
+    let template = loader.get("page").unwrap();
+    let value = json!({
+        "title": "🦀",
+        "items": [{"name": "a"}, {"name": "b"}],
+    });
+    let mut expected = Vec::new();
+    template.render(&loader, &value, &mut expected).unwrap();
+    assert_eq!(expected, "<a><b> 🦀".as_bytes());
+    let rc = Rc::new(value.clone());
+    let mut rendered = Vec::new();
+    template.render_shared(&loader, Rc::clone(&rc), &mut rendered).unwrap();
+    assert_eq!(rendered, expected);
+    // the handle is only held during the render
+    assert_eq!(Rc::strong_count(&rc), 1);
+    let arc = Arc::new(value.clone());
+    let mut rendered = Vec::new();
+    template.render_shared(&loader, Arc::clone(&arc), &mut rendered).unwrap();
+    assert_eq!(rendered, expected);
+    assert_eq!(Arc::strong_count(&arc), 1);
+    let mut rendered = Vec::new();
+    template.render_shared(&loader, &value, &mut rendered).unwrap();
+    assert_eq!(rendered, expected);
+}
+
+#[test]
+fn miri_iso_render_error_template() {
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "page" => "hello {{ name }}",
+        "broken" => "partial output {{> missing }}",
+        "error" => "error for {{ name }}",
+        "broken_error" => "{{> missing }}",
+    }).unwrap();
+    let data = json!({"name": "John"});
+    assert!(loader.render_to_string("broken", &data).is_err());
+    loader.on_error_render("error");
+    assert_eq!(loader.error_template(), Some("error"));
+    assert_eq!(loader.render_to_string("page", &data).unwrap(), "hello John");
+    // nothing from the failed render is written
+    assert_eq!(loader.render_to_string("broken", &data).unwrap(), "error for John");
+    let mut rendered = Vec::new();
+    loader.render("broken", &data, &mut rendered).unwrap();
+    assert_eq!(rendered, b"error for John");
+    // missing templates render the error template too
+    assert_eq!(loader.render_serializable_to_string("missing", &hashmap! {"name" => "🦀"}).unwrap(), "error for 🦀");
+    // but errors rendering the error template are returned
+    loader.on_error_render("broken_error");
+    assert_eq!(
+        loader.render_to_string("broken", &data).unwrap_err(),
+        MoostacheError::LoaderErrorTemplateNotFound("missing".into()),
+    );
+    loader.on_error_render("nope");
+    assert_eq!(
+        loader.render_to_string("broken", &data).unwrap_err(),
+        MoostacheError::LoaderErrorTemplateNotFound("nope".into()),
+    );
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_error_template() {
+    let dir = TempDir::new("error-template");
+    dir.write("broken.html", "{{# unclosed }}");
+    dir.write("error.html", "sorry {{ name }}");
+    let mut loader = FileLoader::try_from(dir.config()).unwrap();
+    let data = json!({"name": "John"});
+    assert!(loader.render_to_string("broken", &data).unwrap_err().is_parse_error());
+    loader.on_error_render("error");
+    assert_eq!(loader.render_to_string("broken", &data).unwrap(), "sorry John");
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_custom_delimiters() {
+    let dir = TempDir::new("delimiters");
+    dir.write("page.html", "<p v-if=\"{{ ok }}\">[[> greet ]]</p>");
+    dir.write("greet.html", "hello [[ name ]]");
+    let config = LoaderConfig {
+        parse_options: ParseOptions {
+            delimiters: Delimiters { open: "[[", close: "]]" },
+            ..ParseOptions::default()
+        },
+        ..dir.config()
+    };
+    let data = json!({"name": "John"});
+    let loader = FileLoader::try_from(config.clone()).unwrap();
+    assert_eq!(loader.render_to_string("page", &data).unwrap(), "<p v-if=\"{{ ok }}\">hello John</p>");
+    let loader = HashMapLoader::try_from(config).unwrap();
+    assert_eq!(loader.render_to_string("page", &data).unwrap(), "<p v-if=\"{{ ok }}\">hello John</p>");
+}
+
+#[test]
+fn miri_iso_render_value_renderer() {
+    use serde_json::Value;
+    // formats dates like 2024-03-09 as 09/03/2024
+    fn format_date(value: &Value, _escaped: bool, writer: &mut dyn Write) -> Option<io::Result<()>> {
+        let date = value.as_str()?;
+        let bytes = date.as_bytes();
+        let is_date = bytes.len() == 10
+            && bytes[4] == b'-'
+            && bytes[7] == b'-'
+            && bytes.iter().enumerate().all(|(idx, b)| idx == 4 || idx == 7 || b.is_ascii_digit());
+        if !is_date {
+            return None;
+        }
+        Some(write!(writer, "{}/{}/{}<", &date[8..], &date[5..7], &date[..4]))
+    }
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "page" => "{{ date }} {{{ date }}} {{ name }} {{ date:12 }}|{{> partial }}",
+        "partial" => "{{ other }} {{ count }}",
+    }).unwrap();
+    let data = json!({
+        "date": "2024-03-09",
+        "other": "2024-3-9",
+        "name": "<b>",
+        "count": 3,
+    });
+    let rendered = loader.render_to_string("page", &data).unwrap();
+    assert_eq!(rendered, "2024-03-09 2024-03-09 &lt;b&gt; 2024-03-09  |2024-3-9 3");
+    loader.set_value_renderer(format_date);
+    let rendered = loader.render_to_string("page", &data).unwrap();
+    assert_eq!(rendered, "09/03/2024&lt; 09/03/2024< &lt;b&gt; 09/03/2024&lt; |2024-3-9 3");
+    // write errors fail the render
+    loader.set_value_renderer(|_: &Value, _: bool, _: &mut dyn Write| {
+        Some(Err(io::Error::other("nope")))
+    });
+    let err = loader.render_to_string("page", &data).unwrap_err();
+    assert!(matches!(err, MoostacheError::IoError(..)));
+}
+
+#[test]
+fn miri_iso_render_value_renderer_invalid_utf8() {
+    use serde_json::Value;
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "page" => "a {{{ name }}} b",
+    }).unwrap();
+    loader.set_value_renderer(|_: &Value, _: bool, writer: &mut dyn Write| {
+        Some(writer.write_all(&[0xff, 0xfe]))
+    });
+    let data = json!({"name": "John"});
+    let err = loader.render_to_string("page", &data).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorInvalidUtf8);
+    let template = loader.get("page").unwrap();
+    let err = template.render_to_string(&loader, &data).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorInvalidUtf8);
+    let err = RenderSession::new(template, &loader).render(&data).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorInvalidUtf8);
+    // appending leaves the string unchanged
+    let mut rendered = String::from("before");
+    let err = template.render_append(&loader, &data, &mut rendered).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorInvalidUtf8);
+    assert_eq!(rendered, "before");
+}
+
+#[test]
+fn miri_iso_render_session_invalid_utf8_keeps_buffer() {
+    use serde_json::Value;
+    let mut loader = HashMapLoader::try_from(hashmap! {
+        "page" => "{{#items}}{{{ . }}}{{/items}}",
+    }).unwrap();
+    loader.set_value_renderer(|value: &Value, _: bool, writer: &mut dyn Write| {
+        (value == "bad").then(|| writer.write_all(&[0xff, 0xfe]))
+    });
+    let template = loader.get("page").unwrap();
+    let mut session = RenderSession::new(template, &loader);
+    let good = json!({"items": vec!["a"; 100]});
+    assert_eq!(session.render(&good).unwrap(), "a".repeat(100));
+    let capacity = session.buffer.capacity();
+    let err = session.render(&json!({"items": ["a", "bad"]})).unwrap_err();
+    assert_eq!(err, MoostacheError::RenderErrorInvalidUtf8);
+    assert_eq!(session.buffer.capacity(), capacity);
+    assert_eq!(session.render(&good).unwrap(), "a".repeat(100));
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_post_render() {
+    let mut loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+    loader.set_post_render(|rendered| rendered.to_uppercase());
+    let rendered = loader.render_to_string("greet", &json!({"name": "🦀"})).unwrap();
+    assert_eq!(rendered, "HELLO 🦀");
+}
+
+#[test]
+fn miri_iso_render_inline_partial_shadows_loader() {
+    let source = "{{> a }} {{> b }}!";
+    let data = json!({"name": "world"});
+    let loader = HashMapLoader::try_from(hashmap! {
+        "a" => "loader a",
+        "b" => "loader b {{name}}",
+    }).unwrap();
+    let inline_a = Template::parse("inline a {{name}}").unwrap();
+    let partials = HashMap::from([("a", &inline_a)]);
+    let template = Template::parse(source).unwrap();
+    let mut rendered = Vec::new();
+    template.render_with_partials(&loader, &data, &partials, &mut rendered).unwrap();
+    assert_eq!(rendered, b"inline a world loader b world!");
+}
+
+#[test]
+fn miri_iso_render_inline_partial_nested() {
+    // inline partials can also be used by partials
+    // which were loaded from the loader
+    let source = "{{> a }}!";
+    let loader = HashMapLoader::try_from(hashmap! {
+        "a" => "a {{> b }}",
+    }).unwrap();
+    let inline_b = Template::parse("inline b").unwrap();
+    let partials = HashMap::from([("b", &inline_b)]);
+    let template = Template::parse(source).unwrap();
+    let mut rendered = Vec::new();
+    template.render_with_partials(&loader, &json!(null), &partials, &mut rendered).unwrap();
+    assert_eq!(rendered, b"a inline b!");
+}
+
+#[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_hashmap_from_config() {
     let source = "{{>greet}}!";
     let data = json!({"name": "world"});
@@ -1437,6 +4211,7 @@ fn miri_render_partial_hashmap_from_config() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_hashmap_from_config_too_many_templates() {
     let err = HashMapLoader::try_from(LoaderConfig {
         cache_size: 1,
@@ -1447,6 +4222,7 @@ fn miri_render_partial_hashmap_from_config_too_many_templates() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_file() {
     let source = "{{>greet}}!";
     let data = json!({"name": "world"});
@@ -1460,6 +4236,7 @@ fn miri_render_partial_file() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_file_utf8() {
     let source = "{{>greet}}!";
     let data = json!({"name": "🦀"});
@@ -1473,9 +4250,10 @@ fn miri_render_partial_file_utf8() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partials_exceed_cache() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "tpl",
+        templates_extension: "tpl".into(),
         cache_size: 1, // cache size of only 1
         ..LoaderConfig::default()
     }).unwrap();
@@ -1488,14 +4266,399 @@ fn miri_render_partials_exceed_cache() {
     assert_eq!(rendered, expected);
 }
 
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_cache_stats() {
+    let loader = FileLoader::try_from(LoaderConfig {
+        cache_size: 1,
+        ..LoaderConfig::default()
+    }).unwrap();
+    assert_eq!(loader.stats(), CacheStats::default());
+    loader.get("greet").unwrap(); // miss
+    loader.get("greet").unwrap(); // hit
+    loader.get("nested/person").unwrap(); // miss, evicts greet
+    loader.get("greet").unwrap(); // miss, evicts nested/person
+    loader.get("missing").unwrap_err(); // miss
+    assert_eq!(loader.stats(), CacheStats {
+        gets: 5,
+        hits: 1,
+        misses: 4,
+        evictions: 2,
+    });
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_overlay_loader() {
+    let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+    let mut overlay = OverlayLoader::new(&loader);
+    let data = json!({"name": "John"});
+    let template = Template::parse("{{> greet }}|{{> nested/person }}").unwrap();
+    assert_eq!(template.render_to_string(&overlay, &data).unwrap(), "hello John|John");
+    // the overlaid partial is used, greet is still loaded from disk
+    assert!(overlay.insert("nested/person".into(), Template::parse("<{{name}}>").unwrap()).is_none());
+    assert!(overlay.is_overlaid("nested/person"));
+    assert!(!overlay.is_overlaid("greet"));
+    assert_eq!(template.render_to_string(&overlay, &data).unwrap(), "hello <John>|<John>");
+    assert_eq!(overlay.render_to_string("greet", &data).unwrap(), "hello <John>");
+    // the file loader isn't changed
+    assert_eq!(loader.render_to_string("greet", &data).unwrap(), "hello John");
+    let replaced = overlay.insert("nested/person".into(), Template::parse("[{{name}}]").unwrap());
+    assert_eq!(replaced.unwrap().source(), "<{{name}}>");
+    assert_eq!(overlay.render_to_string("greet", &data).unwrap(), "hello [John]");
+    // removing it falls back to disk again
+    assert_eq!(overlay.remove("nested/person").unwrap().source(), "[{{name}}]");
+    assert!(overlay.remove("nested/person").is_none());
+    assert_eq!(template.render_to_string(&overlay, &data).unwrap(), "hello John|John");
+    assert_eq!(overlay.get("missing").unwrap_err(), MoostacheError::IoError("missing".into(), io::ErrorKind::NotFound));
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_pinned_template_survives_eviction() {
+    // templates can be pinned through a shared loader
+    let loader = Rc::new(FileLoader::try_from(LoaderConfig {
+        cache_size: 1,
+        ..LoaderConfig::default()
+    }).unwrap());
+    assert!(!loader.is_pinned("greet"));
+    loader.pin("greet").unwrap();
+    assert!(loader.is_pinned("greet"));
+    // fill the cache with other templates
+    loader.get("nested/person").unwrap(); // miss
+    loader.get("dismiss.mustache").unwrap(); // miss, evicts nested/person
+    loader.get("nesting-0").unwrap_err(); // miss
+    loader.get("greet").unwrap(); // hit
+    assert_eq!(loader.stats(), CacheStats {
+        gets: 4,
+        hits: 1,
+        misses: 3,
+        evictions: 1,
+    });
+    // greet is a hit, nested/person is a miss which evicts dismiss.mustache
+    let rendered = loader.render_to_string("greet", &json!({"name": "🦀"})).unwrap();
+    assert_eq!(rendered, "hello 🦀");
+    assert_eq!(loader.stats().evictions, 2);
+    // pinning an already cached template moves it out of the cache
+    loader.pin("nested/person").unwrap();
+    loader.get("dismiss.mustache").unwrap(); // miss, nothing to evict
+    loader.get("nested/person").unwrap(); // hit
+    assert_eq!(loader.stats().evictions, 2);
+    assert_eq!(loader.stats().hits, 3);
+    let err = loader.pin("missing").unwrap_err();
+    assert_eq!(err, MoostacheError::IoError("missing".into(), io::ErrorKind::NotFound));
+    assert!(!loader.is_pinned("missing"));
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_unpin() {
+    let mut loader = FileLoader::try_from(LoaderConfig {
+        cache_size: 1,
+        ..LoaderConfig::default()
+    }).unwrap();
+    loader.pin("greet").unwrap();
+    // replacing a pinned template keeps it pinned
+    loader.insert("greet".into(), Template::parse("hi").unwrap()).unwrap();
+    loader.get("nested/person").unwrap();
+    assert_eq!(loader.render_to_string("greet", &json!({})).unwrap(), "hi");
+    assert!(loader.unpin("greet")); // evicts nested/person
+    assert!(!loader.unpin("greet"));
+    assert!(!loader.is_pinned("greet"));
+    loader.get("nested/person").unwrap(); // miss, evicts greet
+    assert_eq!(loader.render_to_string("greet", &json!({"name": "🦀"})).unwrap(), "hello 🦀");
+    loader.pin("greet").unwrap();
+    assert!(loader.remove("greet").is_some());
+    assert!(!loader.is_pinned("greet"));
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_loaders_from_owned_config() {
+    // e.g. built from std::env::var at runtime
+    fn owned_config() -> LoaderConfig<'static> {
+        let dir = String::from("./templates/");
+        let ext = format!("{}tml", "h");
+        LoaderConfig {
+            templates_directory: dir.into(),
+            templates_extension: ext.into(),
+            ..LoaderConfig::default()
+        }
+    }
+    let data = json!({"name": "🦀"});
+    let loader = FileLoader::try_from(owned_config()).unwrap();
+    assert_eq!(loader.render_to_string("greet", &data).unwrap(), "hello 🦀");
+    let loader = HashMapLoader::try_from(owned_config()).unwrap();
+    assert_eq!(loader.render_to_string("greet", &data).unwrap(), "hello 🦀");
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_len_and_capacity() {
+    let mut loader = FileLoader::try_from(LoaderConfig {
+        cache_size: 2,
+        ..LoaderConfig::default()
+    }).unwrap();
+    assert_eq!(loader.capacity(), 2);
+    assert_eq!(loader.len(), 0);
+    assert!(loader.is_empty());
+    loader.get("greet").unwrap();
+    assert_eq!(loader.len(), 1);
+    loader.get("greet").unwrap();
+    assert_eq!(loader.len(), 1);
+    loader.get("nested/person").unwrap();
+    assert_eq!(loader.len(), 2);
+    loader.get("shout").unwrap_err();
+    loader.get("blog/post.rel").unwrap_err();
+    loader.insert("extra".into(), Template::parse("extra").unwrap());
+    assert_eq!(loader.len(), 2);
+    assert_eq!(loader.capacity(), 2);
+    // pinned templates aren't in the cache
+    loader.pin("nested/person").unwrap();
+    assert_eq!(loader.len(), 1);
+    loader.remove("extra").unwrap();
+    assert!(loader.is_empty());
+}
+
+#[test]
+fn miri_iso_hash_map_loader_recording() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "page" => "{{> a }}{{#items}}{{> b }}{{/items}}{{^items}}{{> c }}{{/items}}",
+        "a" => "a",
+        "b" => "b{{> a }}",
+        "c" => "c",
+    }).unwrap();
+    // nothing is recorded unless recording
+    let template = loader.get("page").unwrap();
+    assert_eq!(loader.stop_recording(), Vec::<String>::new());
+    loader.start_recording();
+    let rendered = template.render_to_string(&loader, &json!({"items": [1, 2]})).unwrap();
+    assert_eq!(rendered, "ababa");
+    assert_eq!(loader.stop_recording(), ["a", "b"]);
+    // stopping ends the recording
+    template.render_to_string(&loader, &json!({})).unwrap();
+    assert_eq!(loader.stop_recording(), Vec::<String>::new());
+    loader.start_recording();
+    loader.render_to_string("page", &json!({})).unwrap();
+    assert!(loader.get("missing").is_err());
+    assert_eq!(loader.stop_recording(), ["page", "a", "c"]);
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_recording_and_preload() {
+    let dir = TempDir::new("recording");
+    dir.write("page.html", "{{> a }} {{> b }}");
+    dir.write("a.html", "a");
+    dir.write("b.html", "b");
+    let config = dir.config();
+    let loader = FileLoader::try_from(config.clone()).unwrap();
+    loader.start_recording();
+    assert_eq!(loader.render_to_string("page", &json!({})).unwrap(), "a b");
+    let recorded = loader.stop_recording();
+    assert_eq!(recorded, ["page", "a", "b"]);
+    // preloading warms the cache so renders only hit it
+    let loader = FileLoader::try_from(config.clone()).unwrap();
+    loader.preload(&recorded).unwrap();
+    assert_eq!(loader.len(), 3);
+    assert_eq!(loader.stats().misses, 3);
+    assert_eq!(loader.render_to_string("page", &json!({})).unwrap(), "a b");
+    assert_eq!(loader.stats().misses, 3);
+    // templates before a missing one are still preloaded
+    let loader = FileLoader::try_from(config).unwrap();
+    assert!(loader.preload(["a", "missing", "b"]).is_err());
+    assert_eq!(loader.len(), 1);
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_last_modified() {
+    use std::time::{Duration, SystemTime};
+    let dir = TempDir::new("last-modified");
+    dir.write("page.html", "{{> header }} {{> footer }}");
+    dir.write("header.html", "{{> logo }}");
+    dir.write("footer.html", "{{> logo }}");
+    dir.write("logo.html", "moo");
+    dir.write("unused.html", "moo");
+    let set_modified = |name: &str, modified: SystemTime| {
+        fs::File::options()
+            .write(true)
+            .open(dir.path(name))
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    };
+    let epoch = SystemTime::UNIX_EPOCH;
+    for name in ["page.html", "header.html", "footer.html", "logo.html", "unused.html"] {
+        set_modified(name, epoch + Duration::from_hours(1));
+    }
+    let loader = FileLoader::try_from(dir.config()).unwrap();
+    assert_eq!(loader.last_modified("page").unwrap(), epoch + Duration::from_hours(1));
+    // modifying a nested partial updates the page
+    set_modified("logo.html", epoch + Duration::from_hours(2));
+    assert_eq!(loader.last_modified("page").unwrap(), epoch + Duration::from_hours(2));
+    assert_eq!(loader.last_modified("header").unwrap(), epoch + Duration::from_hours(2));
+    // but not modifying an unrelated template
+    set_modified("unused.html", epoch + Duration::from_hours(3));
+    assert_eq!(loader.last_modified("page").unwrap(), epoch + Duration::from_hours(2));
+    // even if the page is cached, new partials are found
+    loader.get("page").unwrap();
+    dir.write("page.html", "{{> header }} {{> unused }}");
+    set_modified("page.html", epoch + Duration::from_hours(1));
+    assert_eq!(loader.last_modified("page").unwrap(), epoch + Duration::from_hours(3));
+    // cycles are fine
+    dir.write("logo.html", "{{> header }}");
+    assert!(loader.last_modified("page").unwrap() > epoch + Duration::from_hours(3));
+    dir.write("footer.html", "{{> missing }}");
+    assert!(matches!(loader.last_modified("footer"), Err(MoostacheError::IoError(..))));
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_clear_cache() {
+    let dir = TempDir::new("clear-cache");
+    dir.write("page.html", "old {{name}}");
+    dir.write("pinned.html", "old pinned");
+    let loader = FileLoader::try_from(dir.config()).unwrap();
+    loader.pin("pinned").unwrap();
+    let data = json!({"name": "🦀"});
+    assert_eq!(loader.render_to_string("page", &data).unwrap(), "old 🦀");
+    dir.write("page.html", "new {{name}}");
+    dir.write("pinned.html", "new pinned");
+    // still cached
+    assert_eq!(loader.render_to_string("page", &data).unwrap(), "old 🦀");
+    loader.clear_cache();
+    assert!(loader.is_empty());
+    assert_eq!(loader.render_to_string("page", &data).unwrap(), "new 🦀");
+    // pinned templates are kept
+    assert_eq!(loader.render_to_string("pinned", &data).unwrap(), "old pinned");
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_cache_bytes() {
+    let dir = TempDir::new("cache-bytes");
+    for i in 0..4 {
+        dir.write(&format!("t{i}.html"), format!("{i} {}", "x".repeat(400)));
+    }
+    dir.write("huge.html", "y".repeat(5000));
+    let unlimited = FileLoader::try_from(dir.config()).unwrap();
+    unlimited.get("t0").unwrap();
+    let one = unlimited.cached_bytes();
+    assert!(one > 400);
+    // room for two templates but not three
+    let limit = one * 5 / 2;
+    let loader = FileLoader::try_from(LoaderConfig {
+        cache_bytes: Some(limit),
+        ..dir.config()
+    }).unwrap();
+    for i in 0..4 {
+        let rendered = loader.render_to_string(&format!("t{i}"), &json!({})).unwrap();
+        assert!(rendered.starts_with(&format!("{i} x")));
+        assert!(loader.cached_bytes() <= limit);
+    }
+    assert_eq!(loader.cached_bytes(), one * 2);
+    assert_eq!(loader.stats().evictions, 2);
+    // templates larger than the limit aren't cached
+    assert_eq!(loader.render_to_string("huge", &json!({})).unwrap(), "y".repeat(5000));
+    assert_eq!(loader.cached_bytes(), one * 2);
+    assert_eq!(loader.stats().evictions, 2);
+    loader.clear_cache();
+    assert_eq!(loader.cached_bytes(), 0);
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_loader_fallback_extensions() {
+    let dir = TempDir::new("fallback-extensions");
+    dir.write("page.html", "{{> legacy }} {{> both }}");
+    dir.write("legacy.htm", "htm {{name}}");
+    dir.write("both.htm", "htm both");
+    dir.write("both.html", "html both");
+    dir.write("other.mustache", "mustache");
+    let config = LoaderConfig {
+        templates_extension: ".html".into(),
+        fallback_extensions: vec!["htm".into()],
+        ..dir.config()
+    };
+    let data = json!({"name": "🦀"});
+    let file_loader = FileLoader::try_from(config.clone()).unwrap();
+    let hashmap_loader = HashMapLoader::try_from(config).unwrap();
+    // .htm only partials resolve, and .html wins if both exist
+    assert_eq!(file_loader.render_to_string("page", &data).unwrap(), "htm 🦀 html both");
+    assert_eq!(hashmap_loader.render_to_string("page", &data).unwrap(), "htm 🦀 html both");
+    assert_eq!(file_loader.find("*").unwrap(), ["both", "legacy", "page"]);
+    // extensions which aren't listed aren't tried
+    assert!(file_loader.get("other").unwrap_err().is_io_error());
+    let err = MoostacheError::LoaderErrorTemplateNotFound("other".into());
+    assert_eq!(hashmap_loader.get("other").unwrap_err(), err);
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_file_loader_find() {
+    let loader = FileLoader::try_from(LoaderConfig::default()).unwrap();
+    assert_eq!(loader.find("emails/*").unwrap(), ["emails/reset-password", "emails/welcome"]);
+    assert_eq!(
+        loader.find("emails/**").unwrap(),
+        ["emails/drafts/old", "emails/reset-password", "emails/welcome"],
+    );
+    assert_eq!(loader.find("emails/*-*").unwrap(), ["emails/reset-password"]);
+    assert_eq!(loader.find("gree?").unwrap(), ["greet"]);
+    assert_eq!(loader.find("**/person").unwrap(), ["nested/person"]);
+    assert!(loader.find("emails/").unwrap().is_empty());
+    assert!(loader.find("emails/notes").unwrap().is_empty());
+    // found templates can be loaded by name
+    let data = json!({"name": "🦀"});
+    for name in loader.find("emails/*").unwrap() {
+        assert!(loader.render_to_string(&name, &data).unwrap().contains("🦀"));
+    }
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_render_case_insensitive_extension_hashmap() {
+    let data = json!({"name": "world"});
+    let loader = HashMapLoader::try_from(
+        LoaderConfig::default()
+    ).unwrap();
+    let err = loader.render_to_string("shout", &data).unwrap_err();
+    assert_eq!(err, MoostacheError::LoaderErrorTemplateNotFound("shout".into()));
+    let loader = HashMapLoader::try_from(LoaderConfig {
+        case_insensitive_extension: true,
+        ..LoaderConfig::default()
+    }).unwrap();
+    let rendered = loader.render_to_string("shout", &data).unwrap();
+    assert_eq!(rendered, "HELLO world!");
+    let rendered = loader.render_to_string("greet", &data).unwrap();
+    assert_eq!(rendered, "hello world");
+}
+
+#[test]
+#[cfg(feature = "fs")]
+fn miri_render_case_insensitive_extension_file() {
+    let data = json!({"name": "world"});
+    let loader = FileLoader::try_from(LoaderConfig {
+        case_insensitive_extension: true,
+        ..LoaderConfig::default()
+    }).unwrap();
+    let rendered = loader.render_to_string("shout", &data).unwrap();
+    assert_eq!(rendered, "HELLO world!");
+    let rendered = loader.render_to_string("greet", &data).unwrap();
+    assert_eq!(rendered, "hello world");
+    let err = loader.render_to_string("missing", &data).unwrap_err();
+    assert_eq!(err, MoostacheError::IoError("missing".into(), io::ErrorKind::NotFound));
+}
+
 ////////////////////////////////////////////////////////
 // TEST RENDERING TEMPLATES WITH PARTIALS WITH ERRORS //
 ////////////////////////////////////////////////////////
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_invalid_comment() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1507,9 +4670,10 @@ fn miri_render_partial_invalid_comment() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_invalid_escaped_variable() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1521,9 +4685,10 @@ fn miri_render_partial_invalid_escaped_variable() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_invalid_unescaped_variable() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1535,9 +4700,10 @@ fn miri_render_partial_invalid_unescaped_variable() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_invalid_inverted_section_start() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1549,9 +4715,10 @@ fn miri_render_partial_invalid_inverted_section_start() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_invalid_section_start() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1563,9 +4730,10 @@ fn miri_render_partial_invalid_section_start() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_invalid_section_end() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1577,9 +4745,10 @@ fn miri_render_partial_invalid_section_end() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_mismatched_section_end() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1591,9 +4760,10 @@ fn miri_render_partial_mismatched_section_end() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_unclosed_sections() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1605,9 +4775,10 @@ fn miri_render_partial_unclosed_sections() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_invalid_partial() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1619,9 +4790,10 @@ fn miri_render_partial_invalid_partial() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_no_content() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1633,9 +4805,10 @@ fn miri_render_partial_no_content() {
 }
 
 #[test]
+#[cfg(feature = "fs")]
 fn miri_render_partial_nonexistent_partial() {
     let loader = FileLoader::try_from(LoaderConfig {
-        templates_extension: "error",
+        templates_extension: "error".into(),
         ..LoaderConfig::default()
     }).unwrap();
     let rendered = loader.render_to_string(
@@ -1649,78 +4822,1113 @@ fn miri_render_partial_nonexistent_partial() {
     assert_eq!(rendered, expected);
 }
 
+///////////////////////
+// TEST RENDER DIFFS //
+///////////////////////
+
+#[test]
+fn miri_iso_render_range() {
+    let source = "<h1>{{title}}</h1>{{#a}}{{#b}}[{{.}}]{{/b}}{{|}}no a{{/a}}{{^c}}no c{{/c}}!";
+    let template = Template::parse(source).unwrap();
+    let data = json!({"title": "t", "a": true, "b": [1, 2]});
+    let full = template.render_no_partials_to_string(&data).unwrap();
+    assert_eq!(full, "<h1>t</h1>[1][2]no c!");
+    let render_range = |frags: Range<usize>| {
+        let mut writer = Vec::new();
+        template.render_range(frags, &(), &data, &mut writer).map(|()| String::from_utf8(writer).unwrap())
+    };
+    // 0 "<h1>", 1 title, 2 "</h1>", 3 #a, 4 #b, 5 "[", 6 ".", 7 "]",
+    // 8 else of a, 9 "no a", 10 ^c, 11 "no c", 12 "!"
+    assert_eq!(render_range(0..13).unwrap(), full);
+    assert_eq!(render_range(1..3).unwrap(), &full[4..10]);
+    assert_eq!(render_range(3..10).unwrap(), &full[10..16]);
+    assert_eq!(render_range(3..13).unwrap(), &full[10..]);
+    assert_eq!(render_range(10..12).unwrap(), "no c");
+    assert_eq!(render_range(12..12).unwrap(), "");
+    assert_eq!(render_range(13..13).unwrap(), "");
+    // within the content of a section, rendered with
+    // only the root scope, including a section right
+    // before the else of the section it's nested in
+    assert_eq!(render_range(7..8).unwrap(), "]");
+    assert_eq!(render_range(4..8).unwrap(), "[1][2]");
+    assert_eq!(render_range(9..10).unwrap(), "no a");
+    // out of bounds, backwards, or splitting a section
+    for frags in [0..14, 14..14, Range { start: 2, end: 1 }, 3..5, 0..4, 4..9, 5..9, 6..11, 3..8, 8..10, 9..11, 10..11] {
+        let err = render_range(frags.clone()).unwrap_err();
+        assert_eq!(err, MoostacheError::RenderErrorInvalidFragmentRange(frags));
+    }
+}
+
+#[test]
+fn miri_iso_render_diff_single_variable() {
+    let template = Template::parse("<h1>{{title}}</h1><p>{{body}}</p>").unwrap();
+    let diff = template.render_diff(
+        &(),
+        &json!({"title": "hello", "body": "world"}),
+        &json!({"title": "hello", "body": "moostache"}),
+    ).unwrap();
+    assert_eq!(diff.rendered, "<h1>hello</h1><p>moostache</p>");
+    assert_eq!(diff.changed, vec![ChangedFragment {
+        fragment_index: 3,
+        old: 17..22,
+        new: 17..26,
+    }]);
+    assert_eq!(&diff.rendered[diff.changed[0].new.clone()], "moostache");
+}
+
+#[test]
+fn miri_iso_render_diff_section() {
+    let template = Template::parse("{{#items}}<li>{{.}}</li>{{/items}}!{{name}}").unwrap();
+    let diff = template.render_diff(
+        &(),
+        &json!({"items": [1, 2], "name": "same"}),
+        &json!({"items": [1, 2, 3], "name": "same"}),
+    ).unwrap();
+    assert_eq!(diff.rendered, "<li>1</li><li>2</li><li>3</li>!same");
+    assert_eq!(diff.changed, vec![ChangedFragment {
+        fragment_index: 0,
+        old: 0..20,
+        new: 0..30,
+    }]);
+}
+
+#[test]
+fn miri_iso_render_diff_unchanged() {
+    let template = Template::parse("hello {{name}}!").unwrap();
+    let data = json!({"name": "world"});
+    let diff = template.render_diff(&(), &data, &data).unwrap();
+    assert_eq!(diff.rendered, "hello world!");
+    assert!(diff.changed.is_empty());
+}
+
+///////////////////////////////
+// TEST FLATTENING TEMPLATES //
+///////////////////////////////
+
+#[test]
+fn miri_iso_flatten_renders_same_as_original() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "page" => "{{#user}}{{> card }} {{/user}}{{^user}}{{> empty }}{{/user}}{{> footer }}",
+        "card" => "<{{name}}>{{#tags}}{{> tag }}{{/tags}}",
+        "tag" => "[{{.}}]",
+        "empty" => "nobody",
+        "footer" => " {{#year}}({{.}}){{/year}}{{% raw %}}{{> raw }}{{% endraw %}}",
+    }).unwrap();
+    let template = loader.get("page").unwrap();
+    let flattened = template.flatten(&loader).unwrap();
+    assert!(flattened.frags().iter().all(|frag| !matches!(frag, Fragment::Partial(_))));
+    for data in [
+        json!({"user": [{"name": "🦀", "tags": ["a", "b"]}, {"name": "b"}], "year": 2024}),
+        json!({"user": {"name": "c", "tags": []}}),
+        json!({"user": false, "year": 1}),
+    ] {
+        let expected = template.render_to_string(&loader, &data).unwrap();
+        let rendered = flattened.render_no_partials_to_string(&data).unwrap();
+        assert_eq!(rendered, expected);
+    }
+}
+
+#[test]
+fn miri_iso_flatten_cyclic_partials() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "a" => "a {{#list}}{{> b }}{{/list}}",
+        "b" => "b {{> a }}",
+    }).unwrap();
+    let template = Template::parse("{{> a }}").unwrap();
+    let err = template.flatten(&loader).unwrap_err();
+    assert_eq!(err, MoostacheError::LoaderErrorCyclicPartial("a".into()));
+    // same partial more than once is fine if it's not a cycle
+    let template = Template::parse("{{> b }}{{> b }}").unwrap();
+    let loader = HashMapLoader::try_from(hashmap! {
+        "b" => "b",
+    }).unwrap();
+    let flattened = template.flatten(&loader).unwrap();
+    assert_eq!(flattened.render_no_partials_to_string(&json!(null)).unwrap(), "bb");
+}
+
+#[test]
+fn miri_iso_flatten_too_many_nested_fragments() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "many" => "{{b}}".repeat(40_000),
+    }).unwrap();
+    let template = Template::parse("{{#a}}{{> many }}{{> many }}{{/a}}").unwrap();
+    let err = template.flatten(&loader).unwrap_err();
+    assert_eq!(err, MoostacheError::ParseErrorTooManyFragments(String::new()));
+}
+
+///////////////////////////////
+// TEST LOADING ZIP ARCHIVES //
+///////////////////////////////
+
+#[cfg(feature = "zip")]
+fn zip_archive(files: &[(&str, &str)]) -> io::Cursor<Vec<u8>> {
+    let mut archive = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    for (path, content) in files {
+        archive.start_file(*path, zip::write::SimpleFileOptions::default()).unwrap();
+        archive.write_all(content.as_bytes()).unwrap();
+    }
+    archive.finish().unwrap()
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn miri_iso_zip_loader_renders_partials() {
+    let archive = zip_archive(&[
+        ("templates/page.html", "{{> partials/header }}, {{#names}}<{{.}}>{{/names}}"),
+        ("templates/partials/header.html", "hello {{title}}"),
+        ("templates/shout.HTML", "HELLO"),
+        ("templates/readme.md", "not a template"),
+        ("other.html", "not in templates directory"),
+    ]);
+    let loader = ZipLoader::new(archive, LoaderConfig::default()).unwrap();
+    let data = json!({"title": "friends", "names": ["a", "b"]});
+    let rendered = loader.render_to_string("page", &data).unwrap();
+    assert_eq!(rendered, "hello friends, <a><b>");
+    for name in ["shout", "readme", "readme.md", "other", "../other"] {
+        let err = loader.get(name).unwrap_err();
+        assert_eq!(err, MoostacheError::LoaderErrorTemplateNotFound(name.into()));
+    }
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn miri_iso_zip_loader_config() {
+    let archive = zip_archive(&[
+        ("greet.mustache", "hello {{name}}"),
+        ("shout.MUSTACHE", "HELLO {{name}}"),
+        ("invalid.mustache", "{{# unclosed }}"),
+    ]);
+    let config = LoaderConfig {
+        templates_directory: "".into(),
+        templates_extension: "mustache".into(),
+        fallback_extensions: Vec::new(),
+        case_insensitive_extension: true,
+        cache_size: 1,
+        cache_bytes: None,
+        parse_options: ParseOptions::default(),
+    };
+    let loader = ZipLoader::new(archive, config).unwrap();
+    let data = json!({"name": "world"});
+    assert_eq!(loader.render_to_string("greet", &data).unwrap(), "hello world");
+    assert_eq!(loader.render_to_string("shout", &data).unwrap(), "HELLO world");
+    assert_eq!(loader.render_to_string("greet", &data).unwrap(), "hello world");
+    let err = loader.get("invalid").unwrap_err();
+    assert_eq!(err, MoostacheError::ParseErrorUnclosedSectionTags("invalid".into()));
+    let config = LoaderConfig {
+        cache_size: 0,
+        ..LoaderConfig::default()
+    };
+    let err = ZipLoader::new(zip_archive(&[]), config).unwrap_err();
+    assert_eq!(err, MoostacheError::ConfigErrorNonPositiveCacheSize);
+    let err = ZipLoader::new(io::Cursor::new(b"not a zip"), LoaderConfig::default()).unwrap_err();
+    assert!(err.is_io_error());
+}
+
+#[cfg(feature = "zip")]
+#[test]
+fn miri_iso_zip_loader_fallback_extensions() {
+    let archive = zip_archive(&[
+        ("templates/legacy.htm", "htm"),
+        ("templates/both.html", "html"),
+        ("templates/both.htm", "htm"),
+        ("templates/reversed.htm", "htm"),
+        ("templates/reversed.html", "html"),
+    ]);
+    let config = LoaderConfig {
+        fallback_extensions: vec![".htm".into()],
+        ..LoaderConfig::default()
+    };
+    let loader = ZipLoader::new(archive, config).unwrap();
+    let data = json!({});
+    assert_eq!(loader.render_to_string("legacy", &data).unwrap(), "htm");
+    // regardless of their order in the archive
+    assert_eq!(loader.render_to_string("both", &data).unwrap(), "html");
+    assert_eq!(loader.render_to_string("reversed", &data).unwrap(), "html");
+}
+
+////////////////////////////////////
+// TEST SERIALIZING LOADERCONFIGS //
+////////////////////////////////////
+
+#[cfg(all(feature = "fs", feature = "serde"))]
+#[test]
+fn miri_iso_loader_config_serde_round_trip() {
+    let config = LoaderConfig {
+        templates_directory: "./templates/".into(),
+        templates_extension: ".mustache".into(),
+        fallback_extensions: vec![".html".into(), ".htm".into()],
+        case_insensitive_extension: true,
+        cache_size: 7,
+        cache_bytes: Some(4096),
+        parse_options: ParseOptions::default(),
+    };
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(
+        json,
+        r#"{"templates_directory":"./templates/","templates_extension":".mustache","fallback_extensions":[".html",".htm"],"case_insensitive_extension":true,"cache_size":7,"cache_bytes":4096}"#,
+    );
+    let deserialized: LoaderConfig<'static> = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized, config);
+    // missing fields are set to their defaults
+    let deserialized: LoaderConfig = serde_json::from_str(r#"{"cache_size": 7}"#).unwrap();
+    assert_eq!(deserialized, LoaderConfig { cache_size: 7, ..LoaderConfig::default() });
+    assert!(serde_json::from_str::<LoaderConfig>(r#"{"cache_size": "7"}"#).is_err());
+}
+
+/////////////////////////////////
+// TEST HASHMAPLOADER FROM VEC //
+/////////////////////////////////
+
+#[test]
+fn miri_iso_hashmap_loader_from_vec() {
+    let loader = HashMapLoader::try_from(vec![
+        ("layout".to_owned(), "{{> content }}!".to_owned()),
+        ("content".to_owned(), "first".to_owned()),
+        ("content".to_owned(), "last".to_owned()),
+    ]).unwrap();
+    // last template with a duplicate name wins
+    let rendered = loader.render_to_string("layout", &json!(null)).unwrap();
+    assert_eq!(rendered, "last!");
+    let loader = HashMapLoader::try_from(Vec::<(&str, &str)>::new()).unwrap();
+    assert!(loader.get("missing").is_err());
+}
+
+#[test]
+fn miri_iso_hashmap_loader_from_vec_invalid() {
+    let err = HashMapLoader::try_from(vec![
+        ("valid", "{{ a }}"),
+        ("invalid", "{{# a }}"),
+        ("also invalid", ""),
+    ]).unwrap_err();
+    assert_eq!(err, MoostacheError::ParseErrorUnclosedSectionTags("invalid".into()));
+}
+
+/////////////////////////////////
+// TEST MERGING HASHMAPLOADERS //
+/////////////////////////////////
+
+#[test]
+fn miri_iso_merge_hashmap_loaders() {
+    let mut base = HashMapLoader::try_from(hashmap! {
+        "layout" => "base layout {{> content }}",
+        "content" => "base content",
+    }).unwrap();
+    let app = HashMapLoader::try_from(hashmap! {
+        "content" => "app content",
+        "extra" => "app extra",
+    }).unwrap();
+    let replaced = base.merge(app);
+    assert_eq!(replaced, vec!["content".to_owned()]);
+    let rendered = base.render_to_string("layout", &json!(null)).unwrap();
+    assert_eq!(rendered, "base layout app content");
+    let rendered = base.render_to_string("extra", &json!(null)).unwrap();
+    assert_eq!(rendered, "app extra");
+}
+
+////////////////////////////////////
+// TEST DETECTING CYCLIC PARTIALS //
+////////////////////////////////////
+
+#[test]
+fn miri_iso_detect_cycles_two_templates() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "a" => "a {{> b }}",
+        "b" => "b {{> a }}",
+    }).unwrap();
+    let cycle = loader.detect_cycles().unwrap_err();
+    assert_eq!(cycle, vec!["a".to_owned(), "b".to_owned(), "a".to_owned()]);
+}
+
+#[test]
+fn miri_iso_detect_cycles_self_include() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "a" => "{{> b }}",
+        "b" => "{{#list}}{{> b }}{{/list}}",
+    }).unwrap();
+    let cycle = loader.detect_cycles().unwrap_err();
+    assert_eq!(cycle, vec!["b".to_owned(), "b".to_owned()]);
+}
+
+#[test]
+fn miri_iso_detect_cycles_none() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "a" => "{{> b }} {{> c }} {{> missing }}",
+        "b" => "{{> c }}",
+        "c" => "c",
+    }).unwrap();
+    assert_eq!(loader.detect_cycles(), Ok(()));
+}
+
+#[test]
+fn miri_iso_transitive_partials() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "a" => "a {{#list}}{{> b }}{{/list}}",
+        "b" => "b {{> c }}{{> c }}",
+        "c" => "c",
+        "d" => "d {{> a }}",
+    }).unwrap();
+    let partials = loader.transitive_partials("a").unwrap();
+    assert_eq!(partials, maplit::hashset! {"b".to_owned(), "c".to_owned()});
+    assert!(loader.transitive_partials("c").unwrap().is_empty());
+    let err = loader.transitive_partials("e").unwrap_err();
+    assert_eq!(err, MoostacheError::LoaderErrorTemplateNotFound("e".into()));
+}
+
+#[test]
+fn miri_iso_transitive_partials_cycle() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "a" => "a {{> b }}",
+        "b" => "b {{> c }}",
+        "c" => "c {{> a }}{{> missing }}",
+    }).unwrap();
+    let err = loader.transitive_partials("a").unwrap_err();
+    assert_eq!(err, MoostacheError::LoaderErrorTemplateNotFound("missing".into()));
+    let loader = HashMapLoader::try_from(hashmap! {
+        "a" => "a {{> b }}",
+        "b" => "b {{> c }}",
+        "c" => "c {{> a }}",
+    }).unwrap();
+    let partials = loader.transitive_partials("a").unwrap();
+    assert_eq!(partials, maplit::hashset! {"a".to_owned(), "b".to_owned(), "c".to_owned()});
+}
+
+///////////////////////////////
+// TEST INSPECTING TEMPLATES //
+///////////////////////////////
+
+#[test]
+fn miri_iso_inspect_mixed_template() {
+    let source = "a{{! comment }}{{#b}}{{ c.d }}{{{e}}}{{/b}}{{^ f }}{{>g/h}}{{% raw %}}{{i}}{{% endraw %}}{{/f}}🦀";
+    let expected = vec![
+        FragmentInfo {
+            kind: FragmentKind::Literal("a".into()),
+            span: 0..1,
+        },
+        FragmentInfo {
+            kind: FragmentKind::SectionStart { name: "b".into(), inverted: false, nested: 2 },
+            span: 15..21,
+        },
+        FragmentInfo {
+            kind: FragmentKind::Variable { name: "c.d".into(), escaped: true },
+            span: 21..30,
+        },
+        FragmentInfo {
+            kind: FragmentKind::Variable { name: "e".into(), escaped: false },
+            span: 30..37,
+        },
+        FragmentInfo {
+            kind: FragmentKind::SectionStart { name: "f".into(), inverted: true, nested: 2 },
+            span: 43..51,
+        },
+        FragmentInfo {
+            kind: FragmentKind::Partial("g/h".into()),
+            span: 51..59,
+        },
+        FragmentInfo {
+            kind: FragmentKind::Literal("{{i}}".into()),
+            span: 70..75,
+        },
+        FragmentInfo {
+            kind: FragmentKind::Literal("🦀".into()),
+            span: 95..99,
+        },
+    ];
+    // same spans whether the source is borrowed or owned
+    assert_eq!(Template::parse(source).unwrap().inspect(), expected);
+    assert_eq!(Template::parse(source.to_owned()).unwrap().inspect(), expected);
+    for info in &expected {
+        if let FragmentKind::Literal(literal) = &info.kind {
+            assert_eq!(&source[info.span.clone()], literal);
+        }
+    }
+}
+
+#[test]
+fn miri_iso_debug_tree_heavy_section_nesting() {
+    let source = "prefix{{#s1}}infix1{{#s1a}}infix2{{#s1aa}}content-1aa{{/s1aa}}{{^s1aa}}nothing-1aa{{/s1aa}}{{#s1ab}}content-1ab{{/s1ab}}{{^s1ab}}nothing-1ab{{/s1ab}}{{/s1a}}{{^s1a}}nothing-1a{{/s1a}}infix3{{#s1b}}content-1b{{/s1b}}{{^s1b}}nothing-1b{{/s1b}}infix4{{/s1}}suffix";
+    let template = Template::parse(source).unwrap();
+    let expected = indoc::indoc! {r#"
+        "prefix"
+        {{# s1 }}
+          "infix1"
+          {{# s1a }}
+            "infix2"
+            {{# s1aa }}
+              "content-1aa"
+            {{/ s1aa }}
+            {{^ s1aa }}
+              "nothing-1aa"
+            {{/ s1aa }}
+            {{# s1ab }}
+              "content-1ab"
+            {{/ s1ab }}
+            {{^ s1ab }}
+              "nothing-1ab"
+            {{/ s1ab }}
+          {{/ s1a }}
+          {{^ s1a }}
+            "nothing-1a"
+          {{/ s1a }}
+          "infix3"
+          {{# s1b }}
+            "content-1b"
+          {{/ s1b }}
+          {{^ s1b }}
+            "nothing-1b"
+          {{/ s1b }}
+          "infix4"
+        {{/ s1 }}
+        "suffix"
+    "#};
+    assert_eq!(template.debug_tree(), expected);
+}
+
+#[test]
+fn miri_iso_debug_tree_elses_and_empty_sections() {
+    let source = "{{#a}}{{#b}}{{|}}{{{c}}}\n{{/b}}{{|}}{{>* d}}{{#e}}{{/e}}{{/a}}";
+    let template = Template::parse(source).unwrap();
+    let expected = indoc::indoc! {r#"
+        {{# a }}
+          {{# b }}
+          {{|}}
+            {{{ c }}}
+            "\n"
+          {{/ b }}
+        {{|}}
+          {{>* d }}
+          {{# e }}
+          {{/ e }}
+        {{/ a }}
+    "#};
+    assert_eq!(template.debug_tree(), expected);
+}
+
+#[test]
+fn miri_iso_inspect_missing_section() {
+    let template = Template::parse("{{^? a }}{{b}}{{/ a }}").unwrap();
+    let expected = vec![
+        FragmentInfo {
+            kind: FragmentKind::MissingSectionStart { name: "a".into(), nested: 1 },
+            span: 0..9,
+        },
+        FragmentInfo {
+            kind: FragmentKind::Variable { name: "b".into(), escaped: true },
+            span: 9..14,
+        },
+    ];
+    assert_eq!(template.inspect(), expected);
+    assert_eq!(template.debug_tree(), "{{^? a }}\n  {{ b }}\n{{/ a }}\n");
+}
+
+#[test]
+fn miri_iso_render_session() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "status" => "{{#services}}{{> service }}{{/services}}\n\n",
+        "service" => "{{name}}: {{#up}}up{{/up}}{{^up}}down{{/up}}\n",
+    }).unwrap();
+    let template = loader.get("status").unwrap();
+    let mut session = RenderSession::new(template, &loader);
+    let rendered = session.render(&json!({"services": [{"name": "db", "up": true}]})).unwrap();
+    assert_eq!(rendered, "db: up\n\n\n");
+    let data = json!({"services": [{"name": "db", "up": false}, {"name": "<web>", "up": true}]});
+    let rendered = session.render(&data).unwrap().to_owned();
+    assert_eq!(rendered, template.render_to_string(&loader, &data).unwrap());
+    assert_eq!(rendered, "db: down\n&lt;web&gt;: up\n\n\n");
+
+    let template = Template::parse("{{a}}{{> missing }}").unwrap();
+    let mut session = RenderSession::new(&template, &loader);
+    let err = session.render(&json!({"a": 1})).unwrap_err();
+    assert_eq!(err, MoostacheError::LoaderErrorTemplateNotFound("missing".into()));
+    let template = Template::parse("{{a}}").unwrap();
+    let mut session = RenderSession::new(&template, &());
+    assert_eq!(session.render(&json!({"a": 1})).unwrap(), "1");
+    assert_eq!(session.render(&json!({"a": "é"})).unwrap(), "é");
+}
+
+#[test]
+fn miri_iso_partial_count() {
+    let template = Template::parse("{{> a }}{{#b}}{{> c }}{{/b}}").unwrap();
+    assert_eq!(template.partial_count(), 2);
+    let template = Template::parse("{{> a }}{{> a }}{{>* b }}").unwrap();
+    assert_eq!(template.partial_count(), 2);
+    let template = Template::parse("hello {{ name }}!").unwrap();
+    assert_eq!(template.partial_count(), 0);
+}
+
+#[test]
+fn miri_iso_validate() {
+    assert_eq!(Template::validate("hello {{ name }}{{# items }}{{ . }}{{/ items }}"), Ok(()));
+    // the source doesn't have to outlive the check
+    let source = String::from("{{> partial }}");
+    assert_eq!(Template::validate(&source), Ok(()));
+    drop(source);
+    assert_eq!(Template::validate(""), Err(MoostacheError::ParseErrorNoContent(String::new())));
+    assert_eq!(Template::validate("{{# name }}"), Err(MoostacheError::ParseErrorUnclosedSectionTags(String::new())));
+    assert_eq!(
+        Template::validate("{{# a }}{{/ b }}"),
+        Err(MoostacheError::ParseErrorMismatchedSectionEndTag(String::new())),
+    );
+    // same errors as parse
+    for source in ["{{ a b }}", "{{{ a }}", "{{! a", "{{#a}}{{|}}{{|}}{{/a}}"] {
+        assert_eq!(Template::validate(source), Template::parse(source).map(|_| ()), "{source}");
+    }
+}
+
+#[test]
+fn miri_iso_parse_with_warnings() {
+    let source = "{{#a}}{{/a}}{{#b}}{{|}}x{{/b}}{{> p}}{{#c}}{{> p}}{{/c}}{{^?d}}{{/d}}{{> q}}";
+    let (template, warnings) = Template::parse_with_warnings(source).unwrap();
+    assert_eq!(template, Template::parse(source).unwrap());
+    assert_eq!(warnings, vec![
+        Warning::EmptySection { name: "a".into(), span: 0..6 },
+        Warning::DuplicatePartial { name: "p".into(), span: 43..50 },
+        Warning::EmptySection { name: "d".into(), span: 56..63 },
+    ]);
+    assert_eq!(&source[warnings[1].span()], "{{> p}}");
+    let (_, warnings) = Template::parse_with_warnings("{{#a}} {{/a}}").unwrap();
+    assert!(warnings.is_empty());
+    assert!(Template::parse_with_warnings("{{#a}}").is_err());
+}
+
+///////////////////////////////
+// TEST TOKENIZING TEMPLATES //
+///////////////////////////////
+
+#[test]
+fn miri_iso_tokenize() {
+    use TokenKind::*;
+    let source = "<p>{{ a.b:5 }}{{{ c }}}{{#d}}{{^? e}}{{|}}{{/d}}{{> f }}{{>* g}}{{! h }}{{% raw %}}{{ i }}{{%endraw%}}🦀</p>";
+    let tokens = tokenize(source);
+    // spans cover the whole source contiguously
+    let mut end = 0;
+    for token in &tokens {
+        assert_eq!(token.span.start, end);
+        assert!(token.span.end > token.span.start);
+        end = token.span.end;
+    }
+    assert_eq!(end, source.len());
+    let tokens: Vec<_> = tokens.into_iter().map(|token| (token.kind, &source[token.span])).collect();
+    assert_eq!(tokens, [
+        (Text, "<p>"),
+        (TagOpen, "{{"), (Whitespace, " "), (Name, "a.b:5"), (Whitespace, " "), (TagClose, "}}"),
+        (TagOpen, "{{{"), (Whitespace, " "), (Name, "c"), (Whitespace, " "), (TagClose, "}}}"),
+        (TagOpen, "{{"), (SectionStart, "#"), (Name, "d"), (TagClose, "}}"),
+        (TagOpen, "{{"), (InvertedSectionStart, "^"), (InvertedSectionStart, "?"), (Whitespace, " "), (Name, "e"), (TagClose, "}}"),
+        (TagOpen, "{{"), (SectionElse, "|"), (TagClose, "}}"),
+        (TagOpen, "{{"), (SectionEnd, "/"), (Name, "d"), (TagClose, "}}"),
+        (TagOpen, "{{"), (Partial, ">"), (Whitespace, " "), (Name, "f"), (Whitespace, " "), (TagClose, "}}"),
+        (TagOpen, "{{"), (Partial, ">"), (Partial, "*"), (Whitespace, " "), (Name, "g"), (TagClose, "}}"),
+        (TagOpen, "{{"), (Comment, "! h "), (TagClose, "}}"),
+        (TagOpen, "{{"), (RawBlock, "%"), (Whitespace, " "), (Name, "raw"), (Whitespace, " "), (RawBlock, "%"), (TagClose, "}}"),
+        (Text, "{{ i }}"),
+        (TagOpen, "{{"), (RawBlock, "%"), (Name, "endraw"), (RawBlock, "%"), (TagClose, "}}"),
+        (Text, "🦀</p>"),
+    ]);
+    // invalid templates are tokenized too, anything
+    // which isn't a complete tag is text
+    for (source, expected) in [
+        ("", vec![]),
+        ("{{", vec![(Text, "{{")]),
+        ("a {{ b", vec![(Text, "a {{ b")]),
+        ("{{}}", vec![(TagOpen, "{{"), (TagClose, "}}")]),
+        ("{{{ a }}", vec![(TagOpen, "{{"), (Name, "{"), (Whitespace, " "), (Name, "a"), (Whitespace, " "), (TagClose, "}}")]),
+        ("{{% raw %}} {{ a }}", vec![(TagOpen, "{{"), (RawBlock, "%"), (Whitespace, " "), (Name, "raw"), (Whitespace, " "), (RawBlock, "%"), (TagClose, "}}"), (Text, " {{ a }}")]),
+    ] {
+        let tokens: Vec<_> = tokenize(source).into_iter().map(|token| (token.kind, &source[token.span])).collect();
+        assert_eq!(tokens, expected, "{source}");
+    }
+}
+
+#[test]
+fn miri_iso_tokenize_delimiters() {
+    use TokenKind::*;
+    let options = ParseOptions {
+        delimiters: Delimiters { open: "[[", close: "]]" },
+        ..ParseOptions::default()
+    };
+    let source = "{{ a }}[[ b ]][[{ c }]][[# d ]][[/d]][[% raw %]][[ e ]][[%endraw%]]";
+    let tokens: Vec<_> = tokenize_with_options(source, &options)
+        .into_iter()
+        .map(|token| (token.kind, &source[token.span]))
+        .collect();
+    assert_eq!(tokens, [
+        (Text, "{{ a }}"),
+        (TagOpen, "[["), (Whitespace, " "), (Name, "b"), (Whitespace, " "), (TagClose, "]]"),
+        (TagOpen, "[[{"), (Whitespace, " "), (Name, "c"), (Whitespace, " "), (TagClose, "}]]"),
+        (TagOpen, "[["), (SectionStart, "#"), (Whitespace, " "), (Name, "d"), (Whitespace, " "), (TagClose, "]]"),
+        (TagOpen, "[["), (SectionEnd, "/"), (Name, "d"), (TagClose, "]]"),
+        (TagOpen, "[["), (RawBlock, "%"), (Whitespace, " "), (Name, "raw"), (Whitespace, " "), (RawBlock, "%"), (TagClose, "]]"),
+        (Text, "[[ e ]]"),
+        (TagOpen, "[["), (RawBlock, "%"), (Name, "endraw"), (RawBlock, "%"), (TagClose, "]]"),
+    ]);
+    // the default delimiters are the same as tokenize
+    let source = "{{{ a }}}} {{ b }}";
+    assert_eq!(tokenize_with_options(source, &ParseOptions::default()), tokenize(source));
+    // nothing is a tag with empty delimiters
+    let options = ParseOptions {
+        delimiters: Delimiters { open: "", close: "" },
+        ..ParseOptions::default()
+    };
+    let tokens = tokenize_with_options(source, &options);
+    assert_eq!(tokens, [Token { kind: Text, span: 0..source.len() }]);
+}
+
+////////////////////////
+// TEST MISSING PATHS //
+////////////////////////
+
+#[test]
+fn miri_iso_missing_paths_partial_data() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "page" => "{{title}} {{#user}}{{name}} {{email}}{{/user}} {{#items}}{{label}} {{price}}{{/items}} {{> footer }}",
+        "footer" => "{{copyright}} {{^empty}}{{year}}{{/empty}}",
+    }).unwrap();
+    let data = json!({
+        "title": "shop",
+        "user": {"name": "🦀"},
+        "items": [{"label": "a", "price": 1}, {"label": "b"}, {"price": 2}],
+        "year": null,
+    });
+    let template = loader.get("page").unwrap();
+    let missing = template.missing_paths(&loader, &data).unwrap();
+    assert_eq!(missing, vec!["email", "price", "label", "copyright", "year"]);
+    // nothing inside a missing section is reported
+    let missing = template.missing_paths(&loader, &json!({"items": []})).unwrap();
+    assert_eq!(missing, vec!["title", "user", "copyright", "year"]);
+}
+
+#[test]
+fn miri_iso_missing_paths_missing_section() {
+    let template = Template::parse("{{^? a }}{{b}}{{/ a }}{{^? c }}{{d}}{{/ c }}").unwrap();
+    let missing = template.missing_paths(&(), &json!({"c": null})).unwrap();
+    assert_eq!(missing, vec!["b"]);
+}
+
+#[test]
+fn miri_iso_missing_paths_none() {
+    let template = Template::parse("{{a.b}} {{#c}}{{.}}{{/c}}").unwrap();
+    let data = json!({"a": {"b": false}, "c": [1, 2]});
+    let missing = template.missing_paths(&(), &data).unwrap();
+    assert!(missing.is_empty());
+}
+
+#[test]
+fn miri_iso_unused_keys() {
+    let loader = HashMapLoader::try_from(hashmap! {
+        "page" => "{{title}} {{#posts as post}}{{post.title}} {{../author.name}}{{/posts}} {{> footer }} {{>* sidebar }}",
+        "footer" => "{{#user}}{{[email]}}{{/user}}{{^? footer }}{{/ footer }}",
+        "links" => "{{@count}} {{.}} {{#links.*}}{{/links.*}}",
+    }).unwrap();
+    let template = loader.get("page").unwrap();
+    let data = json!({
+        "title": "blog",
+        "posts": [],
+        "author": {"name": "John"},
+        "sidebar": "links",
+        "links": {},
+        "email": "john@example.com",
+        "stale": true,
+        "zip": null,
+    });
+    assert_eq!(template.unused_keys(&loader, &data).unwrap(), ["stale", "zip"]);
+    assert_eq!(
+        template.check_unused_keys(&loader, &data),
+        Err(MoostacheError::CheckErrorUnusedKeys(vec!["stale".into(), "zip".into()])),
+    );
+    // only objects have keys
+    assert!(template.unused_keys(&loader, &json!([1, 2])).unwrap().is_empty());
+    template.check_unused_keys(&loader, &json!("title")).unwrap();
+    // the paths of equality and range sections, but not their
+    // literals, are references
+    if cfg!(feature = "helpers") {
+        let template = Template::parse("{{#eq year 2024}}{{/eq}}{{#range 0 pages}}{{/range}}").unwrap();
+        let data = json!({"year": 1, "pages": 2, "0": 3, "2024": 4});
+        assert_eq!(template.unused_keys(&(), &data).unwrap(), ["0", "2024"]);
+    }
+}
+
+#[test]
+fn miri_iso_check_against() {
+    #[derive(Default, serde_derive::Serialize)]
+    struct Address {
+        city: String,
+    }
+    #[derive(Default, serde_derive::Serialize)]
+    struct User {
+        name: String,
+        admin: bool,
+        address: Address,
+        tags: Vec<String>,
+    }
+    let check = |source: &'static str| {
+        Template::parse(source).unwrap().check_against::<User, _, _>(&())
+    };
+    assert_eq!(check("{{name}} {{address.city}} {{^admin}}user{{/admin}} {{#tags}}{{.}}{{/tags}}"), Ok(()));
+    assert_eq!(
+        check("{{name}} {{email}} {{address.zip}}"),
+        Err(MoostacheError::CheckErrorMissingPaths(vec!["email".into(), "address.zip".into()])),
+    );
+    // partials are checked too
+    let loader = HashMapLoader::try_from(hashmap! {
+        "user" => "{{name}} {{nickname}}",
+    }).unwrap();
+    let template = Template::parse("{{> user }}").unwrap();
+    assert_eq!(
+        template.check_against::<User, _, _>(&loader),
+        Err(MoostacheError::CheckErrorMissingPaths(vec!["nickname".into()])),
+    );
+}
+
+/////////////////////////////////
+// TEST STRICT VARIABLE NAMES //
+/////////////////////////////////
+
+#[test]
+fn miri_iso_lenient_variable_names() {
+    let options = ParseOptions::default();
+    let data = json!({"-x": 1, "x-": 2, "a": {"-b": 3}, "a-b": 4});
+    for (source, expected) in [
+        ("{{ -x }}", "1"),
+        ("{{{ x- }}}", "2"),
+        ("{{ a.-b }}", "3"),
+        ("{{ a-b }}", "4"),
+        ("{{#x-}}{{.}}{{/x-}}", "2"),
+    ] {
+        let template = Template::parse_with_options(source, &options).unwrap();
+        assert_eq!(expected, template.render_no_partials_to_string(&data).unwrap());
+    }
+}
+
+#[test]
+fn miri_iso_custom_delimiters() {
+    let options = ParseOptions {
+        delimiters: Delimiters { open: "[[", close: "]]" },
+        ..ParseOptions::default()
+    };
+    let source = "{{ vue }} [[ name ]] [[{ html }]][[! comment ]][[#items]][[.]][[|]]none[[/items]][[> p ]][[% raw %]][[ a ]][[% endraw %]]";
+    let template = Template::parse_with_options(source, &options)
+        .expect("template parsed successfully");
+    let expected_frags = vec![
+        Fragment::Literal("{{ vue }} "),
+        Fragment::EscapedVariable("name"),
+        Fragment::Literal(" "),
+        Fragment::UnescapedVariable("html"),
+        Fragment::Section("items"),
+        Fragment::EscapedVariable("."),
+        Fragment::SectionElse("|"),
+        Fragment::Literal("none"),
+        Fragment::Partial("p"),
+        Fragment::Literal("[[ a ]]"),
+    ];
+    assert_eq!(template.frags(), expected_frags);
+    let spans: Vec<_> = template.inspect().into_iter().map(|info| &source[info.span]).collect();
+    assert_eq!(&spans[..4], ["{{ vue }} ", "[[ name ]]", " ", "[[{ html }]]"]);
+
+    let loader = HashMapLoader::try_from_with_options(vec![("p", "<[[ name ]]>")], &options).unwrap();
+    let data = json!({"name": "John", "html": "<b>", "items": [1, 2]});
+    assert_eq!(
+        template.render_to_string(&loader, &data).unwrap(),
+        "{{ vue }} John <b>12<John>[[ a ]]",
+    );
+
+    // delimiters longer than 2 chars, or the same on both sides
+    for (open, close) in [("<%=", "%>"), ("$", "$")] {
+        let options = ParseOptions {
+            delimiters: Delimiters { open, close },
+            ..ParseOptions::default()
+        };
+        let source = format!("{open}#a{close}{open}.{close}{open}/a{close}{open}! {close}");
+        let template = Template::parse_with_options(source, &options).unwrap();
+        assert_eq!(template.render_no_partials_to_string(&json!({"a": "x"})).unwrap(), "x");
+    }
+
+    // errors
+    assert_eq!(
+        Template::parse_with_options("[[# a ]]", &options).unwrap_err(),
+        MoostacheError::ParseErrorUnclosedSectionTags(String::new()),
+    );
+    assert_eq!(
+        Template::parse_with_options("[[ a }}", &options).unwrap_err(),
+        MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new()),
+    );
+    for (open, close) in [("", "]]"), ("[[", ""), ("[ [", "]]"), ("[[", "]]\n")] {
+        let options = ParseOptions {
+            delimiters: Delimiters { open, close },
+            ..ParseOptions::default()
+        };
+        assert_eq!(
+            Template::parse_with_options("a", &options).unwrap_err(),
+            MoostacheError::ParseErrorInvalidDelimiters(String::new()),
+        );
+    }
+}
+
+#[test]
+fn miri_iso_hash_map_loader_custom_delimiters() {
+    let options = ParseOptions {
+        delimiters: Delimiters { open: "[[", close: "]]" },
+        ..ParseOptions::default()
+    };
+    let loader = HashMapLoader::try_from_with_options(
+        vec![("page", "[[> greet ]] {{ a + b }}"), ("greet", "hi [[ name ]]")],
+        &options,
+    ).unwrap();
+    assert_eq!(loader.render_to_string("page", &json!({"name": "John"})).unwrap(), "hi John {{ a + b }}");
+    let err = HashMapLoader::try_from_with_options(vec![("bad", "[[# a ]]")], &options).unwrap_err();
+    assert_eq!(err, MoostacheError::ParseErrorUnclosedSectionTags("bad".into()));
+}
+
+#[test]
+fn miri_iso_strict_variable_names() {
+    let options = ParseOptions {
+        strict_variable_names: true,
+        ..ParseOptions::default()
+    };
+    let data = json!({"a-b": {"c-d": 1}});
+    let template = Template::parse_with_options("{{ a-b.c-d }}", &options).unwrap();
+    assert_eq!("1", template.render_no_partials_to_string(&data).unwrap());
+
+    for (source, expected) in [
+        ("{{ -x }}", MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new())),
+        ("{{ x- }}", MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new())),
+        ("{{ a.-b }}", MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new())),
+        ("{{{ x- }}}", MoostacheError::ParseErrorInvalidUnescapedVariableTag(String::new())),
+        ("{{#x-}}{{/x-}}", MoostacheError::ParseErrorInvalidSectionStartTag(String::new())),
+        ("{{^-x}}{{/-x}}", MoostacheError::ParseErrorInvalidInvertedSectionStartTag(String::new())),
+    ] {
+        assert_eq!(expected, Template::parse_with_options(source, &options).unwrap_err());
+    }
+}
+
+#[test]
+fn miri_iso_dotted_variable_names() {
+    for strict_variable_names in [false, true] {
+        let options = ParseOptions {
+            strict_variable_names,
+            ..ParseOptions::default()
+        };
+        for source in ["{{ x. }}", "{{ .x }}", "{{ x..y }}"] {
+            assert_eq!(
+                MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new()),
+                Template::parse_with_options(source, &options).unwrap_err(),
+            );
+        }
+    }
+}
+
+#[test]
+fn miri_iso_bracket_variable_names() {
+    let template = Template::parse("{{ items[0].name }}{{# [1] }}{{/ [1] }}{{ a[b][0] }}").unwrap();
+    let expected = temp(
+        vec![
+            Fragment::EscapedVariable("items[0].name"),
+            Fragment::Section("[1]"),
+            Fragment::EscapedVariable("a[b][0]"),
+        ],
+        vec![SectionSkip { nested_sections: 0, nested_fragments: 0, has_aggregates: false }],
+    );
+    assert_eq!(template, expected);
+    for source in ["{{ x[] }}", "{{ x[0 }}", "{{ x0] }}", "{{ x.[0] }}", "{{ x[[0]] }}", "{{ x[.] }}"] {
+        assert_eq!(
+            MoostacheError::ParseErrorInvalidEscapedVariableTag(String::new()),
+            Template::parse(source).unwrap_err(),
+        );
+    }
+    let data = json!({
+        "items": [{"name": "a"}, {"name": "b"}],
+        "a": {"b": ["c"]},
+    });
+    for (bracketed, dotted) in [
+        ("items[0].name", "items.0.name"),
+        ("items[1][name]", "items.1.name"),
+        ("a[b][0]", "a.b.0"),
+        ("a[b].0", "a.b.0"),
+        ("items[2].name", "items.2.name"),
+    ] {
+        assert_eq!(resolve_value(bracketed, &[&data], None), resolve_value(dotted, &[&data], None));
+        let bracketed = Template::parse(format!("{{{{ {bracketed} }}}}")).unwrap();
+        let dotted = Template::parse(format!("{{{{ {dotted} }}}}")).unwrap();
+        assert_eq!(
+            bracketed.render_no_partials_to_string(&data).unwrap(),
+            dotted.render_no_partials_to_string(&data).unwrap(),
+        );
+    }
+    assert_eq!(resolve_value("items[0].name", &[&data], None), &json!("a"));
+    assert_eq!(resolve_value("[1]", &[&json!(["x", "y"])], None), &json!("y"));
+    let template = Template::parse("{{#items}}{{[name]}}{{/items}}").unwrap();
+    assert_eq!(template.render_no_partials_to_string(&data).unwrap(), "ab");
+}
+
+/////////////////////////////////////
+// TEST CLASSIFYING MOOSTACHEERRORS //
+/////////////////////////////////////
+
+#[test]
+fn miri_iso_moostache_error_classification() {
+    use MoostacheError::*;
+    let parse_errors = [
+        Template::parse("").unwrap_err(),
+        Template::parse("{{# a }}").unwrap_err(),
+        Template::parse("{{|}}").unwrap_err(),
+        ParseErrorInvalidRawBlock("raw".into()),
+        ParseErrorTooManySections(String::new()),
+        ParseErrorTooManyFragments(String::new()),
+        ParseErrorInvalidDelimiters(String::new()),
+    ];
+    for err in &parse_errors {
+        assert!(err.is_parse_error());
+        assert!(!err.is_io_error());
+    }
+    assert_eq!(parse_errors[0].template_name(), None);
+    assert_eq!(parse_errors[3].template_name(), Some("raw"));
+
+    let io_errors = [
+        IoError("file".into(), io::ErrorKind::NotFound),
+        RenderErrorWouldBlock,
+    ];
+    for err in &io_errors {
+        assert!(!err.is_parse_error());
+        assert!(err.is_io_error());
+    }
+    assert_eq!(io_errors[0].template_name(), Some("file"));
+    assert_eq!(io_errors[1].template_name(), None);
+
+    let other_errors = [
+        LoaderErrorTemplateNotFound("missing".into()),
+        LoaderErrorCyclicPartial("cycle".into()),
+        RenderErrorInvalidDynamicPartial("variable".into()),
+        RenderErrorCannotStringifyContainer("variable".into()),
+        CheckErrorMissingPaths(vec!["variable".into()]),
+        CheckErrorUnusedKeys(vec!["key".into()]),
+        RenderErrorInvalidUtf8,
+        RenderErrorInvalidFragmentRange(1..2),
+        SerializationError("key must be a string".into()),
+        RenderErrorCancelled,
+        RenderErrorOutputTooLarge,
+        ConfigErrorNonPositiveCacheSize,
+    ];
+    for err in &other_errors {
+        assert!(!err.is_parse_error());
+        assert!(!err.is_io_error());
+    }
+    let names: Vec<_> = other_errors.iter().map(MoostacheError::template_name).collect();
+    assert_eq!(names, [Some("missing"), Some("cycle"), None, None, None, None, None, None, None, None, None, None]);
+}
+
 //////////////////////////////////////
 // TEST MOOSTACHEERROR DISPLAY IMPL //
 //////////////////////////////////////
 
 #[test]
-fn miri_iso_moostache_error_display_impl() {
+fn miri_iso_moostache_parse_error_display_impl() {
     use MoostacheError::*;
-    let mut err = ParseErrorGeneric("".into());
+    let mut err = ParseErrorGeneric(String::new());
     assert_eq!("error parsing anonymous template", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template", &err.to_string());
 
-    err = ParseErrorNoContent("".into());
+    err = ParseErrorNoContent(String::new());
     assert_eq!("error parsing anonymous template: empty template", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: empty template", &err.to_string());
 
-    err = ParseErrorUnclosedSectionTags("".into());
+    err = ParseErrorUnclosedSectionTags(String::new());
     assert_eq!("error parsing anonymous template: unclosed section tags", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: unclosed section tags", &err.to_string());
 
-    err = ParseErrorInvalidEscapedVariableTag("".into());
+    err = ParseErrorInvalidEscapedVariableTag(String::new());
     assert_eq!("error parsing anonymous template: invalid escaped variable tag, expected {{ variable }}", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: invalid escaped variable tag, expected {{ variable }}", &err.to_string());
 
-    err = ParseErrorInvalidUnescapedVariableTag("".into());
+    err = ParseErrorInvalidUnescapedVariableTag(String::new());
     assert_eq!("error parsing anonymous template: invalid unescaped variable tag, expected {{{ variable }}}", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: invalid unescaped variable tag, expected {{{ variable }}}", &err.to_string());
 
-    err = ParseErrorInvalidSectionEndTag("".into());
+    err = ParseErrorInvalidSectionEndTag(String::new());
     assert_eq!("error parsing anonymous template: invalid section eng tag, expected {{/ section }}", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: invalid section eng tag, expected {{/ section }}", &err.to_string());
 
-    err = ParseErrorMismatchedSectionEndTag("".into());
+    err = ParseErrorMismatchedSectionEndTag(String::new());
     assert_eq!("error parsing anonymous template: mismatched section eng tag, expected {{# section }} ... {{/ section }}", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: mismatched section eng tag, expected {{# section }} ... {{/ section }}", &err.to_string());
 
-    err = ParseErrorInvalidCommentTag("".into());
+    err = ParseErrorInvalidCommentTag(String::new());
     assert_eq!("error parsing anonymous template: invalid comment tag, expected {{! comment }}", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: invalid comment tag, expected {{! comment }}", &err.to_string());
 
-    err = ParseErrorInvalidSectionStartTag("".into());
+    err = ParseErrorInvalidSectionStartTag(String::new());
     assert_eq!("error parsing anonymous template: invalid section start tag, expected {{# section }}", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: invalid section start tag, expected {{# section }}", &err.to_string());
 
-    err = ParseErrorInvalidInvertedSectionStartTag("".into());
+    err = ParseErrorInvalidInvertedSectionStartTag(String::new());
     assert_eq!("error parsing anonymous template: invalid inverted section start tag, expected {{^ section }}", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: invalid inverted section start tag, expected {{^ section }}", &err.to_string());
 
-    err = ParseErrorInvalidPartialTag("".into());
+    err = ParseErrorInvalidPartialTag(String::new());
     assert_eq!("error parsing anonymous template: invalid partial tag, expected {{> partial }}", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error parsing \"name\" template: invalid partial tag, expected {{> partial }}", &err.to_string());
 
-    err = IoError("".into(), std::io::ErrorKind::NotFound);
+    err = ParseErrorInvalidRawBlock(String::new());
+    assert_eq!("error parsing anonymous template: invalid raw block, expected {{% raw %}} ... {{% endraw %}}", &err.to_string());
+    err = err.set_name("name");
+    assert_eq!("error parsing \"name\" template: invalid raw block, expected {{% raw %}} ... {{% endraw %}}", &err.to_string());
+
+    err = ParseErrorInvalidElseTag(String::new());
+    assert_eq!("error parsing anonymous template: invalid else tag, expected {{|}}", &err.to_string());
+    err = err.set_name("name");
+    assert_eq!("error parsing \"name\" template: invalid else tag, expected {{|}}", &err.to_string());
+
+    err = ParseErrorElseOutsideSection(String::new());
+    assert_eq!("error parsing anonymous template: else tag outside of section, expected {{# section }} ... {{|}} ... {{/ section }}", &err.to_string());
+    err = err.set_name("name");
+    assert_eq!("error parsing \"name\" template: else tag outside of section, expected {{# section }} ... {{|}} ... {{/ section }}", &err.to_string());
+
+    err = ParseErrorDuplicateElse(String::new());
+    assert_eq!("error parsing anonymous template: section has more than one else tag", &err.to_string());
+    err = err.set_name("name");
+    assert_eq!("error parsing \"name\" template: section has more than one else tag", &err.to_string());
+
+    err = ParseErrorTooManySections(String::new());
+    assert_eq!("error parsing anonymous template: section has more than 65535 nested sections", &err.to_string());
+    err = err.set_name("name");
+    assert_eq!("error parsing \"name\" template: section has more than 65535 nested sections", &err.to_string());
+
+    err = ParseErrorTooManyFragments(String::new());
+    assert_eq!("error parsing anonymous template: section has more than 65535 nested fragments", &err.to_string());
+    err = err.set_name("name");
+    assert_eq!("error parsing \"name\" template: section has more than 65535 nested fragments", &err.to_string());
+
+    err = ParseErrorInvalidDelimiters(String::new());
+    assert_eq!("error parsing anonymous template: invalid delimiters, expected non-empty delimiters without whitespace", &err.to_string());
+    err = err.set_name("name");
+    assert_eq!("error parsing \"name\" template: invalid delimiters, expected non-empty delimiters without whitespace", &err.to_string());
+}
+
+#[test]
+fn miri_iso_moostache_error_display_impl() {
+    use MoostacheError::*;
+    let mut err = IoError(String::new(), std::io::ErrorKind::NotFound);
     assert_eq!("error reading anonymous template: entity not found", &err.to_string());
     err = err.set_name("name");
     assert_eq!("error reading \"name\" template: entity not found", &err.to_string());
 
-    err = LoaderErrorTemplateNotFound("".into());
+    err = LoaderErrorTemplateNotFound(String::new());
     assert_eq!("loader error: anonymous template not found", &err.to_string());
     err = err.set_name("name");
     assert_eq!("loader error: \"name\" template not found", &err.to_string());
 
+    err = LoaderErrorCyclicPartial("name".into());
+    assert_eq!("loader error: \"name\" template includes itself via partials", &err.to_string());
+
     err = LoaderErrorNonUtf8FilePath("some.file".into());
     assert_eq!("loader error: can't load non-utf8 file path: some.file", &err.to_string());
 
@@ -1733,6 +5941,33 @@ fn miri_iso_moostache_error_display_impl() {
     err = ConfigErrorTooManyTemplates;
     assert_eq!("config error: templates in directory exceeds cache size", &err.to_string());
 
-    err = SerializationError;
-    assert_eq!("serialization error: could not serialize data to serde_json::Value", &err.to_string());
+    err = SerializationError("key must be a string".into());
+    assert_eq!("serialization error: could not serialize data to serde_json::Value: key must be a string", &err.to_string());
+
+    err = RenderErrorWouldBlock;
+    assert_eq!("render error: writer would block", &err.to_string());
+
+    err = RenderErrorCancelled;
+    assert_eq!("render error: render was cancelled", &err.to_string());
+
+    err = RenderErrorOutputTooLarge;
+    assert_eq!("render error: output exceeds max output bytes", &err.to_string());
+
+    err = RenderErrorInvalidDynamicPartial("name".into());
+    assert_eq!("render error: dynamic partial \"name\" isn't a non-empty string", &err.to_string());
+
+    err = RenderErrorCannotStringifyContainer("name".into());
+    assert_eq!("render error: variable \"name\" is an array or object", &err.to_string());
+
+    err = RenderErrorInvalidUtf8;
+    assert_eq!("render error: output isn't valid utf-8", &err.to_string());
+
+    err = RenderErrorInvalidFragmentRange(1..2);
+    assert_eq!("render error: fragment range 1..2 is out of bounds or splits a section", &err.to_string());
+
+    err = CheckErrorMissingPaths(vec!["a".into(), "b.c".into()]);
+    assert_eq!("check error: paths don't resolve: a, b.c", &err.to_string());
+
+    err = CheckErrorUnusedKeys(vec!["a".into(), "b".into()]);
+    assert_eq!("check error: keys aren't used: a, b", &err.to_string());
 }